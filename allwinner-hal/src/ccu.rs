@@ -1,14 +1,28 @@
 //! Clock Control Unit peripheral.
 
+mod dependent;
 mod factor;
+mod plan;
 mod pll;
 mod source;
 
+pub use dependent::{
+    apb1_frequency, psi_frequency, recompute_dependents, Dependents, Peripheral, Pll,
+};
 pub(crate) use factor::calculate_best_peripheral_factors_nm;
-pub use factor::{AxiFactorN, FactorP, PeriFactorN};
-pub use pll::{PllCpuControl, PllDdrControl, PllPeri0Control};
-pub use source::{CpuClockSource, DramClockSource, SmhcClockSource, SpiClockSource};
-
+pub use factor::{
+    calculate_pixel_clock_factors, AxiFactorN, FactorP, PeriFactorN, PixelClockFactors,
+};
+pub use plan::{ClockPlan, ClockRegisters, ModuleClock};
+pub use pll::{
+    bring_up_pll, PllCpuControl, PllDdrControl, PllPeri0Control, PllRegister, PllVideo0Control,
+};
+pub use source::{
+    ApbClockSource, CpuClockSource, DramClockSource, MbusClockSource, PsiClockSource,
+    SmhcClockSource, SpiClockSource, TconTvClockSource,
+};
+
+use embedded_hal::delay::DelayNs;
 use embedded_time::rate::Hertz;
 use volatile_register::RW;
 
@@ -21,6 +35,19 @@ pub struct Clocks {
     pub apb1: Hertz,
 }
 
+/// Get clock configuration of D1-like chips as left by the boot ROM.
+///
+/// PSI runs from PLL_PERI(1X) at 600 MHz, APB1 from the 24-MHz oscillator.
+/// Use [`psi_frequency`] and [`apb1_frequency`] instead once the clock tree
+/// has been reconfigured.
+#[inline]
+pub const fn d1_boot_clocks() -> Clocks {
+    Clocks {
+        psi: Hertz(600_000_000),
+        apb1: Hertz(24_000_000),
+    }
+}
+
 /// Clock Control Unit registers.
 #[repr(C)]
 pub struct RegisterBlock {
@@ -32,35 +59,73 @@ pub struct RegisterBlock {
     _reserved1: [u32; 3],
     /// 0x20 - Peripheral PLL 0 Control register.
     pub pll_peri0_control: RW<PllPeri0Control>,
-    _reserved2: [u32; 311],
+    _reserved2: [u32; 7],
+    /// 0x40 - Video PLL 0 Control register.
+    pub pll_video0_control: RW<PllVideo0Control>,
+    _reserved3: [u32; 303],
     /// 0x500 - CPU AXI Configuration register.
     pub cpu_axi_config: RW<CpuAxiConfig>,
-    _reserved3: [u32; 15],
+    _reserved4: [u32; 3],
+    /// 0x510 - PSI, AHB1 and AHB2 Clock register.
+    pub psi_clock: RW<PsiClock>,
+    _reserved5: [u32; 3],
+    /// 0x520 - APB0 Clock register.
+    pub apb0_clock: RW<ApbClock>,
+    /// 0x524 - APB1 Clock register.
+    pub apb1_clock: RW<ApbClock>,
+    _reserved6: [u32; 6],
     /// 0x540 - MBUS Clock register.
     pub mbus_clock: RW<MbusClock>,
-    _reserved4: [u32; 175],
+    _reserved8: [u32; 175],
     /// 0x800 - DRAM Clock register.
     pub dram_clock: RW<DramClock>,
-    _reserved5: [u32; 2],
+    _reserved9: [u32; 2],
     /// 0x80c - DRAM Bus Gating Reset register.
     pub dram_bgr: RW<DramBusGating>,
-    _reserved6: [u32; 8],
+    _reserved10: [u32; 8],
     /// 0x830..=0x838 - SMHC0 Clock register, SMHC1 Clock register and SMHC2 Clock register.
     pub smhc_clk: [RW<SmhcClock>; 3],
-    _reserved7: [u32; 4],
+    _reserved11: [u32; 4],
     /// 0x84c - SMHC Bus Gating Reset register.
     pub smhc_bgr: RW<SmhcBusGating>,
-    _reserved8: [u32; 47],
+    _reserved12: [u32; 47],
     /// 0x90c - UART Bus Gating Reset register.
     pub uart_bgr: RW<UartBusGating>,
-    _reserved9: [u32; 12],
+    _reserved13: [u32; 12],
     /// 0x940..=0x944 - SPI0 Clock register and SPI1 Clock register.
     pub spi_clk: [RW<SpiClock>; 2],
-    _reserved10: [u32; 9],
+    _reserved14: [u32; 9],
     /// 0x96c - SPI Bus Gating Reset register.
     pub spi_bgr: RW<SpiBusGating>,
+    _reserved15: [u32; 101],
+    /// 0xb04 - HDMI 24M Clock register.
+    pub hdmi_24m_clk: RW<Hdmi24MClock>,
+    _reserved16: [u32; 30],
+    /// 0xb80 - TCON TV Clock register.
+    pub tcon_tv_clk: RW<TconTvClock>,
 }
 
+assert_offsets!(RegisterBlock {
+    pll_cpu_control: 0x0,
+    pll_ddr_control: 0x10,
+    pll_peri0_control: 0x20,
+    pll_video0_control: 0x40,
+    cpu_axi_config: 0x500,
+    psi_clock: 0x510,
+    apb0_clock: 0x520,
+    apb1_clock: 0x524,
+    mbus_clock: 0x540,
+    dram_clock: 0x800,
+    dram_bgr: 0x80c,
+    smhc_clk: 0x830,
+    smhc_bgr: 0x84c,
+    uart_bgr: 0x90c,
+    spi_clk: 0x940,
+    spi_bgr: 0x96c,
+    hdmi_24m_clk: 0xb04,
+    tcon_tv_clk: 0xb80,
+});
+
 /// CPU AXI Configuration register.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[repr(transparent)]
@@ -86,6 +151,11 @@ impl CpuAxiConfig {
             _ => panic!("impossible clock source"),
         }
     }
+    /// Check if CPU clock is sourced from the CPU PLL.
+    #[inline]
+    pub const fn is_sourced_from_pll_cpu(self) -> bool {
+        (self.0 & Self::CPU_CLK_SEL) >> 24 == 3
+    }
     /// Set AXI CPU clock source.
     #[inline]
     pub const fn set_clock_source(self, val: CpuClockSource) -> Self {
@@ -147,6 +217,140 @@ impl CpuAxiConfig {
     }
 }
 
+/// PSI, AHB1 and AHB2 Clock register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct PsiClock(u32);
+
+impl PsiClock {
+    const CLK_SRC_SEL: u32 = 0x3 << 24;
+    const FACTOR_N: u32 = 0x3 << 8;
+    const FACTOR_M: u32 = 0x3;
+
+    /// Get PSI clock source.
+    #[inline]
+    pub const fn clock_source(self) -> PsiClockSource {
+        match (self.0 & Self::CLK_SRC_SEL) >> 24 {
+            0x0 => PsiClockSource::Hosc,
+            0x1 => PsiClockSource::Clk32K,
+            0x2 => PsiClockSource::Clk16MRC,
+            0x3 => PsiClockSource::PllPeri1x,
+            _ => unreachable!(),
+        }
+    }
+    /// Set PSI clock source.
+    #[inline]
+    pub const fn set_clock_source(self, val: PsiClockSource) -> Self {
+        Self((self.0 & !Self::CLK_SRC_SEL) | ((val as u32) << 24))
+    }
+    /// Get PSI clock divide factor N.
+    #[inline]
+    pub const fn factor_n(self) -> PeriFactorN {
+        match (self.0 & Self::FACTOR_N) >> 8 {
+            0 => PeriFactorN::N1,
+            1 => PeriFactorN::N2,
+            2 => PeriFactorN::N4,
+            3 => PeriFactorN::N8,
+            _ => unreachable!(),
+        }
+    }
+    /// Set PSI clock divide factor N.
+    #[inline]
+    pub const fn set_factor_n(self, val: PeriFactorN) -> Self {
+        Self((self.0 & !Self::FACTOR_N) | ((val as u32) << 8))
+    }
+    /// Get PSI clock divide factor M (from 0 to 3).
+    #[inline]
+    pub const fn factor_m(self) -> u8 {
+        (self.0 & Self::FACTOR_M) as u8
+    }
+    /// Set PSI clock divide factor M (from 0 to 3).
+    #[inline]
+    pub const fn set_factor_m(self, val: u8) -> Self {
+        Self((self.0 & !Self::FACTOR_M) | val as u32)
+    }
+}
+
+impl Default for PsiClock {
+    #[inline]
+    fn default() -> Self {
+        Self(0x0000_0000)
+    }
+}
+
+/// APB Clock register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct ApbClock(u32);
+
+impl ApbClock {
+    const CLK_SRC_SEL: u32 = 0x3 << 24;
+    const FACTOR_N: u32 = 0x3 << 8;
+    const FACTOR_M: u32 = 0x1f;
+
+    /// Get APB clock source.
+    #[inline]
+    pub const fn clock_source(self) -> ApbClockSource {
+        match (self.0 & Self::CLK_SRC_SEL) >> 24 {
+            0x0 => ApbClockSource::Hosc,
+            0x1 => ApbClockSource::Clk32K,
+            0x2 => ApbClockSource::Psi,
+            0x3 => ApbClockSource::PllPeri1x,
+            _ => unreachable!(),
+        }
+    }
+    /// Set APB clock source.
+    #[inline]
+    pub const fn set_clock_source(self, val: ApbClockSource) -> Self {
+        let val = match val {
+            ApbClockSource::Hosc => 0x0,
+            ApbClockSource::Clk32K => 0x1,
+            ApbClockSource::Psi => 0x2,
+            ApbClockSource::PllPeri1x => 0x3,
+        };
+        Self((self.0 & !Self::CLK_SRC_SEL) | (val << 24))
+    }
+    /// Get APB clock divide factor N.
+    #[inline]
+    pub const fn factor_n(self) -> PeriFactorN {
+        match (self.0 & Self::FACTOR_N) >> 8 {
+            0 => PeriFactorN::N1,
+            1 => PeriFactorN::N2,
+            2 => PeriFactorN::N4,
+            3 => PeriFactorN::N8,
+            _ => unreachable!(),
+        }
+    }
+    /// Set APB clock divide factor N.
+    #[inline]
+    pub const fn set_factor_n(self, val: PeriFactorN) -> Self {
+        let val = match val {
+            PeriFactorN::N1 => 0,
+            PeriFactorN::N2 => 1,
+            PeriFactorN::N4 => 2,
+            PeriFactorN::N8 => 3,
+        };
+        Self((self.0 & !Self::FACTOR_N) | (val << 8))
+    }
+    /// Get APB clock divide factor M.
+    #[inline]
+    pub const fn factor_m(self) -> u8 {
+        (self.0 & Self::FACTOR_M) as u8
+    }
+    /// Set APB clock divide factor M.
+    #[inline]
+    pub const fn set_factor_m(self, val: u8) -> Self {
+        Self((self.0 & !Self::FACTOR_M) | val as u32)
+    }
+}
+
+impl Default for ApbClock {
+    #[inline]
+    fn default() -> Self {
+        Self(0x0000_0000)
+    }
+}
+
 /// MBUS Clock register.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[repr(transparent)]
@@ -154,6 +358,8 @@ pub struct MbusClock(u32);
 
 impl MbusClock {
     const MBUS_RST: u32 = 0x1 << 30;
+    const MBUS_CLK_SEL: u32 = 0x3 << 24;
+    const MBUS_M: u32 = 0x7;
 
     /// If reset is asserted.
     #[inline]
@@ -170,6 +376,32 @@ impl MbusClock {
     pub const fn deassert_reset(self) -> Self {
         Self(self.0 | Self::MBUS_RST)
     }
+    /// Get clock source.
+    #[inline]
+    pub const fn clock_source(self) -> MbusClockSource {
+        match ((self.0 & Self::MBUS_CLK_SEL) >> 24) as u8 {
+            0x0 => MbusClockSource::Hosc,
+            0x1 => MbusClockSource::PllPeri2x,
+            0x2 => MbusClockSource::PllDdr,
+            0x3 => MbusClockSource::PllPeri800M,
+            _ => unreachable!(),
+        }
+    }
+    /// Set clock source.
+    #[inline]
+    pub const fn set_clock_source(self, val: MbusClockSource) -> Self {
+        Self((self.0 & !Self::MBUS_CLK_SEL) | ((val as u32) << 24))
+    }
+    /// Get factor m (from 0 to 7); clock is divided by `factor_m + 1`.
+    #[inline]
+    pub const fn factor_m(self) -> u8 {
+        (self.0 & Self::MBUS_M) as u8
+    }
+    /// Set factor m (from 0 to 7); clock is divided by `factor_m + 1`.
+    #[inline]
+    pub const fn set_factor_m(self, val: u8) -> Self {
+        Self((self.0 & !Self::MBUS_M) | val as u32)
+    }
 }
 
 /// DRAM Clock register.
@@ -271,6 +503,16 @@ impl DramBusGating {
     pub const fn gate_pass(self) -> Self {
         Self(self.0 | Self::DRAM_GATING)
     }
+    /// Get if the dram gating is masked.
+    #[inline]
+    pub const fn is_gated(self) -> bool {
+        self.0 & Self::DRAM_GATING == 0
+    }
+    /// Get if dram reset is asserted.
+    #[inline]
+    pub const fn is_in_reset(self) -> bool {
+        self.0 & Self::DRAM_RST == 0
+    }
 }
 
 /// UART Bus Gating Reset register.
@@ -299,6 +541,16 @@ impl UartBusGating {
     pub const fn deassert_reset<const I: usize>(self) -> Self {
         Self(self.0 | (1 << (I + 16)))
     }
+    /// Get if clock gate for UART `I` is masked.
+    #[inline]
+    pub const fn is_gated<const I: usize>(self) -> bool {
+        self.0 & (1 << I) == 0
+    }
+    /// Get if reset signal for UART `I` is asserted.
+    #[inline]
+    pub const fn is_in_reset<const I: usize>(self) -> bool {
+        self.0 & (1 << (I + 16)) == 0
+    }
 }
 
 /// SPI Clock register.
@@ -394,6 +646,16 @@ impl SpiBusGating {
     pub const fn deassert_reset<const I: usize>(self) -> Self {
         Self(self.0 | (1 << (I + 16)))
     }
+    /// Get if clock gate for SPI `I` is masked.
+    #[inline]
+    pub const fn is_gated<const I: usize>(self) -> bool {
+        self.0 & (1 << I) == 0
+    }
+    /// Get if reset signal for SPI `I` is asserted.
+    #[inline]
+    pub const fn is_in_reset<const I: usize>(self) -> bool {
+        self.0 & (1 << (I + 16)) == 0
+    }
 }
 
 /// SMHC Clock register.
@@ -478,6 +740,47 @@ impl SmhcClock {
     pub const fn is_clock_gating_enabled(self) -> bool {
         self.0 & Self::CLK_GATING != 0
     }
+    /// Set divide factors N and M for the highest frequency not above `target_hz`
+    /// from a clock source of `source_hz`.
+    ///
+    /// A target at or above the source frequency selects the minimum divider.
+    /// Returns `None` if the target is zero or would need factor M above 15.
+    #[inline]
+    pub const fn set_frequency(self, source_hz: u32, target_hz: u32) -> Option<Self> {
+        if target_hz == 0 {
+            return None;
+        }
+        let factors = [
+            (PeriFactorN::N1, 1),
+            (PeriFactorN::N2, 2),
+            (PeriFactorN::N4, 4),
+            (PeriFactorN::N8, 8),
+        ];
+        let mut best: Option<(PeriFactorN, u32, u32)> = None;
+        let mut i = 0;
+        while i < factors.len() {
+            let (factor_n, n) = factors[i];
+            i += 1;
+            // in u64, as `n * target_hz` overflows u32 from about 537 MHz
+            let m = (source_hz as u64).div_ceil(n as u64 * target_hz as u64) as u32;
+            let m = if m == 0 { 1 } else { m };
+            if m > 16 {
+                continue;
+            }
+            let actual = source_hz / n / m;
+            let better = match best {
+                None => true,
+                Some((_, _, best_actual)) => actual > best_actual,
+            };
+            if better {
+                best = Some((factor_n, m, actual));
+            }
+        }
+        match best {
+            Some((factor_n, m, _)) => Some(self.set_factor_n(factor_n).set_factor_m((m - 1) as u8)),
+            None => None,
+        }
+    }
 }
 
 /// SMHC Clock Reset register.
@@ -506,6 +809,247 @@ impl SmhcBusGating {
     pub const fn deassert_reset<const I: usize>(self) -> Self {
         Self(self.0 | (1 << (I + 16)))
     }
+    /// Get if clock gate for SMHC `I` is masked.
+    #[inline]
+    pub const fn is_gated<const I: usize>(self) -> bool {
+        self.0 & (1 << I) == 0
+    }
+    /// Get if reset signal for SMHC `I` is asserted.
+    #[inline]
+    pub const fn is_in_reset<const I: usize>(self) -> bool {
+        self.0 & (1 << (I + 16)) == 0
+    }
+}
+
+/// HDMI 24M Clock register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct Hdmi24MClock(u32);
+
+impl Hdmi24MClock {
+    const CLK_GATING: u32 = 1 << 31;
+
+    /// Enable clock gating.
+    #[inline]
+    pub const fn enable_clock_gating(self) -> Self {
+        Self(self.0 | Self::CLK_GATING)
+    }
+    /// Disable clock gating.
+    #[inline]
+    pub const fn disable_clock_gating(self) -> Self {
+        Self(self.0 & !Self::CLK_GATING)
+    }
+    /// Get if clock gating is enabled.
+    #[inline]
+    pub const fn is_clock_gating_enabled(self) -> bool {
+        self.0 & Self::CLK_GATING != 0
+    }
+}
+
+/// TCON TV Clock register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct TconTvClock(u32);
+
+impl TconTvClock {
+    const CLK_SRC_SEL: u32 = 0x7 << 24;
+    const FACTOR_N: u32 = 0x3 << 8;
+    const FACTOR_M: u32 = 0xf;
+    const CLK_GATING: u32 = 1 << 31;
+
+    /// Get TCON TV clock source.
+    #[inline]
+    pub const fn clock_source(self) -> TconTvClockSource {
+        match (self.0 & Self::CLK_SRC_SEL) >> 24 {
+            0x0 => TconTvClockSource::Hosc,
+            0x1 => TconTvClockSource::PllVideo0x1,
+            0x2 => TconTvClockSource::PllVideo0x4,
+            0x3 => TconTvClockSource::PllVideo1x1,
+            0x4 => TconTvClockSource::PllVideo1x4,
+            0x5 => TconTvClockSource::PllPeri2x,
+            0x6 => TconTvClockSource::PllAudio1Div2,
+            _ => panic!("impossible clock source"),
+        }
+    }
+    /// Set TCON TV clock source.
+    #[inline]
+    pub const fn set_clock_source(self, val: TconTvClockSource) -> Self {
+        let val = match val {
+            TconTvClockSource::Hosc => 0x0,
+            TconTvClockSource::PllVideo0x1 => 0x1,
+            TconTvClockSource::PllVideo0x4 => 0x2,
+            TconTvClockSource::PllVideo1x1 => 0x3,
+            TconTvClockSource::PllVideo1x4 => 0x4,
+            TconTvClockSource::PllPeri2x => 0x5,
+            TconTvClockSource::PllAudio1Div2 => 0x6,
+        };
+        Self((self.0 & !Self::CLK_SRC_SEL) | (val << 24))
+    }
+    /// Get TCON TV clock divide factor N.
+    #[inline]
+    pub const fn factor_n(self) -> PeriFactorN {
+        match (self.0 & Self::FACTOR_N) >> 8 {
+            0 => PeriFactorN::N1,
+            1 => PeriFactorN::N2,
+            2 => PeriFactorN::N4,
+            3 => PeriFactorN::N8,
+            _ => unreachable!(),
+        }
+    }
+    /// Set TCON TV clock divide factor N.
+    #[inline]
+    pub const fn set_factor_n(self, val: PeriFactorN) -> Self {
+        let val = match val {
+            PeriFactorN::N1 => 0,
+            PeriFactorN::N2 => 1,
+            PeriFactorN::N4 => 2,
+            PeriFactorN::N8 => 3,
+        };
+        Self((self.0 & !Self::FACTOR_N) | (val << 8))
+    }
+    /// Get TCON TV clock divide factor M.
+    #[inline]
+    pub const fn factor_m(self) -> u8 {
+        (self.0 & Self::FACTOR_M) as u8
+    }
+    /// Set TCON TV clock divide factor M.
+    #[inline]
+    pub const fn set_factor_m(self, val: u8) -> Self {
+        Self((self.0 & !Self::FACTOR_M) | val as u32)
+    }
+    /// Enable clock gating.
+    #[inline]
+    pub const fn enable_clock_gating(self) -> Self {
+        Self(self.0 | Self::CLK_GATING)
+    }
+    /// Disable clock gating.
+    #[inline]
+    pub const fn disable_clock_gating(self) -> Self {
+        Self(self.0 & !Self::CLK_GATING)
+    }
+    /// Get if clock gating is enabled.
+    #[inline]
+    pub const fn is_clock_gating_enabled(self) -> bool {
+        self.0 & Self::CLK_GATING != 0
+    }
+}
+
+/// Configure video PLL 0 and TCON TV clock to approximate pixel clock `target_hz`.
+///
+/// HDMI 24M clock is enabled as well. Returns the achieved pixel clock frequency in Hz.
+///
+/// PLL lock is polled every microsecond for at most `lock_timeout_us` microseconds;
+/// on timeout, TCON TV clock is left gated off.
+pub fn set_pixel_clock(
+    ccu: &RegisterBlock,
+    target_hz: u32,
+    delay: &mut impl DelayNs,
+    lock_timeout_us: u32,
+) -> Result<u32, PllLockTimeout> {
+    let factors = calculate_pixel_clock_factors(target_hz);
+    unsafe {
+        ccu.tcon_tv_clk.modify(|v| v.disable_clock_gating());
+        ccu.pll_video0_control.modify(|v| {
+            v.disable_pll()
+                .mask_pll_output()
+                .set_pll_n(factors.pll_n)
+                .set_pll_m(factors.pll_m)
+        });
+        ccu.pll_video0_control
+            .modify(|v| v.enable_pll_ldo().enable_pll().enable_lock());
+        let mut waited_us = 0;
+        while !ccu.pll_video0_control.read().is_locked() {
+            if waited_us >= lock_timeout_us {
+                return Err(PllLockTimeout);
+            }
+            delay.delay_us(1);
+            waited_us += 1;
+        }
+        ccu.pll_video0_control.modify(|v| v.unmask_pll_output());
+        ccu.tcon_tv_clk.modify(|v| {
+            v.set_clock_source(factors.source)
+                .set_factor_n(factors.factor_n)
+                .set_factor_m(factors.factor_m)
+                .enable_clock_gating()
+        });
+        ccu.hdmi_24m_clk.modify(|v| v.enable_clock_gating());
+    }
+    Ok(factors.frequency)
+}
+
+/// Check if the CPU is running from a safe oscillator.
+///
+/// Returns true only if CPU clock is sourced from the 24-MHz 'HOSC' oscillator or
+/// the 16-MHz RC oscillator, i.e. the CPU PLL can be reprogrammed without glitches.
+#[inline]
+pub fn cpu_running_on_safe_source(ccu: &RegisterBlock) -> bool {
+    matches!(
+        ccu.cpu_axi_config.read().clock_source(),
+        CpuClockSource::Hosc | CpuClockSource::Clk16MRC
+    )
+}
+
+/// Error of a PLL that failed to lock within the allowed time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PllLockTimeout;
+
+/// Reprogram DDR PLL and DRAM clock while keeping the memory controller safe.
+///
+/// Factors (N, M1, M0) are taken from `pll`; its enable, lock and gate bits are ignored.
+/// The sequence is: mask DRAM clock, assert DRAM and MBUS reset, reprogram DDR PLL,
+/// wait for lock, unmask PLL output, reprogram DRAM clock, deassert resets and unmask
+/// DRAM clock. PLL lock is polled every microsecond for at most `lock_timeout_us`
+/// microseconds; on timeout, DRAM and MBUS are left in reset with clock masked.
+///
+/// # Safety
+///
+/// DRAM content is lost; caller must not access DRAM during and after this function
+/// until the memory controller is initialized again.
+pub unsafe fn reconfigure_dram_pll(
+    ccu: &RegisterBlock,
+    pll: PllDdrControl,
+    source: DramClockSource,
+    factor_m: u8,
+    factor_n: PeriFactorN,
+    delay: &mut impl DelayNs,
+    lock_timeout_us: u32,
+) -> Result<(), PllLockTimeout> {
+    ccu.dram_clock.modify(|v| v.mask_clock());
+    ccu.dram_bgr.modify(|v| v.gate_mask());
+    ccu.dram_bgr.modify(|v| v.assert_reset());
+    ccu.mbus_clock.modify(|v| v.assert_reset());
+
+    let val = ccu
+        .pll_ddr_control
+        .read()
+        .mask_pll_output()
+        .set_pll_n(pll.pll_n())
+        .set_pll_m1(pll.pll_m1())
+        .set_pll_m0(pll.pll_m0())
+        .enable_pll()
+        .enable_pll_ldo()
+        .disable_lock();
+    ccu.pll_ddr_control.write(val);
+    ccu.pll_ddr_control.write(val.enable_lock());
+    let mut waited_us = 0;
+    while !ccu.pll_ddr_control.read().is_locked() {
+        if waited_us >= lock_timeout_us {
+            return Err(PllLockTimeout);
+        }
+        delay.delay_us(1);
+        waited_us += 1;
+    }
+    // PLL output should be stable for 20 us after lock before use.
+    delay.delay_us(20);
+    ccu.pll_ddr_control.modify(|v| v.unmask_pll_output());
+
+    DRAM::configure(ccu, source, factor_m, factor_n);
+
+    ccu.dram_bgr.modify(|v| v.deassert_reset());
+    ccu.mbus_clock.modify(|v| v.deassert_reset());
+    ccu.dram_bgr.modify(|v| v.gate_pass());
+    ccu.dram_clock.modify(|v| v.unmask_clock());
+    Ok(())
 }
 
 /// Peripheral that have clock reset feature in CCU.
@@ -526,6 +1070,34 @@ pub trait ClockGate: ClockReset {
     unsafe fn disable_in(ccu: &RegisterBlock);
     /// Deassert reset signal and unmask the clock gate.
     unsafe fn enable_in(ccu: &RegisterBlock);
+    /// Unmask clock gate, leaving reset signal unchanged.
+    ///
+    /// # Safety
+    ///
+    /// Caller must own this peripheral, or its driver may see it clocked unexpectedly.
+    #[inline]
+    unsafe fn enable_clock(ccu: &RegisterBlock) {
+        Self::unmask_gate_only(ccu);
+    }
+    /// Mask clock gate, leaving reset signal unchanged.
+    ///
+    /// # Safety
+    ///
+    /// Registers of this peripheral must not be accessed until its clock is enabled again.
+    #[inline]
+    unsafe fn disable_clock(ccu: &RegisterBlock) {
+        Self::mask_gate_only(ccu);
+    }
+    /// Assert and then deassert reset signal, leaving clock gate unchanged.
+    ///
+    /// # Safety
+    ///
+    /// All register state of this peripheral is lost; its driver must configure it again.
+    #[inline]
+    unsafe fn pulse_reset(ccu: &RegisterBlock) {
+        Self::assert_reset_only(ccu);
+        Self::deassert_reset_only(ccu);
+    }
     /// Reset this peripheral without reconfiguring clocks (if applicable).
     #[inline]
     unsafe fn reset(ccu: &RegisterBlock) {
@@ -754,19 +1326,84 @@ impl<const I: usize> ClockConfig for SPI<I> {
     }
 }
 
+/// SD/MMC Host Controller clock type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SMHC<const IDX: usize>;
+
+impl<const I: usize> ClockReset for SMHC<I> {
+    #[inline]
+    unsafe fn assert_reset_only(ccu: &RegisterBlock) {
+        ccu.smhc_bgr.modify(|v| v.assert_reset::<I>());
+    }
+    #[inline]
+    unsafe fn deassert_reset_only(ccu: &RegisterBlock) {
+        ccu.smhc_bgr.modify(|v| v.deassert_reset::<I>());
+    }
+}
+
+impl<const I: usize> ClockGate for SMHC<I> {
+    #[inline]
+    unsafe fn unmask_gate_only(ccu: &RegisterBlock) {
+        ccu.smhc_bgr.modify(|v| v.gate_pass::<I>());
+    }
+    #[inline]
+    unsafe fn mask_gate_only(ccu: &RegisterBlock) {
+        ccu.smhc_bgr.modify(|v| v.gate_mask::<I>());
+    }
+    #[inline]
+    unsafe fn disable_in(ccu: &RegisterBlock) {
+        ccu.smhc_bgr
+            .modify(|v| v.gate_mask::<I>().assert_reset::<I>());
+    }
+    #[inline]
+    unsafe fn enable_in(ccu: &RegisterBlock) {
+        ccu.smhc_bgr
+            .modify(|v| v.gate_pass::<I>().deassert_reset::<I>());
+    }
+}
+
+impl<const I: usize> ClockConfig for SMHC<I> {
+    type Source = SmhcClockSource;
+
+    #[inline]
+    unsafe fn configure(
+        ccu: &RegisterBlock,
+        source: Self::Source,
+        factor_m: u8,
+        factor_n: PeriFactorN,
+    ) {
+        let smhc_clk = ccu.smhc_clk[I].read();
+        ccu.smhc_clk[I].write(
+            smhc_clk
+                .set_clock_source(source)
+                .set_factor_m(factor_m)
+                .set_factor_n(factor_n)
+                .enable_clock_gating(),
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    extern crate std;
     use super::{
-        AxiFactorN, CpuAxiConfig, CpuClockSource, DramBusGating, DramClock, DramClockSource,
-        FactorP, MbusClock, PeriFactorN, RegisterBlock,
+        apb1_frequency, cpu_running_on_safe_source, d1_boot_clocks, psi_frequency, AxiFactorN,
+        CpuAxiConfig, CpuClockSource, DramBusGating, DramClock, DramClockSource, FactorP,
+        MbusClock, MbusClockSource, PeriFactorN, PllPeri0Control, PsiClock, PsiClockSource,
+        RegisterBlock, SmhcClock, SmhcClockSource, DRAM, SMHC, SPI, UART,
     };
+    use embedded_time::rate::Hertz;
     use memoffset::offset_of;
     #[test]
     fn offset_ccu() {
         assert_eq!(offset_of!(RegisterBlock, pll_cpu_control), 0x0);
         assert_eq!(offset_of!(RegisterBlock, pll_ddr_control), 0x10);
         assert_eq!(offset_of!(RegisterBlock, pll_peri0_control), 0x20);
+        assert_eq!(offset_of!(RegisterBlock, pll_video0_control), 0x40);
         assert_eq!(offset_of!(RegisterBlock, cpu_axi_config), 0x500);
+        assert_eq!(offset_of!(RegisterBlock, psi_clock), 0x510);
+        assert_eq!(offset_of!(RegisterBlock, apb0_clock), 0x520);
+        assert_eq!(offset_of!(RegisterBlock, apb1_clock), 0x524);
         assert_eq!(offset_of!(RegisterBlock, mbus_clock), 0x540);
         assert_eq!(offset_of!(RegisterBlock, dram_clock), 0x800);
         assert_eq!(offset_of!(RegisterBlock, dram_bgr), 0x80c);
@@ -775,6 +1412,8 @@ mod tests {
         assert_eq!(offset_of!(RegisterBlock, uart_bgr), 0x90c);
         assert_eq!(offset_of!(RegisterBlock, spi_clk), 0x940);
         assert_eq!(offset_of!(RegisterBlock, spi_bgr), 0x96c);
+        assert_eq!(offset_of!(RegisterBlock, hdmi_24m_clk), 0xb04);
+        assert_eq!(offset_of!(RegisterBlock, tcon_tv_clk), 0xb80);
     }
 
     #[test]
@@ -865,6 +1504,40 @@ mod tests {
         val = val.assert_reset();
         assert!(val.is_reset_asserted());
         assert_eq!(val.0, 0x00000000);
+
+        for i in 0..4u8 {
+            let cs_tmp = match i {
+                0x0 => MbusClockSource::Hosc,
+                0x1 => MbusClockSource::PllPeri2x,
+                0x2 => MbusClockSource::PllDdr,
+                0x3 => MbusClockSource::PllPeri800M,
+                _ => unreachable!(),
+            };
+
+            val = val.set_clock_source(cs_tmp);
+            assert_eq!(val.clock_source(), cs_tmp);
+            assert_eq!(val.0, (i as u32) << 24);
+        }
+
+        val = MbusClock(0x0).set_factor_m(0x7);
+        assert_eq!(val.factor_m(), 0x7);
+        assert_eq!(val.0, 0x00000007);
+
+        // source and divider leave reset bit untouched
+        val = MbusClock(0x0)
+            .deassert_reset()
+            .set_clock_source(MbusClockSource::PllDdr)
+            .set_factor_m(0x3);
+        assert!(!val.is_reset_asserted());
+        assert_eq!(val.0, 0x42000003);
+        val = val
+            .set_clock_source(MbusClockSource::Hosc)
+            .set_factor_m(0x0);
+        assert!(!val.is_reset_asserted());
+        assert_eq!(val.0, 0x40000000);
+        val = val.assert_reset();
+        assert_eq!(val.clock_source(), MbusClockSource::Hosc);
+        assert_eq!(val.0, 0x00000000);
     }
 
     #[test]
@@ -934,26 +1607,36 @@ mod tests {
     #[test]
     fn struct_dram_bgr_functions() {
         let mut val = DramBusGating(0x0);
+        assert!(val.is_gated());
+        assert!(val.is_in_reset());
 
         val = val.deassert_reset();
         assert_eq!(val.0, 0x00010000);
+        assert!(!val.is_in_reset());
 
         val = val.assert_reset();
         assert_eq!(val.0, 0x00000000);
+        assert!(val.is_in_reset());
 
         val = val.gate_pass();
         assert_eq!(val.0, 0x00000001);
+        assert!(!val.is_gated());
 
         val = val.gate_mask();
         assert_eq!(val.0, 0x00000000);
+        assert!(val.is_gated());
     }
 
     #[test]
     fn struct_uart_bgr_functions() {
         let mut val = super::UartBusGating(0x0);
+        assert!(val.is_gated::<0>());
+        assert!(val.is_in_reset::<0>());
 
         val = val.gate_pass::<0>();
         assert_eq!(val.0, 0x00000001);
+        assert!(!val.is_gated::<0>());
+        assert!(val.is_gated::<1>());
 
         val = val.gate_mask::<0>();
         assert_eq!(val.0, 0x00000000);
@@ -972,6 +1655,8 @@ mod tests {
 
         val = val.deassert_reset::<1>();
         assert_eq!(val.0, 0x00020000);
+        assert!(!val.is_in_reset::<1>());
+        assert!(val.is_in_reset::<0>());
 
         val = val.assert_reset::<1>();
         assert_eq!(val.0, 0x00000000);
@@ -1038,9 +1723,13 @@ mod tests {
     #[test]
     fn struct_spi_bgr_functions() {
         let mut val = super::SpiBusGating(0x0);
+        assert!(val.is_gated::<0>());
+        assert!(val.is_in_reset::<0>());
 
         val = val.gate_pass::<0>();
         assert_eq!(val.0, 0x00000001);
+        assert!(!val.is_gated::<0>());
+        assert!(val.is_gated::<1>());
 
         val = val.gate_mask::<0>();
         assert_eq!(val.0, 0x00000000);
@@ -1059,8 +1748,346 @@ mod tests {
 
         val = val.deassert_reset::<1>();
         assert_eq!(val.0, 0x00020000);
+        assert!(!val.is_in_reset::<1>());
+        assert!(val.is_in_reset::<0>());
 
         val = val.assert_reset::<1>();
         assert_eq!(val.0, 0x00000000);
     }
+
+    #[test]
+    fn struct_tcon_tv_clock_functions() {
+        let mut val = super::TconTvClock(0x0);
+
+        val = val.set_clock_source(super::TconTvClockSource::PllVideo0x4);
+        assert_eq!(val.clock_source(), super::TconTvClockSource::PllVideo0x4);
+        assert_eq!(val.0, 0x02000000);
+
+        val = val.set_factor_n(PeriFactorN::N8);
+        assert_eq!(val.factor_n(), PeriFactorN::N8);
+        assert_eq!(val.0, 0x02000300);
+
+        val = val.set_factor_m(0x0f);
+        assert_eq!(val.factor_m(), 0x0f);
+        assert_eq!(val.0, 0x0200030f);
+
+        val = val.enable_clock_gating();
+        assert!(val.is_clock_gating_enabled());
+        assert_eq!(val.0, 0x8200030f);
+
+        let mut val = super::Hdmi24MClock(0x0);
+
+        val = val.enable_clock_gating();
+        assert!(val.is_clock_gating_enabled());
+        assert_eq!(val.0, 0x80000000);
+
+        val = val.disable_clock_gating();
+        assert!(!val.is_clock_gating_enabled());
+        assert_eq!(val.0, 0x00000000);
+    }
+
+    #[test]
+    fn struct_smhc_bgr_functions() {
+        let mut val = super::SmhcBusGating(0x0);
+        assert!(val.is_gated::<2>());
+        assert!(val.is_in_reset::<2>());
+
+        val = val.gate_pass::<2>().deassert_reset::<2>();
+        assert_eq!(val.0, 0x00040004);
+        assert!(!val.is_gated::<2>());
+        assert!(!val.is_in_reset::<2>());
+        assert!(val.is_gated::<0>());
+        assert!(val.is_in_reset::<0>());
+
+        val = val.gate_mask::<2>();
+        assert_eq!(val.0, 0x00040000);
+        assert!(val.is_gated::<2>());
+        assert!(!val.is_in_reset::<2>());
+
+        val = val.assert_reset::<2>();
+        assert_eq!(val.0, 0x00000000);
+        assert!(val.is_in_reset::<2>());
+    }
+
+    #[test]
+    fn struct_apb_clock_functions() {
+        let mut val = super::ApbClock(0x0);
+
+        val = val.set_clock_source(super::ApbClockSource::PllPeri1x);
+        assert_eq!(val.clock_source(), super::ApbClockSource::PllPeri1x);
+        assert_eq!(val.0, 0x03000000);
+
+        val = val.set_factor_n(PeriFactorN::N4);
+        assert_eq!(val.factor_n(), PeriFactorN::N4);
+        assert_eq!(val.0, 0x03000200);
+
+        val = val.set_factor_m(0x1f);
+        assert_eq!(val.factor_m(), 0x1f);
+        assert_eq!(val.0, 0x0300021f);
+
+        val = val.set_clock_source(super::ApbClockSource::Hosc);
+        assert_eq!(val.clock_source(), super::ApbClockSource::Hosc);
+        assert_eq!(val.0, 0x0000021f);
+    }
+
+    #[test]
+    fn struct_psi_clock_functions() {
+        let mut val = super::PsiClock(0x0);
+
+        val = val.set_clock_source(super::PsiClockSource::PllPeri1x);
+        assert_eq!(val.clock_source(), super::PsiClockSource::PllPeri1x);
+        assert_eq!(val.0, 0x03000000);
+
+        val = val.set_factor_n(PeriFactorN::N2);
+        assert_eq!(val.factor_n(), PeriFactorN::N2);
+        assert_eq!(val.0, 0x03000100);
+
+        val = val.set_factor_m(0x2);
+        assert_eq!(val.factor_m(), 0x2);
+        assert_eq!(val.0, 0x03000102);
+
+        val = val.set_clock_source(super::PsiClockSource::Clk16MRC);
+        assert_eq!(val.clock_source(), super::PsiClockSource::Clk16MRC);
+        assert_eq!(val.0, 0x02000102);
+    }
+
+    struct MockDelay;
+
+    impl embedded_hal::delay::DelayNs for MockDelay {
+        fn delay_ns(&mut self, _ns: u32) {
+            std::thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn reconfigure_dram_pll_sequence() {
+        use super::{reconfigure_dram_pll, PllDdrControl, PllLockTimeout};
+        use core::sync::atomic::{AtomicU32, Ordering};
+
+        const PLL_DDR: usize = 0x10 / 4;
+        const MBUS_CLK: usize = 0x540 / 4;
+        const DRAM_CLK: usize = 0x800 / 4;
+        const DRAM_BGR: usize = 0x80c / 4;
+        const PLL_ENABLE_LOCK_ENABLE: u32 = (1 << 31) | (1 << 29);
+
+        let memory = [const { AtomicU32::new(0) }; 0x400];
+        let ccu = unsafe { &*(memory.as_ptr() as *const RegisterBlock) };
+        // DRAM running from PLL_DDR: clock unmasked, gate passed, resets deasserted
+        memory[DRAM_CLK].store(0x8000_0000, Ordering::SeqCst);
+        memory[DRAM_BGR].store(0x0001_0001, Ordering::SeqCst);
+        memory[MBUS_CLK].store(0x4000_0000, Ordering::SeqCst);
+        let pll = PllDdrControl::default()
+            .set_pll_n(65)
+            .set_pll_m1(0)
+            .set_pll_m0(1);
+
+        let snapshot = std::thread::scope(|s| {
+            let hardware = s.spawn(|| {
+                // emulate PLL locking once it is enabled with lock enabled
+                while memory[PLL_DDR].load(Ordering::SeqCst) & PLL_ENABLE_LOCK_ENABLE
+                    != PLL_ENABLE_LOCK_ENABLE
+                {
+                    std::thread::yield_now();
+                }
+                let snapshot = [
+                    memory[DRAM_CLK].load(Ordering::SeqCst),
+                    memory[DRAM_BGR].load(Ordering::SeqCst),
+                    memory[MBUS_CLK].load(Ordering::SeqCst),
+                    memory[PLL_DDR].load(Ordering::SeqCst),
+                ];
+                memory[PLL_DDR].fetch_or(1 << 28, Ordering::SeqCst);
+                snapshot
+            });
+            let ans = unsafe {
+                reconfigure_dram_pll(
+                    ccu,
+                    pll,
+                    DramClockSource::PllDdr,
+                    0,
+                    PeriFactorN::N2,
+                    &mut MockDelay,
+                    u32::MAX,
+                )
+            };
+            assert_eq!(ans, Ok(()));
+            hardware.join().unwrap()
+        });
+        // while PLL is locking: DRAM clock masked and not yet reprogrammed,
+        // DRAM gated and in reset, MBUS in reset, PLL output masked
+        assert_eq!(snapshot[0], 0x0000_0000);
+        assert_eq!(snapshot[1], 0x0000_0000);
+        assert_eq!(snapshot[2], 0x0000_0000);
+        assert_eq!(snapshot[3] & (1 << 27), 0);
+        assert_eq!(snapshot[3] & 0xff03, 0x4101);
+        // afterwards: DRAM clock reprogrammed and unmasked, resets released
+        let pll = ccu.pll_ddr_control.read();
+        assert!(pll.is_pll_output_unmasked());
+        assert!(pll.is_locked());
+        let dram_clk = ccu.dram_clock.read();
+        assert!(dram_clk.is_clock_unmasked());
+        assert_eq!(dram_clk.factor_n(), PeriFactorN::N2);
+        assert_eq!(memory[DRAM_BGR].load(Ordering::SeqCst), 0x0001_0001);
+        assert_eq!(memory[MBUS_CLK].load(Ordering::SeqCst), 0x4000_0000);
+
+        // PLL never locks: DRAM is left masked and in reset
+        memory[PLL_DDR].store(0, Ordering::SeqCst);
+        let ans = unsafe {
+            reconfigure_dram_pll(
+                ccu,
+                pll,
+                DramClockSource::PllDdr,
+                0,
+                PeriFactorN::N1,
+                &mut MockDelay,
+                100,
+            )
+        };
+        assert_eq!(ans, Err(PllLockTimeout));
+        assert!(!ccu.dram_clock.read().is_clock_unmasked());
+        assert_eq!(memory[DRAM_BGR].load(Ordering::SeqCst), 0x0000_0000);
+        assert_eq!(memory[MBUS_CLK].load(Ordering::SeqCst), 0x0000_0000);
+    }
+
+    #[test]
+    fn set_pixel_clock_lock_timeout() {
+        use super::{set_pixel_clock, PllLockTimeout};
+        use core::sync::atomic::{AtomicU32, Ordering};
+
+        const TCON_TV_CLK: usize = 0xb80 / 4;
+
+        let memory = [const { AtomicU32::new(0) }; 0x400];
+        let ccu = unsafe { &*(memory.as_ptr() as *const RegisterBlock) };
+        memory[TCON_TV_CLK].store(0x8000_0000, Ordering::SeqCst);
+        // video PLL never locks
+        let ans = set_pixel_clock(ccu, 148_500_000, &mut MockDelay, 100);
+        assert_eq!(ans, Err(PllLockTimeout));
+        assert!(ccu.pll_video0_control.read().is_pll_enabled());
+        assert!(!ccu.tcon_tv_clk.read().is_clock_gating_enabled());
+    }
+
+    #[test]
+    fn clock_gate_fine_control() {
+        use super::ClockGate;
+        use core::sync::atomic::{AtomicU32, Ordering};
+
+        let memory = [const { AtomicU32::new(0) }; 0x400];
+        let ccu = unsafe { &*(memory.as_ptr() as *const RegisterBlock) };
+        /// Check gate and reset operations of `T` on bus gating register at `offset`,
+        /// with gate bit `gate` and reset bit `reset`.
+        fn check<T: ClockGate>(
+            memory: &[AtomicU32],
+            ccu: &RegisterBlock,
+            offset: usize,
+            gate: u32,
+            reset: u32,
+        ) {
+            let bgr = &memory[offset / 4];
+            // other peripherals on the same register keep their bits
+            let others = !(gate | reset);
+            bgr.store(others, Ordering::SeqCst);
+            unsafe { T::enable_clock(ccu) };
+            assert_eq!(bgr.load(Ordering::SeqCst), others | gate);
+            unsafe { T::pulse_reset(ccu) };
+            assert_eq!(bgr.load(Ordering::SeqCst), others | gate | reset);
+            unsafe { T::disable_clock(ccu) };
+            assert_eq!(bgr.load(Ordering::SeqCst), others | reset);
+            bgr.store(0, Ordering::SeqCst);
+            unsafe { T::pulse_reset(ccu) };
+            assert_eq!(bgr.load(Ordering::SeqCst), reset);
+        }
+        check::<DRAM>(&memory, ccu, 0x80c, 1 << 0, 1 << 16);
+        check::<SMHC<1>>(&memory, ccu, 0x84c, 1 << 1, 1 << 17);
+        check::<UART<3>>(&memory, ccu, 0x90c, 1 << 3, 1 << 19);
+        check::<SPI<0>>(&memory, ccu, 0x96c, 1 << 0, 1 << 16);
+        check::<SPI<1>>(&memory, ccu, 0x96c, 1 << 1, 1 << 17);
+    }
+
+    #[test]
+    fn smhc_clock_set_frequency() {
+        const PERI_1X: u32 = 600_000_000;
+        let val = SmhcClock(0).set_frequency(PERI_1X, 50_000_000).unwrap();
+        assert_eq!(val.factor_n(), PeriFactorN::N1);
+        assert_eq!(val.factor_m(), 11);
+
+        // 600 MHz / 14 = 42.9 MHz is the closest without exceeding 45 MHz
+        let val = SmhcClock(0).set_frequency(PERI_1X, 45_000_000).unwrap();
+        assert_eq!(val.factor_n(), PeriFactorN::N1);
+        assert_eq!(val.factor_m(), 13);
+
+        // 400 kHz needs a divider of 1500; 600 MHz / 8 / 16 is the slowest
+        assert_eq!(SmhcClock(0).set_frequency(PERI_1X, 400_000), None);
+        let val = SmhcClock(0).set_frequency(PERI_1X, 5_000_000).unwrap();
+        assert_eq!(val.factor_n(), PeriFactorN::N8);
+        assert_eq!(val.factor_m(), 14);
+
+        // target above source clamps to the minimum divider
+        let val = SmhcClock(0x8100_0000)
+            .set_frequency(24_000_000, 50_000_000)
+            .unwrap();
+        assert_eq!(val.0, 0x8100_0000);
+        // high targets do not overflow `n * target_hz`
+        let val = SmhcClock(0).set_frequency(PERI_1X, PERI_1X).unwrap();
+        assert_eq!((val.factor_n(), val.factor_m()), (PeriFactorN::N1, 0));
+        let val = SmhcClock(0).set_frequency(PERI_1X, u32::MAX).unwrap();
+        assert_eq!((val.factor_n(), val.factor_m()), (PeriFactorN::N1, 0));
+        assert_eq!(SmhcClock(0).set_frequency(24_000_000, 0), None);
+    }
+
+    #[test]
+    fn smhc_clock_config() {
+        use super::{ClockConfig, ClockGate};
+        use core::sync::atomic::{AtomicU32, Ordering};
+        let memory = [const { AtomicU32::new(0) }; 0x400];
+        let ccu = unsafe { &*(memory.as_ptr() as *const RegisterBlock) };
+        unsafe {
+            SMHC::<2>::reconfigure(ccu, SmhcClockSource::PllPeri1x, 2, PeriFactorN::N2);
+        }
+        assert_eq!(memory[0x838 / 4].load(Ordering::SeqCst), 0x8100_0102);
+        assert_eq!(memory[0x84c / 4].load(Ordering::SeqCst), 0x0004_0004);
+        unsafe { SMHC::<2>::disable_in(ccu) };
+        assert_eq!(memory[0x84c / 4].load(Ordering::SeqCst), 0x0000_0000);
+    }
+
+    #[test]
+    fn cpu_safe_source() {
+        use core::sync::atomic::{AtomicU32, Ordering};
+        let memory = [const { AtomicU32::new(0) }; 0x400];
+        let ccu = unsafe { &*(memory.as_ptr() as *const RegisterBlock) };
+        let cases = [
+            (CpuClockSource::Hosc, false, true),
+            (CpuClockSource::Clk32K, false, false),
+            (CpuClockSource::Clk16MRC, false, true),
+            (CpuClockSource::PllCpu, true, false),
+            (CpuClockSource::PllPeri1x, false, false),
+            (CpuClockSource::PllPeri2x, false, false),
+            (CpuClockSource::PllPeri800M, false, false),
+        ];
+        for (source, from_pll_cpu, safe) in cases {
+            let val = CpuAxiConfig(0x0000_0301).set_clock_source(source);
+            assert_eq!(val.is_sourced_from_pll_cpu(), from_pll_cpu, "{:?}", source);
+            memory[0x500 / 4].store(val.0, Ordering::SeqCst);
+            assert_eq!(cpu_running_on_safe_source(ccu), safe, "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn d1_boot_clock_defaults() {
+        let clocks = d1_boot_clocks();
+        assert_eq!(clocks.psi, Hertz(600_000_000u32));
+        assert_eq!(clocks.apb1, Hertz(24_000_000u32));
+        let wafer = crate::wafer::d1::boot_clocks();
+        assert_eq!((wafer.psi, wafer.apb1), (clocks.psi, clocks.apb1));
+
+        // boot ROM clock tree: PLL_PERI(1X) at 600 MHz feeds PSI, APB1 from HOSC
+        let memory = [const { core::sync::atomic::AtomicU32::new(0) }; 0x400];
+        let ccu = unsafe { &*(memory.as_ptr() as *const RegisterBlock) };
+        unsafe {
+            ccu.pll_peri0_control
+                .write(PllPeri0Control::default().set_pll_n(99).set_pll_p0(1));
+            ccu.psi_clock
+                .write(PsiClock::default().set_clock_source(PsiClockSource::PllPeri1x));
+        }
+        assert_eq!(psi_frequency(ccu), clocks.psi);
+        assert_eq!(apb1_frequency(ccu, clocks.psi), clocks.apb1);
+    }
 }
@@ -0,0 +1,377 @@
+use super::{
+    ApbClockSource, Clocks, DramClockSource, PeriFactorN, PsiClockSource, RegisterBlock,
+    SmhcClockSource, SpiClockSource, TconTvClockSource,
+};
+use embedded_time::rate::Hertz;
+
+/// Frequency of the 24-MHz 'HOSC' external oscillator.
+const HOSC: u64 = 24_000_000;
+/// Frequency of the 32-KHz clock.
+const CLK32K: u64 = 32_768;
+/// Nominal frequency of the 16-MHz RC oscillator.
+const CLK16M_RC: u64 = 16_000_000;
+
+/// Phase-locked loop that peripheral clocks can be sourced from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Pll {
+    /// Peripheral PLL 0, including its 1x, 2x and 800M outputs.
+    Peri0,
+    /// DDR PLL.
+    Ddr,
+    /// Video PLL 0, including its 1x and 4x outputs.
+    Video0,
+}
+
+/// Peripheral whose clock frequency follows a PLL.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Peripheral {
+    /// UART instance, clocked from APB1.
+    Uart(u8),
+    /// SPI instance.
+    Spi(u8),
+    /// SMHC instance.
+    Smhc(u8),
+    /// DRAM controller.
+    Dram,
+    /// TCON TV.
+    TconTv,
+}
+
+/// Registry of peripherals depending on PLLs, holding up to `N` entries.
+///
+/// Drivers register the PLL their clock is sourced from; after reprogramming
+/// a PLL, [`recompute_dependents`] lists affected peripherals so that their
+/// dividers can be derived again.
+#[derive(Clone, Copy, Debug)]
+pub struct Dependents<const N: usize> {
+    entries: [Option<(Peripheral, Pll)>; N],
+}
+
+impl<const N: usize> Dependents<N> {
+    /// Create an empty registry.
+    #[inline]
+    pub const fn new() -> Self {
+        Self { entries: [None; N] }
+    }
+    /// Register that `peripheral` is clocked from `pll`.
+    ///
+    /// A peripheral registered before is updated to the new PLL. Returns
+    /// `Err(peripheral)` if the registry is full.
+    #[inline]
+    pub fn register(&mut self, peripheral: Peripheral, pll: Pll) -> Result<(), Peripheral> {
+        if let Some(entry) = self
+            .entries
+            .iter_mut()
+            .find(|e| matches!(e, Some((p, _)) if *p == peripheral))
+        {
+            *entry = Some((peripheral, pll));
+            return Ok(());
+        }
+        match self.entries.iter_mut().find(|e| e.is_none()) {
+            Some(entry) => {
+                *entry = Some((peripheral, pll));
+                Ok(())
+            }
+            None => Err(peripheral),
+        }
+    }
+    /// Remove `peripheral` from the registry.
+    #[inline]
+    pub fn unregister(&mut self, peripheral: Peripheral) {
+        for entry in self.entries.iter_mut() {
+            if matches!(entry, Some((p, _)) if *p == peripheral) {
+                *entry = None;
+            }
+        }
+    }
+    /// Iterate over peripherals depending on `pll`.
+    #[inline]
+    pub fn dependents_of(&self, pll: Pll) -> impl Iterator<Item = Peripheral> + '_ {
+        self.entries.iter().filter_map(move |e| {
+            e.filter(|(_, p)| *p == pll)
+                .map(|(peripheral, _)| peripheral)
+        })
+    }
+}
+
+impl<const N: usize> Default for Dependents<N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// List peripherals depending on `pll` along with their current frequencies.
+///
+/// Call this after reprogramming `pll`. Frequencies are read back from `ccu`;
+/// it is `None` if the peripheral is sourced from a clock whose frequency
+/// cannot be derived from CCU registers.
+#[inline]
+pub fn recompute_dependents<'a, const N: usize>(
+    ccu: &'a RegisterBlock,
+    dependents: &'a Dependents<N>,
+    pll: Pll,
+    clocks: &'a Clocks,
+) -> impl Iterator<Item = (Peripheral, Option<Hertz>)> + 'a {
+    dependents.dependents_of(pll).map(move |p| {
+        (
+            p,
+            peripheral_frequency(ccu, p, clocks).map(|f| Hertz(f as u32)),
+        )
+    })
+}
+
+/// Calculate PSI clock frequency from CCU configuration.
+///
+/// The 16-MHz RC oscillator is taken at its nominal frequency.
+#[inline]
+pub fn psi_frequency(ccu: &RegisterBlock) -> Hertz {
+    let psi = ccu.psi_clock.read();
+    let source = match psi.clock_source() {
+        PsiClockSource::Hosc => HOSC,
+        PsiClockSource::Clk32K => CLK32K,
+        PsiClockSource::Clk16MRC => CLK16M_RC,
+        PsiClockSource::PllPeri1x => pll_peri0_2x(ccu) / 2,
+    };
+    Hertz((source / factor_n(psi.factor_n()) / (psi.factor_m() as u64 + 1)) as u32)
+}
+
+/// Calculate APB1 clock frequency from CCU configuration and PSI clock frequency `psi`.
+///
+/// Use it with [`psi_frequency`] to fill in [`Clocks::apb1`] from what the
+/// clock tree actually produces.
+#[inline]
+pub fn apb1_frequency(ccu: &RegisterBlock, psi: Hertz) -> Hertz {
+    let apb1 = ccu.apb1_clock.read();
+    let source = match apb1.clock_source() {
+        ApbClockSource::Hosc => HOSC,
+        ApbClockSource::Clk32K => CLK32K,
+        ApbClockSource::Psi => psi.0 as u64,
+        ApbClockSource::PllPeri1x => pll_peri0_2x(ccu) / 2,
+    };
+    Hertz((source / factor_n(apb1.factor_n()) / (apb1.factor_m() as u64 + 1)) as u32)
+}
+
+fn peripheral_frequency(
+    ccu: &RegisterBlock,
+    peripheral: Peripheral,
+    clocks: &Clocks,
+) -> Option<u64> {
+    match peripheral {
+        Peripheral::Uart(_) => Some(apb1_frequency(ccu, clocks.psi).0 as u64),
+        Peripheral::Spi(i) => {
+            let spi_clk = ccu.spi_clk[i as usize].read();
+            let source = match spi_clk.clock_source() {
+                SpiClockSource::Hosc => HOSC,
+                SpiClockSource::PllPeri1x => pll_peri0_2x(ccu) / 2,
+                SpiClockSource::PllPeri2x => pll_peri0_2x(ccu),
+                SpiClockSource::PllAudio1Div2 | SpiClockSource::PllAudio1Div5 => return None,
+            };
+            Some(source / factor_n(spi_clk.factor_n()) / (spi_clk.factor_m() as u64 + 1))
+        }
+        Peripheral::Smhc(i) => {
+            let smhc_clk = ccu.smhc_clk[i as usize].read();
+            let source = match smhc_clk.clock_source() {
+                SmhcClockSource::Hosc => HOSC,
+                SmhcClockSource::PllPeri1x => pll_peri0_2x(ccu) / 2,
+                SmhcClockSource::PllPeri2x => pll_peri0_2x(ccu),
+                SmhcClockSource::PllPeri800M => pll_peri0_800m(ccu),
+                SmhcClockSource::PllAudio1Div2 => return None,
+            };
+            Some(source / factor_n(smhc_clk.factor_n()) / (smhc_clk.factor_m() as u64 + 1))
+        }
+        Peripheral::Dram => {
+            let dram_clk = ccu.dram_clock.read();
+            let source = match dram_clk.clock_source() {
+                DramClockSource::PllDdr => {
+                    let pll = ccu.pll_ddr_control.read();
+                    HOSC * (pll.pll_n() as u64 + 1)
+                        / (pll.pll_m1() as u64 + 1)
+                        / (pll.pll_m0() as u64 + 1)
+                }
+                DramClockSource::PllPeri2x => pll_peri0_2x(ccu),
+                DramClockSource::PllPeri800M => pll_peri0_800m(ccu),
+                DramClockSource::PllAudio1Div2 => return None,
+            };
+            Some(source / factor_n(dram_clk.factor_n()) / (dram_clk.factor_m() as u64 + 1))
+        }
+        Peripheral::TconTv => {
+            let tcon_clk = ccu.tcon_tv_clk.read();
+            let source = match tcon_clk.clock_source() {
+                TconTvClockSource::Hosc => HOSC,
+                TconTvClockSource::PllVideo0x1 => pll_video0_4x(ccu) / 4,
+                TconTvClockSource::PllVideo0x4 => pll_video0_4x(ccu),
+                TconTvClockSource::PllPeri2x => pll_peri0_2x(ccu),
+                TconTvClockSource::PllVideo1x1
+                | TconTvClockSource::PllVideo1x4
+                | TconTvClockSource::PllAudio1Div2 => return None,
+            };
+            Some(source / factor_n(tcon_clk.factor_n()) / (tcon_clk.factor_m() as u64 + 1))
+        }
+    }
+}
+
+#[inline]
+fn pll_peri0_2x(ccu: &RegisterBlock) -> u64 {
+    let pll = ccu.pll_peri0_control.read();
+    pll.peri_2x_hz(Hertz(HOSC as u32)).0 as u64
+}
+
+#[inline]
+fn pll_peri0_800m(ccu: &RegisterBlock) -> u64 {
+    let pll = ccu.pll_peri0_control.read();
+    pll.peri_800m_hz(Hertz(HOSC as u32)).0 as u64
+}
+
+#[inline]
+fn pll_video0_4x(ccu: &RegisterBlock) -> u64 {
+    let pll = ccu.pll_video0_control.read();
+    HOSC * (pll.pll_n() as u64 + 1) / (pll.pll_m() as u64 + 1)
+}
+
+#[inline]
+const fn factor_n(val: PeriFactorN) -> u64 {
+    match val {
+        PeriFactorN::N1 => 1,
+        PeriFactorN::N2 => 2,
+        PeriFactorN::N4 => 4,
+        PeriFactorN::N8 => 8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apb1_frequency, psi_frequency, recompute_dependents, Dependents, Peripheral, Pll};
+    use crate::ccu::{
+        ApbClock, ApbClockSource, Clocks, PeriFactorN, PllPeri0Control, PsiClock, PsiClockSource,
+        RegisterBlock, SmhcClock, SmhcClockSource, SpiClock, SpiClockSource, TconTvClock,
+        TconTvClockSource,
+    };
+    use core::sync::atomic::AtomicU32;
+    use embedded_time::rate::Hertz;
+
+    #[test]
+    fn recompute_pll_peri0_dependents() {
+        let memory = [const { AtomicU32::new(0) }; 0x400];
+        let ccu = unsafe { &*(memory.as_ptr() as *const RegisterBlock) };
+        let clocks = Clocks {
+            psi: Hertz(200_000_000u32),
+            apb1: Hertz(24_000_000u32),
+        };
+        unsafe {
+            // PLL_PERI(2X) = 1.2 GHz, PLL_PERI(1X) = 600 MHz, PLL_PERI(800M) = 800 MHz
+            ccu.pll_peri0_control.write(PllPeri0Control::default());
+            ccu.apb1_clock.write(
+                ApbClock(0)
+                    .set_clock_source(ApbClockSource::PllPeri1x)
+                    .set_factor_n(PeriFactorN::N2)
+                    .set_factor_m(2),
+            );
+            ccu.spi_clk[0].write(
+                SpiClock(0)
+                    .set_clock_source(SpiClockSource::PllPeri1x)
+                    .set_factor_n(PeriFactorN::N1)
+                    .set_factor_m(5),
+            );
+            ccu.smhc_clk[2].write(
+                SmhcClock(0)
+                    .set_clock_source(SmhcClockSource::PllPeri800M)
+                    .set_factor_n(PeriFactorN::N2)
+                    .set_factor_m(1),
+            );
+            // PLL_VIDEO0(4X) = 2.4 GHz, PLL_VIDEO0(1X) = 600 MHz
+            ccu.pll_video0_control.modify(|v| v.set_pll_n(99));
+            ccu.tcon_tv_clk.write(
+                TconTvClock(0)
+                    .set_clock_source(TconTvClockSource::PllVideo0x1)
+                    .set_factor_n(PeriFactorN::N4),
+            );
+        }
+
+        let mut dependents = Dependents::<4>::new();
+        dependents
+            .register(Peripheral::Uart(0), Pll::Peri0)
+            .unwrap();
+        dependents.register(Peripheral::Spi(0), Pll::Peri0).unwrap();
+        dependents
+            .register(Peripheral::TconTv, Pll::Video0)
+            .unwrap();
+        dependents
+            .register(Peripheral::Smhc(2), Pll::Peri0)
+            .unwrap();
+        assert_eq!(
+            dependents.register(Peripheral::Dram, Pll::Ddr),
+            Err(Peripheral::Dram)
+        );
+
+        let mut ans = [(Peripheral::Dram, None); 3];
+        let mut count = 0;
+        for item in recompute_dependents(ccu, &dependents, Pll::Peri0, &clocks) {
+            ans[count] = item;
+            count += 1;
+        }
+        assert_eq!(count, 3);
+        assert_eq!(ans[0], (Peripheral::Uart(0), Some(Hertz(100_000_000u32))));
+        assert_eq!(ans[1], (Peripheral::Spi(0), Some(Hertz(100_000_000u32))));
+        assert_eq!(ans[2], (Peripheral::Smhc(2), Some(Hertz(200_000_000u32))));
+
+        // halve PLL_PERI: N = 50 instead of 100
+        unsafe {
+            ccu.pll_peri0_control.modify(|v| v.set_pll_n(49));
+        }
+        let mut ans = [(Peripheral::Dram, None); 3];
+        let mut count = 0;
+        for item in recompute_dependents(ccu, &dependents, Pll::Peri0, &clocks) {
+            ans[count] = item;
+            count += 1;
+        }
+        assert_eq!(count, 3);
+        assert_eq!(ans[0], (Peripheral::Uart(0), Some(Hertz(50_000_000u32))));
+        assert_eq!(ans[1], (Peripheral::Spi(0), Some(Hertz(50_000_000u32))));
+        assert_eq!(ans[2], (Peripheral::Smhc(2), Some(Hertz(100_000_000u32))));
+
+        dependents.unregister(Peripheral::Spi(0));
+        assert_eq!(
+            recompute_dependents(ccu, &dependents, Pll::Peri0, &clocks).count(),
+            2
+        );
+        assert_eq!(
+            recompute_dependents(ccu, &dependents, Pll::Video0, &clocks).next(),
+            Some((Peripheral::TconTv, Some(Hertz(150_000_000u32))))
+        );
+    }
+
+    #[test]
+    fn psi_and_apb1_frequency() {
+        let memory = [const { AtomicU32::new(0) }; 0x400];
+        let ccu = unsafe { &*(memory.as_ptr() as *const RegisterBlock) };
+        // PLL_PERI(2X) = 24 MHz * 100 / 2 = 1.2 GHz, PLL_PERI(1X) = 600 MHz
+        unsafe {
+            ccu.pll_peri0_control
+                .write(PllPeri0Control::RESET.set_pll_n(99).set_pll_p0(1));
+            ccu.psi_clock.write(
+                PsiClock::default()
+                    .set_clock_source(PsiClockSource::PllPeri1x)
+                    .set_factor_n(PeriFactorN::N1)
+                    .set_factor_m(2),
+            );
+            ccu.apb1_clock.write(
+                ApbClock::default()
+                    .set_clock_source(ApbClockSource::Psi)
+                    .set_factor_n(PeriFactorN::N2)
+                    .set_factor_m(0),
+            );
+        }
+        let psi = psi_frequency(ccu);
+        assert_eq!(psi, Hertz(200_000_000u32));
+        assert_eq!(apb1_frequency(ccu, psi), Hertz(100_000_000u32));
+
+        unsafe {
+            ccu.psi_clock
+                .write(PsiClock::default().set_clock_source(PsiClockSource::Hosc));
+        }
+        let psi = psi_frequency(ccu);
+        assert_eq!(psi, Hertz(24_000_000u32));
+        assert_eq!(apb1_frequency(ccu, psi), Hertz(12_000_000u32));
+    }
+}
@@ -1,5 +1,7 @@
 //! Divide factors.
 
+use super::TconTvClockSource;
+
 /// Peripheral clock divide factor N.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum PeriFactorN {
@@ -60,4 +62,95 @@ pub fn calculate_best_peripheral_factors_nm(f_src: u32, f_dst: u32) -> (PeriFact
     (factor_n, factor_m)
 }
 
-// TODO: test module
+/// Video PLL and TCON TV clock factors to generate a pixel clock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PixelClockFactors {
+    /// Video PLL 0 N factor, register value (multiplier minus one).
+    pub pll_n: u8,
+    /// Video PLL 0 M factor, register value (input divider minus one).
+    pub pll_m: u8,
+    /// TCON TV clock source.
+    pub source: TconTvClockSource,
+    /// TCON TV clock divide factor N.
+    pub factor_n: PeriFactorN,
+    /// TCON TV clock divide factor M, register value (divider minus one).
+    pub factor_m: u8,
+    /// Achieved pixel clock frequency in Hz.
+    pub frequency: u32,
+}
+
+/// Calculate video PLL and TCON TV clock factors that best approximate `f_dst`.
+///
+/// Video PLL 0 runs at 24 MHz * N / M within 252 MHz ..= 2.4 GHz at its 4x output.
+/// On equal error, the lowest PLL frequency is preferred.
+pub fn calculate_pixel_clock_factors(f_dst: u32) -> PixelClockFactors {
+    const HOSC: u64 = 24_000_000;
+    const VCO_MIN: u64 = 252_000_000;
+    const VCO_MAX: u64 = 2_400_000_000;
+    let mut best: Option<(u32, u64, PixelClockFactors)> = None;
+    for pll_m in 1u64..=2 {
+        for pll_n in 12u64..=256 {
+            let vco = HOSC * pll_n / pll_m;
+            if !(VCO_MIN..=VCO_MAX).contains(&vco) {
+                continue;
+            }
+            for (source, post_div) in [
+                (TconTvClockSource::PllVideo0x1, 4),
+                (TconTvClockSource::PllVideo0x4, 1),
+            ] {
+                let f_src = vco / post_div;
+                for (factor_n, n) in [
+                    (PeriFactorN::N1, 1),
+                    (PeriFactorN::N2, 2),
+                    (PeriFactorN::N4, 4),
+                    (PeriFactorN::N8, 8),
+                ] {
+                    for m in 1u64..=16 {
+                        let actual = (f_src / n / m) as u32;
+                        let err = actual.abs_diff(f_dst);
+                        let better = match best {
+                            None => true,
+                            Some((best_err, best_vco, _)) => {
+                                err < best_err || (err == best_err && vco < best_vco)
+                            }
+                        };
+                        if better {
+                            let factors = PixelClockFactors {
+                                pll_n: (pll_n - 1) as u8,
+                                pll_m: (pll_m - 1) as u8,
+                                source,
+                                factor_n,
+                                factor_m: (m - 1) as u8,
+                                frequency: actual,
+                            };
+                            best = Some((err, vco, factors));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    best.unwrap().2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{calculate_pixel_clock_factors, PeriFactorN, TconTvClockSource};
+
+    #[test]
+    fn pixel_clock_1080p60() {
+        let factors = calculate_pixel_clock_factors(148_500_000);
+        assert_eq!(factors.pll_n, 98);
+        assert_eq!(factors.pll_m, 1);
+        assert_eq!(factors.source, TconTvClockSource::PllVideo0x1);
+        assert_eq!(factors.factor_n, PeriFactorN::N1);
+        assert_eq!(factors.factor_m, 1);
+        assert_eq!(factors.frequency, 148_500_000);
+    }
+
+    #[test]
+    fn pixel_clock_720p60() {
+        let factors = calculate_pixel_clock_factors(74_250_000);
+        assert_eq!(factors.frequency, 74_250_000);
+    }
+}
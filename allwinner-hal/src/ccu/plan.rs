@@ -0,0 +1,395 @@
+//! Clock tree plans evaluated at compile time.
+use super::{
+    bring_up_pll, cpu_running_on_safe_source, ApbClock, ApbClockSource, AxiFactorN, CpuAxiConfig,
+    CpuClockSource, FactorP, PeriFactorN, PllCpuControl, PllLockTimeout, PllPeri0Control, PsiClock,
+    RegisterBlock, SmhcBusGating, SmhcClock, SmhcClockSource, SpiBusGating, SpiClock,
+    SpiClockSource, UartBusGating,
+};
+use embedded_hal::delay::DelayNs;
+
+/// Clock source and divide factors of a module clock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ModuleClock<S> {
+    /// Clock source.
+    pub source: S,
+    /// Divide factor N.
+    pub factor_n: PeriFactorN,
+    /// Divide factor M, as written into the register.
+    pub factor_m: u8,
+}
+
+/// Description of a fixed clock tree.
+///
+/// PLL factors are raw register field values. Peripherals set to `None` or
+/// `false` are left gated and in reset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ClockPlan {
+    /// CPU PLL N factor.
+    pub pll_cpu_n: u8,
+    /// CPU PLL M factor.
+    pub pll_cpu_m: u8,
+    /// Peripheral PLL 0 N factor.
+    pub pll_peri0_n: u8,
+    /// Peripheral PLL 0 M factor.
+    pub pll_peri0_m: u8,
+    /// Peripheral PLL 0 P0 factor.
+    pub pll_peri0_p0: u8,
+    /// Peripheral PLL 0 P1 factor.
+    pub pll_peri0_p1: u8,
+    /// CPU clock source.
+    pub cpu_source: CpuClockSource,
+    /// CPU clock divide factor P.
+    pub cpu_factor_p: FactorP,
+    /// CPU AXI divide factor N.
+    pub cpu_axi_factor_n: AxiFactorN,
+    /// CPU clock divide factor M.
+    pub cpu_factor_m: u8,
+    /// APB1 clock.
+    pub apb1: ModuleClock<ApbClockSource>,
+    /// UART0 to UART5 bus clocks.
+    pub uart: [bool; 6],
+    /// SPI0 and SPI1 module clocks.
+    pub spi: [Option<ModuleClock<SpiClockSource>>; 2],
+    /// SMHC0 to SMHC2 module clocks.
+    pub smhc: [Option<ModuleClock<SmhcClockSource>>; 3],
+}
+
+/// Register values produced by a [`ClockPlan`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ClockRegisters {
+    /// CPU PLL Control register.
+    pub pll_cpu_control: PllCpuControl,
+    /// Peripheral PLL 0 Control register.
+    pub pll_peri0_control: PllPeri0Control,
+    /// CPU AXI Configuration register.
+    pub cpu_axi_config: CpuAxiConfig,
+    /// APB1 Clock register.
+    pub apb1_clock: ApbClock,
+    /// UART Bus Gating Reset register.
+    pub uart_bgr: UartBusGating,
+    /// SPI0 and SPI1 Clock registers.
+    pub spi_clk: [SpiClock; 2],
+    /// SPI Bus Gating Reset register.
+    pub spi_bgr: SpiBusGating,
+    /// SMHC0 to SMHC2 Clock registers.
+    pub smhc_clk: [SmhcClock; 3],
+    /// SMHC Bus Gating Reset register.
+    pub smhc_bgr: SmhcBusGating,
+}
+
+impl ClockPlan {
+    /// Compute register values of this plan.
+    #[inline]
+    pub const fn registers(&self) -> ClockRegisters {
+        let pll_cpu_control = PllCpuControl::RESET
+            .enable_pll()
+            .enable_pll_ldo()
+            .enable_lock()
+            .unmask_pll_output()
+            .set_pll_n(self.pll_cpu_n)
+            .set_pll_m(self.pll_cpu_m);
+        let pll_peri0_control = PllPeri0Control::RESET
+            .enable_pll()
+            .enable_pll_ldo()
+            .enable_lock()
+            .unmask_pll_output()
+            .set_pll_n(self.pll_peri0_n)
+            .set_pll_m(self.pll_peri0_m)
+            .set_pll_p0(self.pll_peri0_p0)
+            .set_pll_p1(self.pll_peri0_p1);
+        let cpu_axi_config = CpuAxiConfig(0)
+            .set_clock_source(self.cpu_source)
+            .set_factor_p(self.cpu_factor_p)
+            .set_factor_n(self.cpu_axi_factor_n)
+            .set_factor_m(self.cpu_factor_m);
+        let apb1_clock = ApbClock(0)
+            .set_clock_source(self.apb1.source)
+            .set_factor_n(self.apb1.factor_n)
+            .set_factor_m(self.apb1.factor_m);
+
+        let mut uart_bgr = UartBusGating(0);
+        if self.uart[0] {
+            uart_bgr = uart_bgr.gate_pass::<0>().deassert_reset::<0>();
+        }
+        if self.uart[1] {
+            uart_bgr = uart_bgr.gate_pass::<1>().deassert_reset::<1>();
+        }
+        if self.uart[2] {
+            uart_bgr = uart_bgr.gate_pass::<2>().deassert_reset::<2>();
+        }
+        if self.uart[3] {
+            uart_bgr = uart_bgr.gate_pass::<3>().deassert_reset::<3>();
+        }
+        if self.uart[4] {
+            uart_bgr = uart_bgr.gate_pass::<4>().deassert_reset::<4>();
+        }
+        if self.uart[5] {
+            uart_bgr = uart_bgr.gate_pass::<5>().deassert_reset::<5>();
+        }
+
+        let mut spi_clk = [SpiClock(0); 2];
+        let mut spi_bgr = SpiBusGating(0);
+        if let Some(clock) = self.spi[0] {
+            spi_clk[0] = spi_clock(clock);
+            spi_bgr = spi_bgr.gate_pass::<0>().deassert_reset::<0>();
+        }
+        if let Some(clock) = self.spi[1] {
+            spi_clk[1] = spi_clock(clock);
+            spi_bgr = spi_bgr.gate_pass::<1>().deassert_reset::<1>();
+        }
+
+        let mut smhc_clk = [SmhcClock(0); 3];
+        let mut smhc_bgr = SmhcBusGating(0);
+        if let Some(clock) = self.smhc[0] {
+            smhc_clk[0] = smhc_clock(clock);
+            smhc_bgr = smhc_bgr.gate_pass::<0>().deassert_reset::<0>();
+        }
+        if let Some(clock) = self.smhc[1] {
+            smhc_clk[1] = smhc_clock(clock);
+            smhc_bgr = smhc_bgr.gate_pass::<1>().deassert_reset::<1>();
+        }
+        if let Some(clock) = self.smhc[2] {
+            smhc_clk[2] = smhc_clock(clock);
+            smhc_bgr = smhc_bgr.gate_pass::<2>().deassert_reset::<2>();
+        }
+
+        ClockRegisters {
+            pll_cpu_control,
+            pll_peri0_control,
+            cpu_axi_config,
+            apb1_clock,
+            uart_bgr,
+            spi_clk,
+            spi_bgr,
+            smhc_clk,
+            smhc_bgr,
+        }
+    }
+}
+
+#[inline]
+const fn spi_clock(clock: ModuleClock<SpiClockSource>) -> SpiClock {
+    SpiClock(0)
+        .set_clock_source(clock.source)
+        .set_factor_n(clock.factor_n)
+        .set_factor_m(clock.factor_m)
+}
+
+#[inline]
+const fn smhc_clock(clock: ModuleClock<SmhcClockSource>) -> SmhcClock {
+    SmhcClock(0)
+        .set_clock_source(clock.source)
+        .set_factor_n(clock.factor_n)
+        .set_factor_m(clock.factor_m)
+        .enable_clock_gating()
+}
+
+impl ClockRegisters {
+    /// Write all register values into the clock control unit.
+    ///
+    /// If the CPU is not running on a safe oscillator, it is switched to 'HOSC'
+    /// first. PLLs are then brought up with [`bring_up_pll`], followed by clock
+    /// sources and dividers, and bus gates and resets at last.
+    ///
+    /// PSI, APB0 and APB1 may run from 'PLL_PERI(1X)', so they are moved to
+    /// undivided 'HOSC' while the peripheral PLL is reprogrammed. PSI and APB0
+    /// are restored once it locks; APB1 takes the value of this plan.
+    ///
+    /// Each PLL lock is waited for at most `lock_timeout_us` microseconds; on
+    /// timeout, the CPU and buses are left on 'HOSC' and no later register is
+    /// written.
+    ///
+    /// # Safety
+    ///
+    /// Changing CPU and bus clocks affects every running peripheral; caller must
+    /// ensure no peripheral is in use while the clock tree is replaced.
+    #[inline]
+    pub unsafe fn apply(
+        &self,
+        ccu: &RegisterBlock,
+        delay: &mut impl DelayNs,
+        lock_timeout_us: u32,
+    ) -> Result<(), PllLockTimeout> {
+        if !cpu_running_on_safe_source(ccu) {
+            ccu.cpu_axi_config
+                .modify(|v| v.set_clock_source(CpuClockSource::Hosc));
+        }
+        ccu.pll_cpu_control
+            .write(self.pll_cpu_control.mask_pll_output());
+        bring_up_pll(&ccu.pll_cpu_control, delay, lock_timeout_us)?;
+        let (psi_clock, apb0_clock) = (ccu.psi_clock.read(), ccu.apb0_clock.read());
+        // APB may run from PSI, so it is moved away first.
+        ccu.apb0_clock.write(ApbClock::default());
+        ccu.apb1_clock.write(ApbClock::default());
+        ccu.psi_clock.write(PsiClock::default());
+        ccu.pll_peri0_control
+            .write(self.pll_peri0_control.mask_pll_output());
+        bring_up_pll(&ccu.pll_peri0_control, delay, lock_timeout_us)?;
+        ccu.psi_clock.write(psi_clock);
+        ccu.apb0_clock.write(apb0_clock);
+        ccu.cpu_axi_config.write(self.cpu_axi_config);
+        ccu.apb1_clock.write(self.apb1_clock);
+        for (reg, val) in ccu.spi_clk.iter().zip(self.spi_clk) {
+            reg.write(val);
+        }
+        for (reg, val) in ccu.smhc_clk.iter().zip(self.smhc_clk) {
+            reg.write(val);
+        }
+        ccu.uart_bgr.write(self.uart_bgr);
+        ccu.spi_bgr.write(self.spi_bgr);
+        ccu.smhc_bgr.write(self.smhc_bgr);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::{ClockPlan, ClockRegisters, ModuleClock};
+    use crate::ccu::{
+        ApbClockSource, AxiFactorN, CpuClockSource, FactorP, PeriFactorN, PllLockTimeout,
+        RegisterBlock, SmhcClockSource, SpiClockSource,
+    };
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    const PLAN: ClockPlan = ClockPlan {
+        pll_cpu_n: 41,
+        pll_cpu_m: 0,
+        pll_peri0_n: 99,
+        pll_peri0_m: 0,
+        pll_peri0_p0: 1,
+        pll_peri0_p1: 2,
+        cpu_source: CpuClockSource::PllCpu,
+        cpu_factor_p: FactorP::P1,
+        cpu_axi_factor_n: AxiFactorN::N2,
+        cpu_factor_m: 0,
+        apb1: ModuleClock {
+            source: ApbClockSource::Hosc,
+            factor_n: PeriFactorN::N1,
+            factor_m: 0,
+        },
+        uart: [true, false, false, false, false, false],
+        spi: [
+            Some(ModuleClock {
+                source: SpiClockSource::PllPeri1x,
+                factor_n: PeriFactorN::N1,
+                factor_m: 5,
+            }),
+            None,
+        ],
+        smhc: [
+            None,
+            None,
+            Some(ModuleClock {
+                source: SmhcClockSource::PllPeri1x,
+                factor_n: PeriFactorN::N2,
+                factor_m: 2,
+            }),
+        ],
+    };
+
+    #[test]
+    fn const_clock_plan_registers() {
+        const REGISTERS: ClockRegisters = PLAN.registers();
+
+        assert!(REGISTERS.pll_cpu_control.is_pll_enabled());
+        assert!(REGISTERS.pll_cpu_control.is_lock_enabled());
+        assert!(REGISTERS.pll_cpu_control.is_pll_output_unmasked());
+        assert_eq!(REGISTERS.pll_cpu_control.pll_n(), 41);
+        assert!(REGISTERS.pll_peri0_control.is_pll_enabled());
+        assert_eq!(REGISTERS.pll_peri0_control.pll_n(), 99);
+        assert_eq!(REGISTERS.pll_peri0_control.pll_p0(), 1);
+        assert_eq!(REGISTERS.pll_peri0_control.pll_p1(), 2);
+
+        assert_eq!(REGISTERS.cpu_axi_config.0, 0x0300_0100);
+        assert_eq!(REGISTERS.apb1_clock.0, 0x0000_0000);
+        assert_eq!(REGISTERS.uart_bgr.0, 0x0001_0001);
+        assert_eq!(REGISTERS.spi_clk[0].0, 0x0100_0005);
+        assert_eq!(REGISTERS.spi_clk[1].0, 0x0000_0000);
+        assert_eq!(REGISTERS.spi_bgr.0, 0x0001_0001);
+        assert_eq!(REGISTERS.smhc_clk[0].0, 0x0000_0000);
+        assert_eq!(REGISTERS.smhc_clk[2].0, 0x8100_0102);
+        assert_eq!(REGISTERS.smhc_bgr.0, 0x0004_0004);
+    }
+
+    const PLL_CPU: usize = 0;
+    const PLL_PERI0: usize = 0x020 / 4;
+    const CPU_AXI: usize = 0x500 / 4;
+    const PSI: usize = 0x510 / 4;
+    const APB0: usize = 0x520 / 4;
+    const APB1: usize = 0x524 / 4;
+
+    /// Delay locking PLLs at `locks`, and recording bus clocks seen while waiting
+    /// for the peripheral PLL.
+    struct LockingDelay<'a> {
+        memory: &'a [AtomicU32],
+        locks: &'a [usize],
+        buses: std::vec::Vec<(u32, u32, u32)>,
+    }
+
+    impl embedded_hal::delay::DelayNs for LockingDelay<'_> {
+        fn delay_ns(&mut self, _: u32) {
+            let load = |offset: usize| self.memory[offset].load(Ordering::SeqCst);
+            if load(PLL_PERI0) & (1 << 31) != 0 {
+                self.buses.push((load(PSI), load(APB0), load(APB1)));
+            }
+            for &offset in self.locks {
+                self.memory[offset].fetch_or(1 << 28, Ordering::SeqCst);
+            }
+        }
+    }
+
+    #[test]
+    fn apply_moves_buses_off_peripheral_pll() {
+        const REGISTERS: ClockRegisters = PLAN.registers();
+        // boot clocks: CPU, PSI and both APBs derived from PLL_PERI(1X)
+        const BOOT_CPU_AXI: u32 = 0x0300_0000;
+        const BOOT_PSI: u32 = 0x0300_0102;
+        const BOOT_APB0: u32 = 0x0200_0001;
+        const BOOT_APB1: u32 = 0x0300_0102;
+
+        let memory = [const { AtomicU32::new(0) }; 0x400];
+        let ccu = unsafe { &*(memory.as_ptr() as *const RegisterBlock) };
+        let boot = || {
+            memory[PLL_PERI0].store(0x4821_6300, Ordering::SeqCst);
+            memory[CPU_AXI].store(BOOT_CPU_AXI, Ordering::SeqCst);
+            memory[PSI].store(BOOT_PSI, Ordering::SeqCst);
+            memory[APB0].store(BOOT_APB0, Ordering::SeqCst);
+            memory[APB1].store(BOOT_APB1, Ordering::SeqCst);
+        };
+
+        boot();
+        let mut delay = LockingDelay {
+            memory: &memory,
+            locks: &[PLL_CPU, PLL_PERI0],
+            buses: std::vec::Vec::new(),
+        };
+        assert_eq!(unsafe { REGISTERS.apply(ccu, &mut delay, 100) }, Ok(()));
+        // buses stay on undivided 'HOSC' until PLL_PERI0 locks
+        assert!(!delay.buses.is_empty());
+        assert!(delay.buses.iter().all(|&buses| buses == (0, 0, 0)));
+        assert_eq!(memory[PSI].load(Ordering::SeqCst), BOOT_PSI);
+        assert_eq!(memory[APB0].load(Ordering::SeqCst), BOOT_APB0);
+        assert_eq!(ccu.apb1_clock.read(), REGISTERS.apb1_clock);
+        assert_eq!(ccu.cpu_axi_config.read(), REGISTERS.cpu_axi_config);
+
+        // PLL_PERI0 never locks: buses are left on 'HOSC'
+        boot();
+        let mut delay = LockingDelay {
+            memory: &memory,
+            locks: &[PLL_CPU],
+            buses: std::vec::Vec::new(),
+        };
+        let ans = unsafe { REGISTERS.apply(ccu, &mut delay, 100) };
+        assert_eq!(ans, Err(PllLockTimeout));
+        assert_eq!(delay.buses.len(), 100);
+        assert_eq!(memory[PSI].load(Ordering::SeqCst), 0);
+        assert_eq!(memory[APB0].load(Ordering::SeqCst), 0);
+        assert_eq!(memory[APB1].load(Ordering::SeqCst), 0);
+        assert_eq!(
+            ccu.cpu_axi_config.read().clock_source(),
+            CpuClockSource::Hosc
+        );
+    }
+}
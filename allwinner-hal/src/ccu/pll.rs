@@ -1,4 +1,13 @@
 //! PLL registers.
+use super::PllLockTimeout;
+use embedded_hal::delay::DelayNs;
+use embedded_time::rate::Hertz;
+use volatile_register::RW;
+
+/// Lowest PLL VCO frequency, `hosc * N / M`, in Hz.
+const PLL_VCO_MIN: u64 = 180_000_000;
+/// Highest PLL VCO frequency in Hz.
+const PLL_VCO_MAX: u64 = 3_000_000_000;
 
 /// CPU PLL Control register.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -6,6 +15,9 @@
 pub struct PllCpuControl(u32);
 
 impl PllCpuControl {
+    /// Register value after reset.
+    pub(crate) const RESET: Self = Self(0x4a00_1000);
+
     const PLL_ENABLE: u32 = 1 << 31;
     const PLL_LDO_ENABLE: u32 = 1 << 30;
     const LOCK_ENABLE: u32 = 1 << 29;
@@ -78,6 +90,20 @@ impl PllCpuControl {
     pub const fn is_pll_output_unmasked(self) -> bool {
         self.0 & Self::PLL_OUTPUT_GATE != 0
     }
+    /// Get register values to power up PLL with current factors.
+    ///
+    /// Write the first value to enable PLL and lock detection with output
+    /// masked, wait until PLL is locked, then write the second value to
+    /// unmask PLL output.
+    #[inline]
+    pub const fn bring_up(self) -> (Self, Self) {
+        let enable = self
+            .enable_pll_ldo()
+            .enable_pll()
+            .enable_lock()
+            .mask_pll_output();
+        (enable, enable.unmask_pll_output())
+    }
     /// Get PLL N factor.
     #[inline]
     pub const fn pll_n(self) -> u8 {
@@ -98,12 +124,52 @@ impl PllCpuControl {
     pub const fn set_pll_m(self, val: u8) -> Self {
         Self((self.0 & !Self::PLL_M) | val as u32)
     }
+    /// Get CPU PLL output frequency, `hosc * N / M`, from oscillator frequency `hosc`.
+    ///
+    /// N and M are register fields plus one.
+    #[inline]
+    pub const fn cpu_hz(self, hosc: Hertz) -> Hertz {
+        let n = self.pll_n() as u64 + 1;
+        let m = self.pll_m() as u64 + 1;
+        Hertz((hosc.0 as u64 * n / m) as u32)
+    }
+    /// Find N and M register fields for the highest CPU PLL output not above `target`.
+    ///
+    /// Output equals VCO, which is kept within 180 MHz ..= 3 GHz; on equal
+    /// output the smallest M is preferred. Returns `None` if `target` is below
+    /// the lowest achievable output.
+    pub const fn factors_for(hosc: Hertz, target: Hertz) -> Option<(u8, u8)> {
+        let mut best: Option<(u8, u8, u64)> = None;
+        let mut m = 1;
+        while m <= 4 {
+            let mut n = 1;
+            while n <= 256 {
+                let vco = hosc.0 as u64 * n / m;
+                n += 1;
+                if vco < PLL_VCO_MIN || vco > PLL_VCO_MAX || vco > target.0 as u64 {
+                    continue;
+                }
+                let better = match best {
+                    None => true,
+                    Some((_, _, best_hz)) => vco > best_hz,
+                };
+                if better {
+                    best = Some(((n - 2) as u8, (m - 1) as u8, vco));
+                }
+            }
+            m += 1;
+        }
+        match best {
+            Some((n, m, _)) => Some((n, m)),
+            None => None,
+        }
+    }
 }
 
 impl Default for PllCpuControl {
     #[inline]
     fn default() -> Self {
-        Self(0x4a00_1000)
+        Self::RESET
     }
 }
 
@@ -186,6 +252,20 @@ impl PllDdrControl {
     pub const fn is_pll_output_unmasked(self) -> bool {
         self.0 & Self::PLL_OUTPUT_GATE != 0
     }
+    /// Get register values to power up PLL with current factors.
+    ///
+    /// Write the first value to enable PLL and lock detection with output
+    /// masked, wait until PLL is locked, then write the second value to
+    /// unmask PLL output.
+    #[inline]
+    pub const fn bring_up(self) -> (Self, Self) {
+        let enable = self
+            .enable_pll_ldo()
+            .enable_pll()
+            .enable_lock()
+            .mask_pll_output();
+        (enable, enable.unmask_pll_output())
+    }
     /// Get PLL N factor.
     #[inline]
     pub const fn pll_n(self) -> u8 {
@@ -216,6 +296,52 @@ impl PllDdrControl {
     pub const fn set_pll_m0(self, val: u8) -> Self {
         Self((self.0 & !Self::PLL_M0) | val as u32)
     }
+    /// Get DDR PLL output frequency, `hosc * N / M1 / M0`, from oscillator frequency `hosc`.
+    ///
+    /// N, M1 and M0 are register fields plus one.
+    #[inline]
+    pub const fn ddr_hz(self, hosc: Hertz) -> Hertz {
+        let n = self.pll_n() as u64 + 1;
+        let m1 = self.pll_m1() as u64 + 1;
+        let m0 = self.pll_m0() as u64 + 1;
+        Hertz((hosc.0 as u64 * n / m1 / m0) as u32)
+    }
+    /// Find N, M1 and M0 register fields for the highest DDR PLL output not above `target`.
+    ///
+    /// VCO, `hosc * N / M1`, is kept within 180 MHz ..= 3 GHz; on equal output
+    /// the smallest dividers are preferred. Returns `None` if `target` is below
+    /// the lowest achievable output.
+    pub const fn factors_for(hosc: Hertz, target: Hertz) -> Option<(u8, u8, u8)> {
+        let mut best: Option<(u8, u8, u8, u64)> = None;
+        let mut m1 = 1;
+        while m1 <= 2 {
+            let mut m0 = 1;
+            while m0 <= 2 {
+                let mut n = 1;
+                while n <= 256 {
+                    let vco = hosc.0 as u64 * n / m1;
+                    let output = vco / m0;
+                    n += 1;
+                    if vco < PLL_VCO_MIN || vco > PLL_VCO_MAX || output > target.0 as u64 {
+                        continue;
+                    }
+                    let better = match best {
+                        None => true,
+                        Some((_, _, _, best_hz)) => output > best_hz,
+                    };
+                    if better {
+                        best = Some(((n - 2) as u8, (m1 - 1) as u8, (m0 - 1) as u8, output));
+                    }
+                }
+                m0 += 1;
+            }
+            m1 += 1;
+        }
+        match best {
+            Some((n, m1, m0, _)) => Some((n, m1, m0)),
+            None => None,
+        }
+    }
 }
 
 impl Default for PllDdrControl {
@@ -231,6 +357,9 @@ impl Default for PllDdrControl {
 pub struct PllPeri0Control(u32);
 
 impl PllPeri0Control {
+    /// Register value after reset.
+    pub(crate) const RESET: Self = Self(0x4821_6300);
+
     const PLL_ENABLE: u32 = 1 << 31;
     const PLL_LDO_ENABLE: u32 = 1 << 30;
     const LOCK_ENABLE: u32 = 1 << 29;
@@ -305,6 +434,20 @@ impl PllPeri0Control {
     pub const fn is_pll_output_unmasked(self) -> bool {
         self.0 & Self::PLL_OUTPUT_GATE != 0
     }
+    /// Get register values to power up PLL with current factors.
+    ///
+    /// Write the first value to enable PLL and lock detection with output
+    /// masked, wait until PLL is locked, then write the second value to
+    /// unmask PLL output.
+    #[inline]
+    pub const fn bring_up(self) -> (Self, Self) {
+        let enable = self
+            .enable_pll_ldo()
+            .enable_pll()
+            .enable_lock()
+            .mask_pll_output();
+        (enable, enable.unmask_pll_output())
+    }
     /// Get PLL P1 factor.
     #[inline]
     pub const fn pll_p1(self) -> u8 {
@@ -345,18 +488,275 @@ impl PllPeri0Control {
     pub const fn set_pll_m(self, val: u8) -> Self {
         Self((self.0 & !Self::PLL_M) | ((val as u32) << 1))
     }
+    /// Get PLL_PERI(2X) frequency, `hosc * N / M / P0`, from oscillator frequency `hosc`.
+    ///
+    /// N, M and P0 are register fields plus one.
+    #[inline]
+    pub const fn peri_2x_hz(self, hosc: Hertz) -> Hertz {
+        Hertz((self.vco_hz(hosc) / (self.pll_p0() as u64 + 1)) as u32)
+    }
+    /// Get PLL_PERI(1X) frequency, half of PLL_PERI(2X).
+    #[inline]
+    pub const fn peri_1x_hz(self, hosc: Hertz) -> Hertz {
+        Hertz((self.vco_hz(hosc) / (self.pll_p0() as u64 + 1) / 2) as u32)
+    }
+    /// Get PLL_PERI(800M) frequency, `hosc * N / M / P1`, from oscillator frequency `hosc`.
+    ///
+    /// N, M and P1 are register fields plus one.
+    #[inline]
+    pub const fn peri_800m_hz(self, hosc: Hertz) -> Hertz {
+        Hertz((self.vco_hz(hosc) / (self.pll_p1() as u64 + 1)) as u32)
+    }
+    #[inline]
+    const fn vco_hz(self, hosc: Hertz) -> u64 {
+        hosc.0 as u64 * (self.pll_n() as u64 + 1) / (self.pll_m() as u64 + 1)
+    }
+    /// Find N, M, P0 and P1 register fields for the highest PLL_PERI(1X) not above `target`.
+    ///
+    /// VCO, `hosc * N / M`, is kept within 180 MHz ..= 3 GHz. P1 is chosen for
+    /// the highest PLL_PERI(800M) not above 800 MHz, which also breaks ties
+    /// between equal PLL_PERI(1X) outputs. Returns `None` if `target` is below
+    /// the lowest achievable output.
+    pub const fn factors_for(hosc: Hertz, target: Hertz) -> Option<(u8, u8, u8, u8)> {
+        const PERI_800M_MAX: u64 = 800_000_000;
+        let mut best: Option<(u8, u8, u8, u8, u64, u64)> = None;
+        let mut m = 1;
+        while m <= 2 {
+            let mut p0 = 1;
+            while p0 <= 8 {
+                let mut n = 1;
+                while n <= 256 {
+                    let vco = hosc.0 as u64 * n / m;
+                    let output = vco / p0 / 2;
+                    let p1 = vco.div_ceil(PERI_800M_MAX);
+                    n += 1;
+                    if vco < PLL_VCO_MIN || vco > PLL_VCO_MAX || output > target.0 as u64 || p1 > 8
+                    {
+                        continue;
+                    }
+                    let peri_800m = vco / p1;
+                    let better = match best {
+                        None => true,
+                        Some((_, _, _, _, best_hz, best_800m)) => {
+                            output > best_hz || (output == best_hz && peri_800m > best_800m)
+                        }
+                    };
+                    if better {
+                        best = Some((
+                            (n - 2) as u8,
+                            (m - 1) as u8,
+                            (p0 - 1) as u8,
+                            (p1 - 1) as u8,
+                            output,
+                            peri_800m,
+                        ));
+                    }
+                }
+                p0 += 1;
+            }
+            m += 1;
+        }
+        match best {
+            Some((n, m, p0, p1, _, _)) => Some((n, m, p0, p1)),
+            None => None,
+        }
+    }
 }
 
 impl Default for PllPeri0Control {
     #[inline]
     fn default() -> Self {
-        Self(0x4821_6300)
+        Self::RESET
     }
 }
 
+/// Video PLL 0 Control register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct PllVideo0Control(u32);
+
+impl PllVideo0Control {
+    const PLL_ENABLE: u32 = 1 << 31;
+    const PLL_LDO_ENABLE: u32 = 1 << 30;
+    const LOCK_ENABLE: u32 = 1 << 29;
+    const LOCK: u32 = 1 << 28;
+    const PLL_OUTPUT_GATE: u32 = 1 << 27;
+    const PLL_N: u32 = 0xff << 8;
+    const PLL_M: u32 = 0x1 << 1;
+
+    /// Get if PLL is enabled.
+    #[inline]
+    pub const fn is_pll_enabled(self) -> bool {
+        self.0 & Self::PLL_ENABLE != 0
+    }
+    /// Enable PLL.
+    #[inline]
+    pub const fn enable_pll(self) -> Self {
+        Self(self.0 | Self::PLL_ENABLE)
+    }
+    /// Disable PLL.
+    #[inline]
+    pub const fn disable_pll(self) -> Self {
+        Self(self.0 & !Self::PLL_ENABLE)
+    }
+    /// Get if PLL LDO is enabled.
+    #[inline]
+    pub const fn is_pll_ldo_enabled(self) -> bool {
+        self.0 & Self::PLL_LDO_ENABLE != 0
+    }
+    /// Enable PLL LDO.
+    #[inline]
+    pub const fn enable_pll_ldo(self) -> Self {
+        Self(self.0 | Self::PLL_LDO_ENABLE)
+    }
+    /// Disable PLL LDO.
+    #[inline]
+    pub const fn disable_pll_ldo(self) -> Self {
+        Self(self.0 & !Self::PLL_LDO_ENABLE)
+    }
+    /// Get if PLL lock is enabled.
+    #[inline]
+    pub const fn is_lock_enabled(self) -> bool {
+        self.0 & Self::LOCK_ENABLE != 0
+    }
+    /// Enable PLL lock.
+    #[inline]
+    pub const fn enable_lock(self) -> Self {
+        Self(self.0 | Self::LOCK_ENABLE)
+    }
+    /// Disable PLL lock.
+    #[inline]
+    pub const fn disable_lock(self) -> Self {
+        Self(self.0 & !Self::LOCK_ENABLE)
+    }
+    /// Get if the PLL locked state is set by hardware.
+    #[inline]
+    pub const fn is_locked(self) -> bool {
+        self.0 & Self::LOCK != 0
+    }
+    /// Unmask (enable) PLL output.
+    #[inline]
+    pub const fn unmask_pll_output(self) -> Self {
+        Self(self.0 | Self::PLL_OUTPUT_GATE)
+    }
+    /// Mask (disable) PLL output.
+    #[inline]
+    pub const fn mask_pll_output(self) -> Self {
+        Self(self.0 & !Self::PLL_OUTPUT_GATE)
+    }
+    /// Get if PLL output is unmasked.
+    #[inline]
+    pub const fn is_pll_output_unmasked(self) -> bool {
+        self.0 & Self::PLL_OUTPUT_GATE != 0
+    }
+    /// Get register values to power up PLL with current factors.
+    ///
+    /// Write the first value to enable PLL and lock detection with output
+    /// masked, wait until PLL is locked, then write the second value to
+    /// unmask PLL output.
+    #[inline]
+    pub const fn bring_up(self) -> (Self, Self) {
+        let enable = self
+            .enable_pll_ldo()
+            .enable_pll()
+            .enable_lock()
+            .mask_pll_output();
+        (enable, enable.unmask_pll_output())
+    }
+    /// Get PLL N factor.
+    #[inline]
+    pub const fn pll_n(self) -> u8 {
+        ((self.0 & Self::PLL_N) >> 8) as u8
+    }
+    /// Set PLL N factor.
+    #[inline]
+    pub const fn set_pll_n(self, val: u8) -> Self {
+        Self((self.0 & !Self::PLL_N) | ((val as u32) << 8))
+    }
+    /// Get PLL M (input divide) factor.
+    #[inline]
+    pub const fn pll_m(self) -> u8 {
+        ((self.0 & Self::PLL_M) >> 1) as u8
+    }
+    /// Set PLL M (input divide) factor.
+    #[inline]
+    pub const fn set_pll_m(self, val: u8) -> Self {
+        Self((self.0 & !Self::PLL_M) | ((val as u32) << 1))
+    }
+}
+
+/// PLL control register.
+pub trait PllRegister: Copy {
+    /// Get register values to power up PLL, see `bring_up` of each register.
+    fn bring_up(self) -> (Self, Self);
+    /// Get if PLL is locked.
+    fn is_locked(self) -> bool;
+}
+
+macro_rules! impl_pll_register {
+    ($($ty: ty),+) => {
+        $(
+        impl PllRegister for $ty {
+            #[inline]
+            fn bring_up(self) -> (Self, Self) {
+                <$ty>::bring_up(self)
+            }
+            #[inline]
+            fn is_locked(self) -> bool {
+                <$ty>::is_locked(self)
+            }
+        }
+        )+
+    };
+}
+
+impl_pll_register!(
+    PllCpuControl,
+    PllDdrControl,
+    PllPeri0Control,
+    PllVideo0Control
+);
+
+/// Power up PLL in `reg` with factors currently in the register.
+///
+/// Enables PLL with output masked, waits for lock, and unmasks output. Lock is
+/// polled every microsecond for at most `lock_timeout_us` microseconds; on
+/// timeout, PLL output is left masked.
+///
+/// # Safety
+///
+/// Changing PLL output affects every clock derived from it.
+#[inline]
+pub unsafe fn bring_up_pll<T: PllRegister>(
+    reg: &RW<T>,
+    delay: &mut impl DelayNs,
+    lock_timeout_us: u32,
+) -> Result<(), PllLockTimeout> {
+    let (enable, unmask) = reg.read().bring_up();
+    unsafe { reg.write(enable) };
+    let mut waited_us = 0;
+    while !reg.read().is_locked() {
+        if waited_us >= lock_timeout_us {
+            return Err(PllLockTimeout);
+        }
+        delay.delay_us(1);
+        waited_us += 1;
+    }
+    unsafe { reg.write(unmask) };
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{PllCpuControl, PllDdrControl, PllPeri0Control};
+    extern crate std;
+
+    use super::{
+        bring_up_pll, PllCpuControl, PllDdrControl, PllLockTimeout, PllPeri0Control,
+        PllVideo0Control,
+    };
+    use core::sync::atomic::{AtomicU32, Ordering};
+    use embedded_time::rate::Hertz;
+    use volatile_register::RW;
 
     #[test]
     fn struct_pll_cpu_control_functions() {
@@ -591,4 +991,211 @@ mod tests {
         assert_eq!(default.pll_n(), 0x63);
         assert_eq!(default.pll_m(), 0x0);
     }
+
+    #[test]
+    fn struct_pll_video0_control_functions() {
+        let mut val = PllVideo0Control(0x0);
+
+        val = val.enable_pll();
+        assert_eq!(val.0, 0x80000000);
+        assert!(val.is_pll_enabled());
+
+        val = val.disable_pll();
+        assert_eq!(val.0, 0x00000000);
+        assert!(!val.is_pll_enabled());
+
+        val = val.enable_pll_ldo();
+        assert_eq!(val.0, 0x40000000);
+        assert!(val.is_pll_ldo_enabled());
+
+        val = val.disable_pll_ldo();
+        assert_eq!(val.0, 0x00000000);
+        assert!(!val.is_pll_ldo_enabled());
+
+        val = val.enable_lock();
+        assert_eq!(val.0, 0x20000000);
+        assert!(val.is_lock_enabled());
+
+        val = val.disable_lock();
+        assert_eq!(val.0, 0x00000000);
+        assert!(!val.is_lock_enabled());
+
+        assert!(PllVideo0Control(0x10000000).is_locked());
+
+        val = val.unmask_pll_output();
+        assert_eq!(val.0, 0x08000000);
+        assert!(val.is_pll_output_unmasked());
+
+        val = val.mask_pll_output();
+        assert_eq!(val.0, 0x00000000);
+        assert!(!val.is_pll_output_unmasked());
+
+        val = val.set_pll_n(0x62);
+        assert_eq!(val.0, 0x00006200);
+        assert_eq!(val.pll_n(), 0x62);
+
+        val = val.set_pll_m(0x1);
+        assert_eq!(val.0, 0x00006202);
+        assert_eq!(val.pll_m(), 0x1);
+    }
+
+    #[test]
+    fn pll_bring_up_values() {
+        // from reset values: PLL and LDO enabled with lock detect, output masked
+        let (enable, unmask) = PllCpuControl(0x4a00_1000).bring_up();
+        assert_eq!(enable.0, 0xe200_1000);
+        assert_eq!(unmask.0, 0xea00_1000);
+        let (enable, unmask) = PllDdrControl(0x4800_2301).bring_up();
+        assert_eq!(enable.0, 0xe000_2301);
+        assert_eq!(unmask.0, 0xe800_2301);
+        let (enable, unmask) = PllPeri0Control(0x4821_6300).bring_up();
+        assert_eq!(enable.0, 0xe021_6300);
+        assert_eq!(unmask.0, 0xe821_6300);
+        let (enable, unmask) = PllVideo0Control(0x0000_6202).bring_up();
+        assert_eq!(enable.0, 0xe000_6202);
+        assert_eq!(unmask.0, 0xe800_6202);
+    }
+
+    #[test]
+    fn pll_bring_up_sequence() {
+        let memory = AtomicU32::new(0x4821_6300);
+        let reg = unsafe { &*(&memory as *const AtomicU32 as *const RW<PllPeri0Control>) };
+        let writes = std::thread::scope(|s| {
+            let hardware = s.spawn(|| {
+                let mut writes = std::vec::Vec::new();
+                // wait for PLL enable, then report lock
+                loop {
+                    let val = memory.load(Ordering::SeqCst);
+                    if val & (1 << 31) != 0 {
+                        writes.push(val);
+                        memory.store(val | (1 << 28), Ordering::SeqCst);
+                        break;
+                    }
+                    std::thread::yield_now();
+                }
+                // wait for output unmask
+                loop {
+                    let val = memory.load(Ordering::SeqCst);
+                    if val & (1 << 27) != 0 {
+                        writes.push(val);
+                        break;
+                    }
+                    std::thread::yield_now();
+                }
+                writes
+            });
+            let ans = unsafe { bring_up_pll(reg, &mut NoDelay, u32::MAX) };
+            assert_eq!(ans, Ok(()));
+            hardware.join().unwrap()
+        });
+        // enabled with output masked first, then unmasked only after lock
+        assert_eq!(writes[0] & 0xf800_0000, 0xe000_0000);
+        assert_eq!(writes[1] & 0xf800_0000, 0xe800_0000);
+        assert_eq!(writes[1] & 0x00ff_ffff, 0x0021_6300);
+
+        // PLL never locks: output stays masked
+        memory.store(0x4821_6300, Ordering::SeqCst);
+        let ans = unsafe { bring_up_pll(reg, &mut NoDelay, 100) };
+        assert_eq!(ans, Err(PllLockTimeout));
+        assert_eq!(memory.load(Ordering::SeqCst) & 0xf800_0000, 0xe000_0000);
+    }
+
+    struct NoDelay;
+
+    impl embedded_hal::delay::DelayNs for NoDelay {
+        fn delay_ns(&mut self, _: u32) {}
+    }
+
+    #[test]
+    fn pll_output_frequencies() {
+        const HOSC: Hertz = Hertz(24_000_000);
+
+        // reset values: CPU at 408 MHz, PERI at 1.2 GHz, 600 MHz and 800 MHz
+        let cpu = PllCpuControl(0x4a00_1000);
+        assert_eq!(cpu.cpu_hz(HOSC), Hertz(408_000_000u32));
+        let peri = PllPeri0Control(0x4821_6300);
+        assert_eq!(peri.peri_2x_hz(HOSC), Hertz(1_200_000_000u32));
+        assert_eq!(peri.peri_1x_hz(HOSC), Hertz(600_000_000u32));
+        assert_eq!(peri.peri_800m_hz(HOSC), Hertz(800_000_000u32));
+
+        // running values set up by boot0: CPU at 1008 MHz, M = 2 on PERI
+        let cpu = PllCpuControl(0xe800_2900);
+        assert_eq!(cpu.cpu_hz(HOSC), Hertz(1_008_000_000u32));
+        let cpu = PllCpuControl(0xe800_2901);
+        assert_eq!(cpu.cpu_hz(HOSC), Hertz(504_000_000u32));
+        let peri = PllPeri0Control(0xe811_c702);
+        // N = 200, M = 2, P0 = 2, P1 = 2
+        assert_eq!(peri.peri_2x_hz(HOSC), Hertz(1_200_000_000u32));
+        assert_eq!(peri.peri_1x_hz(HOSC), Hertz(600_000_000u32));
+        assert_eq!(peri.peri_800m_hz(HOSC), Hertz(1_200_000_000u32));
+
+        // zero P fields divide by one, not by zero
+        let peri = PllPeri0Control(0x0000_3100);
+        assert_eq!(peri.peri_2x_hz(HOSC), Hertz(1_200_000_000u32));
+        assert_eq!(peri.peri_1x_hz(HOSC), Hertz(600_000_000u32));
+        assert_eq!(peri.peri_800m_hz(HOSC), Hertz(1_200_000_000u32));
+
+        // reset value: DDR at 432 MHz
+        let ddr = PllDdrControl(0x4800_2301);
+        assert_eq!(ddr.ddr_hz(HOSC), Hertz(432_000_000u32));
+    }
+
+    #[test]
+    fn pll_factor_solver() {
+        const HOSC: Hertz = Hertz(24_000_000);
+
+        // CPU at 1008 MHz and 408 MHz, as boot0 and reset values
+        assert_eq!(
+            PllCpuControl::factors_for(HOSC, Hertz(1_008_000_000)),
+            Some((41, 0))
+        );
+        assert_eq!(
+            PllCpuControl::factors_for(HOSC, Hertz(408_000_000)),
+            Some((16, 0))
+        );
+        // 1 GHz is not a multiple of 24 MHz, but is reached with M = 3
+        let (n, m) = PllCpuControl::factors_for(HOSC, Hertz(1_000_000_000)).unwrap();
+        assert_eq!((n, m), (124, 2));
+        let cpu = PllCpuControl::default().set_pll_n(n).set_pll_m(m);
+        assert_eq!(cpu.cpu_hz(HOSC), Hertz(1_000_000_000u32));
+        // above VCO range the highest output is chosen, below it nothing is reachable
+        assert_eq!(
+            PllCpuControl::factors_for(HOSC, Hertz(4_000_000_000)),
+            Some((124, 0))
+        );
+        assert_eq!(PllCpuControl::factors_for(HOSC, Hertz(100_000_000)), None);
+
+        // DDR at 792 MHz, and 432 MHz as reset value
+        assert_eq!(
+            PllDdrControl::factors_for(HOSC, Hertz(792_000_000)),
+            Some((32, 0, 0))
+        );
+        let (n, m1, m0) = PllDdrControl::factors_for(HOSC, Hertz(432_000_000)).unwrap();
+        let ddr = PllDdrControl::default()
+            .set_pll_n(n)
+            .set_pll_m1(m1)
+            .set_pll_m0(m0);
+        assert_eq!(ddr.ddr_hz(HOSC), Hertz(432_000_000u32));
+        // M0 halves the lowest VCO
+        assert_eq!(
+            PllDdrControl::factors_for(HOSC, Hertz(100_000_000)),
+            Some((7, 0, 1))
+        );
+        assert_eq!(PllDdrControl::factors_for(HOSC, Hertz(80_000_000)), None);
+
+        // PERI at 600 MHz matches reset factors, with PLL_PERI(800M) at 800 MHz
+        assert_eq!(
+            PllPeri0Control::factors_for(HOSC, Hertz(600_000_000)),
+            Some((99, 0, 1, 2))
+        );
+        let (n, m, p0, p1) = PllPeri0Control::factors_for(HOSC, Hertz(500_000_000)).unwrap();
+        let peri = PllPeri0Control::RESET
+            .set_pll_n(n)
+            .set_pll_m(m)
+            .set_pll_p0(p0)
+            .set_pll_p1(p1);
+        assert_eq!(peri.peri_1x_hz(HOSC), Hertz(500_000_000u32));
+        assert!(peri.peri_800m_hz(HOSC).0 <= 800_000_000);
+        assert_eq!(PllPeri0Control::factors_for(HOSC, Hertz(10_000_000)), None);
+    }
 }
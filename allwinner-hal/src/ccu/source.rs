@@ -17,6 +17,32 @@ pub enum CpuClockSource {
     PllPeri800M = 6,
 }
 
+/// PSI, AHB1 and AHB2 clock source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PsiClockSource {
+    /// 24-MHz 'HOSC' external oscillator.
+    Hosc = 0,
+    /// 32-KHz clock.
+    Clk32K = 1,
+    /// 16-MHz RC oscillator.
+    Clk16MRC = 2,
+    /// Peripheral PLL (1x frequency).
+    PllPeri1x = 3,
+}
+
+/// APB clock source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ApbClockSource {
+    /// 24-MHz 'HOSC' external oscillator.
+    Hosc = 0,
+    /// 32-KHz clock.
+    Clk32K = 1,
+    /// PSI clock.
+    Psi = 2,
+    /// Peripheral PLL (1x frequency).
+    PllPeri1x = 3,
+}
+
 /// Dram clock source.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum DramClockSource {
@@ -30,6 +56,22 @@ pub enum DramClockSource {
     PllPeri800M = 3,
 }
 
+/// MBUS clock source.
+///
+/// Memory bus bandwidth follows this clock; DRAM controller and masters like
+/// DMA and display engine access DRAM through the memory bus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MbusClockSource {
+    /// 24-MHz 'HOSC' external oscillator.
+    Hosc = 0,
+    /// Peripheral PLL (2x frequency).
+    PllPeri2x = 1,
+    /// DRAM PLL.
+    PllDdr = 2,
+    /// Peripheral PLL (800-MHz).
+    PllPeri800M = 3,
+}
+
 /// SPI clock source.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum SpiClockSource {
@@ -59,3 +101,22 @@ pub enum SmhcClockSource {
     /// Audio PLL 1 (divided by 2).
     PllAudio1Div2 = 4,
 }
+
+/// TCON TV (display pixel) clock source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TconTvClockSource {
+    /// 24-MHz 'HOSC' external oscillator.
+    Hosc = 0,
+    /// Video PLL 0 (1x frequency).
+    PllVideo0x1 = 1,
+    /// Video PLL 0 (4x frequency).
+    PllVideo0x4 = 2,
+    /// Video PLL 1 (1x frequency).
+    PllVideo1x1 = 3,
+    /// Video PLL 1 (4x frequency).
+    PllVideo1x4 = 4,
+    /// Peripheral PLL (2x frequency).
+    PllPeri2x = 5,
+    /// Audio PLL 1 (divided by 2).
+    PllAudio1Div2 = 6,
+}
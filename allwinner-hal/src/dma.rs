@@ -0,0 +1,959 @@
+//! Direct Memory Access Controller (DMAC).
+//!
+//! DMA channels are shared by all peripherals with DMA support. To avoid two
+//! drivers using the same channel, [`Dma::split`] hands out owned [`Channel`]
+//! tokens; each channel can only be moved into one consumer:
+//!
+//! ```compile_fail,E0382
+//! use allwinner_hal::dma::{Channel, Dma, RegisterBlock};
+//! fn uart_dma(_ch: Channel<'_, 0>) {}
+//! fn spi_dma(_ch: Channel<'_, 0>) {}
+//! fn setup(dma: &mut Dma<impl AsRef<RegisterBlock>>) {
+//!     let channels = dma.split();
+//!     uart_dma(channels.ch0);
+//!     spi_dma(channels.ch0); // error: channel 0 is already moved
+//! }
+//! ```
+//!
+//! Distinct channels can be given to distinct consumers:
+//!
+//! ```
+//! use allwinner_hal::dma::{Channel, Dma, RegisterBlock};
+//! fn uart_dma(_ch: Channel<'_, 0>) {}
+//! fn spi_dma(_ch: Channel<'_, 1>) {}
+//! fn setup(dma: &mut Dma<impl AsRef<RegisterBlock>>) {
+//!     let channels = dma.split();
+//!     uart_dma(channels.ch0);
+//!     spi_dma(channels.ch1);
+//! }
+//! ```
+//!
+//! Channels chosen at run time are acquired with [`Dma::acquire_channel`],
+//! which fails if the channel is already acquired, and released on drop.
+//! Either kind of channel runs a descriptor chain with `transfer`, returning
+//! a [`Transfer`] guard that keeps the descriptors borrowed until it is done.
+
+use core::{
+    marker::PhantomData,
+    sync::atomic::{AtomicU32, Ordering},
+};
+use volatile_register::{RO, RW};
+
+/// Number of DMA channels.
+pub const CHANNEL_COUNT: usize = 16;
+
+/// Direct Memory Access Controller registers.
+#[repr(C)]
+pub struct RegisterBlock {
+    /// 0x0 - DMAC IRQ Enable registers for channels 0..=7 and 8..=15.
+    pub irq_enable: [RW<u32>; 2],
+    _reserved0: [u32; 2],
+    /// 0x10 - DMAC IRQ Pending registers for channels 0..=7 and 8..=15.
+    pub irq_pending: [RW<u32>; 2],
+    _reserved1: [u32; 4],
+    /// 0x28 - DMAC Auto Gating register.
+    pub auto_gating: RW<u32>,
+    _reserved2: u32,
+    /// 0x30 - DMAC Status register.
+    pub status: RO<u32>,
+    _reserved3: [u32; 51],
+    /// 0x100 - DMAC channel registers.
+    pub channels: [ChannelRegisterBlock; CHANNEL_COUNT],
+}
+
+/// Registers of one DMAC channel.
+#[repr(C)]
+pub struct ChannelRegisterBlock {
+    /// 0x0 - Channel Enable register.
+    pub enable: RW<u32>,
+    /// 0x4 - Channel Pause register.
+    pub pause: RW<u32>,
+    /// 0x8 - Channel Descriptor Address register.
+    pub descriptor_address: RW<u32>,
+    /// 0xc - Channel Configuration register.
+    pub config: RO<u32>,
+    /// 0x10 - Channel Current Source Address register.
+    pub current_source: RO<u32>,
+    /// 0x14 - Channel Current Destination Address register.
+    pub current_destination: RO<u32>,
+    /// 0x18 - Channel Byte Counter Left register.
+    pub byte_counter_left: RO<u32>,
+    /// 0x1c - Channel Parameter register.
+    pub parameter: RO<u32>,
+    _reserved0: [u32; 2],
+    /// 0x28 - Channel Mode register.
+    pub mode: RW<u32>,
+    /// 0x2c - Channel Former Descriptor Address register.
+    pub former_descriptor_address: RO<u32>,
+    /// 0x30 - Channel Package Number register.
+    pub package_number: RO<u32>,
+    _reserved1: [u32; 3],
+}
+
+/// Link address marking the last descriptor of a chain.
+pub const END_OF_CHAIN: u32 = 0xFFFF_F800;
+
+/// DMA request port of DRAM.
+pub const DRQ_DRAM: u8 = 1;
+/// DMA request port of SPI0 receive and transmit FIFO.
+pub const DRQ_SPI0: u8 = 22;
+/// DMA request port of SPI1 receive and transmit FIFO.
+pub const DRQ_SPI1: u8 = 23;
+
+/// Width of one DMA data access.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DataWidth {
+    /// 8-bit access.
+    Bits8 = 0,
+    /// 16-bit access.
+    Bits16 = 1,
+    /// 32-bit access.
+    Bits32 = 2,
+    /// 64-bit access.
+    Bits64 = 3,
+}
+
+/// Number of data accesses in one DMA burst.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BurstLength {
+    /// One access.
+    One = 0,
+    /// Four accesses.
+    Four = 1,
+    /// Eight accesses.
+    Eight = 2,
+    /// Sixteen accesses.
+    Sixteen = 3,
+}
+
+/// Configuration word of a DMA descriptor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[repr(transparent)]
+pub struct DescriptorConfig(u32);
+
+impl DescriptorConfig {
+    const DRQ: u32 = 0x3F;
+    const BURST: u32 = 0x3 << 6;
+    const IO_MODE: u32 = 0x1 << 8;
+    const WIDTH: u32 = 0x3 << 9;
+    const SOURCE: u32 = Self::DRQ | Self::BURST | Self::IO_MODE | Self::WIDTH;
+    const DESTINATION_SHIFT: u32 = 16;
+
+    /// Create a configuration with all fields cleared.
+    #[inline]
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    /// Set source request port, address mode, access width and burst length.
+    ///
+    /// Source address is kept fixed in IO mode, or increased after each access otherwise.
+    #[inline]
+    pub const fn set_source(
+        self,
+        drq: u8,
+        io_mode: bool,
+        width: DataWidth,
+        burst: BurstLength,
+    ) -> Self {
+        let bits = Self::endpoint(drq, io_mode, width, burst);
+        Self((self.0 & !Self::SOURCE) | bits)
+    }
+    /// Set destination request port, address mode, access width and burst length.
+    ///
+    /// Destination address is kept fixed in IO mode, or increased after each access otherwise.
+    #[inline]
+    pub const fn set_destination(
+        self,
+        drq: u8,
+        io_mode: bool,
+        width: DataWidth,
+        burst: BurstLength,
+    ) -> Self {
+        let bits = Self::endpoint(drq, io_mode, width, burst) << Self::DESTINATION_SHIFT;
+        Self((self.0 & !(Self::SOURCE << Self::DESTINATION_SHIFT)) | bits)
+    }
+    /// Get source request port.
+    #[inline]
+    pub const fn source_drq(self) -> u8 {
+        (self.0 & Self::DRQ) as u8
+    }
+    /// Get destination request port.
+    #[inline]
+    pub const fn destination_drq(self) -> u8 {
+        ((self.0 >> Self::DESTINATION_SHIFT) & Self::DRQ) as u8
+    }
+    #[inline]
+    const fn endpoint(drq: u8, io_mode: bool, width: DataWidth, burst: BurstLength) -> u32 {
+        (drq as u32 & Self::DRQ) | (burst as u32) << 6 | (io_mode as u32) << 8 | (width as u32) << 9
+    }
+}
+
+/// DMA transfer descriptor, read by the controller from memory.
+///
+/// Descriptors are given to the controller by address, so they must stay in
+/// place and be visible to the controller until the transfer completes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C, align(4))]
+pub struct Descriptor {
+    /// Request ports, address modes, widths and burst lengths.
+    pub config: DescriptorConfig,
+    /// Source address.
+    pub source: u32,
+    /// Destination address.
+    pub destination: u32,
+    /// Number of bytes to transfer.
+    pub byte_count: u32,
+    /// Wait cycles between data requests.
+    pub parameter: u32,
+    /// Address of next descriptor, or [`END_OF_CHAIN`].
+    pub link: u32,
+}
+
+impl Descriptor {
+    /// Wait cycles between data requests used by default.
+    pub const DEFAULT_WAIT_CYCLES: u32 = 8;
+
+    /// Create a descriptor moving `byte_count` bytes, ending its chain.
+    #[inline]
+    pub const fn new(
+        config: DescriptorConfig,
+        source: u32,
+        destination: u32,
+        byte_count: u32,
+    ) -> Self {
+        Descriptor {
+            config,
+            source,
+            destination,
+            byte_count,
+            parameter: Self::DEFAULT_WAIT_CYCLES,
+            link: END_OF_CHAIN,
+        }
+    }
+    /// Continue the chain with `next` after this descriptor.
+    #[inline]
+    pub fn link_to(&mut self, next: &Descriptor) {
+        self.link = next.address();
+    }
+    /// Get address of this descriptor as seen by the controller.
+    #[inline]
+    pub fn address(&self) -> u32 {
+        self as *const _ as usize as u32
+    }
+}
+
+/// Link `descriptors` into one chain, in slice order.
+///
+/// The last descriptor ends the chain.
+#[inline]
+pub fn link_chain(descriptors: &mut [Descriptor]) {
+    for i in 1..descriptors.len() {
+        let next = descriptors[i].address();
+        descriptors[i - 1].link = next;
+    }
+    if let Some(last) = descriptors.last_mut() {
+        last.link = END_OF_CHAIN;
+    }
+}
+
+/// Largest byte count of one descriptor, rounded down to whole words.
+pub const MAX_DESCRIPTOR_BYTES: usize = 0x1FF_FFFC;
+
+/// Fill `descriptors` with a chain copying `len` bytes from `source` to `destination`.
+///
+/// If both addresses are word aligned, whole words are copied with 32-bit
+/// accesses and the remaining bytes by one descriptor of byte accesses;
+/// otherwise every byte is copied with byte accesses. Descriptors are not
+/// linked. Returns number of descriptors filled and bytes they copy, which
+/// is less than `len` if `descriptors` is too short.
+pub fn build_copy_chain(
+    destination: u32,
+    source: u32,
+    len: usize,
+    descriptors: &mut [Descriptor],
+) -> (usize, usize) {
+    let aligned = (destination | source).is_multiple_of(4);
+    let (mut count, mut copied) = (0, 0);
+    while copied < len && count < descriptors.len() {
+        let remaining = len - copied;
+        let (width, burst, bytes) = if aligned && remaining >= 4 {
+            let bytes = (remaining & !3).min(MAX_DESCRIPTOR_BYTES);
+            (DataWidth::Bits32, BurstLength::Four, bytes)
+        } else {
+            let bytes = remaining.min(MAX_DESCRIPTOR_BYTES);
+            (DataWidth::Bits8, BurstLength::One, bytes)
+        };
+        let config = DescriptorConfig::new()
+            .set_source(DRQ_DRAM, false, width, burst)
+            .set_destination(DRQ_DRAM, false, width, burst);
+        descriptors[count] = Descriptor::new(
+            config,
+            source.wrapping_add(copied as u32),
+            destination.wrapping_add(copied as u32),
+            bytes as u32,
+        );
+        count += 1;
+        copied += bytes;
+    }
+    (count, copied)
+}
+
+/// Data cache maintenance needed around DMA transfers, implemented by the user.
+pub trait CacheMaintenance {
+    /// Write back cached data of `len` bytes at `address` to memory.
+    fn clean_dcache(&mut self, address: usize, len: usize);
+    /// Discard cached data of `len` bytes at `address`, so later reads fetch memory.
+    fn invalidate_dcache(&mut self, address: usize, len: usize);
+}
+
+/// Cache maintenance for memory not cached by the processor; does nothing.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoCache;
+
+impl CacheMaintenance for NoCache {
+    #[inline]
+    fn clean_dcache(&mut self, _address: usize, _len: usize) {}
+    #[inline]
+    fn invalidate_dcache(&mut self, _address: usize, _len: usize) {}
+}
+
+/// DMA channel able to run a descriptor chain.
+pub trait TransferChannel {
+    /// Link `descriptors` into a chain and start transferring it.
+    ///
+    /// # Safety
+    ///
+    /// See [`AnyChannel::transfer`].
+    unsafe fn transfer<'t>(&'t mut self, descriptors: &'t mut [Descriptor]) -> Transfer<'t>;
+    /// Start transferring `descriptors` as already linked, without writing them.
+    ///
+    /// # Safety
+    ///
+    /// See [`AnyChannel::transfer_linked`].
+    unsafe fn transfer_linked<'t>(&'t mut self, descriptors: &'t mut [Descriptor]) -> Transfer<'t>;
+}
+
+/// Copy `src` into `dst` using DMA `channel`, waiting until done.
+///
+/// `src` and `dst` are cleaned before the copy and `dst` is invalidated
+/// after it through `cache`. Each descriptor chain is cleaned after it is
+/// linked, and started without writing it again.
+///
+/// # Safety
+///
+/// Both buffers must be physically addressed, as their addresses are given
+/// to the controller as is.
+///
+/// # Panics
+///
+/// Panics if the two slices have different lengths.
+pub unsafe fn memcpy(
+    channel: &mut impl TransferChannel,
+    dst: &mut [u8],
+    src: &[u8],
+    cache: &mut impl CacheMaintenance,
+) {
+    assert_eq!(dst.len(), src.len());
+    let (destination, source) = (dst.as_mut_ptr() as usize, src.as_ptr() as usize);
+    cache.clean_dcache(source, src.len());
+    // dirty lines of `dst` must not be written back over copied data later
+    cache.clean_dcache(destination, dst.len());
+    // descriptors live on stack while the controller reads them
+    let mut descriptors = [Descriptor::new(DescriptorConfig::new(), 0, 0, 0); 4];
+    let mut done = 0;
+    while done < dst.len() {
+        let (count, bytes) = build_copy_chain(
+            (destination + done) as u32,
+            (source + done) as u32,
+            dst.len() - done,
+            &mut descriptors,
+        );
+        link_chain(&mut descriptors[..count]);
+        cache.clean_dcache(descriptors.as_ptr() as usize, size_of_val(&descriptors));
+        // descriptors and buffers outlive the transfer, as it is waited on here
+        unsafe { channel.transfer_linked(&mut descriptors[..count]) }.wait();
+        done += bytes;
+    }
+    cache.invalidate_dcache(destination, dst.len());
+}
+
+/// Error acquiring a DMA channel at run time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChannelError {
+    /// There is no channel with this number.
+    OutOfRange,
+    /// Channel is already acquired and not yet released.
+    InUse,
+}
+
+/// Managed DMA controller structure.
+pub struct Dma<DMA> {
+    dma: DMA,
+    in_use: AtomicU32,
+}
+
+impl<DMA: AsRef<RegisterBlock>> Dma<DMA> {
+    /// Create a DMA controller instance.
+    #[inline]
+    pub fn new(dma: DMA) -> Self {
+        Self {
+            dma,
+            in_use: AtomicU32::new(0),
+        }
+    }
+    /// Acquire channel `n` until the returned handle is dropped.
+    ///
+    /// Channels from [`split`](Self::split) and from this function cannot
+    /// exist at the same time, as `split` borrows this controller mutably.
+    #[inline]
+    pub fn acquire_channel(&self, n: usize) -> Result<AnyChannel<'_>, ChannelError> {
+        if n >= CHANNEL_COUNT {
+            return Err(ChannelError::OutOfRange);
+        }
+        if self.in_use.fetch_or(1 << n, Ordering::Acquire) & (1 << n) != 0 {
+            return Err(ChannelError::InUse);
+        }
+        Ok(AnyChannel {
+            dma: self.dma.as_ref(),
+            in_use: &self.in_use,
+            index: n,
+        })
+    }
+    /// Split DMA controller into owned channels.
+    ///
+    /// Channels borrow this controller, so `split` cannot be called again
+    /// until all channels from the previous split are dropped.
+    #[inline]
+    pub fn split(&mut self) -> DmaChannels<'_> {
+        let dma = self.dma.as_ref();
+        DmaChannels {
+            ch0: Channel { dma },
+            ch1: Channel { dma },
+            ch2: Channel { dma },
+            ch3: Channel { dma },
+            ch4: Channel { dma },
+            ch5: Channel { dma },
+            ch6: Channel { dma },
+            ch7: Channel { dma },
+            ch8: Channel { dma },
+            ch9: Channel { dma },
+            ch10: Channel { dma },
+            ch11: Channel { dma },
+            ch12: Channel { dma },
+            ch13: Channel { dma },
+            ch14: Channel { dma },
+            ch15: Channel { dma },
+        }
+    }
+    /// Release DMA controller.
+    #[inline]
+    pub fn free(self) -> DMA {
+        self.dma
+    }
+}
+
+/// Owned channels of a DMA controller.
+pub struct DmaChannels<'a> {
+    /// DMA channel 0.
+    pub ch0: Channel<'a, 0>,
+    /// DMA channel 1.
+    pub ch1: Channel<'a, 1>,
+    /// DMA channel 2.
+    pub ch2: Channel<'a, 2>,
+    /// DMA channel 3.
+    pub ch3: Channel<'a, 3>,
+    /// DMA channel 4.
+    pub ch4: Channel<'a, 4>,
+    /// DMA channel 5.
+    pub ch5: Channel<'a, 5>,
+    /// DMA channel 6.
+    pub ch6: Channel<'a, 6>,
+    /// DMA channel 7.
+    pub ch7: Channel<'a, 7>,
+    /// DMA channel 8.
+    pub ch8: Channel<'a, 8>,
+    /// DMA channel 9.
+    pub ch9: Channel<'a, 9>,
+    /// DMA channel 10.
+    pub ch10: Channel<'a, 10>,
+    /// DMA channel 11.
+    pub ch11: Channel<'a, 11>,
+    /// DMA channel 12.
+    pub ch12: Channel<'a, 12>,
+    /// DMA channel 13.
+    pub ch13: Channel<'a, 13>,
+    /// DMA channel 14.
+    pub ch14: Channel<'a, 14>,
+    /// DMA channel 15.
+    pub ch15: Channel<'a, 15>,
+}
+
+/// Exclusive ownership token of DMA channel `N`.
+pub struct Channel<'a, const N: usize> {
+    dma: &'a RegisterBlock,
+}
+
+impl<'a, const N: usize> Channel<'a, N> {
+    /// Offset of channel registers from DMA controller base address.
+    pub const OFFSET: usize = 0x100 + N * 0x40;
+
+    /// Get registers of this channel.
+    #[inline]
+    pub fn register_block(&self) -> &'a ChannelRegisterBlock {
+        &self.dma.channels[N]
+    }
+    /// Start transfer described by the descriptor chain at `descriptor_address`.
+    ///
+    /// # Safety
+    ///
+    /// Every descriptor in the chain, and every source and destination it
+    /// points to, must be physically addressed, valid for the transfer and
+    /// stay in place until this channel is stopped or no longer busy.
+    #[inline]
+    pub unsafe fn start(&mut self, descriptor_address: u32) {
+        let ch = self.register_block();
+        unsafe {
+            ch.descriptor_address.write(descriptor_address);
+            ch.enable.write(1);
+        }
+    }
+    /// Stop transfer on this channel.
+    #[inline]
+    pub fn stop(&mut self) {
+        unsafe { self.register_block().enable.write(0) };
+    }
+    /// Check if this channel is busy transferring.
+    #[inline]
+    pub fn is_busy(&self) -> bool {
+        self.dma.status.read() & (1 << N) != 0
+    }
+    /// Link `descriptors` into a chain and start transferring it.
+    ///
+    /// # Safety
+    ///
+    /// See [`AnyChannel::transfer`].
+    #[inline]
+    pub unsafe fn transfer<'t>(&'t mut self, descriptors: &'t mut [Descriptor]) -> Transfer<'t> {
+        Transfer::start(self.dma, N, descriptors)
+    }
+    /// Start transferring `descriptors` as already linked, without writing them.
+    ///
+    /// # Safety
+    ///
+    /// See [`AnyChannel::transfer_linked`].
+    #[inline]
+    pub unsafe fn transfer_linked<'t>(
+        &'t mut self,
+        descriptors: &'t mut [Descriptor],
+    ) -> Transfer<'t> {
+        Transfer::start_linked(self.dma, N, descriptors)
+    }
+}
+
+impl<const N: usize> TransferChannel for Channel<'_, N> {
+    #[inline]
+    unsafe fn transfer<'t>(&'t mut self, descriptors: &'t mut [Descriptor]) -> Transfer<'t> {
+        unsafe { Channel::transfer(self, descriptors) }
+    }
+    #[inline]
+    unsafe fn transfer_linked<'t>(&'t mut self, descriptors: &'t mut [Descriptor]) -> Transfer<'t> {
+        unsafe { Channel::transfer_linked(self, descriptors) }
+    }
+}
+
+/// DMA channel acquired at run time, released when dropped.
+pub struct AnyChannel<'a> {
+    dma: &'a RegisterBlock,
+    in_use: &'a AtomicU32,
+    index: usize,
+}
+
+impl<'a> AnyChannel<'a> {
+    /// Get number of this channel.
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    /// Get registers of this channel.
+    #[inline]
+    pub fn register_block(&self) -> &'a ChannelRegisterBlock {
+        &self.dma.channels[self.index]
+    }
+    /// Start transfer described by the descriptor chain at `descriptor_address`.
+    ///
+    /// # Safety
+    ///
+    /// Every descriptor in the chain, and every source and destination it
+    /// points to, must be physically addressed, valid for the transfer and
+    /// stay in place until this channel is stopped or no longer busy.
+    #[inline]
+    pub unsafe fn start(&mut self, descriptor_address: u32) {
+        let ch = self.register_block();
+        unsafe {
+            ch.descriptor_address.write(descriptor_address);
+            ch.enable.write(1);
+        }
+    }
+    /// Stop transfer on this channel.
+    #[inline]
+    pub fn stop(&mut self) {
+        unsafe { self.register_block().enable.write(0) };
+    }
+    /// Check if this channel is busy transferring.
+    #[inline]
+    pub fn is_busy(&self) -> bool {
+        self.dma.status.read() & (1 << self.index) != 0
+    }
+    /// Link `descriptors` into a chain and start transferring it.
+    ///
+    /// Descriptors stay borrowed until the returned guard is done or dropped.
+    ///
+    /// # Safety
+    ///
+    /// Source and destination addresses in descriptors are given to the
+    /// controller as is. They must be physically addressed, valid for the
+    /// transfer, and stay valid until the transfer is done or stopped.
+    ///
+    /// The returned guard must be waited on or dropped, not leaked with
+    /// [`core::mem::forget`]; otherwise the channel keeps running after
+    /// `descriptors` and the buffers are released.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `descriptors` is empty.
+    #[inline]
+    pub unsafe fn transfer<'t>(&'t mut self, descriptors: &'t mut [Descriptor]) -> Transfer<'t> {
+        Transfer::start(self.dma, self.index, descriptors)
+    }
+    /// Start transferring `descriptors` as already linked, without writing them.
+    ///
+    /// Use this after [`link_chain`] when the chain has to be cleaned from the
+    /// data cache before the controller reads it.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`transfer`](Self::transfer); in addition, `descriptors` must
+    /// form a chain from its first element, visible to the controller.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `descriptors` is empty.
+    #[inline]
+    pub unsafe fn transfer_linked<'t>(
+        &'t mut self,
+        descriptors: &'t mut [Descriptor],
+    ) -> Transfer<'t> {
+        Transfer::start_linked(self.dma, self.index, descriptors)
+    }
+}
+
+impl TransferChannel for AnyChannel<'_> {
+    #[inline]
+    unsafe fn transfer<'t>(&'t mut self, descriptors: &'t mut [Descriptor]) -> Transfer<'t> {
+        unsafe { AnyChannel::transfer(self, descriptors) }
+    }
+    #[inline]
+    unsafe fn transfer_linked<'t>(&'t mut self, descriptors: &'t mut [Descriptor]) -> Transfer<'t> {
+        unsafe { AnyChannel::transfer_linked(self, descriptors) }
+    }
+}
+
+impl Drop for AnyChannel<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        self.stop();
+        self.in_use.fetch_and(!(1 << self.index), Ordering::Release);
+    }
+}
+
+/// Ongoing transfer of a descriptor chain on one channel.
+///
+/// Dropping the guard before the transfer is done stops the channel. The
+/// guard must not be leaked, see [`AnyChannel::transfer`].
+#[must_use = "dropping a transfer stops it"]
+pub struct Transfer<'t> {
+    dma: &'t RegisterBlock,
+    index: usize,
+    _descriptors: PhantomData<&'t mut [Descriptor]>,
+}
+
+impl<'t> Transfer<'t> {
+    #[inline]
+    fn start(dma: &'t RegisterBlock, index: usize, descriptors: &'t mut [Descriptor]) -> Self {
+        link_chain(descriptors);
+        Self::start_linked(dma, index, descriptors)
+    }
+    #[inline]
+    fn start_linked(
+        dma: &'t RegisterBlock,
+        index: usize,
+        descriptors: &'t mut [Descriptor],
+    ) -> Self {
+        assert!(!descriptors.is_empty());
+        let ch = &dma.channels[index];
+        unsafe {
+            ch.descriptor_address.write(descriptors[0].address());
+            ch.enable.write(1);
+        }
+        Transfer {
+            dma,
+            index,
+            _descriptors: PhantomData,
+        }
+    }
+    /// Check if the whole chain is transferred.
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        self.dma.status.read() & (1 << self.index) == 0
+    }
+    /// Wait for the whole chain to be transferred, then stop the channel.
+    #[inline]
+    pub fn wait(self) {
+        while !self.is_done() {
+            core::hint::spin_loop();
+        }
+        // dropping self stops the channel
+    }
+}
+
+impl Drop for Transfer<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { self.dma.channels[self.index].enable.write(0) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        build_copy_chain, link_chain, memcpy, BurstLength, CacheMaintenance, Channel, ChannelError,
+        ChannelRegisterBlock, DataWidth, Descriptor, DescriptorConfig, Dma, RegisterBlock,
+        DRQ_DRAM, DRQ_SPI0, END_OF_CHAIN, MAX_DESCRIPTOR_BYTES,
+    };
+    use core::{
+        mem::size_of,
+        sync::atomic::{AtomicU32, Ordering},
+    };
+    use memoffset::offset_of;
+
+    #[test]
+    fn offset_dma() {
+        assert_eq!(offset_of!(RegisterBlock, irq_enable), 0x0);
+        assert_eq!(offset_of!(RegisterBlock, irq_pending), 0x10);
+        assert_eq!(offset_of!(RegisterBlock, auto_gating), 0x28);
+        assert_eq!(offset_of!(RegisterBlock, status), 0x30);
+        assert_eq!(offset_of!(RegisterBlock, channels), 0x100);
+        assert_eq!(offset_of!(ChannelRegisterBlock, descriptor_address), 0x08);
+        assert_eq!(offset_of!(ChannelRegisterBlock, byte_counter_left), 0x18);
+        assert_eq!(offset_of!(ChannelRegisterBlock, mode), 0x28);
+        assert_eq!(offset_of!(ChannelRegisterBlock, package_number), 0x30);
+        assert_eq!(size_of::<ChannelRegisterBlock>(), 0x40);
+        assert_eq!(size_of::<RegisterBlock>(), 0x500);
+    }
+
+    struct MockDma<'a>(&'a [AtomicU32; 0x140]);
+
+    impl AsRef<RegisterBlock> for MockDma<'_> {
+        fn as_ref(&self) -> &RegisterBlock {
+            unsafe { &*(self.0.as_ptr() as *const RegisterBlock) }
+        }
+    }
+
+    #[test]
+    fn channel_register_base() {
+        assert_eq!(Channel::<0>::OFFSET, 0x100);
+        assert_eq!(Channel::<5>::OFFSET, 0x240);
+        assert_eq!(Channel::<15>::OFFSET, 0x4c0);
+
+        let memory = [const { AtomicU32::new(0) }; 0x140];
+        let base = memory.as_ptr() as usize;
+        let mut dma = Dma::new(MockDma(&memory));
+        let mut channels = dma.split();
+        let ch5 = channels.ch5.register_block() as *const _ as usize;
+        assert_eq!(ch5 - base, Channel::<5>::OFFSET);
+        let ch15 = channels.ch15.register_block() as *const _ as usize;
+        assert_eq!(ch15 - base, Channel::<15>::OFFSET);
+
+        unsafe { channels.ch5.start(0x4000_1000) };
+        assert_eq!(
+            memory[(0x240 + 0x08) / 4].load(Ordering::Relaxed),
+            0x4000_1000
+        );
+        assert_eq!(memory[0x240 / 4].load(Ordering::Relaxed), 1);
+        assert_eq!(memory[0x100 / 4].load(Ordering::Relaxed), 0);
+
+        assert!(!channels.ch5.is_busy());
+        memory[0x30 / 4].store(1 << 5, Ordering::Relaxed);
+        assert!(channels.ch5.is_busy());
+        assert!(!channels.ch0.is_busy());
+
+        channels.ch5.stop();
+        assert_eq!(memory[0x240 / 4].load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn struct_descriptor_functions() {
+        // DRAM to SPI0 transmit FIFO: bytes, memory address increasing, FIFO fixed
+        let config = DescriptorConfig::default()
+            .set_source(DRQ_DRAM, false, DataWidth::Bits8, BurstLength::One)
+            .set_destination(DRQ_SPI0, true, DataWidth::Bits8, BurstLength::One);
+        assert_eq!(config, DescriptorConfig(0x0116_0001));
+        assert_eq!(config.source_drq(), DRQ_DRAM);
+        assert_eq!(config.destination_drq(), DRQ_SPI0);
+        let config = config
+            .set_source(DRQ_DRAM, false, DataWidth::Bits32, BurstLength::Eight)
+            .set_destination(0x3F, false, DataWidth::Bits64, BurstLength::Sixteen);
+        assert_eq!(config, DescriptorConfig(0x06FF_0481));
+
+        let mut descriptor = Descriptor::new(config, 0x4000_0000, 0x0402_5200, 100);
+        assert_eq!(descriptor.parameter, 8);
+        assert_eq!(descriptor.link, END_OF_CHAIN);
+        let next = Descriptor::new(config, 0x4000_0064, 0x0402_5200, 100);
+        descriptor.link_to(&next);
+        assert_eq!(descriptor.link, &next as *const _ as usize as u32);
+        assert_eq!(size_of::<Descriptor>(), 24);
+    }
+
+    #[test]
+    fn acquire_channel_and_transfer() {
+        extern crate std;
+        let memory = [const { AtomicU32::new(0) }; 0x140];
+        let dma = Dma::new(MockDma(&memory));
+        assert_eq!(
+            dma.acquire_channel(16).err(),
+            Some(ChannelError::OutOfRange)
+        );
+        let mut ch3 = dma.acquire_channel(3).unwrap();
+        assert_eq!(ch3.index(), 3);
+        assert_eq!(dma.acquire_channel(3).err(), Some(ChannelError::InUse));
+        let ch4 = dma.acquire_channel(4).unwrap();
+        drop(ch4);
+        assert!(dma.acquire_channel(4).is_ok());
+
+        let config = DescriptorConfig::new()
+            .set_source(DRQ_DRAM, false, DataWidth::Bits32, BurstLength::Four)
+            .set_destination(DRQ_DRAM, false, DataWidth::Bits32, BurstLength::Four);
+        let mut descriptors = [
+            Descriptor::new(config, 0x4000_0000, 0x4100_0000, 0x1000),
+            Descriptor::new(config, 0x4000_1000, 0x4100_1000, 0x1000),
+            Descriptor::new(config, 0x4000_2000, 0x4100_2000, 0x10),
+        ];
+        let head = descriptors[0].address();
+        // controller is busy until the emulated hardware finishes
+        memory[0x30 / 4].store(1 << 3, Ordering::SeqCst);
+        let transfer = unsafe { ch3.transfer(&mut descriptors) };
+        assert_eq!(memory[(0x1C0 + 0x08) / 4].load(Ordering::SeqCst), head);
+        assert_eq!(memory[0x1C0 / 4].load(Ordering::SeqCst), 1);
+        assert!(!transfer.is_done());
+        std::thread::scope(|s| {
+            s.spawn(|| memory[0x30 / 4].store(0, Ordering::SeqCst));
+            transfer.wait();
+        });
+        assert_eq!(memory[0x1C0 / 4].load(Ordering::SeqCst), 0);
+        assert_eq!(descriptors[0].link, descriptors[1].address());
+        assert_eq!(descriptors[1].link, descriptors[2].address());
+        assert_eq!(descriptors[2].link, END_OF_CHAIN);
+
+        // dropping an unfinished transfer stops its channel
+        memory[0x30 / 4].store(1 << 3, Ordering::SeqCst);
+        drop(unsafe { ch3.transfer(&mut descriptors[2..]) });
+        assert_eq!(memory[0x1C0 / 4].load(Ordering::SeqCst), 0);
+        drop(ch3);
+        assert!(dma.acquire_channel(3).is_ok());
+
+        link_chain(&mut []);
+    }
+
+    #[test]
+    fn copy_chain_widths_and_splits() {
+        let empty = Descriptor::new(DescriptorConfig::new(), 0, 0, 0);
+        let words = DescriptorConfig::new()
+            .set_source(DRQ_DRAM, false, DataWidth::Bits32, BurstLength::Four)
+            .set_destination(DRQ_DRAM, false, DataWidth::Bits32, BurstLength::Four);
+        let bytes = DescriptorConfig::new()
+            .set_source(DRQ_DRAM, false, DataWidth::Bits8, BurstLength::One)
+            .set_destination(DRQ_DRAM, false, DataWidth::Bits8, BurstLength::One);
+
+        // aligned with an odd length: words, then remaining bytes
+        let mut descriptors = [empty; 4];
+        let ans = build_copy_chain(0x4100_0000, 0x4000_0000, 0x103, &mut descriptors);
+        assert_eq!(ans, (2, 0x103));
+        assert_eq!(
+            descriptors[0],
+            Descriptor::new(words, 0x4000_0000, 0x4100_0000, 0x100)
+        );
+        assert_eq!(
+            descriptors[1],
+            Descriptor::new(bytes, 0x4000_0100, 0x4100_0100, 3)
+        );
+
+        // misaligned: bytes only, split at the descriptor limit
+        let len = MAX_DESCRIPTOR_BYTES + 10;
+        let ans = build_copy_chain(0x4100_0001, 0x4000_0000, len, &mut descriptors);
+        assert_eq!(ans, (2, len));
+        assert_eq!(descriptors[0].config, bytes);
+        assert_eq!(descriptors[0].byte_count as usize, MAX_DESCRIPTOR_BYTES);
+        assert_eq!(
+            descriptors[1].source as usize,
+            0x4000_0000 + MAX_DESCRIPTOR_BYTES
+        );
+        assert_eq!(descriptors[1].byte_count, 10);
+
+        // too few descriptors copy a prefix, continued by the next call
+        let len = 3 * MAX_DESCRIPTOR_BYTES;
+        let ans = build_copy_chain(0x4100_0000, 0x4000_0000, len, &mut descriptors[..2]);
+        assert_eq!(ans, (2, 2 * MAX_DESCRIPTOR_BYTES));
+        assert_eq!(descriptors[1].config, words);
+    }
+
+    #[derive(Default)]
+    struct MockCache {
+        events: [(bool, usize, usize); 8],
+        count: usize,
+        /// Link of the first descriptor when a descriptor array is cleaned.
+        cleaned_link: Option<u32>,
+    }
+
+    impl CacheMaintenance for MockCache {
+        fn clean_dcache(&mut self, address: usize, len: usize) {
+            if len.is_multiple_of(size_of::<Descriptor>()) && len > size_of::<Descriptor>() {
+                let first = unsafe { &*(address as *const Descriptor) };
+                self.cleaned_link = Some(first.link);
+            }
+            self.events[self.count] = (true, address, len);
+            self.count += 1;
+        }
+        fn invalidate_dcache(&mut self, address: usize, len: usize) {
+            self.events[self.count] = (false, address, len);
+            self.count += 1;
+        }
+    }
+
+    #[test]
+    fn memcpy_cache_maintenance() {
+        let memory = [const { AtomicU32::new(0) }; 0x140];
+        let dma = Dma::new(MockDma(&memory));
+        let mut channel = dma.acquire_channel(2).unwrap();
+        // word aligned, so words and remaining bytes take two descriptors
+        #[repr(align(4))]
+        struct Aligned([u8; 7]);
+        let src = Aligned([0x5A; 7]);
+        let mut dst = Aligned([0; 7]);
+        let mut cache = MockCache::default();
+        unsafe { memcpy(&mut channel, &mut dst.0, &src.0, &mut cache) };
+        let (src, dst) = (src.0.as_ptr() as usize, dst.0.as_ptr() as usize);
+        // sources and descriptors cleaned before, destination invalidated after
+        assert_eq!(cache.count, 4);
+        assert_eq!(cache.events[0], (true, src, 7));
+        assert_eq!(cache.events[1], (true, dst, 7));
+        assert_eq!((cache.events[2].0, cache.events[2].2), (true, 4 * 24));
+        assert_eq!(cache.events[3], (false, dst, 7));
+        // chain is linked before it is cleaned, and started as cleaned
+        let head = cache.events[2].1 as u32;
+        assert_eq!(cache.cleaned_link, Some(head + 24));
+        assert_eq!(memory[(0x180 + 0x08) / 4].load(Ordering::SeqCst), head);
+        // channel 2 was started, then stopped when done
+        assert_eq!(memory[0x180 / 4].load(Ordering::SeqCst), 0);
+    }
+}
@@ -1,18 +1,24 @@
 //! Allwinner GPIO controller.
+mod config;
 mod disabled;
 mod eint;
+mod flex;
 mod function;
 mod input;
 mod mode;
 mod output;
+mod parallel;
 mod register;
 
+pub use config::{DriveLevel, PadDescription, Pull};
 pub use disabled::Disabled;
-pub use eint::{EintPad, Event};
+pub use eint::{DebounceClock, EintPad, Event};
+pub use flex::FlexPad;
 pub use function::Function;
 pub use input::Input;
 pub use output::Output;
-pub use register::{Eint, PioPow, Port, RegisterBlock};
+pub use parallel::ParallelPort;
+pub use register::{Eint, PendingEints, PioPow, Port, RegisterBlock};
 
 #[allow(unused)]
 macro_rules! impl_gpio_pins {
@@ -41,3 +47,19 @@ const fn port_cfg_index(p: char, n: u8) -> (usize, usize, u8) {
     let cfg_field_idx = (n & 0b111) << 2;
     (port_idx, cfg_reg_idx, cfg_field_idx)
 }
+
+#[inline]
+const fn port_drv_index(p: char, n: u8) -> (usize, usize, u8) {
+    // drive registers have the same 4-bit field layout as configuration registers
+    port_cfg_index(p, n)
+}
+
+#[inline]
+const fn port_pull_index(p: char, n: u8) -> (usize, usize, u8) {
+    assert!(p as usize >= b'B' as usize && p as usize <= b'G' as usize);
+    assert!(n <= 31);
+    let port_idx = p as usize - b'B' as usize;
+    let pull_reg_idx = (n >> 4) as usize;
+    let pull_field_idx = (n & 0b1111) << 1;
+    (port_idx, pull_reg_idx, pull_field_idx)
+}
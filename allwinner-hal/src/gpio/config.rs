@@ -0,0 +1,67 @@
+//! Electrical configuration of GPIO pads.
+
+/// Internal pull resistor of a pad.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Pull {
+    /// No pull resistor.
+    None = 0,
+    /// Pull up.
+    Up = 1,
+    /// Pull down.
+    Down = 2,
+}
+
+impl Pull {
+    /// Decode 2-bit pull register field; the reserved value reads as no pull.
+    #[inline]
+    pub(crate) const fn from_bits(bits: u32) -> Self {
+        match bits & 0b11 {
+            1 => Pull::Up,
+            2 => Pull::Down,
+            _ => Pull::None,
+        }
+    }
+}
+
+/// Output drive strength of a pad, from weakest to strongest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DriveLevel {
+    /// Level 0.
+    L0 = 0,
+    /// Level 1.
+    L1 = 1,
+    /// Level 2.
+    L2 = 2,
+    /// Level 3.
+    L3 = 3,
+}
+
+impl DriveLevel {
+    /// Decode drive register field; only the lower 2 bits select the level.
+    #[inline]
+    pub(crate) const fn from_bits(bits: u32) -> Self {
+        match bits & 0b11 {
+            0 => DriveLevel::L0,
+            1 => DriveLevel::L1,
+            2 => DriveLevel::L2,
+            _ => DriveLevel::L3,
+        }
+    }
+}
+
+/// Decoded state of one pad.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PadDescription {
+    /// Port letter, e.g. `'B'`.
+    pub port: char,
+    /// Pad number in port.
+    pub number: u8,
+    /// Function select field, see [`FlexPad::set_function`](super::FlexPad::set_function).
+    pub function: u8,
+    /// Internal pull resistor.
+    pub pull: Pull,
+    /// Output drive strength.
+    pub drive: DriveLevel,
+    /// Pad level in data register.
+    pub high: bool,
+}
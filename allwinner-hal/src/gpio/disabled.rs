@@ -1,5 +1,6 @@
 use super::{
     eint::EintPad,
+    flex::FlexPad,
     function::Function,
     input::Input,
     mode::{set_mode, HasMode},
@@ -33,6 +34,11 @@ impl<'a, const P: char, const N: u8> Disabled<'a, P, N> {
     pub fn into_eint(self) -> EintPad<'a, P, N> {
         set_mode(self)
     }
+    /// Configures the pad to have its mode selected at runtime.
+    #[inline]
+    pub fn into_flex(self) -> FlexPad<'a, P, N> {
+        unsafe { FlexPad::from_gpio(self.gpio) }
+    }
 
     /// Internal constructor for ROM runtime. Do not use.
     #[doc(hidden)]
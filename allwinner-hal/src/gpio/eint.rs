@@ -37,6 +37,7 @@ impl<'a, const P: char, const N: u8> EintPad<'a, P, N> {
 }
 
 impl<'a, const P: char, const N: u8> EintPad<'a, P, N> {
+    /// Trigger external interrupt of this pad on `event`.
     #[inline]
     pub fn listen(&mut self, event: Event) {
         let event_id = match event {
@@ -55,24 +56,40 @@ impl<'a, const P: char, const N: u8> EintPad<'a, P, N> {
         let cfg_reg = &self.gpio.eint[port_idx].cfg[cfg_reg_idx];
         unsafe { cfg_reg.modify(|cfg| (cfg & mask) | value) };
     }
+    /// Enable external interrupt of this pad.
     #[inline]
     pub fn enable_interrupt(&mut self) {
         let idx = const { port_index(P) };
         unsafe { self.gpio.eint[idx].ctl.modify(|value| value | (1 << N)) }
     }
+    /// Disable external interrupt of this pad.
     #[inline]
     pub fn disable_interrupt(&mut self) {
         let idx = const { port_index(P) };
         unsafe { self.gpio.eint[idx].ctl.modify(|value| value & !(1 << N)) }
     }
+    /// Clear pending external interrupt of this pad.
     #[inline]
     pub fn clear_interrupt_pending_bit(&mut self) {
         unsafe { self.gpio.eint[const { port_index(P) }].status.write(1 << N) }
     }
+    /// Check if external interrupt of this pad is pending.
     #[inline]
-    pub fn check_interrupt(&mut self) -> bool {
+    pub fn check_interrupt(&self) -> bool {
         self.gpio.eint[const { port_index(P) }].status.read() & (1 << N) != 0
     }
+    /// Set debounce clock of external interrupts, sampling pad level at `clock / 2^prescale`.
+    ///
+    /// Glitches shorter than one debounce clock period are filtered out, e.g.
+    /// `DebounceClock::Losc32K` with prescale 5 samples at 1 kHz for mechanical
+    /// switches. Debounce clock is shared by all pads of this port.
+    #[inline]
+    pub fn set_debounce(&mut self, clock: DebounceClock, prescale: u8) {
+        assert!(prescale <= 7, "debounce prescale out of range");
+        let value = ((prescale as u32) << 4) | clock as u32;
+        let idx = const { port_index(P) };
+        unsafe { self.gpio.eint[idx].deb.modify(|deb| (deb & !0x71) | value) }
+    }
 }
 
 impl<'a, const P: char, const N: u8> HasMode<'a> for EintPad<'a, P, N> {
@@ -92,9 +109,71 @@ impl<'a, const P: char, const N: u8> HasMode<'a> for EintPad<'a, P, N> {
 /// External interrupt event.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Event {
+    /// Rising edge.
     PositiveEdge,
+    /// Falling edge.
     NegativeEdge,
+    /// High level.
     HighLevel,
+    /// Low level.
     LowLevel,
+    /// Both rising and falling edges.
     BothEdges,
 }
+
+/// Clock source of external interrupt debounce.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DebounceClock {
+    /// 32 kHz low speed oscillator.
+    Losc32K = 0,
+    /// 24 MHz high speed oscillator.
+    Hosc24M = 1,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DebounceClock, Event};
+    use crate::gpio::{Disabled, RegisterBlock};
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    const WORDS: usize = core::mem::size_of::<RegisterBlock>() / 4;
+
+    #[test]
+    fn eint_pad_configure() {
+        let memory = [const { AtomicU32::new(0) }; WORDS];
+        let gpio = unsafe { &*(&memory as *const _ as *const RegisterBlock) };
+        let load = |offset: usize| memory[offset / 4].load(Ordering::SeqCst);
+        // PD10: configuration register 1 field 2, port D interrupt group at 0x260
+        let mut pad = unsafe { Disabled::<'_, 'D', 10>::__new(gpio) }.into_eint();
+        assert_eq!(load(0x94), 0x0000_0E00);
+
+        pad.listen(Event::NegativeEdge);
+        assert_eq!(load(0x264), 0x0000_0100);
+        pad.listen(Event::BothEdges);
+        assert_eq!(load(0x264), 0x0000_0400);
+        pad.enable_interrupt();
+        assert_eq!(load(0x270), 1 << 10);
+
+        assert!(!pad.check_interrupt());
+        memory[0x274 / 4].store(1 << 10, Ordering::SeqCst);
+        assert!(pad.check_interrupt());
+        assert_eq!(gpio.eint[2].pending_eints().next(), Some(10));
+        pad.clear_interrupt_pending_bit();
+        // status is write-1-to-clear, so only this pad's bit is written
+        assert_eq!(load(0x274), 1 << 10);
+
+        // debounce register keeps bits outside clock select and prescale
+        memory[0x278 / 4].store(0xFFFF_FF00, Ordering::SeqCst);
+        pad.set_debounce(DebounceClock::Losc32K, 5);
+        assert_eq!(load(0x278), 0xFFFF_FF50);
+        pad.set_debounce(DebounceClock::Hosc24M, 0);
+        assert_eq!(load(0x278), 0xFFFF_FF01);
+        pad.set_debounce(DebounceClock::Hosc24M, 7);
+        assert_eq!(load(0x278), 0xFFFF_FF71);
+
+        pad.disable_interrupt();
+        assert_eq!(load(0x270), 0);
+        let _pad = pad.into_input();
+        assert_eq!(load(0x94), 0);
+    }
+}
@@ -0,0 +1,306 @@
+use super::{
+    config::{DriveLevel, PadDescription, Pull},
+    disabled::Disabled,
+    eint::EintPad,
+    function::Function,
+    input::Input,
+    mode::{read_mode_value, write_mode_value, HasMode},
+    output::Output,
+    port_drv_index, port_index, port_pull_index,
+    register::RegisterBlock,
+};
+
+/// Pad whose mode is selected at runtime.
+///
+/// Useful to multiplex one pad across peripherals, e.g. JTAG and UART,
+/// where the function is only known at runtime.
+pub struct FlexPad<'a, const P: char, const N: u8> {
+    gpio: &'a RegisterBlock,
+}
+
+impl<'a, const P: char, const N: u8> FlexPad<'a, P, N> {
+    /// Get current value of the function select field.
+    #[inline]
+    pub fn function(&self) -> u8 {
+        read_mode_value(self.gpio, P, N)
+    }
+    /// Set function select field at runtime.
+    ///
+    /// `n` is 0 for input, 1 for output, 2..=8 for alternate functions,
+    /// 14 for external interrupt and 15 for disabled.
+    #[inline]
+    pub fn set_function(&mut self, n: u8) {
+        assert!(n <= 0xF, "function select value out of range");
+        unsafe { write_mode_value(self.gpio, P, N, n) }
+    }
+    /// Get internal pull resistor setting.
+    #[inline]
+    pub fn pull(&self) -> Pull {
+        let (port_idx, reg_idx, field_idx) = const { port_pull_index(P, N) };
+        Pull::from_bits(self.gpio.port[port_idx].pull[reg_idx].read() >> field_idx)
+    }
+    /// Set internal pull resistor.
+    #[inline]
+    pub fn set_pull(&mut self, pull: Pull) {
+        let (port_idx, reg_idx, field_idx) = const { port_pull_index(P, N) };
+        let value = (pull as u32) << field_idx;
+        unsafe {
+            self.gpio.port[port_idx].pull[reg_idx]
+                .modify(|pull| (pull & !(0b11 << field_idx)) | value)
+        };
+    }
+    /// Get output drive strength.
+    #[inline]
+    pub fn drive_strength(&self) -> DriveLevel {
+        let (port_idx, reg_idx, field_idx) = const { port_drv_index(P, N) };
+        DriveLevel::from_bits(self.gpio.port[port_idx].drv[reg_idx].read() >> field_idx)
+    }
+    /// Set output drive strength.
+    #[inline]
+    pub fn set_drive_strength(&mut self, level: DriveLevel) {
+        let (port_idx, reg_idx, field_idx) = const { port_drv_index(P, N) };
+        let value = (level as u32) << field_idx;
+        unsafe {
+            self.gpio.port[port_idx].drv[reg_idx].modify(|drv| (drv & !(0b11 << field_idx)) | value)
+        };
+    }
+    /// Check if pad level in data register is high.
+    #[inline]
+    pub fn is_high(&self) -> bool {
+        self.gpio.port[const { port_index(P) }].dat.read() & (1 << N) != 0
+    }
+    /// Drive pad high without disturbing other pads of the same port.
+    ///
+    /// The data register has no set or clear alias, so this is a
+    /// read-modify-write inside a critical section; it is safe against
+    /// interrupt handlers driving other pads of this port.
+    #[inline]
+    pub fn set_high_atomic(&mut self) {
+        self.modify_dat_atomic(|dat| dat | (1 << N));
+    }
+    /// Drive pad low without disturbing other pads of the same port.
+    ///
+    /// See [`set_high_atomic`](Self::set_high_atomic) for details.
+    #[inline]
+    pub fn set_low_atomic(&mut self) {
+        self.modify_dat_atomic(|dat| dat & !(1 << N));
+    }
+    /// Toggle pad level without disturbing other pads of the same port.
+    ///
+    /// See [`set_high_atomic`](Self::set_high_atomic) for details.
+    #[inline]
+    pub fn toggle_atomic(&mut self) {
+        self.modify_dat_atomic(|dat| dat ^ (1 << N));
+    }
+    #[inline]
+    fn modify_dat_atomic(&mut self, f: impl FnOnce(u32) -> u32) {
+        let dat = &self.gpio.port[const { port_index(P) }].dat;
+        // not an atomic memory operation, which the core may not support on device memory
+        critical_section::with(|_| unsafe { dat.modify(f) });
+    }
+    /// Get function, pull, drive strength and level of this pad at once.
+    #[inline]
+    pub fn describe(&self) -> PadDescription {
+        PadDescription {
+            port: P,
+            number: N,
+            function: self.function(),
+            pull: self.pull(),
+            drive: self.drive_strength(),
+            high: self.is_high(),
+        }
+    }
+    /// Configures the pad to operate as an input pad.
+    #[inline]
+    pub fn into_input(self) -> Input<'a, P, N> {
+        self.into_mode()
+    }
+    /// Configures the pad to operate as an output pad.
+    #[inline]
+    pub fn into_output(self) -> Output<'a, P, N> {
+        self.into_mode()
+    }
+    /// Configures the pad to operate as an alternate function pad.
+    #[inline]
+    pub fn into_function<const F: u8>(self) -> Function<'a, P, N, F> {
+        self.into_mode()
+    }
+    /// Configures the pad to operate as an external interrupt pad.
+    #[inline]
+    pub fn into_eint(self) -> EintPad<'a, P, N> {
+        self.into_mode()
+    }
+    /// Configures the pad to operate as a disabled pad.
+    #[inline]
+    pub fn into_disabled(self) -> Disabled<'a, P, N> {
+        self.into_mode()
+    }
+    #[inline]
+    fn into_mode<U: HasMode<'a>>(self) -> U {
+        unsafe { write_mode_value(self.gpio, P, N, U::VALUE) };
+        unsafe { U::from_gpio(self.gpio) }
+    }
+    /// Internal constructor from a pad of another mode.
+    #[inline]
+    pub(crate) unsafe fn from_gpio(gpio: &'a RegisterBlock) -> Self {
+        Self { gpio }
+    }
+}
+
+impl<'a, const P: char, const N: u8> embedded_hal::digital::ErrorType for FlexPad<'a, P, N> {
+    type Error = core::convert::Infallible;
+}
+
+/// Drives data register level; it reaches the pad while in output function.
+impl<'a, const P: char, const N: u8> embedded_hal::digital::OutputPin for FlexPad<'a, P, N> {
+    #[inline]
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        let idx = const { port_index(P) };
+        unsafe { self.gpio.port[idx].dat.modify(|value| value & !(1 << N)) };
+        Ok(())
+    }
+    #[inline]
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        let idx = const { port_index(P) };
+        unsafe { self.gpio.port[idx].dat.modify(|value| value | (1 << N)) };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use crate::gpio::{Disabled, DriveLevel, PadDescription, Pull, RegisterBlock};
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    const WORDS: usize = core::mem::size_of::<RegisterBlock>() / 4;
+
+    #[test]
+    fn flex_pad_set_function() {
+        let memory = [const { AtomicU32::new(0) }; WORDS];
+        let gpio = unsafe { &*(&memory as *const _ as *const RegisterBlock) };
+        // PB8 is in port B configuration register 1, field 0
+        let cfg = || memory[0x34 / 4].load(Ordering::SeqCst);
+        memory[0x34 / 4].store(0xFFFF_FFFF, Ordering::SeqCst);
+
+        let pad = unsafe { Disabled::<'_, 'B', 8>::__new(gpio) }.into_function::<6>();
+        assert_eq!(cfg(), 0xFFFF_FFF6);
+        // typed conversion consumes `pad`
+        let pad = pad.into_function::<4>();
+        assert_eq!(cfg(), 0xFFFF_FFF4);
+
+        let mut pad = pad.into_flex();
+        assert_eq!(pad.function(), 4);
+        pad.set_function(6);
+        assert_eq!(cfg(), 0xFFFF_FFF6);
+        assert_eq!(pad.function(), 6);
+        let _pad = pad.into_input();
+        assert_eq!(cfg(), 0xFFFF_FFF0);
+    }
+
+    #[test]
+    fn flex_pad_describe() {
+        let memory = [const { AtomicU32::new(0) }; WORDS];
+        let gpio = unsafe { &*(&memory as *const _ as *const RegisterBlock) };
+        // PE20: configuration register 2 field 4, data bit 20,
+        // drive register 2 field 4, pull register 1 field 4
+        memory[0xC8 / 4].store(0x0003_0000, Ordering::SeqCst);
+        memory[0xD0 / 4].store(0x0010_0000, Ordering::SeqCst);
+        memory[0xDC / 4].store(0x0003_0000, Ordering::SeqCst);
+        memory[0xE8 / 4].store(0x0000_0200, Ordering::SeqCst);
+
+        let pad = unsafe { Disabled::<'_, 'E', 20>::__new(gpio) }
+            .into_function::<3>()
+            .into_flex();
+        assert_eq!(
+            pad.describe(),
+            PadDescription {
+                port: 'E',
+                number: 20,
+                function: 3,
+                pull: Pull::Down,
+                drive: DriveLevel::L3,
+                high: true,
+            }
+        );
+
+        memory[0xD0 / 4].store(0, Ordering::SeqCst);
+        memory[0xDC / 4].store(0x0001_0000, Ordering::SeqCst);
+        memory[0xE8 / 4].store(0x0000_0100, Ordering::SeqCst);
+        let description = pad.describe();
+        assert_eq!(description.pull, Pull::Up);
+        assert_eq!(description.drive, DriveLevel::L1);
+        assert!(!description.high);
+    }
+
+    #[test]
+    fn flex_pad_set_pull_drive() {
+        let memory = [const { AtomicU32::new(0) }; WORDS];
+        let gpio = unsafe { &*(&memory as *const _ as *const RegisterBlock) };
+        // PB11: drive register 1 field 3, pull register 0 field 11
+        let drv = || memory[0x48 / 4].load(Ordering::SeqCst);
+        let pull = || memory[0x54 / 4].load(Ordering::SeqCst);
+        memory[0x48 / 4].store(0x2222_2222, Ordering::SeqCst);
+        memory[0x54 / 4].store(0xFFFF_FFFF, Ordering::SeqCst);
+
+        let mut pad = unsafe { Disabled::<'_, 'B', 11>::__new(gpio) }.into_flex();
+        pad.set_pull(Pull::Up);
+        assert_eq!(pull(), 0xFF7F_FFFF);
+        assert_eq!(pad.pull(), Pull::Up);
+        pad.set_pull(Pull::None);
+        assert_eq!(pull(), 0xFF3F_FFFF);
+        pad.set_drive_strength(DriveLevel::L3);
+        assert_eq!(drv(), 0x2222_3222);
+        assert_eq!(pad.drive_strength(), DriveLevel::L3);
+        pad.set_drive_strength(DriveLevel::L0);
+        assert_eq!(drv(), 0x2222_0222);
+
+        // pads in the upper half of a port use the second pull register
+        memory[0xE4 / 4].store(0xFFFF_FFFF, Ordering::SeqCst);
+        memory[0xE8 / 4].store(0, Ordering::SeqCst);
+        let mut pad = unsafe { Disabled::<'_, 'E', 17>::__new(gpio) }.into_flex();
+        pad.set_pull(Pull::Down);
+        assert_eq!(memory[0xE4 / 4].load(Ordering::SeqCst), 0xFFFF_FFFF);
+        assert_eq!(memory[0xE8 / 4].load(Ordering::SeqCst), 0x0000_0008);
+    }
+
+    #[test]
+    fn flex_pad_atomic_level() {
+        let memory = [const { AtomicU32::new(0) }; WORDS];
+        let gpio = unsafe { &*(&memory as *const _ as *const RegisterBlock) };
+        // port C data register
+        let dat = || memory[0x70 / 4].load(Ordering::SeqCst);
+        memory[0x70 / 4].store(0x8000_0001, Ordering::SeqCst);
+
+        let mut pad = unsafe { Disabled::<'_, 'C', 4>::__new(gpio) }.into_flex();
+        pad.set_high_atomic();
+        assert_eq!(dat(), 0x8000_0011);
+        assert!(pad.is_high());
+        pad.toggle_atomic();
+        assert_eq!(dat(), 0x8000_0001);
+        pad.toggle_atomic();
+        pad.set_low_atomic();
+        assert_eq!(dat(), 0x8000_0001);
+        // usable as chip select of an SPI device
+        embedded_hal::digital::OutputPin::set_high(&mut pad).unwrap();
+        assert_eq!(dat(), 0x8000_0011);
+        embedded_hal::digital::OutputPin::set_low(&mut pad).unwrap();
+        assert_eq!(dat(), 0x8000_0001);
+
+        // two contexts driving different pads of one port lose no updates
+        memory[0x70 / 4].store(0, Ordering::SeqCst);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let gpio = unsafe { &*(&memory as *const _ as *const RegisterBlock) };
+                let mut pad = unsafe { Disabled::<'_, 'C', 2>::__new(gpio) }.into_flex();
+                for _ in 0..1000 {
+                    pad.toggle_atomic();
+                }
+            });
+            for _ in 0..1001 {
+                pad.toggle_atomic();
+            }
+        });
+        assert_eq!(dat(), 0x0000_0010);
+    }
+}
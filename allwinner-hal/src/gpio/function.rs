@@ -1,6 +1,7 @@
 use super::{
     disabled::Disabled,
     eint::EintPad,
+    flex::FlexPad,
     input::Input,
     mode::{borrow_with_mode, set_mode, HasMode},
     output::Output,
@@ -26,6 +27,17 @@ impl<'a, const P: char, const N: u8, const F: u8> Function<'a, P, N, F> {
         set_mode(self)
     }
     /// Configures the pad to operate as an alternate function pad.
+    ///
+    /// The pad is consumed, so the binding of the old function cannot be used anymore:
+    ///
+    /// ```compile_fail,E0382
+    /// # use allwinner_hal::gpio::{Disabled, RegisterBlock};
+    /// # fn f(gpio: &RegisterBlock) {
+    /// let uart_tx = unsafe { Disabled::<'_, 'B', 8>::__new(gpio) }.into_function::<6>();
+    /// let jtag_ms = uart_tx.into_function::<4>();
+    /// let uart_tx = uart_tx.into_function::<6>(); // `uart_tx` was moved
+    /// # }
+    /// ```
     #[inline]
     pub fn into_function<const F2: u8>(self) -> Function<'a, P, N, F2> {
         set_mode(self)
@@ -40,6 +52,11 @@ impl<'a, const P: char, const N: u8, const F: u8> Function<'a, P, N, F> {
     pub fn into_disabled(self) -> Disabled<'a, P, N> {
         set_mode(self)
     }
+    /// Configures the pad to have its mode selected at runtime.
+    #[inline]
+    pub fn into_flex(self) -> FlexPad<'a, P, N> {
+        unsafe { FlexPad::from_gpio(self.gpio) }
+    }
     /// Borrows the pad to temporarily use it as an input pad.
     #[inline]
     pub fn with_input<G, T>(&mut self, f: G) -> T
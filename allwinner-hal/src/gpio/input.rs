@@ -1,6 +1,7 @@
 use super::{
     disabled::Disabled,
     eint::EintPad,
+    flex::FlexPad,
     function::Function,
     mode::{borrow_with_mode, set_mode, HasMode},
     output::Output,
@@ -34,6 +35,11 @@ impl<'a, const P: char, const N: u8> Input<'a, P, N> {
     pub fn into_disabled(self) -> Disabled<'a, P, N> {
         set_mode(self)
     }
+    /// Configures the pad to have its mode selected at runtime.
+    #[inline]
+    pub fn into_flex(self) -> FlexPad<'a, P, N> {
+        unsafe { FlexPad::from_gpio(self.gpio) }
+    }
     /// Borrows the pad to temporarily use it as an output pad.
     #[inline]
     pub fn with_output<F, T>(&mut self, f: F) -> T
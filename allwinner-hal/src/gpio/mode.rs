@@ -46,6 +46,23 @@ unsafe fn write_mode<'a, T: HasMode<'a>, U: HasMode<'a>>(gpio: &RegisterBlock) {
     unsafe { cfg_reg.modify(|cfg| (cfg & mask) | value) };
 }
 
+/// Internal function to set GPIO pad mode from a runtime value.
+#[inline]
+pub unsafe fn write_mode_value(gpio: &RegisterBlock, p: char, n: u8, value: u8) {
+    let (port_idx, cfg_reg_idx, cfg_field_idx) = port_cfg_index(p, n);
+    let mask = !(0xF << cfg_field_idx);
+    let value = ((value & 0xF) as u32) << cfg_field_idx;
+    let cfg_reg = &gpio.port[port_idx].cfg[cfg_reg_idx];
+    unsafe { cfg_reg.modify(|cfg| (cfg & mask) | value) };
+}
+
+/// Internal function to read GPIO pad mode.
+#[inline]
+pub fn read_mode_value(gpio: &RegisterBlock, p: char, n: u8) -> u8 {
+    let (port_idx, cfg_reg_idx, cfg_field_idx) = port_cfg_index(p, n);
+    ((gpio.port[port_idx].cfg[cfg_reg_idx].read() >> cfg_field_idx) & 0xF) as u8
+}
+
 pub trait HasMode<'a> {
     const P: char;
     const N: u8;
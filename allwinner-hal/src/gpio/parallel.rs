@@ -0,0 +1,137 @@
+use super::{mode::HasMode, output::Output, port_index, register::RegisterBlock};
+
+/// Eight output pads of one port driven together as a parallel bus.
+///
+/// Bit `i` of written values drives the `i`-th pad the port was built from.
+/// Each write reads the port data register once and stores it once, so
+/// all pads of the bus change at the same time.
+pub struct ParallelPort<'a, const P: char> {
+    gpio: &'a RegisterBlock,
+    pins: [u8; 8],
+}
+
+impl<'a, const P: char> ParallelPort<'a, P> {
+    /// Drive pads whose bits are set in `mask` to levels in `value`.
+    ///
+    /// Pads outside `mask`, and other pads of this port, are left unchanged.
+    #[inline]
+    pub fn write(&mut self, value: u8, mask: u8) {
+        let (bits, mask) = (self.spread(value & mask), self.spread(mask));
+        let idx = const { port_index(P) };
+        unsafe { self.gpio.port[idx].dat.modify(|dat| (dat & !mask) | bits) };
+    }
+    /// Get levels of all pads in data register.
+    #[inline]
+    pub fn read(&self) -> u8 {
+        let dat = self.gpio.port[const { port_index(P) }].dat.read();
+        let mut value = 0;
+        for (i, &pin) in self.pins.iter().enumerate() {
+            value |= (((dat >> pin) & 1) as u8) << i;
+        }
+        value
+    }
+    #[inline]
+    fn spread(&self, value: u8) -> u32 {
+        let mut bits = 0;
+        for (i, &pin) in self.pins.iter().enumerate() {
+            bits |= (((value >> i) & 1) as u32) << pin;
+        }
+        bits
+    }
+}
+
+impl<
+        'a,
+        const P: char,
+        const N0: u8,
+        const N1: u8,
+        const N2: u8,
+        const N3: u8,
+        const N4: u8,
+        const N5: u8,
+        const N6: u8,
+        const N7: u8,
+    >
+    From<(
+        Output<'a, P, N0>,
+        Output<'a, P, N1>,
+        Output<'a, P, N2>,
+        Output<'a, P, N3>,
+        Output<'a, P, N4>,
+        Output<'a, P, N5>,
+        Output<'a, P, N6>,
+        Output<'a, P, N7>,
+    )> for ParallelPort<'a, P>
+{
+    #[inline]
+    fn from(
+        pads: (
+            Output<'a, P, N0>,
+            Output<'a, P, N1>,
+            Output<'a, P, N2>,
+            Output<'a, P, N3>,
+            Output<'a, P, N4>,
+            Output<'a, P, N5>,
+            Output<'a, P, N6>,
+            Output<'a, P, N7>,
+        ),
+    ) -> Self {
+        // pads are owned, so pad numbers are distinct
+        Self {
+            gpio: pads.0.gpio(),
+            pins: [N0, N1, N2, N3, N4, N5, N6, N7],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParallelPort;
+    use crate::gpio::{Disabled, RegisterBlock};
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    const WORDS: usize = core::mem::size_of::<RegisterBlock>() / 4;
+
+    #[test]
+    fn parallel_port_write() {
+        let memory = [const { AtomicU32::new(0) }; WORDS];
+        let gpio = unsafe { &*(&memory as *const _ as *const RegisterBlock) };
+        // port B data register
+        let dat = || memory[0x40 / 4].load(Ordering::SeqCst);
+        memory[0x40 / 4].store(0x0000_1F00, Ordering::SeqCst);
+
+        let mut bus = ParallelPort::from((
+            unsafe { Disabled::<'_, 'B', 0>::__new(gpio) }.into_output(),
+            unsafe { Disabled::<'_, 'B', 1>::__new(gpio) }.into_output(),
+            unsafe { Disabled::<'_, 'B', 2>::__new(gpio) }.into_output(),
+            unsafe { Disabled::<'_, 'B', 3>::__new(gpio) }.into_output(),
+            unsafe { Disabled::<'_, 'B', 4>::__new(gpio) }.into_output(),
+            unsafe { Disabled::<'_, 'B', 5>::__new(gpio) }.into_output(),
+            unsafe { Disabled::<'_, 'B', 6>::__new(gpio) }.into_output(),
+            unsafe { Disabled::<'_, 'B', 7>::__new(gpio) }.into_output(),
+        ));
+        // all pads are in output mode
+        assert_eq!(memory[0x30 / 4].load(Ordering::SeqCst), 0x1111_1111);
+        bus.write(0xA5, 0xFF);
+        assert_eq!(dat(), 0x0000_1FA5);
+        assert_eq!(bus.read(), 0xA5);
+        bus.write(0x0F, 0x3C);
+        assert_eq!(dat(), 0x0000_1F8D);
+
+        // pads need not be consecutive or in order
+        memory[0x70 / 4].store(0xFFFF_FFFF, Ordering::SeqCst);
+        let mut bus = ParallelPort::from((
+            unsafe { Disabled::<'_, 'C', 7>::__new(gpio) }.into_output(),
+            unsafe { Disabled::<'_, 'C', 6>::__new(gpio) }.into_output(),
+            unsafe { Disabled::<'_, 'C', 5>::__new(gpio) }.into_output(),
+            unsafe { Disabled::<'_, 'C', 4>::__new(gpio) }.into_output(),
+            unsafe { Disabled::<'_, 'C', 3>::__new(gpio) }.into_output(),
+            unsafe { Disabled::<'_, 'C', 2>::__new(gpio) }.into_output(),
+            unsafe { Disabled::<'_, 'C', 1>::__new(gpio) }.into_output(),
+            unsafe { Disabled::<'_, 'C', 16>::__new(gpio) }.into_output(),
+        ));
+        bus.write(0x01, 0xFF);
+        assert_eq!(memory[0x70 / 4].load(Ordering::SeqCst), 0xFFFE_FF81);
+        assert_eq!(bus.read(), 0x01);
+    }
+}
@@ -42,6 +42,40 @@ pub struct Eint {
     _reserved0: [u32; 1],
 }
 
+impl Eint {
+    /// Get an iterator over indices of pads with pending external interrupts.
+    ///
+    /// Useful when several pads of one port share the bank interrupt line.
+    #[inline]
+    pub fn pending_eints(&self) -> PendingEints {
+        PendingEints(self.status.read())
+    }
+    /// Clear pending external interrupts of pads set in `mask`.
+    #[inline]
+    pub fn clear_eints(&self, mask: u32) {
+        // status bits are write-1-to-clear
+        unsafe { self.status.write(mask) }
+    }
+}
+
+/// Iterator over indices of pads with pending external interrupts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PendingEints(u32);
+
+impl Iterator for PendingEints {
+    type Item = u8;
+
+    #[inline]
+    fn next(&mut self) -> Option<u8> {
+        if self.0 == 0 {
+            return None;
+        }
+        let idx = self.0.trailing_zeros();
+        self.0 &= self.0 - 1;
+        Some(idx as u8)
+    }
+}
+
 /// Input/Output Power register group.
 #[repr(C)]
 pub struct PioPow {
@@ -55,6 +89,7 @@ pub struct PioPow {
 #[cfg(test)]
 mod tests {
     use super::{Eint, PioPow, Port, RegisterBlock};
+    use core::cell::UnsafeCell;
     use memoffset::offset_of;
 
     #[test]
@@ -80,6 +115,24 @@ mod tests {
         assert_eq!(offset_of!(Eint, deb), 0x18);
     }
 
+    #[test]
+    fn eint_pending_eints() {
+        let memory = UnsafeCell::new([0u32; 8]);
+        let eint = unsafe { &*(memory.get() as *const Eint) };
+        unsafe { (*memory.get())[5] = 0x8000_0241 };
+        let pending: [u8; 4] = {
+            let mut iter = eint.pending_eints();
+            core::array::from_fn(|_| iter.next().unwrap())
+        };
+        assert_eq!(pending, [0, 6, 9, 31]);
+        assert_eq!(eint.pending_eints().count(), 4);
+
+        eint.clear_eints(0x41);
+        assert_eq!(unsafe { (*memory.get())[5] }, 0x41);
+        unsafe { (*memory.get())[5] = 0 };
+        assert_eq!(eint.pending_eints().next(), None);
+    }
+
     #[test]
     fn offset_pio_pow() {
         assert_eq!(offset_of!(PioPow, mod_sel), 0x00);
@@ -7,9 +7,39 @@
 //! Most of `allwinner-hal` structures have `embedded-hal` traits implemented. Users may combine
 //! this package with `embedded-hal` ecosystem drivers to provide abundant amount of features.
 #![no_std]
+
+/// Check at compile time that fields of a register block are at given offsets.
+///
+/// ```
+/// use allwinner_hal::{assert_offsets, ccu::RegisterBlock};
+/// assert_offsets!(RegisterBlock { uart_bgr: 0x90c });
+/// ```
+///
+/// A wrong offset fails the build instead of a test run:
+///
+/// ```compile_fail,E0080
+/// use allwinner_hal::{assert_offsets, ccu::RegisterBlock};
+/// assert_offsets!(RegisterBlock { uart_bgr: 0x908 });
+/// ```
+#[doc(hidden)]
+#[macro_export]
+macro_rules! assert_offsets {
+    ($Block: ty { $($field: ident: $offset: expr),+ $(,)? }) => {
+        const _: () = {
+            $(
+                assert!(
+                    core::mem::offset_of!($Block, $field) == $offset,
+                    concat!("wrong offset of register ", stringify!($field))
+                );
+            )+
+        };
+    };
+}
+
 #[deny(missing_docs)]
 pub mod ccu;
 pub mod com;
+pub mod dma;
 #[macro_use]
 pub mod gpio;
 pub mod phy;
@@ -18,6 +48,7 @@ pub mod spi;
 #[doc(hidden)]
 pub mod sysctl;
 pub mod uart;
+pub mod wdt;
 
 #[doc(hidden)]
 pub mod prelude {
@@ -6,6 +6,8 @@ mod pad;
 pub use pad::*;
 mod structure;
 pub use structure::*;
+mod idmac;
+pub use idmac::*;
 
 /// Transfer mode.
 pub enum TransferMode {
@@ -17,6 +19,18 @@ pub enum TransferMode {
     Write,
 }
 
+/// How a multiple block transfer is ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum MultiBlockMode {
+    /// Open-ended transfer; controller sends CMD12 after the last block.
+    #[default]
+    AutoStop,
+    /// Controller sends CMD23 with the block count before the transfer.
+    ///
+    /// Card must support CMD23, as shown in its SCR for SD cards.
+    PredefinedCount,
+}
+
 /// Response mode.
 pub enum ResponseMode {
     /// No response.
@@ -27,8 +41,101 @@ pub enum ResponseMode {
     Long,
 }
 
-#[derive(Debug)]
-pub enum SdCardError {
-    Unknown,
+/// Card lock and unlock operation (CMD42).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockOp {
+    /// Set a new password, or replace the current one.
+    ///
+    /// When replacing, the password is the old password followed by the new one.
+    SetPassword,
+    /// Clear the current password.
+    ClearPassword,
+    /// Lock the card with the current password.
+    Lock,
+    /// Unlock the card with the current password.
+    Unlock,
+    /// Erase all card contents along with the password.
+    ForceErase,
+}
+
+/// SD/MMC host controller and card error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SmhcError {
+    /// IDMAC reached a descriptor it does not own.
+    DescriptorUnavailable,
+    /// IDMAC got a bus error on memory access.
+    FatalBusError,
+    /// Card reported an error during DMA transfer.
+    CardError,
+    /// Data block failed CRC check.
+    DataCrcError,
+    /// Card did not send data in time.
+    DataTimeout,
+    /// Data block is not terminated by an end bit.
+    DataEndBitError,
+    /// Buffer for DMA transfer is not word aligned.
+    MisalignedBuffer,
+    /// Not enough IDMAC descriptors for the transfer.
+    DescriptorsTooShort,
+    /// No card is present in the slot.
+    NoCard,
+    /// Card did not echo the CMD8 check pattern, i.e. it does not support host voltage.
+    VoltageMismatch,
+    /// Card did not finish power up while ACMD41 is repeated.
+    InitTimeout,
+    /// Command response failed CRC check.
+    ResponseCrcError,
+    /// Card did not respond to a command in time.
+    ResponseTimeout,
+    /// Card is of a kind this driver does not support, e.g. standard capacity.
+    UnsupportedCard,
+    /// Card kept DAT0 busy for too long before a data command.
+    BusyTimeout,
+    /// Data transfer completed with fewer bytes moved than requested.
+    ShortTransfer,
+    /// Card answered a command with an unexpected response.
     UnexpectedResponse(u8, u128),
+    /// Password is empty or longer than allowed for the lock operation.
+    InvalidPasswordLength(usize),
+    /// Data length is not allowed for the SDIO transfer.
+    InvalidTransferLength(usize),
+}
+
+impl core::fmt::Display for SmhcError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SmhcError::DescriptorUnavailable => f.write_str("DMA descriptor unavailable"),
+            SmhcError::FatalBusError => f.write_str("DMA fatal bus error"),
+            SmhcError::CardError => f.write_str("card error during DMA transfer"),
+            SmhcError::DataCrcError => f.write_str("data CRC error"),
+            SmhcError::DataTimeout => f.write_str("data timeout"),
+            SmhcError::DataEndBitError => f.write_str("data end bit error"),
+            SmhcError::MisalignedBuffer => f.write_str("DMA buffer is not word aligned"),
+            SmhcError::DescriptorsTooShort => f.write_str("not enough DMA descriptors"),
+            SmhcError::NoCard => f.write_str("no card present"),
+            SmhcError::VoltageMismatch => f.write_str("card does not support host voltage"),
+            SmhcError::InitTimeout => f.write_str("card initialization timed out"),
+            SmhcError::ResponseCrcError => f.write_str("response CRC error"),
+            SmhcError::ResponseTimeout => f.write_str("response timeout"),
+            SmhcError::UnsupportedCard => f.write_str("unsupported card"),
+            SmhcError::BusyTimeout => f.write_str("card busy timeout"),
+            SmhcError::ShortTransfer => {
+                f.write_str("data transfer moved fewer bytes than requested")
+            }
+            SmhcError::UnexpectedResponse(command, response) => {
+                write!(f, "unexpected response 0x{:x} to CMD{}", response, command)
+            }
+            SmhcError::InvalidPasswordLength(len) => write!(f, "invalid password length {}", len),
+            SmhcError::InvalidTransferLength(len) => write!(f, "invalid transfer length {}", len),
+        }
+    }
+}
+
+/// Error while power cycling the card.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerCycleError<E> {
+    /// Power pad could not be driven.
+    Pad(E),
+    /// Controller did not take the initialization command.
+    Smhc(SmhcError),
 }
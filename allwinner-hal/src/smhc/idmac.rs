@@ -0,0 +1,159 @@
+//! Internal DMA controller (IDMAC) descriptors.
+
+/// IDMAC descriptor in chain mode.
+///
+/// IDMAC fetches descriptors from memory by itself, so a descriptor must stay
+/// in place until the transfer completes. Buffer and next descriptor addresses
+/// are stored in units of words, i.e. physical address shifted right by two.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(C, align(16))]
+pub struct IdmacDescriptor {
+    config: u32,
+    size: u32,
+    buffer: u32,
+    next: u32,
+}
+
+impl IdmacDescriptor {
+    /// Descriptor is owned by IDMAC; cleared by IDMAC when done.
+    pub const OWN: u32 = 1 << 31;
+    /// Error happened on transfer of this descriptor.
+    pub const ERROR: u32 = 1 << 30;
+    /// Address of next descriptor is in `next`.
+    pub const CHAIN: u32 = 1 << 4;
+    /// First descriptor of a transfer.
+    pub const FIRST: u32 = 1 << 3;
+    /// Last descriptor of a transfer.
+    pub const LAST: u32 = 1 << 2;
+    /// Don't raise transfer interrupt when this descriptor is done.
+    pub const DISABLE_INTERRUPT: u32 = 1 << 1;
+
+    /// Create an empty descriptor not owned by IDMAC.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            config: 0,
+            size: 0,
+            buffer: 0,
+            next: 0,
+        }
+    }
+    /// Get configuration flags.
+    #[inline]
+    pub fn config(&self) -> u32 {
+        unsafe { core::ptr::read_volatile(&self.config) }
+    }
+    /// Check if the descriptor is owned by IDMAC.
+    #[inline]
+    pub fn is_owned_by_dma(&self) -> bool {
+        self.config() & Self::OWN != 0
+    }
+    /// Get buffer size in bytes.
+    #[inline]
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+    /// Get buffer address in words.
+    #[inline]
+    pub fn buffer(&self) -> u32 {
+        self.buffer
+    }
+    /// Get next descriptor address in words.
+    #[inline]
+    pub fn next(&self) -> u32 {
+        self.next
+    }
+}
+
+/// Largest buffer of one descriptor in bytes.
+pub const DESCRIPTOR_BUFFER_MAX: usize = 4096;
+
+/// Number of descriptors needed for a transfer of `len` bytes.
+#[inline]
+pub const fn descriptors_needed(len: usize) -> usize {
+    len.div_ceil(DESCRIPTOR_BUFFER_MAX)
+}
+
+/// Fill `descriptors` with a chain covering `len` bytes at bus address `buffer`,
+/// returning the used part.
+///
+/// Each descriptor is handed to IDMAC by setting its OWN bit; interrupts are
+/// only raised on the last one. Returns `None` if `len` is zero or
+/// `descriptors` is too short.
+pub(crate) fn build_chain(
+    descriptors: &mut [IdmacDescriptor],
+    buffer: usize,
+    len: usize,
+) -> Option<&mut [IdmacDescriptor]> {
+    let count = descriptors_needed(len);
+    if count == 0 || descriptors.len() < count {
+        return None;
+    }
+    let chain = &mut descriptors[..count];
+    let base = chain.as_ptr() as usize;
+    for (i, descriptor) in chain.iter_mut().enumerate() {
+        let offset = i * DESCRIPTOR_BUFFER_MAX;
+        let size = (len - offset).min(DESCRIPTOR_BUFFER_MAX);
+        let mut config = IdmacDescriptor::OWN | IdmacDescriptor::CHAIN;
+        if i == 0 {
+            config |= IdmacDescriptor::FIRST;
+        }
+        let next = if i + 1 == count {
+            config |= IdmacDescriptor::LAST;
+            0
+        } else {
+            config |= IdmacDescriptor::DISABLE_INTERRUPT;
+            base + (i + 1) * core::mem::size_of::<IdmacDescriptor>()
+        };
+        let value = IdmacDescriptor {
+            config,
+            size: size as u32,
+            buffer: ((buffer + offset) >> 2) as u32,
+            next: (next >> 2) as u32,
+        };
+        unsafe { core::ptr::write_volatile(descriptor, value) };
+    }
+    Some(chain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_chain, descriptors_needed, IdmacDescriptor};
+    use core::mem::{align_of, size_of};
+
+    #[test]
+    fn descriptor_chain() {
+        assert_eq!(size_of::<IdmacDescriptor>(), 16);
+        assert_eq!(align_of::<IdmacDescriptor>(), 16);
+        assert_eq!(descriptors_needed(512), 1);
+        assert_eq!(descriptors_needed(4096), 1);
+        assert_eq!(descriptors_needed(10 * 512), 2);
+
+        let mut descriptors = [IdmacDescriptor::new(); 4];
+        let base = descriptors.as_ptr() as usize;
+        let chain = build_chain(&mut descriptors, 0x4000_0000, 9 * 1024).unwrap();
+        assert_eq!(chain.len(), 3);
+        // first: owned, chained, first, no interrupt
+        assert_eq!(chain[0].config(), 0x8000_001A);
+        assert_eq!(chain[0].size(), 4096);
+        assert_eq!(chain[0].buffer(), 0x1000_0000);
+        assert_eq!(chain[0].next(), ((base + 16) >> 2) as u32);
+        assert_eq!(chain[1].config(), 0x8000_0012);
+        assert_eq!(chain[1].buffer(), 0x1000_0400);
+        assert_eq!(chain[1].next(), ((base + 32) >> 2) as u32);
+        // last: owned, chained, last, raises interrupt
+        assert_eq!(chain[2].config(), 0x8000_0014);
+        assert_eq!(chain[2].size(), 1024);
+        assert_eq!(chain[2].buffer(), 0x1000_0800);
+        assert_eq!(chain[2].next(), 0);
+        assert!(chain.iter().all(IdmacDescriptor::is_owned_by_dma));
+        assert!(!descriptors[3].is_owned_by_dma());
+
+        // a single descriptor is both first and last
+        let chain = build_chain(&mut descriptors, 0x4000_0000, 512).unwrap();
+        assert_eq!(chain[0].config(), 0x8000_001C);
+
+        assert!(build_chain(&mut descriptors, 0x4000_0000, 5 * 4096).is_none());
+        assert!(build_chain(&mut descriptors, 0x4000_0000, 0).is_none());
+    }
+}
@@ -31,31 +31,72 @@ pub struct RegisterBlock {
     pub status: RO<Status>,
     /// 0x40 - SMC FIFO Water Level Register.
     pub fifo_water_level: RW<FifoWaterLevel>,
-    _reserved0: [u32; 6],
+    _reserved0: u32,
+    /// 0x48 - SMC Transferred Byte Count Register 0, between controller and card.
+    pub transferred_byte_count0: RO<u32>,
+    /// 0x4C - SMC Transferred Byte Count Register 1, between host bus and FIFO.
+    pub transferred_byte_count1: RO<u32>,
+    _reserved1: [u32; 3],
     /// 0x5c - SMC New Timing Set Register.
     pub new_timing_set: RW<NewTimingSet>,
-    _reserved1: [u32; 8],
+    _reserved2: [u32; 8],
     /// 0x80 - SMC IDMAC Control Register.
-    pub dma_control: RW<u32>,
+    pub dma_control: RW<DmaControl>,
     /// 0x84 - SMC IDMAC Descriptor List Base Address Register.
     pub dma_descriptor_base: RW<u32>,
     /// 0x88 - SMC IDMAC Status Register.
-    pub dma_state: RW<u32>,
+    pub dma_state: RW<DmaState>,
     /// 0x8C - SMC IDMAC Interrupt Enable Register.
     pub dma_interrupt_enable: RW<u32>,
-    _reserved2: [u32; 44],
+    _reserved3: [u32; 30],
+    /// 0x108 - SMC Auto Command 23 Argument Register.
+    pub auto_cmd23_arg: RW<u32>,
+    _reserved4: [u32; 11],
+    /// 0x138 - SMC Extended Command Register.
+    pub extended_command: RW<ExtendedCommand>,
+    _reserved5: u32,
     /// 0x140 - Drive Delay Control register.
     pub drive_delay_control: RW<DriveDelayControl>,
     /// 0x144 - Sample Delay Control Register
     pub sample_delay_control: RW<SampleDelayControl>,
-    _reserved3: [u32; 15],
+    _reserved6: [u32; 15],
     /// 0x184 - deskew control control register.
     pub skew_control: RW<u32>,
-    _reserved4: [u32; 30],
+    _reserved7: [u32; 30],
     /// 0x200 - SMC FIFO Access Address.
     pub fifo: RW<u32>,
 }
 
+assert_offsets!(RegisterBlock {
+    global_control: 0x00,
+    clock_control: 0x04,
+    timeout: 0x08,
+    card_type: 0x0C,
+    block_size: 0x10,
+    byte_count: 0x14,
+    command: 0x18,
+    argument: 0x1C,
+    responses: 0x20,
+    interrupt_mask: 0x30,
+    interrupt_state_masked: 0x34,
+    interrupt_state_raw: 0x38,
+    status: 0x3C,
+    fifo_water_level: 0x40,
+    transferred_byte_count0: 0x48,
+    transferred_byte_count1: 0x4C,
+    new_timing_set: 0x5C,
+    dma_control: 0x80,
+    dma_descriptor_base: 0x84,
+    dma_state: 0x88,
+    dma_interrupt_enable: 0x8C,
+    auto_cmd23_arg: 0x108,
+    extended_command: 0x138,
+    drive_delay_control: 0x140,
+    sample_delay_control: 0x144,
+    skew_control: 0x184,
+    fifo: 0x200,
+});
+
 /// Global control register.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[repr(transparent)]
@@ -79,8 +120,19 @@ pub enum DdrMode {
     Ddr,
 }
 
+/// Time unit of timeout limits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TimeUnit {
+    /// One card clock period.
+    Clock1,
+    /// 256 card clock periods.
+    Clock256,
+}
+
 impl GlobalControl {
     const FIFO_AC_MOD: u32 = 1 << 31;
+    const TIME_UNIT_CMD: u32 = 1 << 12;
+    const TIME_UNIT_DAT: u32 = 1 << 11;
     const DDR_MOD: u32 = 1 << 10;
     const DMA_ENB: u32 = 1 << 5;
     const INT_ENB: u32 = 1 << 4;
@@ -106,6 +158,42 @@ impl GlobalControl {
         };
         Self((self.0 & !Self::FIFO_AC_MOD) | (mode << 31))
     }
+    /// Get time unit of command (response) timeout limit.
+    #[inline]
+    pub const fn command_time_unit(self) -> TimeUnit {
+        match (self.0 & Self::TIME_UNIT_CMD) >> 12 {
+            0 => TimeUnit::Clock1,
+            1 => TimeUnit::Clock256,
+            _ => unreachable!(),
+        }
+    }
+    /// Set time unit of command (response) timeout limit.
+    #[inline]
+    pub const fn set_command_time_unit(self, unit: TimeUnit) -> Self {
+        let unit = match unit {
+            TimeUnit::Clock1 => 0x0,
+            TimeUnit::Clock256 => 0x1,
+        };
+        Self((self.0 & !Self::TIME_UNIT_CMD) | (unit << 12))
+    }
+    /// Get time unit of data timeout limit.
+    #[inline]
+    pub const fn data_time_unit(self) -> TimeUnit {
+        match (self.0 & Self::TIME_UNIT_DAT) >> 11 {
+            0 => TimeUnit::Clock1,
+            1 => TimeUnit::Clock256,
+            _ => unreachable!(),
+        }
+    }
+    /// Set time unit of data timeout limit.
+    #[inline]
+    pub const fn set_data_time_unit(self, unit: TimeUnit) -> Self {
+        let unit = match unit {
+            TimeUnit::Clock1 => 0x0,
+            TimeUnit::Clock256 => 0x1,
+        };
+        Self((self.0 & !Self::TIME_UNIT_DAT) | (unit << 11))
+    }
     /// Get DDR mode.
     #[inline]
     pub const fn ddr_mode(self) -> DdrMode {
@@ -243,6 +331,7 @@ pub struct TimeOut(u32);
 
 impl TimeOut {
     const DTO_LMT: u32 = 0xFFFFFF << 8;
+    const RTO_LMT: u32 = 0xFF;
 
     /// Get data timeout limit.
     #[inline]
@@ -254,6 +343,16 @@ impl TimeOut {
     pub const fn set_data_timeout_limit(self, limit: u32) -> Self {
         Self((self.0 & !Self::DTO_LMT) | (limit << 8))
     }
+    /// Get response timeout limit.
+    #[inline]
+    pub const fn response_timeout_limit(self) -> u8 {
+        (self.0 & Self::RTO_LMT) as u8
+    }
+    /// Set response timeout limit.
+    #[inline]
+    pub const fn set_response_timeout_limit(self, limit: u8) -> Self {
+        Self((self.0 & !Self::RTO_LMT) | limit as u32)
+    }
 }
 
 /// Card type register.
@@ -549,6 +648,100 @@ impl Default for Command {
     }
 }
 
+/// Response type of an SD/MMC command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ResponseType {
+    /// No response.
+    None,
+    /// Normal response.
+    R1,
+    /// Normal response with busy signal on data line.
+    R1b,
+    /// CID or CSD register, long response.
+    R2,
+    /// OCR register, without CRC.
+    R3,
+    /// SDIO OCR register, without CRC.
+    R4,
+    /// SDIO register access response.
+    R5,
+    /// Published RCA response.
+    R6,
+    /// Card interface condition.
+    R7,
+}
+
+impl ResponseType {
+    /// If a response is expected.
+    #[inline]
+    pub const fn has_response(self) -> bool {
+        !matches!(self, ResponseType::None)
+    }
+    /// If the response is 136 bits long.
+    #[inline]
+    pub const fn is_long(self) -> bool {
+        matches!(self, ResponseType::R2)
+    }
+    /// If the response carries a CRC to be checked.
+    #[inline]
+    pub const fn check_crc(self) -> bool {
+        !matches!(
+            self,
+            ResponseType::None | ResponseType::R3 | ResponseType::R4
+        )
+    }
+}
+
+/// Get response type of standard SD/MMC command `index`.
+///
+/// Application commands are looked up by their index as sent after CMD55,
+/// e.g. index 41 is ACMD41. Returns `None` for unknown or reserved commands.
+#[inline]
+pub const fn standard_response_type(index: u8) -> Option<ResponseType> {
+    Some(match index {
+        0 | 4 | 15 => ResponseType::None,
+        2 | 9 | 10 => ResponseType::R2,
+        3 => ResponseType::R6,
+        5 => ResponseType::R4,
+        7 | 12 | 20 | 28 | 29 | 38 => ResponseType::R1b,
+        8 => ResponseType::R7,
+        41 => ResponseType::R3,
+        52 | 53 => ResponseType::R5,
+        6 | 11 | 13 | 16 | 17 | 18 | 19 | 23 | 24 | 25 | 27 | 30 | 32 | 33 | 42 | 51 | 55 | 56 => {
+            ResponseType::R1
+        }
+        _ => return None,
+    })
+}
+
+impl Command {
+    /// Build command and argument for standard SD/MMC command `index`.
+    ///
+    /// Response receive, long response and response CRC check are set from
+    /// the standard response type of this command; data transfer flags are
+    /// left for the caller. Returns `None` if `index` is not a known command.
+    #[inline]
+    pub const fn for_standard(index: u8, arg: u32) -> Option<(Command, Argument)> {
+        let Some(response) = standard_response_type(index) else {
+            return None;
+        };
+        let mut cmd = Command(0)
+            .set_command_start()
+            .set_command_index(index)
+            .enable_wait_for_complete();
+        if response.has_response() {
+            cmd = cmd.enable_response_receive();
+        }
+        if response.is_long() {
+            cmd = cmd.enable_long_response();
+        }
+        if response.check_crc() {
+            cmd = cmd.enable_check_response_crc();
+        }
+        Some((cmd, Argument(0).set_argument(arg)))
+    }
+}
+
 /// Argument register.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[repr(transparent)]
@@ -742,7 +935,7 @@ impl InterruptStateMasked {
 }
 
 /// Raw Interrupt state register.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
 #[repr(transparent)]
 pub struct InterruptStateRaw(u32);
 
@@ -824,7 +1017,9 @@ pub struct Status(u32);
 
 impl Status {
     const FIFO_LEVEL: u32 = 0x1FF << 17;
+    const FSM_BUSY: u32 = 1 << 10;
     const CARD_BUSY: u32 = 1 << 9;
+    const CARD_PRESENT: u32 = 1 << 8;
     const FIFO_FULL: u32 = 1 << 3;
     const FIFO_EMPTY: u32 = 1 << 2;
 
@@ -833,11 +1028,21 @@ impl Status {
     pub const fn fifo_level(self) -> u16 {
         ((self.0 & Self::FIFO_LEVEL) >> 17) as u16
     }
+    /// Is the data state machine busy?
+    #[inline]
+    pub const fn data_fsm_busy(self) -> bool {
+        self.0 & Self::FSM_BUSY != 0
+    }
     /// Is the card busy?
     #[inline]
     pub const fn card_busy(self) -> bool {
         self.0 & Self::CARD_BUSY != 0
     }
+    /// Is a card present?
+    #[inline]
+    pub const fn card_present(self) -> bool {
+        self.0 & Self::CARD_PRESENT != 0
+    }
     /// Is the FIFO full?
     #[inline]
     pub const fn fifo_full(self) -> bool {
@@ -928,6 +1133,7 @@ pub enum NtsTimingPhase {
 impl NewTimingSet {
     const MODE_SELECT: u32 = 1 << 31;
     const DAT_SAMPLE_TIMING_PHASE: u32 = 0x3 << 8;
+    const CMD_SAMPLE_TIMING_PHASE: u32 = 0x3 << 4;
 
     /// If new mode is enabled.
     #[inline]
@@ -960,6 +1166,22 @@ impl NewTimingSet {
     pub const fn set_sample_timing_phase(self, phase: NtsTimingPhase) -> Self {
         Self((self.0 & !Self::DAT_SAMPLE_TIMING_PHASE) | ((phase as u32) << 8))
     }
+    /// Get command sample timing phase.
+    #[inline]
+    pub const fn command_sample_timing_phase(self) -> NtsTimingPhase {
+        match (self.0 & Self::CMD_SAMPLE_TIMING_PHASE) >> 4 {
+            0x0 => NtsTimingPhase::Offset90,
+            0x1 => NtsTimingPhase::Offset180,
+            0x2 => NtsTimingPhase::Offset270,
+            0x3 => NtsTimingPhase::Offset0,
+            _ => unreachable!(),
+        }
+    }
+    /// Set command sample timing phase.
+    #[inline]
+    pub const fn set_command_sample_timing_phase(self, phase: NtsTimingPhase) -> Self {
+        Self((self.0 & !Self::CMD_SAMPLE_TIMING_PHASE) | ((phase as u32) << 4))
+    }
 }
 
 /// Drive Delay Control register.
@@ -1018,6 +1240,9 @@ pub struct SampleDelayControl(u32);
 impl SampleDelayControl {
     const SAMP_DL_SW: u32 = 0x3F << 0;
     const SAMP_DL_SW_EN: u32 = 1 << 7;
+    const SAMP_DL: u32 = 0x3F << 8;
+    const SAMP_DL_CAL_DONE: u32 = 1 << 14;
+    const SAMP_DL_CAL_START: u32 = 1 << 15;
 
     /// Set sample delay software.
     #[inline]
@@ -1044,15 +1269,181 @@ impl SampleDelayControl {
     pub const fn is_sample_delay_software_enabled(self) -> bool {
         (self.0 & Self::SAMP_DL_SW_EN) != 0
     }
+    /// Start sample delay calibration.
+    #[inline]
+    pub const fn start_sample_delay_cal(self) -> Self {
+        Self(self.0 | Self::SAMP_DL_CAL_START)
+    }
+    /// Stop sample delay calibration.
+    #[inline]
+    pub const fn stop_sample_delay_cal(self) -> Self {
+        Self(self.0 & !Self::SAMP_DL_CAL_START)
+    }
+    /// Get if sample delay calibration is done.
+    #[inline]
+    pub const fn is_sample_delay_cal_done(self) -> bool {
+        (self.0 & Self::SAMP_DL_CAL_DONE) != 0
+    }
+    /// Get sample delay found by calibration.
+    #[inline]
+    pub const fn sample_delay_cal(self) -> u8 {
+        ((self.0 & Self::SAMP_DL) >> 8) as u8
+    }
+}
+
+/// IDMAC control register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct DmaControl(u32);
+
+impl DmaControl {
+    const DES_LOAD_CTRL: u32 = 1 << 31;
+    const IDMAC_ENB: u32 = 1 << 7;
+    const FIX_BUST_CTRL: u32 = 1 << 1;
+    const IDMAC_RST: u32 = 1 << 0;
+
+    /// Enable IDMAC.
+    #[inline]
+    pub const fn enable_dma(self) -> Self {
+        Self(self.0 | Self::IDMAC_ENB)
+    }
+    /// Disable IDMAC.
+    #[inline]
+    pub const fn disable_dma(self) -> Self {
+        Self(self.0 & !Self::IDMAC_ENB)
+    }
+    /// Is IDMAC enabled?
+    #[inline]
+    pub const fn is_dma_enabled(self) -> bool {
+        self.0 & Self::IDMAC_ENB != 0
+    }
+    /// Enable fixed burst transfers.
+    #[inline]
+    pub const fn enable_fixed_burst(self) -> Self {
+        Self(self.0 | Self::FIX_BUST_CTRL)
+    }
+    /// Disable fixed burst transfers.
+    #[inline]
+    pub const fn disable_fixed_burst(self) -> Self {
+        Self(self.0 & !Self::FIX_BUST_CTRL)
+    }
+    /// Is fixed burst enabled?
+    #[inline]
+    pub const fn is_fixed_burst_enabled(self) -> bool {
+        self.0 & Self::FIX_BUST_CTRL != 0
+    }
+    /// Reload descriptor from descriptor list base address on next start.
+    #[inline]
+    pub const fn set_descriptor_reload(self) -> Self {
+        Self(self.0 | Self::DES_LOAD_CTRL)
+    }
+    /// Reset IDMAC; cleared by hardware when reset completes.
+    #[inline]
+    pub const fn set_soft_reset(self) -> Self {
+        Self(self.0 | Self::IDMAC_RST)
+    }
+    /// Is soft reset cleared?
+    #[inline]
+    pub const fn is_soft_reset_cleared(self) -> bool {
+        self.0 & Self::IDMAC_RST == 0
+    }
+}
+
+impl Default for DmaControl {
+    #[inline]
+    fn default() -> Self {
+        Self(0x0000_0000)
+    }
+}
+
+/// Extended command register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[repr(transparent)]
+pub struct ExtendedCommand(u32);
+
+impl ExtendedCommand {
+    const AUTO_CMD23_EN: u32 = 1 << 0;
+
+    /// Send CMD23 with the auto command 23 argument before the next data command.
+    #[inline]
+    pub const fn enable_auto_cmd23(self) -> Self {
+        Self(self.0 | Self::AUTO_CMD23_EN)
+    }
+    /// Do not send CMD23 automatically.
+    #[inline]
+    pub const fn disable_auto_cmd23(self) -> Self {
+        Self(self.0 & !Self::AUTO_CMD23_EN)
+    }
+    /// Is automatic CMD23 enabled?
+    #[inline]
+    pub const fn is_auto_cmd23_enabled(self) -> bool {
+        self.0 & Self::AUTO_CMD23_EN != 0
+    }
+}
+
+/// IDMAC status register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct DmaState(u32);
+
+impl DmaState {
+    const ABN_INT_SUM: u32 = 1 << 9;
+    const NOR_INT_SUM: u32 = 1 << 8;
+    const ERR_FLAG_SUM: u32 = 1 << 5;
+    const DES_UNAVL_INT: u32 = 1 << 4;
+    const FATAL_BERR_INT: u32 = 1 << 2;
+    const RX_INT: u32 = 1 << 1;
+    const TX_INT: u32 = 1 << 0;
+
+    /// Has data reception finished for a descriptor?
+    #[inline]
+    pub const fn rx_int_occurs(self) -> bool {
+        self.0 & Self::RX_INT != 0
+    }
+    /// Has data transmission finished for a descriptor?
+    #[inline]
+    pub const fn tx_int_occurs(self) -> bool {
+        self.0 & Self::TX_INT != 0
+    }
+    /// Is a descriptor unavailable, i.e. not owned by IDMAC?
+    #[inline]
+    pub const fn des_unavl_int(self) -> bool {
+        self.0 & Self::DES_UNAVL_INT != 0
+    }
+    /// Has a fatal bus error occurred on IDMAC memory access?
+    #[inline]
+    pub const fn fatal_berr_int(self) -> bool {
+        self.0 & Self::FATAL_BERR_INT != 0
+    }
+    /// Has the card reported an error during transfer?
+    #[inline]
+    pub const fn card_err_sum(self) -> bool {
+        self.0 & Self::ERR_FLAG_SUM != 0
+    }
+    /// Clear all interrupt flags.
+    #[inline]
+    pub const fn clear_all(self) -> Self {
+        Self(
+            self.0
+                | Self::ABN_INT_SUM
+                | Self::NOR_INT_SUM
+                | Self::ERR_FLAG_SUM
+                | Self::DES_UNAVL_INT
+                | Self::FATAL_BERR_INT
+                | Self::RX_INT
+                | Self::TX_INT,
+        )
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{
         AccessMode, Argument, BlockSize, BurstSize, BusWidth, ByteCount, CardType, ClockControl,
-        Command, DdcTimingPhase, DdrMode, DriveDelayControl, FifoWaterLevel, GlobalControl,
-        Interrupt, InterruptMask, InterruptStateMasked, InterruptStateRaw, NewTimingSet,
-        NtsTimingPhase, RegisterBlock, Status, TimeOut, TransferDirection,
+        Command, DdcTimingPhase, DdrMode, DmaControl, DmaState, DriveDelayControl, ExtendedCommand,
+        FifoWaterLevel, GlobalControl, Interrupt, InterruptMask, InterruptStateMasked,
+        InterruptStateRaw, NewTimingSet, NtsTimingPhase, RegisterBlock, ResponseType,
+        SampleDelayControl, Status, TimeOut, TimeUnit, TransferDirection,
     };
     use memoffset::offset_of;
     #[test]
@@ -1071,11 +1462,15 @@ mod tests {
         assert_eq!(offset_of!(RegisterBlock, interrupt_state_raw), 0x38);
         assert_eq!(offset_of!(RegisterBlock, status), 0x3C);
         assert_eq!(offset_of!(RegisterBlock, fifo_water_level), 0x40);
+        assert_eq!(offset_of!(RegisterBlock, transferred_byte_count0), 0x48);
+        assert_eq!(offset_of!(RegisterBlock, transferred_byte_count1), 0x4C);
         assert_eq!(offset_of!(RegisterBlock, new_timing_set), 0x5C);
         assert_eq!(offset_of!(RegisterBlock, dma_control), 0x80);
         assert_eq!(offset_of!(RegisterBlock, dma_descriptor_base), 0x84);
         assert_eq!(offset_of!(RegisterBlock, dma_state), 0x88);
         assert_eq!(offset_of!(RegisterBlock, dma_interrupt_enable), 0x8C);
+        assert_eq!(offset_of!(RegisterBlock, auto_cmd23_arg), 0x108);
+        assert_eq!(offset_of!(RegisterBlock, extended_command), 0x138);
         assert_eq!(offset_of!(RegisterBlock, drive_delay_control), 0x140);
         assert_eq!(offset_of!(RegisterBlock, sample_delay_control), 0x144);
         assert_eq!(offset_of!(RegisterBlock, skew_control), 0x184);
@@ -1094,6 +1489,22 @@ mod tests {
         assert_eq!(val.access_mode(), AccessMode::Dma);
         assert_eq!(val.0, 0x00000000);
 
+        val = val.set_command_time_unit(TimeUnit::Clock256);
+        assert_eq!(val.command_time_unit(), TimeUnit::Clock256);
+        assert_eq!(val.0, 0x00001000);
+
+        val = val.set_command_time_unit(TimeUnit::Clock1);
+        assert_eq!(val.command_time_unit(), TimeUnit::Clock1);
+        assert_eq!(val.0, 0x00000000);
+
+        val = val.set_data_time_unit(TimeUnit::Clock256);
+        assert_eq!(val.data_time_unit(), TimeUnit::Clock256);
+        assert_eq!(val.0, 0x00000800);
+
+        val = val.set_data_time_unit(TimeUnit::Clock1);
+        assert_eq!(val.data_time_unit(), TimeUnit::Clock1);
+        assert_eq!(val.0, 0x00000000);
+
         val = val.set_ddr_mode(DdrMode::Ddr);
         assert_eq!(val.ddr_mode(), DdrMode::Ddr);
         assert_eq!(val.0, 0x00000400);
@@ -1174,6 +1585,10 @@ mod tests {
         val = val.set_data_timeout_limit(0xFFFFFF);
         assert_eq!(val.data_timeout_limit(), 0xFFFFFF);
         assert_eq!(val.0, 0xFFFFFF00);
+
+        val = val.set_response_timeout_limit(0x40);
+        assert_eq!(val.response_timeout_limit(), 0x40);
+        assert_eq!(val.0, 0xFFFFFF40);
     }
 
     #[test]
@@ -1491,6 +1906,16 @@ mod tests {
 
         val = Status(0x00000200);
         assert!(val.card_busy());
+        assert!(!val.data_fsm_busy());
+        assert!(!val.card_present());
+
+        val = Status(0x00000100);
+        assert!(val.card_present());
+        assert!(!val.card_busy());
+
+        val = Status(0x00000400);
+        assert!(val.data_fsm_busy());
+        assert!(!val.card_busy());
 
         val = Status(0x00000008);
         assert!(val.fifo_full());
@@ -1499,6 +1924,68 @@ mod tests {
         assert!(val.fifo_empty());
     }
 
+    #[test]
+    fn struct_extended_command_functions() {
+        let mut val = ExtendedCommand::default();
+        assert!(!val.is_auto_cmd23_enabled());
+
+        val = val.enable_auto_cmd23();
+        assert!(val.is_auto_cmd23_enabled());
+        assert_eq!(val.0, 0x00000001);
+
+        val = val.disable_auto_cmd23();
+        assert!(!val.is_auto_cmd23_enabled());
+        assert_eq!(val.0, 0x00000000);
+    }
+
+    #[test]
+    fn struct_dma_control_functions() {
+        let mut val = DmaControl::default();
+
+        val = val.enable_dma();
+        assert!(val.is_dma_enabled());
+        assert_eq!(val.0, 0x00000080);
+
+        val = val.enable_fixed_burst();
+        assert!(val.is_fixed_burst_enabled());
+        assert_eq!(val.0, 0x00000082);
+
+        val = val.set_descriptor_reload();
+        assert_eq!(val.0, 0x80000082);
+
+        assert!(val.is_soft_reset_cleared());
+        val = val.set_soft_reset();
+        assert!(!val.is_soft_reset_cleared());
+        assert_eq!(val.0, 0x80000083);
+
+        val = val.disable_dma().disable_fixed_burst();
+        assert!(!val.is_dma_enabled());
+        assert!(!val.is_fixed_burst_enabled());
+        assert_eq!(val.0, 0x80000001);
+    }
+
+    #[test]
+    fn struct_dma_state_functions() {
+        let mut val = DmaState(0x00000002);
+        assert!(val.rx_int_occurs());
+        assert!(!val.tx_int_occurs());
+
+        val = DmaState(0x00000001);
+        assert!(val.tx_int_occurs());
+
+        val = DmaState(0x00000010);
+        assert!(val.des_unavl_int());
+
+        val = DmaState(0x00000004);
+        assert!(val.fatal_berr_int());
+
+        val = DmaState(0x00000020);
+        assert!(val.card_err_sum());
+
+        val = DmaState(0x00000000).clear_all();
+        assert_eq!(val.0, 0x00000337);
+    }
+
     #[test]
     fn struct_fifo_water_level_functions() {
         let mut val = FifoWaterLevel(0x0);
@@ -1568,6 +2055,10 @@ mod tests {
             val = val.set_sample_timing_phase(tp_tmp);
             assert_eq!(val.sample_timing_phase(), tp_tmp);
             assert_eq!(val.0, val_tmp);
+
+            let cmd = NewTimingSet(0x0).set_command_sample_timing_phase(tp_tmp);
+            assert_eq!(cmd.command_sample_timing_phase(), tp_tmp);
+            assert_eq!(cmd.0, val_tmp >> 4);
         }
     }
 
@@ -1591,4 +2082,73 @@ mod tests {
         assert_eq!(val.command_drive_phase(), DdcTimingPhase::Sdr90Ddr45);
         assert_eq!(val.0, 0x00000000);
     }
+
+    #[test]
+    fn struct_sample_delay_control_functions() {
+        let mut val = SampleDelayControl(0x0);
+
+        val = val.set_sample_delay_software(0x2A);
+        assert_eq!(val.sample_delay_software(), 0x2A);
+        assert_eq!(val.0, 0x0000002A);
+
+        val = val.enable_sample_delay_software();
+        assert!(val.is_sample_delay_software_enabled());
+        assert_eq!(val.0, 0x000000AA);
+
+        val = val.disable_sample_delay_software();
+        assert!(!val.is_sample_delay_software_enabled());
+        assert_eq!(val.0, 0x0000002A);
+
+        val = val.start_sample_delay_cal();
+        assert_eq!(val.0, 0x0000802A);
+
+        val = val.stop_sample_delay_cal();
+        assert_eq!(val.0, 0x0000002A);
+
+        let val = SampleDelayControl(0x0000_5500);
+        assert!(val.is_sample_delay_cal_done());
+        assert_eq!(val.sample_delay_cal(), 0x15);
+        assert!(!SampleDelayControl(0x0000_3F00).is_sample_delay_cal_done());
+    }
+
+    #[test]
+    fn command_for_standard() {
+        // CMD0: GO_IDLE_STATE, no response
+        let (cmd, arg) = Command::for_standard(0, 0).unwrap();
+        assert!(!cmd.is_response_receive_enabled());
+        assert!(!cmd.is_long_response_enabled());
+        assert!(!cmd.is_check_response_crc_enabled());
+        assert_eq!(cmd.0, 0x8000_2000);
+        assert_eq!(arg.argument(), 0);
+
+        // CMD2: ALL_SEND_CID, long response
+        let (cmd, _) = Command::for_standard(2, 0).unwrap();
+        assert!(cmd.is_response_receive_enabled());
+        assert!(cmd.is_long_response_enabled());
+        assert!(cmd.is_check_response_crc_enabled());
+        assert_eq!(cmd.command_index(), 2);
+
+        // CMD8: SEND_IF_COND, short response with CRC
+        let (cmd, arg) = Command::for_standard(8, 0x1AA).unwrap();
+        assert!(cmd.is_response_receive_enabled());
+        assert!(!cmd.is_long_response_enabled());
+        assert!(cmd.is_check_response_crc_enabled());
+        assert_eq!(cmd.0, 0x8000_2148);
+        assert_eq!(arg.argument(), 0x1AA);
+
+        // ACMD41: SD_SEND_OP_COND, R3 without CRC
+        let (cmd, _) = Command::for_standard(41, 0x40ff_8000).unwrap();
+        assert!(cmd.is_response_receive_enabled());
+        assert!(!cmd.is_check_response_crc_enabled());
+
+        // CMD17: READ_SINGLE_BLOCK, data flags left to caller
+        let (cmd, _) = Command::for_standard(17, 0).unwrap();
+        assert!(cmd.is_check_response_crc_enabled());
+        assert!(!cmd.is_data_transfer_enabled());
+
+        assert_eq!(Command::for_standard(1, 0), None);
+        assert_eq!(Command::for_standard(63, 0), None);
+        assert_eq!(super::standard_response_type(12), Some(ResponseType::R1b));
+        assert_eq!(super::standard_response_type(9), Some(ResponseType::R2));
+    }
 }
@@ -1,17 +1,178 @@
 use super::{
+    idmac::{build_chain, IdmacDescriptor},
     register::{
-        AccessMode, BlockSize, BusWidth, CardType, Command, RegisterBlock, TransferDirection,
+        AccessMode, BlockSize, BusWidth, CardType, Command, DmaControl, ExtendedCommand, Interrupt,
+        InterruptStateRaw, NewTimingSet, NtsTimingPhase, RegisterBlock, TimeUnit,
+        TransferDirection,
     },
-    ResponseMode, SdCardError, TransferMode,
+    LockOp, MultiBlockMode, PowerCycleError, ResponseMode, SmhcError, TransferMode,
 };
-use crate::ccu::{self, Clocks, SmhcClockSource};
+use crate::ccu::{self, ClockConfig, Clocks, SmhcClockSource};
 use core::arch::asm;
+use core::sync::atomic::{fence, Ordering};
+use embedded_hal::{delay::DelayNs, digital::OutputPin};
 use embedded_sdmmc::{Block, BlockDevice, BlockIdx};
 
 /// Managed SMHC structure with peripheral and pins.
 pub struct Smhc<SMHC, PADS> {
     smhc: SMHC,
     pads: PADS,
+    /// SMHC module clock frequency in Hz.
+    module_clock: u32,
+}
+
+/// Card clock during card identification; SD specification allows at most 400 kHz.
+const INIT_CARD_CLOCK: u32 = 400_000;
+/// Card clock after card identification.
+const OPERATING_CARD_CLOCK: u32 = 10_000_000;
+
+/// Card clock divider field producing at most `card_clock` from `module_clock`.
+///
+/// Card clock is `module_clock / (2 * divider)`, or `module_clock` if divider is 0.
+#[inline]
+const fn card_clock_divider(module_clock: u32, card_clock: u32) -> u8 {
+    if card_clock >= module_clock {
+        return 0;
+    }
+    if card_clock == 0 {
+        return u8::MAX;
+    }
+    let divider = module_clock.div_ceil(card_clock).div_ceil(2);
+    if divider > u8::MAX as u32 {
+        u8::MAX
+    } else {
+        divider as u8
+    }
+}
+
+/// Command and data sample phases chosen by [`Smhc::tune_timing`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TimingPhases {
+    /// Command sample timing phase.
+    pub command: NtsTimingPhase,
+    /// Data sample timing phase.
+    pub data: NtsTimingPhase,
+}
+
+/// Sample phases in order of increasing offset.
+const TUNING_PHASES: [NtsTimingPhase; 4] = [
+    NtsTimingPhase::Offset0,
+    NtsTimingPhase::Offset90,
+    NtsTimingPhase::Offset180,
+    NtsTimingPhase::Offset270,
+];
+
+/// Index of the middle of the widest run of passing phases in circular `pass`.
+///
+/// Of equally wide runs the earlier one is taken; of an even-length run, the
+/// earlier of its two middle phases.
+fn widest_window_center(pass: [bool; 4]) -> Option<usize> {
+    let n = pass.len();
+    if pass.iter().all(|&p| p) {
+        return Some((n - 1) / 2);
+    }
+    let mut best: Option<(usize, usize)> = None;
+    for start in 0..n {
+        // a run starts at a passing phase after a failing one
+        if !pass[start] || pass[(start + n - 1) % n] {
+            continue;
+        }
+        let len = (0..n).take_while(|&i| pass[(start + i) % n]).count();
+        if best.is_none_or(|(_, best_len)| len > best_len) {
+            best = Some((start, len));
+        }
+    }
+    best.map(|(start, len)| (start + (len - 1) / 2) % n)
+}
+
+/// CMD21 tuning block on a 4-bit bus.
+const TUNING_BLOCK_4BIT: [u8; 64] = [
+    0xff, 0x0f, 0xff, 0x00, 0xff, 0xcc, 0xc3, 0xcc, 0xc3, 0x3c, 0xcc, 0xff, 0xfe, 0xff, 0xfe, 0xef,
+    0xff, 0xdf, 0xff, 0xdd, 0xff, 0xfb, 0xff, 0xfb, 0xbf, 0xff, 0x7f, 0xff, 0x77, 0xf7, 0xbd, 0xef,
+    0xff, 0xf0, 0xff, 0xf0, 0x0f, 0xfc, 0xcc, 0x3c, 0xcc, 0x33, 0xcc, 0xcf, 0xff, 0xef, 0xff, 0xee,
+    0xff, 0xfd, 0xff, 0xfd, 0xdf, 0xff, 0xbf, 0xff, 0xbb, 0xff, 0xf7, 0xff, 0xf7, 0x7f, 0x7b, 0xde,
+];
+
+/// CMD21 tuning block on an 8-bit bus.
+const TUNING_BLOCK_8BIT: [u8; 128] = [
+    0xff, 0xff, 0x00, 0xff, 0xff, 0xff, 0x00, 0x00, 0xff, 0xff, 0xcc, 0xcc, 0xcc, 0x33, 0xcc, 0xcc,
+    0xcc, 0x33, 0x33, 0xcc, 0xcc, 0xcc, 0xff, 0xff, 0xff, 0xee, 0xff, 0xff, 0xff, 0xee, 0xee, 0xff,
+    0xff, 0xff, 0xdd, 0xff, 0xff, 0xff, 0xdd, 0xdd, 0xff, 0xff, 0xff, 0xbb, 0xff, 0xff, 0xff, 0xbb,
+    0xbb, 0xff, 0xff, 0xff, 0x77, 0xff, 0xff, 0xff, 0x77, 0x77, 0xff, 0x77, 0xbb, 0xdd, 0xee, 0xff,
+    0xff, 0xff, 0xff, 0x00, 0xff, 0xff, 0xff, 0x00, 0x00, 0xff, 0xff, 0xcc, 0xcc, 0xcc, 0x33, 0xcc,
+    0xcc, 0xcc, 0x33, 0x33, 0xcc, 0xcc, 0xcc, 0xff, 0xff, 0xff, 0xee, 0xff, 0xff, 0xff, 0xee, 0xee,
+    0xff, 0xff, 0xff, 0xdd, 0xff, 0xff, 0xff, 0xdd, 0xdd, 0xff, 0xff, 0xff, 0xbb, 0xff, 0xff, 0xff,
+    0xbb, 0xbb, 0xff, 0xff, 0xff, 0x77, 0xff, 0xff, 0xff, 0x77, 0x77, 0xff, 0x77, 0xbb, 0xdd, 0xee,
+];
+
+/// Largest software sample delay.
+const SAMPLE_DELAY_MAX: u8 = 0x3F;
+
+/// Number of polls before hardware sample delay calibration is given up.
+const SAMPLE_DELAY_CAL_POLLS: u32 = 0x1_0000;
+
+/// Number of polls before a card holding DAT0 busy is given up.
+const CARD_BUSY_POLLS: u32 = 0x10_0000;
+
+/// Number of card status (CMD13) polls before a card in programming state is given up.
+const CARD_STATUS_POLLS: u32 = 0x1_0000;
+
+/// Number of polls before a command not taken by the controller is given up.
+const COMMAND_POLLS: u32 = 0x100_0000;
+
+/// Number of polls before a data transfer making no progress is given up.
+const DATA_POLLS: u32 = 0x100_0000;
+
+/// Index of the middle of the widest run of passing delays in `pass`.
+///
+/// Of equally wide runs the earlier one is taken.
+fn widest_run_center(pass: &[bool]) -> Option<usize> {
+    let mut best: Option<(usize, usize)> = None;
+    let mut start = 0;
+    for (i, &p) in pass.iter().enumerate() {
+        if !p {
+            start = i + 1;
+            continue;
+        }
+        let len = i + 1 - start;
+        if best.is_none_or(|(_, best_len)| len > best_len) {
+            best = Some((start, len));
+        }
+    }
+    best.map(|(start, len)| start + (len - 1) / 2)
+}
+
+/// Block layout of a data transfer.
+#[derive(Clone, Copy)]
+struct DataLayout {
+    /// Size of one block in bytes.
+    block_size: u16,
+    /// Total number of bytes.
+    byte_count: u32,
+    /// Controller sends CMD12 after the data transfer.
+    auto_stop: bool,
+    /// Block count controller sends with CMD23 before the data command, if any.
+    auto_cmd23: Option<u32>,
+    /// Data is moved by CPU through the FIFO, or by IDMAC.
+    access_mode: AccessMode,
+}
+
+impl DataLayout {
+    /// Layout of a transfer of `count` 512-byte blocks, ended as `mode` selects.
+    ///
+    /// Single blocks are always ended by CMD12 as before; `mode` only
+    /// applies to multiple block transfers.
+    #[inline]
+    const fn blocks(count: usize, mode: MultiBlockMode, access_mode: AccessMode) -> Self {
+        let predefined = count > 1 && matches!(mode, MultiBlockMode::PredefinedCount);
+        DataLayout {
+            block_size: 512,
+            byte_count: (count * 512) as u32,
+            auto_stop: !predefined,
+            auto_cmd23: if predefined { Some(count as u32) } else { None },
+            access_mode,
+        }
+    }
 }
 
 impl<SMHC: AsRef<RegisterBlock>, PADS> Smhc<SMHC, PADS> {
@@ -23,25 +184,16 @@ impl<SMHC: AsRef<RegisterBlock>, PADS> Smhc<SMHC, PADS> {
         clocks: &Clocks,
         ccu: &ccu::RegisterBlock,
     ) -> Self {
-        let divider = 2;
         let (factor_n, factor_m) =
             ccu::calculate_best_peripheral_factors_nm(clocks.psi.0, 20_000_000);
+        let module_clock = (clocks.psi.0 >> factor_n as u32) / (factor_m as u32 + 1);
         unsafe {
             smhc.as_ref()
                 .clock_control
                 .modify(|val| val.disable_card_clock());
         }
         unsafe {
-            ccu.smhc_bgr.modify(|val| val.assert_reset::<SMHC_IDX>());
-            ccu.smhc_bgr.modify(|val| val.gate_mask::<SMHC_IDX>());
-            ccu.smhc_clk[SMHC_IDX].modify(|val| {
-                val.set_clock_source(SmhcClockSource::PllPeri1x)
-                    .set_factor_n(factor_n)
-                    .set_factor_m(factor_m)
-                    .enable_clock_gating()
-            });
-            ccu.smhc_bgr.modify(|val| val.deassert_reset::<SMHC_IDX>());
-            ccu.smhc_bgr.modify(|val| val.gate_pass::<SMHC_IDX>());
+            ccu::SMHC::<SMHC_IDX>::reconfigure(ccu, SmhcClockSource::PllPeri1x, factor_m, factor_n);
         }
         unsafe {
             let smhc = smhc.as_ref();
@@ -56,46 +208,62 @@ impl<SMHC: AsRef<RegisterBlock>, PADS> Smhc<SMHC, PADS> {
             smhc.global_control.modify(|val| val.disable_interrupt());
         }
         unsafe {
-            let smhc = smhc.as_ref();
-            smhc.command.modify(|val| {
-                val.enable_wait_for_complete()
-                    .enable_change_clock()
-                    .set_command_start()
-            });
-            while !smhc.command.read().is_command_start_cleared() {
-                core::hint::spin_loop();
-            }
-        }
-        unsafe {
-            let smhc = smhc.as_ref();
-            smhc.clock_control
-                .modify(|val| val.set_card_clock_divider(divider - 1));
-            smhc.sample_delay_control.modify(|val| {
+            smhc.as_ref().sample_delay_control.modify(|val| {
                 val.set_sample_delay_software(0)
                     .enable_sample_delay_software()
             });
-            smhc.clock_control.modify(|val| val.enable_card_clock());
-        }
-        unsafe {
-            let smhc = smhc.as_ref();
-            smhc.command.modify(|val| {
-                val.enable_wait_for_complete()
-                    .enable_change_clock()
-                    .set_command_start()
-            });
-            while !smhc.command.read().is_command_start_cleared() {
-                core::hint::spin_loop();
-            }
         }
+        let ans = Self {
+            smhc,
+            pads,
+            module_clock,
+        };
+        // a controller not taking the clock update fails again on card initialization
+        ans.set_card_clock(INIT_CARD_CLOCK).ok();
         unsafe {
-            let smhc = smhc.as_ref();
+            let smhc = ans.smhc.as_ref();
             smhc.card_type
                 .write(CardType::default().set_bus_width(BusWidth::OneBit));
             smhc.block_size
                 .write(BlockSize::default().set_block_size(512)); // TODO
         }
-
-        Self { smhc, pads }
+        ans
+    }
+    /// Set card clock to at most `freq` Hz, returning the actual card clock frequency.
+    ///
+    /// Card clock is stopped while the divider changes, and the update-clock
+    /// command is issued both before and after the change. Returns
+    /// `ResponseTimeout` if the controller does not take the update.
+    #[inline]
+    pub fn set_card_clock(&self, freq: u32) -> Result<u32, SmhcError> {
+        let divider = card_clock_divider(self.module_clock, freq);
+        let smhc = self.smhc.as_ref();
+        unsafe {
+            smhc.clock_control.modify(|val| val.disable_card_clock());
+        }
+        self.update_card_clock()?;
+        unsafe {
+            smhc.clock_control
+                .modify(|val| val.set_card_clock_divider(divider).enable_card_clock());
+        }
+        self.update_card_clock()?;
+        Ok(match divider {
+            0 => self.module_clock,
+            n => self.module_clock / (2 * n as u32),
+        })
+    }
+    /// Send the update-clock command and wait until the controller accepts it.
+    #[inline]
+    fn update_card_clock(&self) -> Result<(), SmhcError> {
+        unsafe {
+            self.smhc.as_ref().command.write(
+                Command::default()
+                    .enable_wait_for_complete()
+                    .enable_change_clock()
+                    .set_command_start(),
+            );
+        }
+        self.wait_command_accepted()
     }
     /// Get a temporary borrow on the underlying GPIO pads.
     #[inline]
@@ -115,6 +283,56 @@ impl<SMHC: AsRef<RegisterBlock>, PADS> Smhc<SMHC, PADS> {
         }
         (self.smhc, self.pads)
     }
+    /// Set data and response timeout limits.
+    ///
+    /// Limits are counted in `unit` card clock periods; the controller raises
+    /// data or response timeout interrupts when a command or data transfer exceeds them.
+    /// Software waits for the controller give up after a fixed number of polls
+    /// as well, returning `ResponseTimeout` or `DataTimeout`, in case it never
+    /// raises an interrupt, e.g. when its clock is off.
+    #[inline]
+    pub fn set_timeouts(&self, data_limit: u32, resp_limit: u8, unit: TimeUnit) {
+        let smhc = self.smhc.as_ref();
+        unsafe {
+            smhc.timeout.modify(|val| {
+                val.set_data_timeout_limit(data_limit)
+                    .set_response_timeout_limit(resp_limit)
+            });
+            smhc.global_control
+                .modify(|val| val.set_data_time_unit(unit).set_command_time_unit(unit));
+        }
+    }
+    /// Power cycle the card and send the initialization sequence.
+    ///
+    /// If `power_pad` is provided, it is driven low to cut card power for 1 ms, then
+    /// driven high and held for another 1 ms to let the supply ramp up; SD specification
+    /// requires at least 1 ms of each. Afterwards the controller sends at least 74 card
+    /// clock cycles along with `CMD0`. The card should be initialized again after this
+    /// function returns, e.g. by creating a new [`SdCard`].
+    pub fn power_cycle<P: OutputPin>(
+        &mut self,
+        power_pad: Option<&mut P>,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), PowerCycleError<P::Error>> {
+        if let Some(pad) = power_pad {
+            pad.set_low().map_err(PowerCycleError::Pad)?;
+            delay.delay_ms(1);
+            pad.set_high().map_err(PowerCycleError::Pad)?;
+            delay.delay_ms(1);
+        }
+        let smhc = self.smhc.as_ref();
+        unsafe {
+            smhc.argument.modify(|val| val.set_argument(0));
+            smhc.command.write(
+                Command::default()
+                    .set_command_index(0)
+                    .enable_send_init_seq()
+                    .enable_wait_for_complete()
+                    .set_command_start(),
+            );
+        }
+        self.wait_command_accepted().map_err(PowerCycleError::Smhc)
+    }
     /// Send a command to the card.
     #[inline]
     pub fn send_card_command(
@@ -125,6 +343,52 @@ impl<SMHC: AsRef<RegisterBlock>, PADS> Smhc<SMHC, PADS> {
         response_mode: ResponseMode,
         crc_check: bool,
     ) {
+        self.send_command(cmd, arg, transfer_mode, response_mode, crc_check, 512)
+    }
+    /// Send a command to the card with a data transfer of `byte_count` bytes.
+    #[inline]
+    fn send_command(
+        &self,
+        cmd: u8,
+        arg: u32,
+        transfer_mode: TransferMode,
+        response_mode: ResponseMode,
+        crc_check: bool,
+        byte_count: u32,
+    ) {
+        self.send_data_command(
+            cmd,
+            arg,
+            transfer_mode,
+            response_mode,
+            crc_check,
+            DataLayout {
+                block_size: byte_count.min(512) as u16,
+                byte_count,
+                auto_stop: true,
+                auto_cmd23: None,
+                access_mode: AccessMode::Ahb,
+            },
+        )
+    }
+    /// Send a command to the card with a data transfer laid out as `layout`.
+    #[inline]
+    fn send_data_command(
+        &self,
+        cmd: u8,
+        arg: u32,
+        transfer_mode: TransferMode,
+        response_mode: ResponseMode,
+        crc_check: bool,
+        layout: DataLayout,
+    ) {
+        let DataLayout {
+            block_size,
+            byte_count,
+            auto_stop,
+            auto_cmd23,
+            access_mode,
+        } = layout;
         let (data_trans, trans_dir) = match transfer_mode {
             TransferMode::Disable => (false, TransferDirection::Read),
             TransferMode::Read => (true, TransferDirection::Read),
@@ -138,20 +402,32 @@ impl<SMHC: AsRef<RegisterBlock>, PADS> Smhc<SMHC, PADS> {
         let smhc = self.smhc.as_ref();
         if data_trans {
             unsafe {
-                smhc.byte_count.modify(|w| w.set_byte_count(512)); // TODO
+                smhc.block_size.modify(|w| w.set_block_size(block_size));
+                smhc.byte_count.modify(|w| w.set_byte_count(byte_count));
                 smhc.global_control
-                    .modify(|w| w.set_access_mode(AccessMode::Ahb));
+                    .modify(|w| w.set_access_mode(access_mode));
             }
         }
         unsafe {
+            // cleared for other commands, so CMD23 only precedes this one
+            let extended = ExtendedCommand::default();
+            match auto_cmd23 {
+                Some(count) => {
+                    smhc.auto_cmd23_arg.write(count);
+                    smhc.extended_command.write(extended.enable_auto_cmd23());
+                }
+                None => smhc.extended_command.write(extended.disable_auto_cmd23()),
+            }
             smhc.argument.modify(|val| val.set_argument(arg));
             smhc.command.write({
                 let mut val = Command::default()
                     .set_command_start()
                     .set_command_index(cmd)
                     .set_transfer_direction(trans_dir)
-                    .enable_wait_for_complete()
-                    .enable_auto_stop();
+                    .enable_wait_for_complete();
+                if auto_stop {
+                    val = val.enable_auto_stop();
+                }
                 if data_trans {
                     val = val.enable_data_transfer();
                 }
@@ -168,6 +444,52 @@ impl<SMHC: AsRef<RegisterBlock>, PADS> Smhc<SMHC, PADS> {
             });
         };
     }
+    /// Wait until the card releases DAT0 after a previous write or erase.
+    ///
+    /// Returns `BusyTimeout` if the card is still busy after a bounded number of polls.
+    #[inline]
+    pub fn wait_card_ready(&self) -> Result<(), SmhcError> {
+        let smhc = self.smhc.as_ref();
+        for _ in 0..CARD_BUSY_POLLS {
+            if !smhc.status.read().card_busy() {
+                return Ok(());
+            }
+            core::hint::spin_loop();
+        }
+        Err(SmhcError::BusyTimeout)
+    }
+    /// Check if a card is present in the slot.
+    #[inline]
+    pub fn is_card_present(&self) -> bool {
+        self.smhc.as_ref().status.read().card_present()
+    }
+    /// Wait until a card is inserted, or `timed_out` returns `true`.
+    ///
+    /// Returns `true` on card insertion, clearing the insertion flag. A flag
+    /// raised before this call counts as insertion; clear it first to wait
+    /// for a new card only.
+    #[inline]
+    pub fn wait_for_card_insertion(&self, mut timed_out: impl FnMut() -> bool) -> bool {
+        let smhc = self.smhc.as_ref();
+        loop {
+            if smhc
+                .interrupt_state_raw
+                .read()
+                .has_interrupt(Interrupt::CardInserted)
+            {
+                unsafe {
+                    smhc.interrupt_state_raw.write(
+                        InterruptStateRaw::default().clear_interrupt(Interrupt::CardInserted),
+                    )
+                };
+                return true;
+            }
+            if timed_out() {
+                return false;
+            }
+            core::hint::spin_loop();
+        }
+    }
     /// Read the response from the card.
     #[inline]
     pub fn read_response(&self) -> u128 {
@@ -179,51 +501,565 @@ impl<SMHC: AsRef<RegisterBlock>, PADS> Smhc<SMHC, PADS> {
         response
     }
     /// Read data from first-in-first-out buffer.
+    ///
+    /// A trailing partial word fills the rest of `buf` with its lower bytes.
+    ///
+    /// Returns an error if the controller reports a data CRC error, data timeout
+    /// or end bit error, either while waiting for data or after all data is read,
+    /// or `DataTimeout` if no data arrives after a bounded number of polls.
     #[inline]
-    pub fn read_data(&self, buf: &mut [u8]) {
+    pub fn read_data(&self, buf: &mut [u8]) -> Result<(), SmhcError> {
         let smhc = self.smhc.as_ref();
-        for i in 0..buf.len() / 4 {
-            while smhc.status.read().fifo_empty() {
-                core::hint::spin_loop();
+        for chunk in buf.chunks_mut(4) {
+            self.wait_fifo(|| !smhc.status.read().fifo_empty())?;
+            let data = smhc.fifo.read().to_le_bytes();
+            chunk.copy_from_slice(&data[..chunk.len()]);
+        }
+        self.check_data_error()
+    }
+    /// Wait until `ready` returns true, checking for data errors meanwhile.
+    #[inline]
+    fn wait_fifo(&self, ready: impl Fn() -> bool) -> Result<(), SmhcError> {
+        for _ in 0..DATA_POLLS {
+            if ready() {
+                return Ok(());
+            }
+            self.check_data_error()?;
+            core::hint::spin_loop();
+        }
+        Err(SmhcError::DataTimeout)
+    }
+    /// Check raw interrupt state for data errors, clearing the error found.
+    #[inline]
+    fn check_data_error(&self) -> Result<(), SmhcError> {
+        let smhc = self.smhc.as_ref();
+        let state = smhc.interrupt_state_raw.read();
+        let (interrupt, error) = if state.has_interrupt(Interrupt::DataCrcError) {
+            (Interrupt::DataCrcError, SmhcError::DataCrcError)
+        } else if state.has_interrupt(Interrupt::DataTimeoutBootDataStart) {
+            (Interrupt::DataTimeoutBootDataStart, SmhcError::DataTimeout)
+        } else if state.has_interrupt(Interrupt::DataEndBitError) {
+            (Interrupt::DataEndBitError, SmhcError::DataEndBitError)
+        } else {
+            return Ok(());
+        };
+        unsafe {
+            smhc.interrupt_state_raw
+                .write(InterruptStateRaw::default().clear_interrupt(interrupt))
+        };
+        Err(error)
+    }
+    /// Check raw interrupt state for command response errors, clearing the error found.
+    #[inline]
+    fn check_response_error(&self) -> Result<(), SmhcError> {
+        let smhc = self.smhc.as_ref();
+        let state = smhc.interrupt_state_raw.read();
+        let (interrupt, error) = if state.has_interrupt(Interrupt::ResponseTimeoutBootAckReceived) {
+            (
+                Interrupt::ResponseTimeoutBootAckReceived,
+                SmhcError::ResponseTimeout,
+            )
+        } else if state.has_interrupt(Interrupt::ResponseCrcError) {
+            (Interrupt::ResponseCrcError, SmhcError::ResponseCrcError)
+        } else {
+            return Ok(());
+        };
+        unsafe {
+            smhc.interrupt_state_raw
+                .write(InterruptStateRaw::default().clear_interrupt(interrupt))
+        };
+        Err(error)
+    }
+    /// Write data into first-in-first-out buffer.
+    ///
+    /// A trailing partial word is padded with zeros.
+    ///
+    /// Returns an error if the controller reports a data CRC error, which includes
+    /// a negative CRC status from the card, data timeout or end bit error, either
+    /// while waiting for FIFO space or after all data is written, or `DataTimeout`
+    /// if no FIFO space frees up after a bounded number of polls.
+    #[inline]
+    pub fn write_data(&self, buf: &[u8]) -> Result<(), SmhcError> {
+        let smhc = self.smhc.as_ref();
+        for chunk in buf.chunks(4) {
+            let mut word = [0u8; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.wait_fifo(|| !smhc.status.read().fifo_full())?;
+            unsafe { smhc.fifo.write(u32::from_le_bytes(word)) };
+        }
+        self.check_data_error()
+    }
+    /// Wait until IDMAC finishes a transfer in `direction`.
+    ///
+    /// Returns an error as soon as IDMAC reports an unavailable descriptor,
+    /// a fatal bus error or a card error, or `DataTimeout` if it reports
+    /// nothing after a bounded number of polls, e.g. when it is not clocked.
+    #[inline]
+    pub fn wait_dma(&self, direction: TransferDirection) -> Result<(), SmhcError> {
+        let smhc = self.smhc.as_ref();
+        for _ in 0..DATA_POLLS {
+            let state = smhc.dma_state.read();
+            let ans = if state.fatal_berr_int() {
+                Err(SmhcError::FatalBusError)
+            } else if state.des_unavl_int() {
+                Err(SmhcError::DescriptorUnavailable)
+            } else if state.card_err_sum() {
+                Err(SmhcError::CardError)
+            } else {
+                match direction {
+                    TransferDirection::Read if state.rx_int_occurs() => Ok(()),
+                    TransferDirection::Write if state.tx_int_occurs() => Ok(()),
+                    _ => {
+                        core::hint::spin_loop();
+                        continue;
+                    }
+                }
+            };
+            unsafe { smhc.dma_state.write(state.clear_all()) };
+            return ans;
+        }
+        Err(SmhcError::DataTimeout)
+    }
+    /// Get numbers of bytes moved by the current or last data transfer.
+    ///
+    /// Returns bytes transferred between controller and card, then bytes
+    /// transferred between host bus and FIFO. Counters are reset by every data
+    /// command and only settle once its transfer completes; read them then.
+    #[inline]
+    pub fn transferred_bytes(&self) -> (u32, u32) {
+        let smhc = self.smhc.as_ref();
+        (
+            smhc.transferred_byte_count0.read(),
+            smhc.transferred_byte_count1.read(),
+        )
+    }
+    /// Check that the completed transfer moved `expected` bytes on both sides.
+    #[inline]
+    fn check_transferred(&self, expected: usize) -> Result<(), SmhcError> {
+        let (card, fifo) = self.transferred_bytes();
+        if (card.min(fifo) as usize) < expected {
+            return Err(SmhcError::ShortTransfer);
+        }
+        Ok(())
+    }
+    /// Read consecutive 512-byte blocks starting from block `start_block`.
+    ///
+    /// A single block is read with CMD17; multiple blocks are read with CMD18,
+    /// ended as `mode` selects: by CMD12 sent automatically after the data, or
+    /// by a block count sent with CMD23 before it.
+    /// Returns `BusyTimeout` if the card stays busy from a previous write.
+    #[inline]
+    pub fn read_blocks(
+        &self,
+        start_block: u32,
+        buf: &mut [[u8; 512]],
+        mode: MultiBlockMode,
+    ) -> Result<(), SmhcError> {
+        let cmd = match buf.len() {
+            0 => return Ok(()),
+            1 => 17,
+            _ => 18,
+        };
+        self.wait_card_ready()?;
+        self.send_data_command(
+            cmd,
+            start_block,
+            TransferMode::Read,
+            ResponseMode::Short,
+            true,
+            DataLayout::blocks(buf.len(), mode, AccessMode::Ahb),
+        );
+        self.wait_command_accepted()?;
+        for block in buf.iter_mut() {
+            self.read_data(block)?;
+        }
+        self.check_transferred(buf.len() * 512)
+    }
+    /// Write consecutive 512-byte blocks starting from block `start_block`.
+    ///
+    /// A single block is written with CMD24; multiple blocks are written with
+    /// CMD25, ended as `mode` selects like [`read_blocks`](Self::read_blocks).
+    /// Returns after the controller reports the data transfer complete, or
+    /// `BusyTimeout` if the card stays busy from a previous write.
+    #[inline]
+    pub fn write_blocks(
+        &self,
+        start_block: u32,
+        buf: &[[u8; 512]],
+        mode: MultiBlockMode,
+    ) -> Result<(), SmhcError> {
+        let cmd = match buf.len() {
+            0 => return Ok(()),
+            1 => 24,
+            _ => 25,
+        };
+        self.wait_card_ready()?;
+        self.send_data_command(
+            cmd,
+            start_block,
+            TransferMode::Write,
+            ResponseMode::Short,
+            true,
+            DataLayout::blocks(buf.len(), mode, AccessMode::Ahb),
+        );
+        self.wait_command_accepted()?;
+        for block in buf {
+            self.write_data(block)?;
+        }
+        self.wait_data_complete()?;
+        self.check_transferred(buf.len() * 512)
+    }
+    /// Read consecutive 512-byte blocks starting from block `start_block` using IDMAC.
+    ///
+    /// A descriptor chain for `buf` is built in `descriptors`, which needs one
+    /// descriptor for every 4096 bytes; see [`descriptors_needed`]. `buf` must be
+    /// word aligned. Addresses are given to IDMAC as is, so both buffers must be
+    /// physically addressed, and either uncached or with descriptors cleaned
+    /// before and `buf` invalidated after this call by the caller.
+    ///
+    /// Multiple blocks are ended as `mode` selects like [`read_blocks`](Self::read_blocks).
+    ///
+    /// [`descriptors_needed`]: crate::smhc::descriptors_needed
+    pub fn read_blocks_dma(
+        &mut self,
+        start_block: u32,
+        buf: &mut [[u8; 512]],
+        descriptors: &mut [IdmacDescriptor],
+        mode: MultiBlockMode,
+    ) -> Result<(), SmhcError> {
+        let cmd = match buf.len() {
+            0 => return Ok(()),
+            1 => 17,
+            _ => 18,
+        };
+        self.wait_card_ready()?;
+        let address = buf.as_mut_ptr() as usize;
+        if !address.is_multiple_of(4) {
+            return Err(SmhcError::MisalignedBuffer);
+        }
+        let byte_count = buf.len() * 512;
+        let chain =
+            build_chain(descriptors, address, byte_count).ok_or(SmhcError::DescriptorsTooShort)?;
+        let chain_base = chain.as_ptr() as usize;
+        fence(Ordering::SeqCst);
+        let smhc = self.smhc.as_ref();
+        unsafe {
+            smhc.dma_state.write(smhc.dma_state.read().clear_all());
+            smhc.dma_descriptor_base.write((chain_base >> 2) as u32);
+            smhc.dma_control
+                .write(DmaControl::default().enable_fixed_burst().enable_dma());
+            smhc.global_control.modify(|w| w.enable_dma());
+        }
+        self.send_data_command(
+            cmd,
+            start_block,
+            TransferMode::Read,
+            ResponseMode::Short,
+            true,
+            DataLayout::blocks(buf.len(), mode, AccessMode::Dma),
+        );
+        let ans = self
+            .wait_command_accepted()
+            .and_then(|_| self.wait_dma(TransferDirection::Read))
+            .and_then(|_| self.check_data_error())
+            .and_then(|_| self.check_transferred(byte_count));
+        fence(Ordering::SeqCst);
+        let smhc = self.smhc.as_ref();
+        unsafe {
+            smhc.global_control
+                .modify(|w| w.disable_dma().set_access_mode(AccessMode::Ahb));
+            let control = DmaControl::default();
+            smhc.dma_control.write(if ans.is_err() {
+                control.set_soft_reset()
+            } else {
+                control
+            });
+        }
+        ans
+    }
+    /// Wait until the controller reports data transfer complete or a data error.
+    ///
+    /// Returns `DataTimeout` if neither is reported after a bounded number of polls.
+    #[inline]
+    fn wait_data_complete(&self) -> Result<(), SmhcError> {
+        let smhc = self.smhc.as_ref();
+        for _ in 0..DATA_POLLS {
+            self.check_data_error()?;
+            if smhc
+                .interrupt_state_raw
+                .read()
+                .has_interrupt(Interrupt::DataTransferComplete)
+            {
+                unsafe {
+                    smhc.interrupt_state_raw.write(
+                        InterruptStateRaw::default()
+                            .clear_interrupt(Interrupt::DataTransferComplete),
+                    )
+                };
+                return Ok(());
+            }
+            core::hint::spin_loop();
+        }
+        Err(SmhcError::DataTimeout)
+    }
+    /// Tune command and data sample phases for the current card clock.
+    ///
+    /// New timing mode is enabled, and block 0 is read with CRC check at each
+    /// phase: command phases are swept first, checking the response, then data
+    /// phases at the chosen command phase, checking the data. Each phase is set
+    /// to the middle of its widest window of passing phases. Returns the chosen
+    /// phases, or `None` if no phase passes, in which case phases are left unchanged.
+    pub fn tune_timing(&mut self) -> Option<TimingPhases> {
+        let original = self.smhc.as_ref().new_timing_set.read();
+        let mut block = [[0u8; 512]];
+        let data = original.sample_timing_phase();
+        let mut pass = [false; 4];
+        for (i, &phase) in TUNING_PHASES.iter().enumerate() {
+            self.set_sample_phases(original, phase, data);
+            let result = self.read_blocks(0, &mut block, MultiBlockMode::AutoStop);
+            pass[i] = !self.take_response_error() && result != Err(SmhcError::DataTimeout);
+        }
+        let Some(command) = widest_window_center(pass) else {
+            unsafe { self.smhc.as_ref().new_timing_set.write(original) };
+            return None;
+        };
+        let command = TUNING_PHASES[command];
+        for (i, &phase) in TUNING_PHASES.iter().enumerate() {
+            self.set_sample_phases(original, command, phase);
+            let result = self.read_blocks(0, &mut block, MultiBlockMode::AutoStop);
+            pass[i] = !self.take_response_error() && result.is_ok();
+        }
+        let Some(data) = widest_window_center(pass) else {
+            unsafe { self.smhc.as_ref().new_timing_set.write(original) };
+            return None;
+        };
+        let data = TUNING_PHASES[data];
+        self.set_sample_phases(original, command, data);
+        Some(TimingPhases { command, data })
+    }
+    /// Calibrate sample delay for HS200 and HS400 modes, returning the delay chosen.
+    ///
+    /// Hardware calibration is tried first. If it does not finish, every
+    /// software delay is tried by reading the tuning block with CMD21 and
+    /// comparing it to the pattern for the current bus width; the middle of
+    /// the widest window of passing delays is chosen. Returns `None` if no
+    /// delay passes, in which case the delay setting is left unchanged.
+    pub fn calibrate_sample_delay(&mut self) -> Option<u8> {
+        let smhc = self.smhc.as_ref();
+        let original = smhc.sample_delay_control.read();
+        unsafe {
+            smhc.sample_delay_control.write(
+                original
+                    .disable_sample_delay_software()
+                    .start_sample_delay_cal(),
+            )
+        };
+        for _ in 0..SAMPLE_DELAY_CAL_POLLS {
+            let val = smhc.sample_delay_control.read();
+            if val.is_sample_delay_cal_done() {
+                let delay = val.sample_delay_cal();
+                unsafe {
+                    smhc.sample_delay_control.write(
+                        val.stop_sample_delay_cal()
+                            .set_sample_delay_software(delay)
+                            .enable_sample_delay_software(),
+                    )
+                };
+                return Some(delay);
+            }
+            core::hint::spin_loop();
+        }
+        let base = original.stop_sample_delay_cal();
+        let pattern: &[u8] = match smhc.card_type.read().bus_width() {
+            BusWidth::EightBit => &TUNING_BLOCK_8BIT,
+            _ => &TUNING_BLOCK_4BIT,
+        };
+        let mut pass = [false; SAMPLE_DELAY_MAX as usize + 1];
+        for delay in 0..=SAMPLE_DELAY_MAX {
+            unsafe {
+                smhc.sample_delay_control.write(
+                    base.set_sample_delay_software(delay)
+                        .enable_sample_delay_software(),
+                )
+            };
+            pass[delay as usize] = self.tuning_block_matches(pattern);
+        }
+        let Some(delay) = widest_run_center(&pass) else {
+            unsafe { smhc.sample_delay_control.write(base) };
+            return None;
+        };
+        let delay = delay as u8;
+        unsafe {
+            smhc.sample_delay_control.write(
+                base.set_sample_delay_software(delay)
+                    .enable_sample_delay_software(),
+            )
+        };
+        Some(delay)
+    }
+    /// Read the tuning block with CMD21 and compare it to `pattern`.
+    #[inline]
+    fn tuning_block_matches(&self, pattern: &[u8]) -> bool {
+        let mut block = [0u8; 128];
+        let block = &mut block[..pattern.len()];
+        if self.wait_card_ready().is_err() {
+            return false;
+        }
+        self.send_data_command(
+            21,
+            0,
+            TransferMode::Read,
+            ResponseMode::Short,
+            true,
+            DataLayout {
+                block_size: pattern.len() as u16,
+                byte_count: pattern.len() as u32,
+                auto_stop: false,
+                auto_cmd23: None,
+                access_mode: AccessMode::Ahb,
+            },
+        );
+        if self.wait_command_accepted().is_err() {
+            return false;
+        }
+        let response_error = self.take_response_error();
+        let result = self.read_data(block);
+        !response_error && result.is_ok() && block == pattern
+    }
+    /// Enable new timing mode on `base` with `command` and `data` sample phases.
+    #[inline]
+    fn set_sample_phases(&self, base: NewTimingSet, command: NtsTimingPhase, data: NtsTimingPhase) {
+        unsafe {
+            self.smhc.as_ref().new_timing_set.write(
+                base.enable_new_mode()
+                    .set_command_sample_timing_phase(command)
+                    .set_sample_timing_phase(data),
+            )
+        }
+    }
+    /// Check raw interrupt state for response errors, clearing them.
+    #[inline]
+    fn take_response_error(&self) -> bool {
+        let smhc = self.smhc.as_ref();
+        let state = smhc.interrupt_state_raw.read();
+        let mut clear = InterruptStateRaw::default();
+        let mut error = false;
+        for interrupt in [
+            Interrupt::ResponseError,
+            Interrupt::ResponseCrcError,
+            Interrupt::ResponseTimeoutBootAckReceived,
+        ] {
+            if state.has_interrupt(interrupt) {
+                clear = clear.clear_interrupt(interrupt);
+                error = true;
+            }
+        }
+        if error {
+            unsafe { smhc.interrupt_state_raw.write(clear) };
+        }
+        error
+    }
+    /// Wait until the controller has accepted the last command.
+    ///
+    /// Returns `ResponseTimeout` if it is not accepted after a bounded number of polls.
+    #[inline]
+    fn wait_command_accepted(&self) -> Result<(), SmhcError> {
+        let command = &self.smhc.as_ref().command;
+        for _ in 0..COMMAND_POLLS {
+            if command.read().is_command_start_cleared() {
+                return Ok(());
             }
-            let data = smhc.fifo.read();
-            buf[i * 4] = (data & 0xff) as u8;
-            buf[i * 4 + 1] = ((data >> 8) & 0xff) as u8;
-            buf[i * 4 + 2] = ((data >> 16) & 0xff) as u8;
-            buf[i * 4 + 3] = ((data >> 24) & 0xff) as u8;
+            core::hint::spin_loop();
+        }
+        Err(SmhcError::ResponseTimeout)
+    }
+}
+
+/// Maximum size of CMD42 data block: flags, password length and two passwords.
+const LOCK_UNLOCK_BLOCK_MAX: usize = 2 + 2 * 16;
+
+/// Assemble CMD42 data block for `op` into `buf`, returning its length in bytes.
+fn lock_unlock_block(
+    op: LockOp,
+    password: &[u8],
+    buf: &mut [u8; LOCK_UNLOCK_BLOCK_MAX],
+) -> Result<usize, SmhcError> {
+    const SET_PWD: u8 = 1 << 0;
+    const CLR_PWD: u8 = 1 << 1;
+    const LOCK_UNLOCK: u8 = 1 << 2;
+    const ERASE: u8 = 1 << 3;
+    let (flags, max_len) = match op {
+        LockOp::SetPassword => (SET_PWD, 32),
+        LockOp::ClearPassword => (CLR_PWD, 16),
+        LockOp::Lock => (LOCK_UNLOCK, 16),
+        LockOp::Unlock => (0, 16),
+        LockOp::ForceErase => {
+            buf[0] = ERASE;
+            return Ok(1);
         }
+    };
+    if password.is_empty() || password.len() > max_len {
+        return Err(SmhcError::InvalidPasswordLength(password.len()));
     }
+    buf[0] = flags;
+    buf[1] = password.len() as u8;
+    buf[2..2 + password.len()].copy_from_slice(password);
+    Ok(2 + password.len())
 }
 
 pub struct SdCard<'a, S, P> {
     smhc: &'a mut Smhc<S, P>,
+    /// Relative card address in the upper half, as used in command arguments.
+    rca: u32,
     block_count: u32,
 }
 
 impl<'a, S: AsRef<RegisterBlock>, P> SdCard<'a, S, P> {
     /// Create an SD card instance.
+    ///
+    /// The card is identified with a card clock of at most 400 kHz, then the
+    /// card clock is raised to operating frequency.
     #[inline]
-    pub fn new(smhc: &'a mut Smhc<S, P>) -> Result<Self, SdCardError> {
+    pub fn new(smhc: &'a mut Smhc<S, P>) -> Result<Self, SmhcError> {
         /// Host supports high capacity
         const OCR_HCS: u32 = 0x40000000;
         /// Card has finished power up routine if bit is high
         const OCR_NBUSY: u32 = 0x80000000;
         /// Valid bits for voltage setting
         const OCR_VOLTAGE_MASK: u32 = 0x007FFF80;
+        /// Times ACMD41 is sent before giving up on card power up
+        const ACMD41_ATTEMPTS: u32 = 100;
+
+        if !smhc.smhc.as_ref().status.read().card_present() {
+            return Err(SmhcError::NoCard);
+        }
+
+        // Identify the card at no more than 400 kHz.
+        smhc.set_card_clock(INIT_CARD_CLOCK)?;
 
         // CMD0(reset) -> CMD8(check voltage and sdcard version)
         // -> CMD55+ACMD41(init and read OCR)
         smhc.send_card_command(0, 0, TransferMode::Disable, ResponseMode::Disable, false);
+        smhc.wait_command_accepted()?;
         Self::sleep(100); // TODO: wait for interrupt instead of sleep
         smhc.send_card_command(8, 0x1AA, TransferMode::Disable, ResponseMode::Short, true);
+        smhc.wait_command_accepted()?;
         Self::sleep(100);
+        smhc.check_response_error()?;
         let data = smhc.read_response();
         if data != 0x1AA {
-            return Err(SdCardError::UnexpectedResponse(8, data));
+            return Err(SmhcError::VoltageMismatch);
         }
+        let mut attempts = 0;
         loop {
+            if attempts == ACMD41_ATTEMPTS {
+                return Err(SmhcError::InitTimeout);
+            }
+            attempts += 1;
             smhc.send_card_command(55, 0, TransferMode::Disable, ResponseMode::Short, true);
+            smhc.wait_command_accepted()?;
             Self::sleep(100);
+            smhc.check_response_error()?;
             smhc.send_card_command(
                 41,
                 OCR_VOLTAGE_MASK & 0x00ff8000 | OCR_HCS,
@@ -231,7 +1067,9 @@ impl<'a, S: AsRef<RegisterBlock>, P> SdCard<'a, S, P> {
                 ResponseMode::Short,
                 false,
             );
+            smhc.wait_command_accepted()?;
             Self::sleep(100);
+            smhc.check_response_error()?;
             let ocr = smhc.read_response() as u32;
             if (ocr & OCR_NBUSY) == OCR_NBUSY {
                 break;
@@ -240,36 +1078,46 @@ impl<'a, S: AsRef<RegisterBlock>, P> SdCard<'a, S, P> {
 
         // Send CMD2 to get CID.
         smhc.send_card_command(2, 0, TransferMode::Disable, ResponseMode::Long, true);
+        smhc.wait_command_accepted()?;
         Self::sleep(100);
         let _cid = smhc.read_response();
 
         // Send CMD3 to get RCA.
         smhc.send_card_command(3, 0, TransferMode::Disable, ResponseMode::Short, true);
+        smhc.wait_command_accepted()?;
         Self::sleep(100);
         let rca = smhc.read_response() as u32;
 
         // Send CMD9 to get CSD.
         smhc.send_card_command(9, rca, TransferMode::Disable, ResponseMode::Long, true);
+        smhc.wait_command_accepted()?;
         Self::sleep(100);
         let csd_raw = smhc.read_response();
         let fixed_csd_raw = csd_raw >> 8; // FIXME: 8bit shift for long response, why?
         let (csd_structure, c_size) = Self::parse_csd_v2(fixed_csd_raw);
         if csd_structure != 1 {
-            return Err(SdCardError::UnexpectedResponse(9, csd_raw));
+            return Err(SmhcError::UnsupportedCard);
         }
 
         // Send CMD7 to select card.
         smhc.send_card_command(7, rca, TransferMode::Disable, ResponseMode::Short, true);
+        smhc.wait_command_accepted()?;
         Self::sleep(100);
 
+        // Identification is done, switch to operating clock.
+        smhc.set_card_clock(OPERATING_CARD_CLOCK)?;
+
         // Set 1 data len, CMD55 -> ACMD6.
         smhc.send_card_command(55, rca, TransferMode::Disable, ResponseMode::Short, true);
+        smhc.wait_command_accepted()?;
         Self::sleep(100);
         smhc.send_card_command(6, 0, TransferMode::Disable, ResponseMode::Short, true);
+        smhc.wait_command_accepted()?;
         Self::sleep(100);
 
         Ok(SdCard {
             smhc,
+            rca: rca & 0xFFFF_0000,
             block_count: (c_size + 1) * 1024,
         })
     }
@@ -279,11 +1127,123 @@ impl<'a, S: AsRef<RegisterBlock>, P> SdCard<'a, S, P> {
         (self.block_count as f64) * (512 as f64) / 1024.0
     }
     /// Read a block from the SD card.
+    ///
+    /// Returns `BusyTimeout` if the card stays busy from a previous write.
     #[inline]
-    pub fn read_block(&self, block: &mut Block, block_idx: u32) {
+    pub fn read_block(&self, block: &mut Block, block_idx: u32) -> Result<(), SmhcError> {
+        self.smhc.wait_card_ready()?;
         self.smhc
             .send_card_command(17, block_idx, TransferMode::Read, ResponseMode::Short, true);
-        self.smhc.read_data(&mut block.contents);
+        self.smhc.wait_command_accepted()?;
+        self.smhc.read_data(&mut block.contents)?;
+        self.smhc.check_transferred(block.contents.len())
+    }
+    /// Read consecutive blocks from the SD card, starting from `start_block_idx`.
+    ///
+    /// Stops at the first block the controller reports a data error on.
+    #[inline]
+    pub fn read_blocks(&self, blocks: &mut [Block], start_block_idx: u32) -> Result<(), SmhcError> {
+        for (i, block) in blocks.iter_mut().enumerate() {
+            self.read_block(block, start_block_idx + i as u32)?;
+        }
+        Ok(())
+    }
+    /// Set, clear or use the card password, or force erase a locked card (CMD42).
+    ///
+    /// `password` is at most 16 bytes, or 32 bytes when replacing a password with
+    /// [`LockOp::SetPassword`], and is ignored by [`LockOp::ForceErase`].
+    /// Returns the card status from the CMD42 response, or `UnexpectedResponse`
+    /// if the card reports the operation failed. Block length is restored to
+    /// 512 bytes afterwards, also when the operation fails.
+    pub fn lock_unlock(&mut self, op: LockOp, password: &[u8]) -> Result<u32, SmhcError> {
+        /// Card status bit: lock or unlock operation failed.
+        const LOCK_UNLOCK_FAILED: u32 = 1 << 24;
+        let mut block = [0u8; LOCK_UNLOCK_BLOCK_MAX];
+        let len = lock_unlock_block(op, password, &mut block)?;
+        self.smhc.wait_card_ready()?;
+        // CMD16: data block length of CMD42 is set by block length.
+        let ans = self
+            .set_block_length(len as u32)
+            .and_then(|_| self.send_lock_unlock_block(&block[..len]));
+        // Restore block length for following block transfers.
+        let restored = self.set_block_length(512);
+        let status = ans?;
+        restored?;
+        if status & LOCK_UNLOCK_FAILED != 0 {
+            return Err(SmhcError::UnexpectedResponse(42, status as u128));
+        }
+        Ok(status)
+    }
+    /// Set block length of following data commands (CMD16).
+    #[inline]
+    fn set_block_length(&self, len: u32) -> Result<(), SmhcError> {
+        self.smhc
+            .send_card_command(16, len, TransferMode::Disable, ResponseMode::Short, true);
+        self.smhc.wait_command_accepted()?;
+        Self::sleep(100);
+        self.smhc.check_response_error()
+    }
+    /// Send CMD42 with data `block`, returning the card status from its response.
+    #[inline]
+    fn send_lock_unlock_block(&self, block: &[u8]) -> Result<u32, SmhcError> {
+        self.smhc.send_command(
+            42,
+            0,
+            TransferMode::Write,
+            ResponseMode::Short,
+            true,
+            block.len() as u32,
+        );
+        self.smhc.wait_command_accepted()?;
+        self.smhc.check_response_error()?;
+        self.smhc.write_data(block)?;
+        self.smhc.wait_data_complete()?;
+        Ok(self.smhc.read_response() as u32)
+    }
+    /// Wait until the card finishes internal programming of written data.
+    ///
+    /// Polls card status (CMD13) until the card leaves programming state, so
+    /// power can be removed without losing data. A transfer still open in
+    /// receive or send data state is ended with CMD12 first. Returns
+    /// `BusyTimeout` if the card stays busy after a bounded number of polls.
+    pub fn sync(&mut self) -> Result<(), SmhcError> {
+        /// Card status: current state field.
+        const CURRENT_STATE: u32 = 0xF << 9;
+        /// Current state: sending data.
+        const STATE_DATA: u32 = 5 << 9;
+        /// Current state: receiving data.
+        const STATE_RCV: u32 = 6 << 9;
+        /// Current state: programming.
+        const STATE_PRG: u32 = 7 << 9;
+        for _ in 0..CARD_STATUS_POLLS {
+            self.smhc.wait_card_ready()?;
+            self.smhc.send_card_command(
+                13,
+                self.rca,
+                TransferMode::Disable,
+                ResponseMode::Short,
+                true,
+            );
+            self.smhc.wait_command_accepted()?;
+            self.smhc.check_response_error()?;
+            match self.smhc.read_response() as u32 & CURRENT_STATE {
+                STATE_DATA | STATE_RCV => {
+                    // CMD12: stop transmission.
+                    self.smhc.send_card_command(
+                        12,
+                        0,
+                        TransferMode::Disable,
+                        ResponseMode::Short,
+                        true,
+                    );
+                    self.smhc.wait_command_accepted()?;
+                    self.smhc.check_response_error()?;
+                }
+                STATE_PRG => core::hint::spin_loop(),
+                _ => return Ok(()),
+            }
+        }
+        Err(SmhcError::BusyTimeout)
     }
     /// Parse CSD register version 2.
     #[inline]
@@ -302,7 +1262,7 @@ impl<'a, S: AsRef<RegisterBlock>, P> SdCard<'a, S, P> {
 }
 
 impl<'a, S: AsRef<RegisterBlock>, P> BlockDevice for SdCard<'a, S, P> {
-    type Error = core::convert::Infallible;
+    type Error = SmhcError;
 
     #[inline]
     fn read(
@@ -311,15 +1271,19 @@ impl<'a, S: AsRef<RegisterBlock>, P> BlockDevice for SdCard<'a, S, P> {
         start_block_idx: BlockIdx,
         _reason: &str,
     ) -> Result<(), Self::Error> {
-        for (i, block) in blocks.iter_mut().enumerate() {
-            self.read_block(block, start_block_idx.0 + i as u32);
-        }
-        Ok(())
+        self.read_blocks(blocks, start_block_idx.0)
     }
 
     #[inline]
-    fn write(&self, _blocks: &[Block], _start_block_idx: BlockIdx) -> Result<(), Self::Error> {
-        todo!();
+    fn write(&self, blocks: &[Block], start_block_idx: BlockIdx) -> Result<(), Self::Error> {
+        for (i, block) in blocks.iter().enumerate() {
+            let block_idx = start_block_idx.0 + i as u32;
+            // one block at a time with CMD24, so stop mode does not matter
+            let buf = core::slice::from_ref(&block.contents);
+            self.smhc
+                .write_blocks(block_idx, buf, MultiBlockMode::AutoStop)?;
+        }
+        Ok(())
     }
 
     #[inline]
@@ -327,3 +1291,1626 @@ impl<'a, S: AsRef<RegisterBlock>, P> BlockDevice for SdCard<'a, S, P> {
         Ok(embedded_sdmmc::BlockCount(self.block_count))
     }
 }
+
+/// Extended CSD register of an eMMC device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExtCsd([u8; 512]);
+
+impl ExtCsd {
+    /// Index of the partition configuration byte.
+    pub const PARTITION_CONFIG: u8 = 179;
+    /// Index of the bus width byte.
+    pub const BUS_WIDTH: u8 = 183;
+    /// Index of the high speed interface timing byte.
+    pub const HS_TIMING: u8 = 185;
+    /// Index of the extended CSD revision byte.
+    pub const EXT_CSD_REV: u8 = 192;
+    /// Index of the card type byte.
+    pub const CARD_TYPE: u8 = 196;
+    /// Index of the first sector count byte.
+    pub const SEC_COUNT: u8 = 212;
+
+    /// Wrap raw extended CSD register contents.
+    #[inline]
+    pub const fn new(raw: [u8; 512]) -> Self {
+        Self(raw)
+    }
+    /// Get raw extended CSD register contents.
+    #[inline]
+    pub const fn raw(&self) -> &[u8; 512] {
+        &self.0
+    }
+    /// Get partition configuration.
+    #[inline]
+    pub const fn partition_config(&self) -> u8 {
+        self.0[Self::PARTITION_CONFIG as usize]
+    }
+    /// Get bus width mode.
+    #[inline]
+    pub const fn bus_width(&self) -> u8 {
+        self.0[Self::BUS_WIDTH as usize]
+    }
+    /// Get high speed interface timing.
+    #[inline]
+    pub const fn hs_timing(&self) -> u8 {
+        self.0[Self::HS_TIMING as usize]
+    }
+    /// Get extended CSD revision.
+    #[inline]
+    pub const fn ext_csd_rev(&self) -> u8 {
+        self.0[Self::EXT_CSD_REV as usize]
+    }
+    /// Get supported device types.
+    #[inline]
+    pub const fn card_type(&self) -> u8 {
+        self.0[Self::CARD_TYPE as usize]
+    }
+    /// Get device capacity in 512-byte sectors.
+    #[inline]
+    pub const fn sec_count(&self) -> u32 {
+        let i = Self::SEC_COUNT as usize;
+        u32::from_le_bytes([self.0[i], self.0[i + 1], self.0[i + 2], self.0[i + 3]])
+    }
+}
+
+/// CMD6 argument writing `value` into extended CSD byte `index`.
+#[inline]
+const fn switch_argument(index: u8, value: u8) -> u32 {
+    /// Access mode: write byte.
+    const WRITE_BYTE: u32 = 0b11 << 24;
+    WRITE_BYTE | (index as u32) << 16 | (value as u32) << 8
+}
+
+/// eMMC device.
+pub struct Emmc<'a, S, P> {
+    smhc: &'a mut Smhc<S, P>,
+    rca: u32,
+}
+
+impl<'a, S: AsRef<RegisterBlock>, P> Emmc<'a, S, P> {
+    /// Create an eMMC instance on a device selected in transfer state.
+    ///
+    /// `rca` is the relative card address assigned by CMD3.
+    #[inline]
+    pub fn new(smhc: &'a mut Smhc<S, P>, rca: u16) -> Self {
+        Emmc {
+            smhc,
+            rca: (rca as u32) << 16,
+        }
+    }
+    /// Read the extended CSD register (CMD8).
+    ///
+    /// CMD8 is a single block read that ends by itself, so no CMD12 is sent.
+    #[inline]
+    pub fn read_ext_csd(&self) -> Result<ExtCsd, SmhcError> {
+        let mut buf = [0u8; 512];
+        self.smhc.wait_card_ready()?;
+        self.smhc.send_data_command(
+            8,
+            0,
+            TransferMode::Read,
+            ResponseMode::Short,
+            true,
+            DataLayout {
+                block_size: 512,
+                byte_count: 512,
+                auto_stop: false,
+                auto_cmd23: None,
+                access_mode: AccessMode::Ahb,
+            },
+        );
+        self.smhc.wait_command_accepted()?;
+        self.smhc.read_data(&mut buf)?;
+        Ok(ExtCsd::new(buf))
+    }
+    /// Write `value` into extended CSD byte `index` (CMD6 SWITCH).
+    ///
+    /// Waits until the device releases busy state, then checks the switch
+    /// error bit of the card status (CMD13).
+    #[inline]
+    pub fn write_ext_csd(&self, index: u8, value: u8) -> Result<(), SmhcError> {
+        /// Card status bit: device did not switch to the requested mode.
+        const SWITCH_ERROR: u32 = 1 << 7;
+        self.smhc.send_card_command(
+            6,
+            switch_argument(index, value),
+            TransferMode::Disable,
+            ResponseMode::Short,
+            true,
+        );
+        self.smhc.wait_command_accepted()?;
+        self.smhc.wait_card_ready()?;
+        self.smhc.send_card_command(
+            13,
+            self.rca,
+            TransferMode::Disable,
+            ResponseMode::Short,
+            true,
+        );
+        self.smhc.wait_command_accepted()?;
+        let status = self.smhc.read_response();
+        if status as u32 & SWITCH_ERROR != 0 {
+            return Err(SmhcError::UnexpectedResponse(6, status));
+        }
+        Ok(())
+    }
+}
+
+/// CMD52 argument accessing one byte at `address` of SDIO function `function`.
+///
+/// Writes `value` if `write` is set, otherwise reads.
+#[inline]
+const fn io_rw_direct_argument(write: bool, function: u8, address: u32, value: u8) -> u32 {
+    (write as u32) << 31
+        | ((function as u32) & 0x7) << 28
+        | (address & 0x1_FFFF) << 9
+        | value as u32
+}
+
+/// CMD53 argument transferring `count` bytes, or `count` blocks in block mode.
+///
+/// A count of 512 bytes is encoded as zero.
+#[inline]
+const fn io_rw_extended_argument(
+    write: bool,
+    function: u8,
+    block_mode: bool,
+    increment: bool,
+    address: u32,
+    count: u16,
+) -> u32 {
+    (write as u32) << 31
+        | ((function as u32) & 0x7) << 28
+        | (block_mode as u32) << 27
+        | (increment as u32) << 26
+        | (address & 0x1_FFFF) << 9
+        | (count as u32) & 0x1FF
+}
+
+/// R5 response flags reporting an error: CRC, illegal command, general error,
+/// invalid function number and out of range.
+const R5_ERROR_FLAGS: u32 = 0xCB00;
+
+/// SDIO card.
+pub struct SdioCard<'a, S, P> {
+    smhc: &'a mut Smhc<S, P>,
+    functions: u8,
+}
+
+impl<'a, S: AsRef<RegisterBlock>, P> SdioCard<'a, S, P> {
+    /// Create an SDIO card instance.
+    ///
+    /// The card is initialized with CMD5, given a relative address with CMD3 and
+    /// selected with CMD7; the card clock is then raised to operating frequency
+    /// and the SDIO interrupt is unmasked.
+    #[inline]
+    pub fn new(smhc: &'a mut Smhc<S, P>) -> Result<Self, SmhcError> {
+        /// Card is ready to operate after initialization.
+        const OCR_READY: u32 = 1 << 31;
+        /// Valid bits for voltage setting.
+        const OCR_VOLTAGE_MASK: u32 = 0x00FF_FF00;
+        /// Times CMD5 is sent before giving up on card power up.
+        const CMD5_ATTEMPTS: u32 = 100;
+
+        smhc.set_card_clock(INIT_CARD_CLOCK)?;
+
+        // CMD5 without voltage window queries the I/O OCR; R4 has no CRC.
+        smhc.send_card_command(5, 0, TransferMode::Disable, ResponseMode::Short, false);
+        smhc.wait_command_accepted()?;
+        smhc.check_response_error()?;
+        let ocr = smhc.read_response();
+        let functions = ((ocr >> 28) & 0x7) as u8;
+        if functions == 0 {
+            return Err(SmhcError::UnexpectedResponse(5, ocr));
+        }
+        let mut attempts = 0;
+        loop {
+            if attempts == CMD5_ATTEMPTS {
+                return Err(SmhcError::InitTimeout);
+            }
+            attempts += 1;
+            smhc.send_card_command(
+                5,
+                ocr as u32 & OCR_VOLTAGE_MASK,
+                TransferMode::Disable,
+                ResponseMode::Short,
+                false,
+            );
+            smhc.wait_command_accepted()?;
+            smhc.check_response_error()?;
+            if smhc.read_response() as u32 & OCR_READY != 0 {
+                break;
+            }
+        }
+
+        smhc.send_card_command(3, 0, TransferMode::Disable, ResponseMode::Short, true);
+        smhc.wait_command_accepted()?;
+        let rca = smhc.read_response() as u32 & 0xFFFF_0000;
+        smhc.send_card_command(7, rca, TransferMode::Disable, ResponseMode::Short, true);
+        smhc.wait_command_accepted()?;
+
+        smhc.set_card_clock(OPERATING_CARD_CLOCK)?;
+        unsafe {
+            smhc.smhc
+                .as_ref()
+                .interrupt_mask
+                .modify(|val| val.unmask_interrupt(Interrupt::Sdio));
+        }
+        Ok(SdioCard { smhc, functions })
+    }
+    /// Get the number of I/O functions reported by the card.
+    #[inline]
+    pub fn functions(&self) -> u8 {
+        self.functions
+    }
+    /// Read one byte at `address` of `function` (CMD52).
+    #[inline]
+    pub fn read_byte(&self, function: u8, address: u32) -> Result<u8, SmhcError> {
+        self.io_rw_direct(io_rw_direct_argument(false, function, address, 0))
+    }
+    /// Write `value` into one byte at `address` of `function` (CMD52).
+    #[inline]
+    pub fn write_byte(&self, function: u8, address: u32, value: u8) -> Result<(), SmhcError> {
+        self.io_rw_direct(io_rw_direct_argument(true, function, address, value))
+            .map(|_| ())
+    }
+    /// Set block size of `function` for block mode transfers.
+    ///
+    /// Block size of function 0 is in the CCCR, other functions in their FBR.
+    #[inline]
+    pub fn set_block_size(&self, function: u8, block_size: u16) -> Result<(), SmhcError> {
+        let base = (function as u32) << 8;
+        let [low, high] = block_size.to_le_bytes();
+        self.write_byte(0, base + 0x10, low)?;
+        self.write_byte(0, base + 0x11, high)
+    }
+    /// Read 1 to 512 bytes from `address` of `function` in byte mode (CMD53).
+    ///
+    /// If `increment` is set, bytes are read from consecutive addresses,
+    /// otherwise all from a fixed address, e.g. a FIFO register.
+    #[inline]
+    pub fn read_bytes(
+        &self,
+        function: u8,
+        address: u32,
+        increment: bool,
+        buf: &mut [u8],
+    ) -> Result<(), SmhcError> {
+        let len = buf.len();
+        if !(1..=512).contains(&len) {
+            return Err(SmhcError::InvalidTransferLength(len));
+        }
+        let arg = io_rw_extended_argument(false, function, false, increment, address, len as u16);
+        self.io_rw_extended(arg, TransferMode::Read, len as u16, len)?;
+        self.smhc.read_data(buf)
+    }
+    /// Write 1 to 512 bytes into `address` of `function` in byte mode (CMD53).
+    #[inline]
+    pub fn write_bytes(
+        &self,
+        function: u8,
+        address: u32,
+        increment: bool,
+        buf: &[u8],
+    ) -> Result<(), SmhcError> {
+        let len = buf.len();
+        if !(1..=512).contains(&len) {
+            return Err(SmhcError::InvalidTransferLength(len));
+        }
+        let arg = io_rw_extended_argument(true, function, false, increment, address, len as u16);
+        self.io_rw_extended(arg, TransferMode::Write, len as u16, len)?;
+        self.smhc.write_data(buf)?;
+        self.smhc.wait_data_complete()
+    }
+    /// Read blocks of `block_size` bytes from `address` of `function` in block mode (CMD53).
+    ///
+    /// Block size must be set on the card with [`set_block_size`](Self::set_block_size)
+    /// before. Length of `buf` is 1 to 511 whole blocks.
+    #[inline]
+    pub fn read_blocks(
+        &self,
+        function: u8,
+        address: u32,
+        increment: bool,
+        block_size: u16,
+        buf: &mut [u8],
+    ) -> Result<(), SmhcError> {
+        let count = Self::block_count(block_size, buf.len())?;
+        let arg = io_rw_extended_argument(false, function, true, increment, address, count);
+        self.io_rw_extended(arg, TransferMode::Read, block_size, buf.len())?;
+        self.smhc.read_data(buf)
+    }
+    /// Write blocks of `block_size` bytes into `address` of `function` in block mode (CMD53).
+    #[inline]
+    pub fn write_blocks(
+        &self,
+        function: u8,
+        address: u32,
+        increment: bool,
+        block_size: u16,
+        buf: &[u8],
+    ) -> Result<(), SmhcError> {
+        let count = Self::block_count(block_size, buf.len())?;
+        let arg = io_rw_extended_argument(true, function, true, increment, address, count);
+        self.io_rw_extended(arg, TransferMode::Write, block_size, buf.len())?;
+        self.smhc.write_data(buf)?;
+        self.smhc.wait_data_complete()
+    }
+    /// Wait until the card signals an SDIO interrupt, then clear it.
+    ///
+    /// In 1-bit mode DAT1 is a dedicated interrupt line, so the interrupt
+    /// is taken as soon as it is flagged. In 4-bit mode DAT1 carries data
+    /// during transfers and the interrupt is only valid while the data lines
+    /// are idle; an interrupt flagged while the data state machine is busy is
+    /// discarded. As the card holds its interrupt until it is serviced, a real
+    /// interrupt is flagged again after the transfer.
+    #[inline]
+    pub fn wait_sdio_interrupt(&self) {
+        let smhc = self.smhc.smhc.as_ref();
+        let four_bit = !matches!(smhc.card_type.read().bus_width(), BusWidth::OneBit);
+        loop {
+            if smhc
+                .interrupt_state_raw
+                .read()
+                .has_interrupt(Interrupt::Sdio)
+            {
+                let valid = !(four_bit && smhc.status.read().data_fsm_busy());
+                unsafe {
+                    smhc.interrupt_state_raw
+                        .write(InterruptStateRaw::default().clear_interrupt(Interrupt::Sdio))
+                };
+                if valid {
+                    return;
+                }
+            }
+            core::hint::spin_loop();
+        }
+    }
+    #[inline]
+    fn io_rw_direct(&self, arg: u32) -> Result<u8, SmhcError> {
+        self.smhc
+            .send_card_command(52, arg, TransferMode::Disable, ResponseMode::Short, true);
+        self.smhc.wait_command_accepted()?;
+        let response = self.smhc.read_response();
+        if response as u32 & R5_ERROR_FLAGS != 0 {
+            return Err(SmhcError::UnexpectedResponse(52, response));
+        }
+        Ok(response as u8)
+    }
+    #[inline]
+    fn io_rw_extended(
+        &self,
+        arg: u32,
+        transfer_mode: TransferMode,
+        block_size: u16,
+        byte_count: usize,
+    ) -> Result<(), SmhcError> {
+        let layout = DataLayout {
+            block_size,
+            byte_count: byte_count as u32,
+            auto_stop: false,
+            auto_cmd23: None,
+            access_mode: AccessMode::Ahb,
+        };
+        self.smhc.wait_card_ready()?;
+        self.smhc
+            .send_data_command(53, arg, transfer_mode, ResponseMode::Short, true, layout);
+        self.smhc.wait_command_accepted()?;
+        let response = self.smhc.read_response();
+        if response as u32 & R5_ERROR_FLAGS != 0 {
+            return Err(SmhcError::UnexpectedResponse(53, response));
+        }
+        Ok(())
+    }
+    #[inline]
+    fn block_count(block_size: u16, len: usize) -> Result<u16, SmhcError> {
+        if block_size == 0 || block_size > 2048 || !len.is_multiple_of(block_size as usize) {
+            return Err(SmhcError::InvalidTransferLength(len));
+        }
+        match len / block_size as usize {
+            count @ 1..=511 => Ok(count as u16),
+            _ => Err(SmhcError::InvalidTransferLength(len)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::{
+        card_clock_divider, io_rw_direct_argument, io_rw_extended_argument, lock_unlock_block,
+        switch_argument, widest_run_center, widest_window_center, Emmc, ExtCsd, SdCard, SdioCard,
+        Smhc, LOCK_UNLOCK_BLOCK_MAX,
+    };
+    use crate::smhc::{
+        IdmacDescriptor, LockOp, MultiBlockMode, PowerCycleError, RegisterBlock, SmhcError,
+        TimeUnit, TransferDirection,
+    };
+    use core::sync::atomic::{AtomicU32, Ordering};
+    use embedded_sdmmc::{Block, BlockDevice, BlockIdx};
+
+    /// Register block backed by plain memory.
+    struct MockSmhc<'a>(&'a [AtomicU32; 0x81]);
+
+    impl AsRef<RegisterBlock> for MockSmhc<'_> {
+        fn as_ref(&self) -> &RegisterBlock {
+            unsafe { &*(self.0 as *const _ as *const RegisterBlock) }
+        }
+    }
+
+    fn memory() -> [AtomicU32; 0x81] {
+        [const { AtomicU32::new(0) }; 0x81]
+    }
+
+    /// Emulate controller clearing command start bit, returning the command seen.
+    ///
+    /// Transferred byte counters report the whole byte count as moved.
+    fn complete_command(memory: &[AtomicU32; 0x81]) -> u32 {
+        loop {
+            let cmd = memory[0x18 / 4].load(Ordering::SeqCst);
+            if cmd & (1 << 31) != 0 {
+                let byte_count = memory[0x14 / 4].load(Ordering::SeqCst);
+                memory[0x48 / 4].store(byte_count, Ordering::SeqCst);
+                memory[0x4C / 4].store(byte_count, Ordering::SeqCst);
+                memory[0x18 / 4].store(cmd & !(1 << 31), Ordering::SeqCst);
+                return cmd;
+            }
+            std::thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn set_timeouts_registers() {
+        let memory = memory();
+        let smhc = Smhc {
+            smhc: MockSmhc(&memory),
+            pads: (),
+            module_clock: 20_000_000,
+        };
+        smhc.set_timeouts(0x123456, 0x40, TimeUnit::Clock256);
+        assert_eq!(memory[0x08 / 4].load(Ordering::SeqCst), 0x12345640);
+        assert_eq!(memory[0x00].load(Ordering::SeqCst), 0x00001800);
+
+        smhc.set_timeouts(0xFFFFFF, 0xFF, TimeUnit::Clock1);
+        assert_eq!(memory[0x08 / 4].load(Ordering::SeqCst), 0xFFFFFFFF);
+        assert_eq!(memory[0x00].load(Ordering::SeqCst), 0x00000000);
+    }
+
+    struct MockPad<'a>(&'a mut [bool; 2], usize);
+
+    impl embedded_hal::digital::ErrorType for MockPad<'_> {
+        type Error = core::convert::Infallible;
+    }
+
+    impl embedded_hal::digital::OutputPin for MockPad<'_> {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.0[self.1] = false;
+            self.1 += 1;
+            Ok(())
+        }
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.0[self.1] = true;
+            self.1 += 1;
+            Ok(())
+        }
+    }
+
+    struct MockDelay(u32);
+
+    impl embedded_hal::delay::DelayNs for MockDelay {
+        fn delay_ns(&mut self, ns: u32) {
+            self.0 += ns;
+        }
+    }
+
+    #[test]
+    fn power_cycle_sequence() {
+        let memory = memory();
+        let mut smhc = Smhc {
+            smhc: MockSmhc(&memory),
+            pads: (),
+            module_clock: 20_000_000,
+        };
+        let mut levels = [true, false];
+        let mut pad = MockPad(&mut levels, 0);
+        let mut delay = MockDelay(0);
+        let cmd = std::thread::scope(|s| {
+            let hardware = s.spawn(|| complete_command(&memory));
+            smhc.power_cycle(Some(&mut pad), &mut delay).unwrap();
+            hardware.join().unwrap()
+        });
+        assert_eq!(levels, [false, true]);
+        assert!(delay.0 >= 2_000_000);
+        // send init sequence, wait for complete, command index 0
+        assert_eq!(cmd, 0x8000_a000);
+        assert_eq!(memory[0x18 / 4].load(Ordering::SeqCst), 0x0000_a000);
+    }
+
+    #[test]
+    fn controller_waits_time_out() {
+        let memory = memory();
+        let mut smhc = Smhc {
+            smhc: MockSmhc(&memory),
+            pads: (),
+            module_clock: 20_000_000,
+        };
+        // command start bit is never cleared
+        memory[0x18 / 4].store(1 << 31, Ordering::SeqCst);
+        assert_eq!(
+            smhc.set_card_clock(400_000),
+            Err(SmhcError::ResponseTimeout)
+        );
+        let ans = smhc.power_cycle(None::<&mut MockPad>, &mut MockDelay(0));
+        assert_eq!(ans, Err(PowerCycleError::Smhc(SmhcError::ResponseTimeout)));
+        memory[0x18 / 4].store(0, Ordering::SeqCst);
+
+        // FIFO never has data, then never has space
+        memory[0x3C / 4].store(1 << 2, Ordering::SeqCst);
+        assert_eq!(smhc.read_data(&mut [0; 4]), Err(SmhcError::DataTimeout));
+        memory[0x3C / 4].store(1 << 3, Ordering::SeqCst);
+        assert_eq!(smhc.write_data(&[0; 4]), Err(SmhcError::DataTimeout));
+
+        // data transfer never completes
+        assert_eq!(smhc.wait_data_complete(), Err(SmhcError::DataTimeout));
+    }
+
+    #[test]
+    fn lock_unlock_data_block() {
+        let mut buf = [0u8; LOCK_UNLOCK_BLOCK_MAX];
+        let cases: [(LockOp, &[u8], &[u8]); 5] = [
+            (LockOp::SetPassword, b"oldnew", b"\x01\x06oldnew"),
+            (LockOp::ClearPassword, b"pw", b"\x02\x02pw"),
+            (LockOp::Lock, b"pw", b"\x04\x02pw"),
+            (LockOp::Unlock, b"pw", b"\x00\x02pw"),
+            (LockOp::ForceErase, b"", b"\x08"),
+        ];
+        for (op, password, expected) in cases {
+            let len = lock_unlock_block(op, password, &mut buf).unwrap();
+            assert_eq!(&buf[..len], expected, "{:?}", op);
+        }
+        assert!(lock_unlock_block(LockOp::SetPassword, &[0xAA; 32], &mut buf).is_ok());
+        assert!(matches!(
+            lock_unlock_block(LockOp::SetPassword, &[0xAA; 33], &mut buf),
+            Err(SmhcError::InvalidPasswordLength(33))
+        ));
+        assert!(matches!(
+            lock_unlock_block(LockOp::Unlock, &[0xAA; 17], &mut buf),
+            Err(SmhcError::InvalidPasswordLength(17))
+        ));
+        assert!(matches!(
+            lock_unlock_block(LockOp::Lock, b"", &mut buf),
+            Err(SmhcError::InvalidPasswordLength(0))
+        ));
+    }
+
+    #[test]
+    fn lock_unlock_command() {
+        let memory = memory();
+        let mut smhc = Smhc {
+            smhc: MockSmhc(&memory),
+            pads: (),
+            module_clock: 20_000_000,
+        };
+        let mut card = SdCard {
+            smhc: &mut smhc,
+            rca: 0,
+            block_count: 0,
+        };
+        // card status: ready for data, transfer state; data transfer complete
+        memory[0x20 / 4].store(0x0000_0900, Ordering::SeqCst);
+        memory[0x38 / 4].store(1 << 3, Ordering::SeqCst);
+        let (status, commands) = std::thread::scope(|s| {
+            let hardware = s.spawn(|| {
+                let set_block_len = complete_command(&memory);
+                let block_len = memory[0x1C / 4].load(Ordering::SeqCst);
+                let lock_unlock = complete_command(&memory);
+                let byte_count = memory[0x14 / 4].load(Ordering::SeqCst);
+                let fifo = loop {
+                    match memory[0x200 / 4].load(Ordering::SeqCst) {
+                        0 => std::thread::yield_now(),
+                        word => break word,
+                    }
+                };
+                complete_command(&memory);
+                [set_block_len, block_len, lock_unlock, byte_count, fifo]
+            });
+            let status = card.lock_unlock(LockOp::Unlock, b"pw").unwrap();
+            (status, hardware.join().unwrap())
+        });
+        assert_eq!(status, 0x0000_0900);
+        let [set_block_len, block_len, lock_unlock, byte_count, fifo] = commands;
+        assert_eq!(set_block_len & 0x3F, 16);
+        assert_eq!(block_len, 4);
+        // command index 42, data transfer, write direction
+        assert_eq!(lock_unlock & 0x3F, 42);
+        assert_ne!(lock_unlock & (1 << 9), 0);
+        assert_ne!(lock_unlock & (1 << 10), 0);
+        assert_eq!(byte_count, 4);
+        assert_eq!(fifo, u32::from_le_bytes(*b"\x00\x02pw"));
+        assert_eq!(memory[0x10 / 4].load(Ordering::SeqCst), 4);
+        assert_eq!(memory[0x1C / 4].load(Ordering::SeqCst), 512);
+
+        // card reports the operation failed
+        memory[0x20 / 4].store(0x0100_0900, Ordering::SeqCst);
+        let ans = std::thread::scope(|s| {
+            s.spawn(|| {
+                for _ in 0..3 {
+                    complete_command(&memory);
+                }
+            });
+            card.lock_unlock(LockOp::Unlock, b"pw")
+        });
+        assert_eq!(ans, Err(SmhcError::UnexpectedResponse(42, 0x0100_0900)));
+        assert_eq!(memory[0x1C / 4].load(Ordering::SeqCst), 512);
+
+        // block length is restored after a data error; arguments of CMD16, CMD42, CMD16
+        memory[0x38 / 4].store(1 << 7, Ordering::SeqCst);
+        let (ans, arguments) = std::thread::scope(|s| {
+            let hardware = s.spawn(|| {
+                [0; 3].map(|_| {
+                    complete_command(&memory);
+                    memory[0x1C / 4].load(Ordering::SeqCst)
+                })
+            });
+            let ans = card.lock_unlock(LockOp::Lock, b"pw");
+            (ans, hardware.join().unwrap())
+        });
+        assert_eq!(ans, Err(SmhcError::DataCrcError));
+        assert_eq!(arguments, [4, 0, 512]);
+    }
+
+    #[test]
+    fn wait_dma_errors() {
+        let memory = memory();
+        let smhc = Smhc {
+            smhc: MockSmhc(&memory),
+            pads: (),
+            module_clock: 20_000_000,
+        };
+        let cases = [
+            (1 << 4, Err(SmhcError::DescriptorUnavailable)),
+            (1 << 2, Err(SmhcError::FatalBusError)),
+            (1 << 5, Err(SmhcError::CardError)),
+            (1 << 1, Ok(())),
+            // error is reported even if reception is also flagged
+            ((1 << 1) | (1 << 4), Err(SmhcError::DescriptorUnavailable)),
+        ];
+        for (state, expected) in cases {
+            memory[0x88 / 4].store(state, Ordering::SeqCst);
+            assert_eq!(smhc.wait_dma(TransferDirection::Read), expected);
+            // flags are cleared by writing ones
+            assert_eq!(memory[0x88 / 4].load(Ordering::SeqCst) & 0x337, 0x337);
+        }
+        memory[0x88 / 4].store(1 << 0, Ordering::SeqCst);
+        assert_eq!(smhc.wait_dma(TransferDirection::Write), Ok(()));
+        // nothing is ever flagged
+        memory[0x88 / 4].store(0, Ordering::SeqCst);
+        let ans = smhc.wait_dma(TransferDirection::Read);
+        assert_eq!(ans, Err(SmhcError::DataTimeout));
+    }
+
+    #[test]
+    fn dma_block_reads() {
+        let memory = memory();
+        let mut smhc = Smhc {
+            smhc: MockSmhc(&memory),
+            pads: (),
+            module_clock: 20_000_000,
+        };
+        let mut buf = [[0u8; 512]; 10];
+        let mut descriptors = [IdmacDescriptor::new(); 2];
+        let (cmd, global, control) = std::thread::scope(|s| {
+            let hardware = s.spawn(|| {
+                let cmd = complete_command(&memory);
+                let global = memory[0].load(Ordering::SeqCst);
+                let control = memory[0x80 / 4].load(Ordering::SeqCst);
+                memory[0x38 / 4].store(0, Ordering::SeqCst);
+                memory[0x88 / 4].store(1 << 1, Ordering::SeqCst);
+                (cmd, global, control)
+            });
+            smhc.read_blocks_dma(7, &mut buf, &mut descriptors, MultiBlockMode::AutoStop)
+                .unwrap();
+            hardware.join().unwrap()
+        });
+        // command index 18, data transfer, read direction, auto stop
+        assert_eq!(cmd & 0x3F, 18);
+        assert_ne!(cmd & (1 << 9), 0);
+        assert_eq!(cmd & (1 << 10), 0);
+        assert_ne!(cmd & (1 << 12), 0);
+        assert_eq!(memory[0x14 / 4].load(Ordering::SeqCst), 5120);
+        assert_eq!(memory[0x1C / 4].load(Ordering::SeqCst), 7);
+        // DMA access mode and IDMAC enabled with fixed burst during transfer
+        assert_eq!(global & (1 << 31), 0);
+        assert_ne!(global & (1 << 5), 0);
+        assert_eq!(control, (1 << 7) | (1 << 1));
+        assert_eq!(
+            memory[0x84 / 4].load(Ordering::SeqCst),
+            (descriptors.as_ptr() as usize >> 2) as u32
+        );
+        assert_eq!(descriptors[0].size(), 4096);
+        assert_eq!(descriptors[1].size(), 1024);
+        assert_eq!(descriptors[0].buffer(), (buf.as_ptr() as usize >> 2) as u32);
+        // DMA is disabled and AHB access restored afterwards
+        let global = memory[0].load(Ordering::SeqCst);
+        assert_ne!(global & (1 << 31), 0);
+        assert_eq!(global & (1 << 5), 0);
+        assert_eq!(memory[0x80 / 4].load(Ordering::SeqCst), 0);
+
+        // IDMAC is reset on error
+        let result = std::thread::scope(|s| {
+            s.spawn(|| {
+                complete_command(&memory);
+                memory[0x88 / 4].store(1 << 2, Ordering::SeqCst);
+            });
+            smhc.read_blocks_dma(7, &mut buf[..1], &mut descriptors, MultiBlockMode::AutoStop)
+        });
+        assert_eq!(result, Err(SmhcError::FatalBusError));
+        assert_eq!(memory[0x80 / 4].load(Ordering::SeqCst), 1 << 0);
+
+        assert_eq!(
+            smhc.read_blocks_dma(0, &mut buf, &mut descriptors[..1], MultiBlockMode::AutoStop),
+            Err(SmhcError::DescriptorsTooShort)
+        );
+        let mut bytes = [0u32; 129];
+        let misaligned = unsafe {
+            core::slice::from_raw_parts_mut(
+                (bytes.as_mut_ptr() as *mut u8).add(1) as *mut [u8; 512],
+                1,
+            )
+        };
+        assert_eq!(
+            smhc.read_blocks_dma(0, misaligned, &mut descriptors, MultiBlockMode::AutoStop),
+            Err(SmhcError::MisalignedBuffer)
+        );
+    }
+
+    #[test]
+    fn transferred_byte_counts() {
+        let memory = memory();
+        let mut smhc = Smhc {
+            smhc: MockSmhc(&memory),
+            pads: (),
+            module_clock: 20_000_000,
+        };
+        let mut buf = [[0u8; 512]; 4];
+        let mut descriptors = [IdmacDescriptor::new(); 1];
+        // IDMAC finishes early, after three of four blocks reached memory
+        let result = std::thread::scope(|s| {
+            s.spawn(|| {
+                complete_command(&memory);
+                memory[0x48 / 4].store(2048, Ordering::SeqCst);
+                memory[0x4C / 4].store(1536, Ordering::SeqCst);
+                memory[0x88 / 4].store(1 << 1, Ordering::SeqCst);
+            });
+            smhc.read_blocks_dma(0, &mut buf, &mut descriptors, MultiBlockMode::AutoStop)
+        });
+        assert_eq!(result, Err(SmhcError::ShortTransfer));
+        assert_eq!(smhc.transferred_bytes(), (2048, 1536));
+        // IDMAC is reset as on other errors
+        assert_eq!(memory[0x80 / 4].load(Ordering::SeqCst), 1 << 0);
+
+        // counters matching the requested length
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                complete_command(&memory);
+                memory[0x88 / 4].store(1 << 1, Ordering::SeqCst);
+            });
+            smhc.read_blocks_dma(0, &mut buf, &mut descriptors, MultiBlockMode::AutoStop)
+                .unwrap();
+        });
+        assert_eq!(smhc.transferred_bytes(), (2048, 2048));
+
+        // write completes with one block still unsent to the card
+        let blocks = [[0u8; 512]; 2];
+        memory[0x38 / 4].store(1 << 3, Ordering::SeqCst);
+        let result = std::thread::scope(|s| {
+            s.spawn(|| {
+                while memory[0x18 / 4].load(Ordering::SeqCst) & (1 << 31) == 0 {
+                    std::thread::yield_now();
+                }
+                memory[0x48 / 4].store(512, Ordering::SeqCst);
+                memory[0x4C / 4].store(1024, Ordering::SeqCst);
+                memory[0x18 / 4].fetch_and(!(1 << 31), Ordering::SeqCst);
+            });
+            smhc.write_blocks(100, &blocks, MultiBlockMode::AutoStop)
+        });
+        assert_eq!(result, Err(SmhcError::ShortTransfer));
+        assert_eq!(smhc.transferred_bytes(), (512, 1024));
+    }
+
+    #[test]
+    fn sample_delay_calibration() {
+        assert_eq!(
+            widest_run_center(&[false, true, true, true, false]),
+            Some(2)
+        );
+        assert_eq!(widest_run_center(&[true, true, false, true, true]), Some(0));
+        assert_eq!(
+            widest_run_center(&[true, false, true, true, true, true]),
+            Some(3)
+        );
+        assert_eq!(widest_run_center(&[true; 64]), Some(31));
+        assert_eq!(widest_run_center(&[false; 64]), None);
+
+        let memory = memory();
+        let mut smhc = Smhc {
+            smhc: MockSmhc(&memory),
+            pads: (),
+            module_clock: 200_000_000,
+        };
+        // hardware calibration is done with a delay of 0x15
+        memory[0x144 / 4].store(0x0000_5585, Ordering::SeqCst);
+        let delay = smhc.calibrate_sample_delay();
+        assert_eq!(delay, Some(0x15));
+        assert_eq!(memory[0x144 / 4].load(Ordering::SeqCst) & 0x80FF, 0x95);
+    }
+
+    #[test]
+    fn card_clock_dividers() {
+        assert_eq!(card_clock_divider(20_000_000, 400_000), 25);
+        assert_eq!(card_clock_divider(24_000_000, 400_000), 30);
+        assert_eq!(card_clock_divider(25_000_000, 400_000), 32);
+        assert_eq!(card_clock_divider(20_000_000, 10_000_000), 1);
+        assert_eq!(card_clock_divider(20_000_000, 50_000_000), 0);
+        assert_eq!(card_clock_divider(600_000_000, 400_000), 255);
+    }
+
+    #[test]
+    fn sd_card_init_clock() {
+        let memory = memory();
+        let mut smhc = Smhc {
+            smhc: MockSmhc(&memory),
+            pads: (),
+            module_clock: 20_000_000,
+        };
+        memory[0x3C / 4].store(1 << 8, Ordering::SeqCst);
+        let (block_count, log) = std::thread::scope(|s| {
+            let hardware = s.spawn(|| {
+                let mut log = std::vec::Vec::new();
+                while log.len() < 14 {
+                    let cmd = memory[0x18 / 4].load(Ordering::SeqCst);
+                    if cmd & (1 << 31) == 0 {
+                        std::thread::yield_now();
+                        continue;
+                    }
+                    // update-clock commands carry no response
+                    if cmd & (1 << 21) == 0 {
+                        let response = match cmd & 0x3F {
+                            8 => [0x1AA, 0, 0, 0],
+                            41 => [0x80FF_8000, 0, 0, 0],
+                            3 => [0x1234_0000, 0, 0, 0],
+                            9 => [0, 0, 0, 0x4000_0000],
+                            _ => [0; 4],
+                        };
+                        for (i, word) in response.into_iter().enumerate() {
+                            memory[0x20 / 4 + i].store(word, Ordering::SeqCst);
+                        }
+                    }
+                    log.push((cmd, memory[0x04 / 4].load(Ordering::SeqCst)));
+                    memory[0x18 / 4].store(cmd & !(1 << 31), Ordering::SeqCst);
+                }
+                log
+            });
+            let card = SdCard::new(&mut smhc).unwrap();
+            (card.block_count, hardware.join().unwrap())
+        });
+        assert_eq!(block_count, 1024);
+
+        // update-clock: start, wait for complete and change clock, no command index
+        const UPDATE_CLOCK: u32 = 0x8020_2000;
+        let clocks: std::vec::Vec<_> = log.iter().map(|&(_, clock)| clock).collect();
+        let commands: std::vec::Vec<_> = log
+            .iter()
+            .map(|&(cmd, _)| (cmd != UPDATE_CLOCK).then_some(cmd & 0x3F))
+            .collect();
+        // card clock stopped, then started at 20 MHz / (2 * 25) = 400 kHz
+        assert_eq!(&commands[..2], [None, None]);
+        assert_eq!(clocks[0] & (1 << 16), 0);
+        assert_eq!(clocks[1], 0x0001_0019);
+        // identification runs entirely at 400 kHz
+        assert_eq!(&commands[2..10], [0, 8, 55, 41, 2, 3, 9, 7].map(Some));
+        assert!(clocks[2..10].iter().all(|&clock| clock == 0x0001_0019));
+        // then switch to 20 MHz / (2 * 1) = 10 MHz
+        assert_eq!(&commands[10..12], [None, None]);
+        assert_eq!(clocks[10], 0x0000_0019);
+        assert_eq!(clocks[11], 0x0001_0001);
+        assert_eq!(&commands[12..], [55, 6].map(Some));
+        assert!(clocks[12..].iter().all(|&clock| clock == 0x0001_0001));
+    }
+
+    #[test]
+    fn sd_card_init_errors() {
+        let memory = memory();
+        let mut smhc = Smhc {
+            smhc: MockSmhc(&memory),
+            pads: (),
+            module_clock: 20_000_000,
+        };
+        assert_eq!(SdCard::new(&mut smhc).err(), Some(SmhcError::NoCard));
+
+        // CMD8 is answered by `response` with raw interrupt state `state`
+        let cases = [
+            (0x1AB, 0, SmhcError::VoltageMismatch),
+            (0, 1 << 8, SmhcError::ResponseTimeout),
+            (0x1AA, 1 << 6, SmhcError::ResponseCrcError),
+        ];
+        memory[0x3C / 4].store(1 << 8, Ordering::SeqCst);
+        for (response, state, expected) in cases {
+            memory[0x38 / 4].store(0, Ordering::SeqCst);
+            let result = std::thread::scope(|s| {
+                s.spawn(|| {
+                    // two update-clock commands, CMD0, then CMD8
+                    for _ in 0..4 {
+                        let cmd = complete_command(&memory);
+                        if cmd & 0x3F == 8 && cmd & (1 << 21) == 0 {
+                            memory[0x20 / 4].store(response, Ordering::SeqCst);
+                            memory[0x38 / 4].store(state, Ordering::SeqCst);
+                        }
+                    }
+                });
+                SdCard::new(&mut smhc).err()
+            });
+            assert_eq!(result, Some(expected));
+        }
+        // response error flag is cleared by writing one
+        assert_eq!(memory[0x38 / 4].load(Ordering::SeqCst), 1 << 6);
+    }
+
+    #[test]
+    fn card_detect() {
+        let memory = memory();
+        let smhc = Smhc {
+            smhc: MockSmhc(&memory),
+            pads: (),
+            module_clock: 20_000_000,
+        };
+        assert!(!smhc.is_card_present());
+        memory[0x3C / 4].store(1 << 8, Ordering::SeqCst);
+        assert!(smhc.is_card_present());
+
+        // no insertion until timeout
+        let mut polls = 0;
+        assert!(!smhc.wait_for_card_insertion(|| {
+            polls += 1;
+            polls == 3
+        }));
+        assert_eq!(polls, 3);
+
+        // card inserted while waiting
+        let mut polls = 0;
+        assert!(smhc.wait_for_card_insertion(|| {
+            polls += 1;
+            if polls == 2 {
+                memory[0x38 / 4].store(1 << 30, Ordering::SeqCst);
+            }
+            false
+        }));
+        assert_eq!(polls, 2);
+        // insertion flag is cleared by writing one
+        assert_eq!(memory[0x38 / 4].load(Ordering::SeqCst), 1 << 30);
+    }
+
+    #[test]
+    fn read_block_data_errors() {
+        let memory = memory();
+        let mut smhc = Smhc {
+            smhc: MockSmhc(&memory),
+            pads: (),
+            module_clock: 20_000_000,
+        };
+        let card = SdCard {
+            smhc: &mut smhc,
+            rca: 0,
+            block_count: 16,
+        };
+        let cases = [
+            (1 << 7, SmhcError::DataCrcError),
+            (1 << 9, SmhcError::DataTimeout),
+            (1 << 15, SmhcError::DataEndBitError),
+        ];
+        for (bit, expected) in cases {
+            // FIFO stays empty; error is raised after the read command is accepted
+            memory[0x3C / 4].store(1 << 2, Ordering::SeqCst);
+            memory[0x38 / 4].store(0, Ordering::SeqCst);
+            let mut blocks = [Block::new(), Block::new()];
+            let (result, cmd) = std::thread::scope(|s| {
+                let hardware = s.spawn(|| {
+                    let cmd = complete_command(&memory);
+                    // data transfer complete is flagged along with the error
+                    memory[0x38 / 4].store(bit | (1 << 3), Ordering::SeqCst);
+                    cmd
+                });
+                let result = card.read_blocks(&mut blocks, 5);
+                (result, hardware.join().unwrap())
+            });
+            assert_eq!(cmd & 0x3F, 17);
+            assert_eq!(memory[0x1C / 4].load(Ordering::SeqCst), 5);
+            assert_eq!(result, Err(expected));
+            // only the error flag is cleared by writing one
+            assert_eq!(memory[0x38 / 4].load(Ordering::SeqCst), bit);
+
+            // error reported after the whole block is received
+            memory[0x3C / 4].store(0, Ordering::SeqCst);
+            memory[0x38 / 4].store(bit, Ordering::SeqCst);
+            let result = std::thread::scope(|s| {
+                s.spawn(|| complete_command(&memory));
+                card.read_block(&mut blocks[0], 7)
+            });
+            assert_eq!(result, Err(expected));
+        }
+
+        // block received, but counters show data missing
+        memory[0x38 / 4].store(0, Ordering::SeqCst);
+        let result = std::thread::scope(|s| {
+            s.spawn(|| {
+                while memory[0x18 / 4].load(Ordering::SeqCst) & (1 << 31) == 0 {
+                    std::thread::yield_now();
+                }
+                memory[0x48 / 4].store(256, Ordering::SeqCst);
+                memory[0x4C / 4].store(512, Ordering::SeqCst);
+                memory[0x18 / 4].fetch_and(!(1 << 31), Ordering::SeqCst);
+            });
+            card.read_block(&mut Block::new(), 7)
+        });
+        assert_eq!(result, Err(SmhcError::ShortTransfer));
+    }
+
+    #[test]
+    fn block_device_write() {
+        let memory = memory();
+        let mut smhc = Smhc {
+            smhc: MockSmhc(&memory),
+            pads: (),
+            module_clock: 20_000_000,
+        };
+        let card = SdCard {
+            smhc: &mut smhc,
+            rca: 0,
+            block_count: 16,
+        };
+        let mut blocks = [Block::new(), Block::new()];
+        blocks[1].contents[508..].copy_from_slice(&[0xAA, 0xBB, 0xCC, 0xDD]);
+        // data transfer complete is flagged for every block
+        memory[0x38 / 4].store(1 << 3, Ordering::SeqCst);
+        let commands = std::thread::scope(|s| {
+            let hardware = s.spawn(|| {
+                let mut commands = std::vec::Vec::new();
+                for _ in 0..2 {
+                    let cmd = complete_command(&memory);
+                    commands.push((cmd & 0x3F, memory[0x1C / 4].load(Ordering::SeqCst)));
+                }
+                commands
+            });
+            card.write(&blocks, BlockIdx(7)).unwrap();
+            hardware.join().unwrap()
+        });
+        // one CMD24 per block, each of one block length
+        assert_eq!(commands, [(24, 7), (24, 8)]);
+        assert_eq!(memory[0x14 / 4].load(Ordering::SeqCst), 512);
+        assert_eq!(memory[0x200 / 4].load(Ordering::SeqCst), 0xDDCC_BBAA);
+
+        // card still busy from a previous write
+        memory[0x3C / 4].store(1 << 9, Ordering::SeqCst);
+        let result = card.write(&blocks, BlockIdx(7));
+        assert_eq!(result, Err(SmhcError::BusyTimeout));
+    }
+
+    #[test]
+    fn card_busy_before_data_command() {
+        let memory = memory();
+        let mut smhc = Smhc {
+            smhc: MockSmhc(&memory),
+            pads: (),
+            module_clock: 20_000_000,
+        };
+        // card holds DAT0 low: no command is issued
+        memory[0x3C / 4].store(1 << 9, Ordering::SeqCst);
+        let mut blocks = [[0u8; 512]; 2];
+        assert_eq!(smhc.wait_card_ready(), Err(SmhcError::BusyTimeout));
+        assert_eq!(
+            smhc.read_blocks(3, &mut blocks, MultiBlockMode::AutoStop),
+            Err(SmhcError::BusyTimeout)
+        );
+        assert_eq!(
+            smhc.write_blocks(3, &blocks, MultiBlockMode::AutoStop),
+            Err(SmhcError::BusyTimeout)
+        );
+        let mut block = Block::new();
+        let mut card = SdCard {
+            smhc: &mut smhc,
+            rca: 0,
+            block_count: 0,
+        };
+        assert_eq!(card.read_block(&mut block, 3), Err(SmhcError::BusyTimeout));
+        let ans = card.lock_unlock(LockOp::Unlock, b"pw");
+        assert_eq!(ans, Err(SmhcError::BusyTimeout));
+        let emmc = Emmc::new(&mut smhc, 1);
+        assert_eq!(emmc.read_ext_csd().err(), Some(SmhcError::BusyTimeout));
+        let sdio = SdioCard {
+            smhc: &mut smhc,
+            functions: 1,
+        };
+        let ans = sdio.read_bytes(1, 0, true, &mut [0; 4]);
+        assert_eq!(ans, Err(SmhcError::BusyTimeout));
+        let ans = sdio.write_blocks(1, 0, true, 4, &[0; 8]);
+        assert_eq!(ans, Err(SmhcError::BusyTimeout));
+        assert_eq!(memory[0x18 / 4].load(Ordering::SeqCst), 0);
+
+        // busy cleared: the read proceeds
+        memory[0x3C / 4].store(0, Ordering::SeqCst);
+        assert_eq!(smhc.wait_card_ready(), Ok(()));
+        memory[0x200 / 4].store(0x0403_0201, Ordering::SeqCst);
+        let (result, cmd) = std::thread::scope(|s| {
+            let hardware = s.spawn(|| complete_command(&memory));
+            let result = smhc.read_blocks(3, &mut blocks[..1], MultiBlockMode::AutoStop);
+            (result, hardware.join().unwrap())
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(cmd & 0x3F, 17);
+        assert_eq!(&blocks[0][..4], [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn sd_card_sync() {
+        let memory = memory();
+        let mut smhc = Smhc {
+            smhc: MockSmhc(&memory),
+            pads: (),
+            module_clock: 20_000_000,
+        };
+        let mut card = SdCard {
+            smhc: &mut smhc,
+            rca: 0x1234_0000,
+            block_count: 16,
+        };
+        // card is receiving data, then programming twice, then in transfer state
+        let responses = [
+            0x0000_0C00,
+            0x0000_0000,
+            0x0000_0E00,
+            0x0000_0E00,
+            0x0000_0900,
+        ];
+        let (result, commands) = std::thread::scope(|s| {
+            let hardware = s.spawn(|| {
+                responses.map(|response| {
+                    let cmd = loop {
+                        let cmd = memory[0x18 / 4].load(Ordering::SeqCst);
+                        if cmd & (1 << 31) != 0 {
+                            break cmd;
+                        }
+                        std::thread::yield_now();
+                    };
+                    let argument = memory[0x1C / 4].load(Ordering::SeqCst);
+                    memory[0x20 / 4].store(response, Ordering::SeqCst);
+                    memory[0x18 / 4].store(cmd & !(1 << 31), Ordering::SeqCst);
+                    (cmd & 0x3F, argument)
+                })
+            });
+            let result = card.sync();
+            (result, hardware.join().unwrap())
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            commands,
+            [
+                (13, 0x1234_0000),
+                (12, 0),
+                (13, 0x1234_0000),
+                (13, 0x1234_0000),
+                (13, 0x1234_0000)
+            ]
+        );
+
+        // card holding DAT0 busy is not polled with CMD13
+        memory[0x3C / 4].store(1 << 9, Ordering::SeqCst);
+        assert_eq!(card.sync(), Err(SmhcError::BusyTimeout));
+        memory[0x3C / 4].store(0, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn ext_csd_decode() {
+        let mut raw = [0u8; 512];
+        // 8 GiB device, revision 1.8, HS200 and DDR capable, 8-bit bus
+        raw[179] = 0x48;
+        raw[183] = 2;
+        raw[185] = 1;
+        raw[192] = 8;
+        raw[196] = 0x57;
+        raw[212..216].copy_from_slice(&[0x00, 0x00, 0xE9, 0x00]);
+        let ext_csd = ExtCsd::new(raw);
+        assert_eq!(ext_csd.partition_config(), 0x48);
+        assert_eq!(ext_csd.bus_width(), 2);
+        assert_eq!(ext_csd.hs_timing(), 1);
+        assert_eq!(ext_csd.ext_csd_rev(), 8);
+        assert_eq!(ext_csd.card_type(), 0x57);
+        assert_eq!(ext_csd.sec_count(), 0x00E9_0000);
+        assert_eq!(ext_csd.raw(), &raw);
+    }
+
+    #[test]
+    fn emmc_switch_command() {
+        assert_eq!(switch_argument(ExtCsd::BUS_WIDTH, 2), 0x03B7_0200);
+        assert_eq!(switch_argument(ExtCsd::HS_TIMING, 1), 0x03B9_0100);
+        assert_eq!(switch_argument(ExtCsd::PARTITION_CONFIG, 0x49), 0x03B3_4900);
+
+        let memory = memory();
+        let mut smhc = Smhc {
+            smhc: MockSmhc(&memory),
+            pads: (),
+            module_clock: 20_000_000,
+        };
+        let emmc = Emmc::new(&mut smhc, 0x0001);
+        for (status, ok) in [(0x0000_0900, true), (0x0000_0980, false)] {
+            // device is busy until the hardware thread releases it
+            memory[0x3C / 4].store(1 << 9, Ordering::SeqCst);
+            let (result, commands) = std::thread::scope(|s| {
+                let hardware = s.spawn(|| {
+                    let switch = complete_command(&memory);
+                    let argument = memory[0x1C / 4].load(Ordering::SeqCst);
+                    memory[0x20 / 4].store(status, Ordering::SeqCst);
+                    memory[0x3C / 4].store(0, Ordering::SeqCst);
+                    let send_status = complete_command(&memory);
+                    let rca = memory[0x1C / 4].load(Ordering::SeqCst);
+                    [switch, argument, send_status, rca]
+                });
+                let result = emmc.write_ext_csd(ExtCsd::BUS_WIDTH, 2);
+                (result, hardware.join().unwrap())
+            });
+            let [switch, argument, send_status, rca] = commands;
+            assert_eq!(switch & 0x3F, 6);
+            assert_eq!(switch & (1 << 9), 0);
+            assert_eq!(argument, 0x03B7_0200);
+            assert_eq!(send_status & 0x3F, 13);
+            assert_eq!(rca, 0x0001_0000);
+            if ok {
+                assert!(result.is_ok());
+            } else {
+                assert!(matches!(result, Err(SmhcError::UnexpectedResponse(6, _))));
+            }
+        }
+        // device never leaves busy state
+        memory[0x3C / 4].store(1 << 9, Ordering::SeqCst);
+        let result = std::thread::scope(|s| {
+            s.spawn(|| complete_command(&memory));
+            emmc.write_ext_csd(ExtCsd::BUS_WIDTH, 2)
+        });
+        assert!(matches!(result, Err(SmhcError::BusyTimeout)));
+
+        // FIFO holds data, every read returns the same word
+        memory[0x3C / 4].store(0, Ordering::SeqCst);
+        memory[0x200 / 4].store(0x0403_0201, Ordering::SeqCst);
+        let (ext_csd, cmd) = std::thread::scope(|s| {
+            let hardware = s.spawn(|| complete_command(&memory));
+            let ext_csd = emmc.read_ext_csd().unwrap();
+            (ext_csd, hardware.join().unwrap())
+        });
+        // command index 8, data transfer, read direction
+        assert_eq!(cmd & 0x3F, 8);
+        assert_ne!(cmd & (1 << 9), 0);
+        assert_eq!(cmd & (1 << 10), 0);
+        // no auto stop, CMD8 ends after one block
+        assert_eq!(cmd & (1 << 12), 0);
+        assert_eq!(memory[0x10 / 4].load(Ordering::SeqCst), 512);
+        assert_eq!(&ext_csd.raw()[..8], [1, 2, 3, 4, 1, 2, 3, 4]);
+        assert_eq!(ext_csd.bus_width(), 4);
+        assert_eq!(ext_csd.sec_count(), 0x0403_0201);
+    }
+
+    #[test]
+    fn raw_block_transfers() {
+        let memory = memory();
+        let smhc = Smhc {
+            smhc: MockSmhc(&memory),
+            pads: (),
+            module_clock: 20_000_000,
+        };
+        // single and multiple block reads, FIFO always holds data
+        memory[0x3C / 4].store(0, Ordering::SeqCst);
+        memory[0x200 / 4].store(0x4433_2211, Ordering::SeqCst);
+        for (count, index) in [(1, 17), (3, 18)] {
+            let mut buf = [[0u8; 512]; 3];
+            let cmd = std::thread::scope(|s| {
+                let hardware = s.spawn(|| complete_command(&memory));
+                smhc.read_blocks(9, &mut buf[..count], MultiBlockMode::AutoStop)
+                    .unwrap();
+                hardware.join().unwrap()
+            });
+            assert_eq!(cmd & 0x3F, index);
+            // data transfer, read direction, auto stop
+            assert_ne!(cmd & (1 << 9), 0);
+            assert_eq!(cmd & (1 << 10), 0);
+            assert_ne!(cmd & (1 << 12), 0);
+            assert_eq!(memory[0x14 / 4].load(Ordering::SeqCst), count as u32 * 512);
+            assert_eq!(memory[0x1C / 4].load(Ordering::SeqCst), 9);
+            assert!(buf[..count]
+                .iter()
+                .all(|b| b[..4] == [0x11, 0x22, 0x33, 0x44]));
+        }
+
+        // multiple block write, completed by hardware after data is sent
+        let mut blocks = [[0u8; 512]; 2];
+        blocks[1][508..].copy_from_slice(&[0xAA, 0xBB, 0xCC, 0xDD]);
+        memory[0x38 / 4].store(0, Ordering::SeqCst);
+        let cmd = std::thread::scope(|s| {
+            let hardware = s.spawn(|| {
+                let cmd = complete_command(&memory);
+                while memory[0x200 / 4].load(Ordering::SeqCst) != 0xDDCC_BBAA {
+                    std::thread::yield_now();
+                }
+                memory[0x38 / 4].store(1 << 3, Ordering::SeqCst);
+                cmd
+            });
+            smhc.write_blocks(100, &blocks, MultiBlockMode::AutoStop)
+                .unwrap();
+            hardware.join().unwrap()
+        });
+        // command index 25, data transfer, write direction, auto stop
+        assert_eq!(cmd & 0x3F, 25);
+        assert_ne!(cmd & (1 << 9), 0);
+        assert_ne!(cmd & (1 << 10), 0);
+        assert_ne!(cmd & (1 << 12), 0);
+        assert_eq!(memory[0x14 / 4].load(Ordering::SeqCst), 1024);
+        // data transfer complete flag is cleared by writing one
+        assert_eq!(memory[0x38 / 4].load(Ordering::SeqCst), 1 << 3);
+
+        // CRC error on write
+        memory[0x38 / 4].store(1 << 7, Ordering::SeqCst);
+        let result = std::thread::scope(|s| {
+            s.spawn(|| complete_command(&memory));
+            smhc.write_blocks(100, &blocks[..1], MultiBlockMode::AutoStop)
+        });
+        assert_eq!(result, Err(SmhcError::DataCrcError));
+        assert_eq!(memory[0x18 / 4].load(Ordering::SeqCst) & 0x3F, 24);
+
+        // CRC status error while FIFO is full stops the write instead of waiting forever
+        memory[0x38 / 4].store(0, Ordering::SeqCst);
+        memory[0x3C / 4].store(1 << 3, Ordering::SeqCst);
+        memory[0x200 / 4].store(0, Ordering::SeqCst);
+        let result = std::thread::scope(|s| {
+            s.spawn(|| {
+                complete_command(&memory);
+                memory[0x38 / 4].store(1 << 7, Ordering::SeqCst);
+            });
+            smhc.write_blocks(100, &blocks, MultiBlockMode::AutoStop)
+        });
+        assert_eq!(result, Err(SmhcError::DataCrcError));
+        assert_eq!(memory[0x200 / 4].load(Ordering::SeqCst), 0);
+        // only the error flag is cleared by writing one
+        assert_eq!(memory[0x38 / 4].load(Ordering::SeqCst), 1 << 7);
+    }
+
+    #[test]
+    fn multi_block_modes() {
+        let memory = memory();
+        let smhc = Smhc {
+            smhc: MockSmhc(&memory),
+            pads: (),
+            module_clock: 20_000_000,
+        };
+        memory[0x3C / 4].store(0, Ordering::SeqCst);
+        let read = |mode, count| {
+            let mut buf = [[0u8; 512]; 3];
+            std::thread::scope(|s| {
+                let hardware = s.spawn(|| complete_command(&memory));
+                smhc.read_blocks(9, &mut buf[..count], mode).unwrap();
+                hardware.join().unwrap()
+            })
+        };
+        let extended = || memory[0x138 / 4].load(Ordering::SeqCst);
+
+        // predefined count: CMD23 with block count, no auto stop
+        let cmd = read(MultiBlockMode::PredefinedCount, 3);
+        assert_eq!(cmd & 0x3F, 18);
+        assert_eq!(cmd & (1 << 12), 0);
+        assert_eq!(extended(), 1);
+        assert_eq!(memory[0x108 / 4].load(Ordering::SeqCst), 3);
+        // open-ended: auto stop, CMD23 disabled again
+        let cmd = read(MultiBlockMode::AutoStop, 3);
+        assert_eq!(cmd & 0x3F, 18);
+        assert_ne!(cmd & (1 << 12), 0);
+        assert_eq!(extended(), 0);
+        // single block needs no count
+        let cmd = read(MultiBlockMode::PredefinedCount, 1);
+        assert_eq!(cmd & 0x3F, 17);
+        assert_eq!(extended(), 0);
+
+        let blocks = [[0u8; 512]; 2];
+        memory[0x38 / 4].store(1 << 3, Ordering::SeqCst);
+        let cmd = std::thread::scope(|s| {
+            let hardware = s.spawn(|| complete_command(&memory));
+            smhc.write_blocks(100, &blocks, MultiBlockMode::PredefinedCount)
+                .unwrap();
+            hardware.join().unwrap()
+        });
+        assert_eq!(cmd & 0x3F, 25);
+        assert_eq!(cmd & (1 << 12), 0);
+        assert_eq!(extended(), 1);
+        assert_eq!(memory[0x108 / 4].load(Ordering::SeqCst), 2);
+        assert_eq!(memory[0x1C / 4].load(Ordering::SeqCst), 100);
+    }
+
+    #[test]
+    fn sdio_command_arguments() {
+        // read CCCR I/O enable register of function 0
+        assert_eq!(io_rw_direct_argument(false, 0, 0x02, 0), 0x0000_0400);
+        // write 0x02 into it, enabling function 1
+        assert_eq!(io_rw_direct_argument(true, 0, 0x02, 0x02), 0x8000_0402);
+        // function 1, address 0x1_8000, incrementing, 64 bytes
+        assert_eq!(
+            io_rw_extended_argument(false, 1, false, true, 0x1_8000, 64),
+            0x1700_0040
+        );
+        // 512 bytes are encoded as zero
+        assert_eq!(
+            io_rw_extended_argument(true, 2, false, false, 0x08, 512),
+            0xA000_1000
+        );
+        // block mode, 4 blocks
+        assert_eq!(
+            io_rw_extended_argument(true, 1, true, true, 0x100, 4),
+            0x9C02_0004
+        );
+    }
+
+    #[test]
+    fn sdio_direct_and_extended() {
+        let memory = memory();
+        let mut smhc = Smhc {
+            smhc: MockSmhc(&memory),
+            pads: (),
+            module_clock: 20_000_000,
+        };
+        let card = SdioCard {
+            smhc: &mut smhc,
+            functions: 1,
+        };
+        // R5 response: flags 0x10 (command state), data 0x5A
+        memory[0x20 / 4].store(0x0000_105A, Ordering::SeqCst);
+        let (value, cmd) = std::thread::scope(|s| {
+            let hardware = s.spawn(|| complete_command(&memory));
+            (card.read_byte(1, 0x10), hardware.join().unwrap())
+        });
+        assert_eq!(value.unwrap(), 0x5A);
+        assert_eq!(cmd & 0x3F, 52);
+        assert_eq!(memory[0x1C / 4].load(Ordering::SeqCst), 0x1000_2000);
+
+        // out of range flag is reported as an error
+        memory[0x20 / 4].store(0x0000_1100, Ordering::SeqCst);
+        let result = std::thread::scope(|s| {
+            s.spawn(|| complete_command(&memory));
+            card.write_byte(1, 0x10, 0xFF)
+        });
+        assert!(matches!(
+            result,
+            Err(SmhcError::UnexpectedResponse(52, 0x1100))
+        ));
+
+        // 6-byte read from a fixed address, no auto stop
+        memory[0x20 / 4].store(0x0000_1000, Ordering::SeqCst);
+        memory[0x3C / 4].store(0, Ordering::SeqCst);
+        memory[0x200 / 4].store(0x4433_2211, Ordering::SeqCst);
+        let mut buf = [0u8; 6];
+        let cmd = std::thread::scope(|s| {
+            let hardware = s.spawn(|| complete_command(&memory));
+            card.read_bytes(1, 0x00, false, &mut buf).unwrap();
+            hardware.join().unwrap()
+        });
+        assert_eq!(buf, [0x11, 0x22, 0x33, 0x44, 0x11, 0x22]);
+        assert_eq!(cmd & 0x3F, 53);
+        assert_eq!(cmd & (1 << 12), 0);
+        assert_eq!(memory[0x10 / 4].load(Ordering::SeqCst), 6);
+        assert_eq!(memory[0x14 / 4].load(Ordering::SeqCst), 6);
+        assert_eq!(memory[0x1C / 4].load(Ordering::SeqCst), 0x1000_0006);
+
+        // block mode uses the given block size
+        let mut buf = [0u8; 128];
+        std::thread::scope(|s| {
+            s.spawn(|| complete_command(&memory));
+            card.read_blocks(1, 0x00, true, 64, &mut buf).unwrap();
+        });
+        assert_eq!(memory[0x10 / 4].load(Ordering::SeqCst), 64);
+        assert_eq!(memory[0x14 / 4].load(Ordering::SeqCst), 128);
+        assert_eq!(memory[0x1C / 4].load(Ordering::SeqCst), 0x1C00_0002);
+
+        assert!(matches!(
+            card.read_bytes(1, 0, true, &mut [0u8; 513]),
+            Err(SmhcError::InvalidTransferLength(513))
+        ));
+        assert!(matches!(
+            card.read_blocks(1, 0, true, 64, &mut [0u8; 100]),
+            Err(SmhcError::InvalidTransferLength(100))
+        ));
+    }
+
+    #[test]
+    fn sdio_card_init_errors() {
+        let memory = memory();
+        let mut smhc = Smhc {
+            smhc: MockSmhc(&memory),
+            pads: (),
+            module_clock: 20_000_000,
+        };
+        // one function, never ready: two update-clock commands, then every CMD5
+        memory[0x20 / 4].store(0x1000_0000, Ordering::SeqCst);
+        let result = std::thread::scope(|s| {
+            s.spawn(|| {
+                for _ in 0..2 + 1 + 100 {
+                    complete_command(&memory);
+                }
+            });
+            SdioCard::new(&mut smhc).err()
+        });
+        assert!(matches!(result, Some(SmhcError::InitTimeout)));
+
+        // card does not respond to CMD5
+        let result = std::thread::scope(|s| {
+            s.spawn(|| {
+                for _ in 0..3 {
+                    if complete_command(&memory) & 0x3F == 5 {
+                        memory[0x38 / 4].store(1 << 8, Ordering::SeqCst);
+                    }
+                }
+            });
+            SdioCard::new(&mut smhc).err()
+        });
+        assert!(matches!(result, Some(SmhcError::ResponseTimeout)));
+    }
+
+    #[test]
+    fn sdio_interrupt_bus_width() {
+        let memory = memory();
+        let mut smhc = Smhc {
+            smhc: MockSmhc(&memory),
+            pads: (),
+            module_clock: 20_000_000,
+        };
+        let card = SdioCard {
+            smhc: &mut smhc,
+            functions: 1,
+        };
+        // 1-bit mode: taken even while data state machine is busy
+        memory[0x0C / 4].store(0, Ordering::SeqCst);
+        memory[0x3C / 4].store(1 << 10, Ordering::SeqCst);
+        memory[0x38 / 4].store(1 << 16, Ordering::SeqCst);
+        card.wait_sdio_interrupt();
+        assert_eq!(memory[0x38 / 4].load(Ordering::SeqCst), 1 << 16);
+
+        // 4-bit mode: flag during data transfer is discarded
+        memory[0x0C / 4].store(1, Ordering::SeqCst);
+        memory[0x38 / 4].store(1 << 16, Ordering::SeqCst);
+        let returned = core::sync::atomic::AtomicBool::new(false);
+        let early = std::thread::scope(|s| {
+            let hardware = s.spawn(|| {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                let early = returned.load(Ordering::SeqCst);
+                // transfer done, card still holds its interrupt
+                memory[0x3C / 4].store(0, Ordering::SeqCst);
+                memory[0x38 / 4].store(1 << 16, Ordering::SeqCst);
+                early
+            });
+            card.wait_sdio_interrupt();
+            returned.store(true, Ordering::SeqCst);
+            hardware.join().unwrap()
+        });
+        assert!(!early);
+        assert_eq!(memory[0x3C / 4].load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn timing_window_selection() {
+        // single passing phase
+        assert_eq!(widest_window_center([false, false, true, false]), Some(2));
+        // window 90..=180 is picked at its earlier middle
+        assert_eq!(widest_window_center([false, true, true, false]), Some(1));
+        // window 270..=90 wraps around
+        assert_eq!(widest_window_center([true, true, false, true]), Some(0));
+        // window 180..=0 wraps around, its middle is 270
+        assert_eq!(widest_window_center([true, false, true, true]), Some(3));
+        // of equal windows the earlier one is taken
+        assert_eq!(widest_window_center([true, false, true, false]), Some(0));
+        assert_eq!(widest_window_center([true; 4]), Some(1));
+        assert_eq!(widest_window_center([false; 4]), None);
+    }
+}
@@ -1,8 +1,15 @@
 //! Serial Peripheral Interface bus.
 
-use crate::ccu::{self, ClockConfig, ClockGate, Clocks, SpiClockSource};
+use crate::{
+    ccu::{self, ClockConfig, ClockGate, Clocks, SpiClockSource},
+    dma,
+};
 use core::cell::UnsafeCell;
-use embedded_hal::spi::Mode;
+use embedded_hal::{
+    delay::DelayNs,
+    digital::OutputPin,
+    spi::{Mode, Operation, SpiBus, SpiDevice},
+};
 use embedded_time::rate::Hertz;
 use volatile_register::{RO, RW};
 
@@ -118,6 +125,7 @@ pub struct TransferControl(u32);
 
 impl TransferControl {
     const XCH: u32 = 1 << 31;
+    const FBS: u32 = 1 << 12;
     const CPOL: u32 = 1 << 1;
     const CPHA: u32 = 1 << 0;
     /// Check if burst exchange has finished.
@@ -145,6 +153,70 @@ impl TransferControl {
         }
         Self(bits)
     }
+    /// Get SPI work mode.
+    #[inline]
+    pub const fn work_mode(self) -> Mode {
+        use embedded_hal::spi::{Phase, Polarity};
+        Mode {
+            polarity: if self.0 & Self::CPOL != 0 {
+                Polarity::IdleHigh
+            } else {
+                Polarity::IdleLow
+            },
+            phase: if self.0 & Self::CPHA != 0 {
+                Phase::CaptureOnSecondTransition
+            } else {
+                Phase::CaptureOnFirstTransition
+            },
+        }
+    }
+    /// Set order of bits in each transferred byte.
+    #[inline]
+    pub const fn set_bit_order(self, order: BitOrder) -> Self {
+        match order {
+            BitOrder::MsbFirst => Self(self.0 & !Self::FBS),
+            BitOrder::LsbFirst => Self(self.0 | Self::FBS),
+        }
+    }
+    /// Get order of bits in each transferred byte.
+    #[inline]
+    pub const fn bit_order(self) -> BitOrder {
+        if self.0 & Self::FBS != 0 {
+            BitOrder::LsbFirst
+        } else {
+            BitOrder::MsbFirst
+        }
+    }
+}
+
+/// Order of bits in each transferred byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum BitOrder {
+    /// Most significant bit first.
+    #[default]
+    MsbFirst,
+    /// Least significant bit first.
+    LsbFirst,
+}
+
+/// SPI bus configuration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// Clock polarity and phase.
+    pub mode: Mode,
+    /// Order of bits in each transferred byte.
+    pub bit_order: BitOrder,
+}
+
+impl From<Mode> for Config {
+    /// Configuration of `mode` with most significant bit first.
+    #[inline]
+    fn from(mode: Mode) -> Self {
+        Config {
+            mode,
+            bit_order: BitOrder::MsbFirst,
+        }
+    }
 }
 
 /// Status of FIFO for current peripheral.
@@ -200,7 +272,7 @@ impl BurstControl {
     const QUAD_EN: u32 = 0x1 << 29;
     // const DRM: u32 = 0x1 << 28;
     const DBC: u32 = 0xf << 24;
-    const STC: u32 = 0xfff << 0;
+    const STC: u32 = 0xff_ffff;
     /// Enable quad mode.
     #[inline]
     pub const fn quad_mode_enable(self) -> Self {
@@ -234,7 +306,7 @@ impl BurstControl {
 
     #[inline]
     pub const fn set_master_single_mode_transmit_counter(self, val: u32) -> Self {
-        Self((self.0 & !Self::STC) | (val & 0xfff))
+        Self((self.0 & !Self::STC) | (val & Self::STC))
     }
 }
 
@@ -297,7 +369,7 @@ impl<SPI: AsRef<RegisterBlock>, const I: usize, PINS: Pins<I>> Spi<SPI, I, PINS>
     pub fn new(
         spi: SPI,
         pins: PINS,
-        mode: impl Into<Mode>,
+        config: impl Into<Config>,
         freq: Hertz,
         clocks: &Clocks,
         ccu: &ccu::RegisterBlock,
@@ -321,15 +393,40 @@ impl<SPI: AsRef<RegisterBlock>, const I: usize, PINS: Pins<I>> Spi<SPI, I, PINS>
         while spi.as_ref().gcr.read().is_software_reset_finished() {
             core::hint::spin_loop();
         }
-        // 4. configure work mode
+        // 4. configure work mode and bit order
+        let config = config.into();
         unsafe {
-            spi.as_ref()
-                .tcr
-                .write(TransferControl::default().set_work_mode(mode.into()))
+            spi.as_ref().tcr.write(
+                TransferControl::default()
+                    .set_work_mode(config.mode)
+                    .set_bit_order(config.bit_order),
+            )
         };
         // Finally, return ownership of this structure.
         Spi { spi, pins }
     }
+    /// Change clock polarity and phase, e.g. before talking to another device on this bus.
+    ///
+    /// Waits for the current burst exchange to finish first.
+    #[inline]
+    pub fn set_mode(&mut self, mode: Mode) {
+        let spi = self.spi.as_ref();
+        while !spi.tcr.read().burst_finished() {
+            core::hint::spin_loop();
+        }
+        unsafe { spi.tcr.modify(|tcr| tcr.set_work_mode(mode)) };
+    }
+    /// Change order of bits in each transferred byte.
+    ///
+    /// Waits for the current burst exchange to finish first.
+    #[inline]
+    pub fn set_bit_order(&mut self, order: BitOrder) {
+        let spi = self.spi.as_ref();
+        while !spi.tcr.read().burst_finished() {
+            core::hint::spin_loop();
+        }
+        unsafe { spi.tcr.modify(|tcr| tcr.set_bit_order(order)) };
+    }
     /// Close SPI and release peripheral.
     #[inline]
     pub fn free(self, ccu: &ccu::RegisterBlock) -> (SPI, PINS) {
@@ -339,6 +436,176 @@ impl<SPI: AsRef<RegisterBlock>, const I: usize, PINS: Pins<I>> Spi<SPI, I, PINS>
     }
 }
 
+/// Receive FIFO trigger level field.
+const FCR_RX_TRIG_LEVEL: u32 = 0xff;
+/// Receive FIFO DMA request enable.
+const FCR_RF_DRQ_EN: u32 = 1 << 8;
+/// Transmit FIFO trigger level field.
+const FCR_TX_TRIG_LEVEL: u32 = 0xff << 16;
+/// Transmit FIFO DMA request enable.
+const FCR_TF_DRQ_EN: u32 = 1 << 24;
+/// Request receive DMA as soon as one byte is in FIFO.
+const DMA_RX_TRIGGER: u32 = 1;
+/// Request transmit DMA while FIFO holds no more than half of its 64 bytes.
+const DMA_TX_TRIGGER: u32 = 32 << 16;
+
+/// Largest number of bytes in one DMA transfer, limited by burst counters.
+pub const MAX_DMA_TRANSFER: usize = 0xff_ffff;
+
+/// DMA descriptors of one SPI transfer, kept in memory by the caller.
+///
+/// DMA controller reads descriptors while transferring, so they are borrowed
+/// for the whole [`Spi::transfer_dma`] call.
+#[repr(C)]
+pub struct DmaDescriptors {
+    tx: dma::Descriptor,
+    rx: dma::Descriptor,
+    rx_discard: dma::Descriptor,
+    sink: u32,
+}
+
+impl DmaDescriptors {
+    /// Create empty descriptors.
+    #[inline]
+    pub const fn new() -> Self {
+        const EMPTY: dma::Descriptor = dma::Descriptor::new(dma::DescriptorConfig::new(), 0, 0, 0);
+        DmaDescriptors {
+            tx: EMPTY,
+            rx: EMPTY,
+            rx_discard: EMPTY,
+            sink: 0,
+        }
+    }
+}
+
+impl Default for DmaDescriptors {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<SPI: AsRef<RegisterBlock>, const I: usize, PINS: Pins<I>> Spi<SPI, I, PINS> {
+    /// Full-duplex transfer driven by DMA channels `TX` and `RX`.
+    ///
+    /// Exchanges `max(read.len(), write.len())` bytes like [`SpiBus::transfer`]:
+    /// the controller sends dummy bytes after `write` ends, and bytes received
+    /// after `read` is full are discarded. Returns when all received bytes
+    /// are moved out of the FIFO, so chip select may be released right after.
+    ///
+    /// Buffers and `descriptors` are cleaned through `cache` once the chains
+    /// are built, and `read` is invalidated after the transfer.
+    ///
+    /// # Safety
+    ///
+    /// Addresses are given to the DMA controller as is, so `read`, `write` and
+    /// `descriptors` must be physically addressed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if more than [`MAX_DMA_TRANSFER`] bytes are exchanged.
+    pub unsafe fn transfer_dma<const TX: usize, const RX: usize>(
+        &mut self,
+        read: &mut [u8],
+        write: &[u8],
+        channels: (&mut dma::Channel<'_, TX>, &mut dma::Channel<'_, RX>),
+        descriptors: &mut DmaDescriptors,
+        cache: &mut impl dma::CacheMaintenance,
+    ) -> Result<(), embedded_hal::spi::ErrorKind> {
+        let len = read.len().max(write.len());
+        assert!(len <= MAX_DMA_TRANSFER);
+        if len == 0 {
+            return Ok(());
+        }
+        let (tx, rx) = channels;
+        let spi = self.spi.as_ref();
+        let drq = if I == 0 { dma::DRQ_SPI0 } else { dma::DRQ_SPI1 };
+        let (width, burst) = (dma::DataWidth::Bits8, dma::BurstLength::One);
+        // 1. build descriptor chains
+        let txd = &spi.txd as *const _ as usize as u32;
+        let rxd = &spi.rxd as *const _ as usize as u32;
+        descriptors.tx = dma::Descriptor::new(
+            dma::DescriptorConfig::new()
+                .set_source(dma::DRQ_DRAM, false, width, burst)
+                .set_destination(drq, true, width, burst),
+            write.as_ptr() as usize as u32,
+            txd,
+            write.len() as u32,
+        );
+        descriptors.rx = dma::Descriptor::new(
+            dma::DescriptorConfig::new()
+                .set_source(drq, true, width, burst)
+                .set_destination(dma::DRQ_DRAM, false, width, burst),
+            rxd,
+            read.as_mut_ptr() as usize as u32,
+            read.len() as u32,
+        );
+        // bytes received after `read` is full go to one word, rewritten every time
+        let sink = &descriptors.sink as *const u32 as usize as u32;
+        descriptors.rx_discard = dma::Descriptor::new(
+            dma::DescriptorConfig::new()
+                .set_source(drq, true, width, burst)
+                .set_destination(dma::DRQ_DRAM, true, width, burst),
+            rxd,
+            sink,
+            (len - read.len()) as u32,
+        );
+        let rx_head = match (read.is_empty(), len > read.len()) {
+            (true, _) => &descriptors.rx_discard,
+            (false, true) => {
+                descriptors.rx.link_to(&descriptors.rx_discard);
+                &descriptors.rx
+            }
+            (false, false) => &descriptors.rx,
+        };
+        cache.clean_dcache(write.as_ptr() as usize, write.len());
+        // dirty lines of `read` must not be written back over received data later
+        cache.clean_dcache(read.as_ptr() as usize, read.len());
+        cache.clean_dcache(
+            descriptors as *const DmaDescriptors as usize,
+            core::mem::size_of::<DmaDescriptors>(),
+        );
+        // 2. configure counters and FIFO requests
+        unsafe { spi.mbc.write(len as u32) };
+        unsafe { spi.mtc.write(write.len() as u32) };
+        let bcc = spi
+            .bcc
+            .read()
+            .set_master_dummy_burst_counter(0)
+            .set_master_single_mode_transmit_counter(write.len() as u32);
+        unsafe { spi.bcc.write(bcc) };
+        let mut fcr = spi.fcr.read() & !(FCR_RX_TRIG_LEVEL | FCR_TX_TRIG_LEVEL);
+        fcr |= FCR_RF_DRQ_EN | DMA_RX_TRIGGER;
+        if !write.is_empty() {
+            fcr |= FCR_TF_DRQ_EN | DMA_TX_TRIGGER;
+        }
+        unsafe { spi.fcr.write(fcr) };
+        // 3. start receive channel first, so no received byte is missed
+        // chains and buffers are borrowed until both channels are stopped below
+        unsafe { rx.start(rx_head.address()) };
+        if !write.is_empty() {
+            unsafe { tx.start(descriptors.tx.address()) };
+        }
+        unsafe { spi.tcr.write(spi.tcr.read().start_burst_exchange()) };
+        // 4. wait for the bus, then for both channels
+        while !spi.tcr.read().burst_finished() {
+            core::hint::spin_loop();
+        }
+        while tx.is_busy() || rx.is_busy() {
+            core::hint::spin_loop();
+        }
+        // 5. drain receive FIFO so the next transfer starts empty
+        while spi.fsr.read().receive_fifo_counter() != 0 {
+            spi.rxd.read_u8();
+        }
+        tx.stop();
+        rx.stop();
+        unsafe { spi.fcr.modify(|fcr| fcr & !(FCR_RF_DRQ_EN | FCR_TF_DRQ_EN)) };
+        cache.invalidate_dcache(read.as_ptr() as usize, read.len());
+        Ok(())
+    }
+}
+
 /// Valid SPI pins.
 pub trait Pins<const I: usize> {
     type Clock: ccu::ClockGate + ccu::ClockConfig<Source = SpiClockSource>;
@@ -476,10 +743,126 @@ impl<SPI: AsRef<RegisterBlock>, const I: usize, PINS: Pins<I>> embedded_hal::spi
     type Error = embedded_hal::spi::ErrorKind;
 }
 
+/// SPI device with exclusive use of a bus, selected by a chip select pad.
+///
+/// Chip select is driven low for each transaction and high after it. Some slow
+/// devices need time between chip select and clock edges; set it with
+/// [`set_cs_delays`](Self::set_cs_delays). Use [`NoDelay`] if they are not needed.
+pub struct ExclusiveDevice<BUS, CS, D> {
+    bus: BUS,
+    cs: CS,
+    delay: D,
+    setup_ns: u32,
+    hold_ns: u32,
+}
+
+impl<BUS, CS: OutputPin, D> ExclusiveDevice<BUS, CS, D> {
+    /// Create a device on `bus`, deselecting it by driving `cs` high.
+    #[inline]
+    pub fn new(bus: BUS, mut cs: CS, delay: D) -> Result<Self, CS::Error> {
+        cs.set_high()?;
+        Ok(ExclusiveDevice {
+            bus,
+            cs,
+            delay,
+            setup_ns: 0,
+            hold_ns: 0,
+        })
+    }
+    /// Set delays from chip select low to first clock edge, and from the end
+    /// of transfer to chip select high, in nanoseconds.
+    #[inline]
+    pub fn set_cs_delays(&mut self, setup_ns: u32, hold_ns: u32) {
+        self.setup_ns = setup_ns;
+        self.hold_ns = hold_ns;
+    }
+    /// Get a reference to the underlying bus.
+    #[inline]
+    pub fn bus(&self) -> &BUS {
+        &self.bus
+    }
+    /// Release bus, chip select pad and delay.
+    #[inline]
+    pub fn free(self) -> (BUS, CS, D) {
+        (self.bus, self.cs, self.delay)
+    }
+}
+
+/// Error of an [`ExclusiveDevice`] transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceError<BUS, CS> {
+    /// Error from the SPI bus.
+    Spi(BUS),
+    /// Error from the chip select pad.
+    Cs(CS),
+}
+
+impl<BUS: embedded_hal::spi::Error, CS: core::fmt::Debug> embedded_hal::spi::Error
+    for DeviceError<BUS, CS>
+{
+    #[inline]
+    fn kind(&self) -> embedded_hal::spi::ErrorKind {
+        match self {
+            DeviceError::Spi(e) => e.kind(),
+            DeviceError::Cs(_) => embedded_hal::spi::ErrorKind::ChipSelectFault,
+        }
+    }
+}
+
+impl<BUS: SpiBus, CS: OutputPin, D> embedded_hal::spi::ErrorType for ExclusiveDevice<BUS, CS, D> {
+    type Error = DeviceError<BUS::Error, CS::Error>;
+}
+
+impl<BUS: SpiBus, CS: OutputPin, D: DelayNs> SpiDevice for ExclusiveDevice<BUS, CS, D> {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        self.cs.set_low().map_err(DeviceError::Cs)?;
+        if self.setup_ns > 0 {
+            self.delay.delay_ns(self.setup_ns);
+        }
+        let ans = operations.iter_mut().try_for_each(|op| match op {
+            Operation::Read(buf) => self.bus.read(buf),
+            Operation::Write(buf) => self.bus.write(buf),
+            Operation::Transfer(read, write) => self.bus.transfer(read, write),
+            Operation::TransferInPlace(buf) => self.bus.transfer_in_place(buf),
+            Operation::DelayNs(ns) => {
+                self.bus.flush()?;
+                self.delay.delay_ns(*ns);
+                Ok(())
+            }
+        });
+        // deselect the device even if the transfer failed
+        let flush = ans.and_then(|_| self.bus.flush()).map_err(DeviceError::Spi);
+        if self.hold_ns > 0 {
+            self.delay.delay_ns(self.hold_ns);
+        }
+        let deselect = self.cs.set_high().map_err(DeviceError::Cs);
+        flush.and(deselect)
+    }
+}
+
+/// Delay provider doing nothing, for devices without chip select delays.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoDelay;
+
+impl DelayNs for NoDelay {
+    #[inline]
+    fn delay_ns(&mut self, _ns: u32) {}
+}
+
 #[cfg(test)]
 mod tests {
-    use super::RegisterBlock;
+    extern crate std;
+    use super::{
+        BitOrder, Clk, Config, DeviceError, DmaDescriptors, ExclusiveDevice, Miso, Mosi, NoDelay,
+        RegisterBlock, Spi, TransferControl,
+    };
+    use crate::dma::{self, Dma, DRQ_DRAM, DRQ_SPI0, END_OF_CHAIN};
+    use core::sync::atomic::{AtomicU32, Ordering};
+    use embedded_hal::spi::{
+        Error, ErrorKind, ErrorType, Operation, SpiBus, SpiDevice, MODE_0, MODE_1, MODE_2, MODE_3,
+    };
     use memoffset::offset_of;
+    use std::{cell::RefCell, vec::Vec};
     #[test]
     fn offset_spi0() {
         assert_eq!(offset_of!(RegisterBlock, ier), 0x10);
@@ -489,4 +872,304 @@ mod tests {
         assert_eq!(offset_of!(RegisterBlock, txd), 0x200);
         assert_eq!(offset_of!(RegisterBlock, rxd), 0x300);
     }
+
+    #[test]
+    fn struct_transfer_control_functions() {
+        let mut val = TransferControl(0x8000_0000);
+        for mode in [MODE_0, MODE_1, MODE_2, MODE_3] {
+            val = val.set_work_mode(mode);
+            assert_eq!(val.work_mode(), mode);
+        }
+        assert_eq!(val.0, 0x8000_0003);
+        val = val.set_bit_order(BitOrder::LsbFirst);
+        assert_eq!(val.bit_order(), BitOrder::LsbFirst);
+        assert_eq!(val.0, 0x8000_1003);
+        val = val.set_work_mode(MODE_0).set_bit_order(BitOrder::MsbFirst);
+        assert_eq!(val.bit_order(), BitOrder::MsbFirst);
+        assert_eq!(val.0, 0x8000_0000);
+
+        let config = Config::from(MODE_3);
+        assert_eq!(config.bit_order, BitOrder::MsbFirst);
+    }
+
+    /// Bus and pad events recorded by mocks.
+    #[derive(Debug, PartialEq, Eq)]
+    enum Event {
+        Cs(bool),
+        Write(Vec<u8>),
+        Read(usize),
+        Flush,
+        Delay(u32),
+    }
+
+    struct MockBus<'a>(&'a RefCell<Vec<Event>>, bool);
+
+    impl ErrorType for MockBus<'_> {
+        type Error = ErrorKind;
+    }
+
+    impl SpiBus for MockBus<'_> {
+        fn read(&mut self, words: &mut [u8]) -> Result<(), ErrorKind> {
+            self.0.borrow_mut().push(Event::Read(words.len()));
+            words.fill(0xA5);
+            Ok(())
+        }
+        fn write(&mut self, words: &[u8]) -> Result<(), ErrorKind> {
+            if self.1 {
+                return Err(ErrorKind::Overrun);
+            }
+            self.0.borrow_mut().push(Event::Write(words.to_vec()));
+            Ok(())
+        }
+        fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), ErrorKind> {
+            self.write(write)?;
+            self.read(read)
+        }
+        fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), ErrorKind> {
+            self.write(words)?;
+            self.read(words)
+        }
+        fn flush(&mut self) -> Result<(), ErrorKind> {
+            self.0.borrow_mut().push(Event::Flush);
+            Ok(())
+        }
+    }
+
+    struct MockPad<'a>(&'a RefCell<Vec<Event>>);
+
+    impl embedded_hal::digital::ErrorType for MockPad<'_> {
+        type Error = core::convert::Infallible;
+    }
+
+    impl embedded_hal::digital::OutputPin for MockPad<'_> {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.0.borrow_mut().push(Event::Cs(false));
+            Ok(())
+        }
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.0.borrow_mut().push(Event::Cs(true));
+            Ok(())
+        }
+    }
+
+    struct MockDelay<'a>(&'a RefCell<Vec<Event>>);
+
+    impl embedded_hal::delay::DelayNs for MockDelay<'_> {
+        fn delay_ns(&mut self, ns: u32) {
+            self.0.borrow_mut().push(Event::Delay(ns));
+        }
+    }
+
+    #[test]
+    fn exclusive_device_transaction() {
+        let events = RefCell::new(Vec::new());
+        let mut device =
+            ExclusiveDevice::new(MockBus(&events, false), MockPad(&events), NoDelay).unwrap();
+        let mut buf = [0u8; 2];
+        device
+            .transaction(&mut [Operation::Write(&[0x9F]), Operation::Read(&mut buf)])
+            .unwrap();
+        assert_eq!(buf, [0xA5, 0xA5]);
+        assert_eq!(
+            events.take(),
+            [
+                Event::Cs(true),
+                Event::Cs(false),
+                Event::Write([0x9F].into()),
+                Event::Read(2),
+                Event::Flush,
+                Event::Cs(true),
+            ]
+        );
+
+        // chip select delays surround the transfer
+        let (bus, cs, _) = device.free();
+        let mut device = ExclusiveDevice::new(bus, cs, MockDelay(&events)).unwrap();
+        device.set_cs_delays(100, 50);
+        events.take();
+        device
+            .transaction(&mut [
+                Operation::Write(&[1]),
+                Operation::DelayNs(10),
+                Operation::Write(&[2]),
+            ])
+            .unwrap();
+        assert_eq!(
+            events.take(),
+            [
+                Event::Cs(false),
+                Event::Delay(100),
+                Event::Write([1].into()),
+                Event::Flush,
+                Event::Delay(10),
+                Event::Write([2].into()),
+                Event::Flush,
+                Event::Delay(50),
+                Event::Cs(true),
+            ]
+        );
+
+        // device is deselected after a bus error
+        let mut device =
+            ExclusiveDevice::new(MockBus(&events, true), MockPad(&events), NoDelay).unwrap();
+        events.take();
+        let e = device.write(&[0x06]).unwrap_err();
+        assert_eq!(e, DeviceError::Spi(ErrorKind::Overrun));
+        assert_eq!(e.kind(), ErrorKind::Overrun);
+        assert_eq!(events.take(), [Event::Cs(false), Event::Cs(true)]);
+    }
+
+    struct MockSpi<'a>(&'a RegisterBlock);
+
+    impl AsRef<RegisterBlock> for MockSpi<'_> {
+        fn as_ref(&self) -> &RegisterBlock {
+            self.0
+        }
+    }
+
+    /// Cache recording maintenance calls, and the receive descriptor byte
+    /// count seen when descriptors are cleaned.
+    #[derive(Default)]
+    struct MockCache {
+        events: Vec<(bool, usize, usize)>,
+        cleaned_rx_count: Option<u32>,
+    }
+
+    impl dma::CacheMaintenance for MockCache {
+        fn clean_dcache(&mut self, address: usize, len: usize) {
+            if len == core::mem::size_of::<DmaDescriptors>() {
+                let descriptors = unsafe { &*(address as *const DmaDescriptors) };
+                self.cleaned_rx_count = Some(descriptors.rx.byte_count);
+            }
+            self.events.push((true, address, len));
+        }
+        fn invalidate_dcache(&mut self, address: usize, len: usize) {
+            self.events.push((false, address, len));
+        }
+    }
+
+    struct MockDmaRegisters<'a>(&'a dma::RegisterBlock);
+
+    impl AsRef<dma::RegisterBlock> for MockDmaRegisters<'_> {
+        fn as_ref(&self) -> &dma::RegisterBlock {
+            self.0
+        }
+    }
+
+    struct MockPin;
+
+    impl Clk<0> for MockPin {}
+    impl Mosi<0> for MockPin {}
+    impl Miso<0> for MockPin {}
+
+    #[test]
+    fn transfer_dma_unequal_lengths() {
+        const SPI_WORDS: usize = core::mem::size_of::<RegisterBlock>() / 4;
+        const DMA_WORDS: usize = core::mem::size_of::<dma::RegisterBlock>() / 4;
+        let spi_memory = [const { AtomicU32::new(0) }; SPI_WORDS];
+        let dma_memory = [const { AtomicU32::new(0) }; DMA_WORDS];
+        let spi_base = &spi_memory as *const _ as usize;
+        let mut spi = Spi {
+            spi: MockSpi(unsafe { &*(spi_base as *const RegisterBlock) }),
+            pins: (MockPin, MockPin, MockPin),
+        };
+        let mut dma = Dma::new(MockDmaRegisters(unsafe {
+            &*(&dma_memory as *const _ as *const dma::RegisterBlock)
+        }));
+        let mut channels = dma.split();
+        let mut descriptors = DmaDescriptors::new();
+        let write = [0x03, 0x00, 0x10, 0x00];
+        let mut read = [0u8; 6];
+
+        let mut cache = MockCache::default();
+        let mut transfer =
+            |read: &mut [u8], descriptors: &mut DmaDescriptors, cache: &mut MockCache| {
+                std::thread::scope(|s| {
+                    // controller finishes burst exchange
+                    s.spawn(|| loop {
+                        let tcr = spi_memory[0x08 / 4].load(Ordering::SeqCst);
+                        if tcr & (1 << 31) != 0 {
+                            spi_memory[0x08 / 4].store(tcr & !(1 << 31), Ordering::SeqCst);
+                            break;
+                        }
+                        std::thread::yield_now();
+                    });
+                    unsafe {
+                        spi.transfer_dma(
+                            read,
+                            &write,
+                            (&mut channels.ch0, &mut channels.ch1),
+                            descriptors,
+                            cache,
+                        )
+                    }
+                    .unwrap();
+                })
+            };
+        transfer(&mut read, &mut descriptors, &mut cache);
+
+        // buffers and built descriptors cleaned before, `read` invalidated after
+        let (write_at, read_at) = (write.as_ptr() as usize, read.as_ptr() as usize);
+        let descriptors_at = &descriptors as *const DmaDescriptors as usize;
+        let size = core::mem::size_of::<DmaDescriptors>();
+        assert_eq!(
+            cache.events,
+            [
+                (true, write_at, 4),
+                (true, read_at, 6),
+                (true, descriptors_at, size),
+                (false, read_at, 6),
+            ]
+        );
+        assert_eq!(cache.cleaned_rx_count, Some(6));
+        // 6 bursts, of which the first 4 are transmitted
+        assert_eq!(spi_memory[0x30 / 4].load(Ordering::SeqCst), 6);
+        assert_eq!(spi_memory[0x34 / 4].load(Ordering::SeqCst), 4);
+        assert_eq!(spi_memory[0x38 / 4].load(Ordering::SeqCst), 4);
+        // trigger levels kept, DMA requests disabled after transfer
+        assert_eq!(spi_memory[0x18 / 4].load(Ordering::SeqCst), 0x0020_0001);
+
+        let tx = &descriptors.tx;
+        assert_eq!(tx.config.source_drq(), DRQ_DRAM);
+        assert_eq!(tx.config.destination_drq(), DRQ_SPI0);
+        assert_eq!(tx.source, write.as_ptr() as usize as u32);
+        assert_eq!(tx.destination, (spi_base + 0x200) as u32);
+        assert_eq!((tx.byte_count, tx.link), (4, END_OF_CHAIN));
+        let rx = &descriptors.rx;
+        assert_eq!(rx.source, (spi_base + 0x300) as u32);
+        assert_eq!(rx.destination, read.as_ptr() as usize as u32);
+        assert_eq!(rx.byte_count, 6);
+        assert_eq!(rx.link, END_OF_CHAIN);
+        // channel 0 transmits, channel 1 receives; both are stopped
+        let channel = |n: usize, offset: usize| {
+            dma_memory[(0x100 + n * 0x40 + offset) / 4].load(Ordering::SeqCst)
+        };
+        assert_eq!(channel(0, 0x08), tx.address());
+        assert_eq!(channel(1, 0x08), rx.address());
+        assert_eq!((channel(0, 0x00), channel(1, 0x00)), (0, 0));
+
+        // reading more than written discards nothing; writing more chains a discard
+        let mut read = [0u8; 2];
+        transfer(&mut read, &mut descriptors, &mut cache);
+        assert_eq!(spi_memory[0x30 / 4].load(Ordering::SeqCst), 4);
+        assert_eq!(spi_memory[0x34 / 4].load(Ordering::SeqCst), 4);
+        let (rx, discard) = (&descriptors.rx, &descriptors.rx_discard);
+        assert_eq!(rx.byte_count, 2);
+        assert_eq!(rx.link, discard.address());
+        assert_eq!(discard.source, (spi_base + 0x300) as u32);
+        assert_eq!(
+            discard.destination,
+            &descriptors.sink as *const u32 as usize as u32
+        );
+        assert_eq!((discard.byte_count, discard.link), (2, END_OF_CHAIN));
+        assert_eq!(channel(1, 0x08), rx.address());
+
+        // mode and bit order change between transfers, keeping other bits
+        spi_memory[0x08 / 4].store(0x0000_0004, Ordering::SeqCst);
+        spi.set_mode(MODE_3);
+        spi.set_bit_order(BitOrder::LsbFirst);
+        assert_eq!(spi_memory[0x08 / 4].load(Ordering::SeqCst), 0x0000_1007);
+        spi.set_mode(MODE_1);
+        assert_eq!(spi_memory[0x08 / 4].load(Ordering::SeqCst), 0x0000_1005);
+    }
 }
@@ -3,8 +3,11 @@
 use core::cell::UnsafeCell;
 
 use crate::ccu::{self, ClockGate, Clocks};
-use embedded_time::rate::Baud;
-use uart16550::{CharLen, Register, Uart16550, PARITY};
+use embedded_time::rate::{Baud, Hertz};
+use uart16550::{
+    CharLen, LineStatus, ModemControl, PendingInterrupt, Register, Uart16550, IIR_FCR, PARITY,
+};
+use volatile_register::RO;
 
 /// Universal Asynchronous Receiver-Transmitter registers.
 #[repr(C)]
@@ -12,6 +15,59 @@ pub struct RegisterBlock {
     uart16550: Uart16550<u32>,
     _reserved0: [u32; 24],
     usr: USR<u32>, // offset = 31(0x7c)
+    tfl: RO<u32>,  // offset = 32(0x80)
+    rfl: RO<u32>,  // offset = 33(0x84)
+}
+
+impl RegisterBlock {
+    const FIFO_LEVEL: u32 = 0x1FF;
+    const MCR_RTS: u8 = 1 << 1;
+    const MCR_AFCE: u8 = 1 << 5;
+    const FCR_FIFO_ENABLE: u8 = 1 << 0;
+    const FCR_RX_RESET: u8 = 1 << 1;
+    const FCR_TX_RESET: u8 = 1 << 2;
+    const FCR_TFT_SHIFT: u8 = 4;
+    const FCR_RT_SHIFT: u8 = 6;
+
+    /// Get number of bytes in transmit FIFO.
+    #[inline]
+    pub fn tx_fifo_level(&self) -> usize {
+        (self.tfl.read() & Self::FIFO_LEVEL) as usize
+    }
+    /// Get number of bytes in receive FIFO.
+    #[inline]
+    pub fn rx_fifo_level(&self) -> usize {
+        (self.rfl.read() & Self::FIFO_LEVEL) as usize
+    }
+    /// Check if receive timeout interrupt is pending.
+    #[inline]
+    pub fn is_rx_timeout_pending(&self) -> bool {
+        matches!(
+            self.uart16550.iir_fcr().read().pending_interrupts(),
+            Some(PendingInterrupt::ReceivedDataTimeout)
+        )
+    }
+    /// Write FIFO control register, which shares its offset with interrupt identification.
+    #[inline]
+    fn write_fifo_control(&self, val: u8) {
+        let fcr = self.uart16550.iir_fcr() as *const IIR_FCR<u32> as *mut u32;
+        unsafe { fcr.write_volatile(val as u32) }
+    }
+    /// Configure hardware flow control.
+    ///
+    /// With `RtsCts`, RTS is deasserted once the receive FIFO reaches its trigger
+    /// level; transmission pauses while CTS is deasserted. FIFO control is left
+    /// untouched, so FIFOs and the receive trigger level must be set up separately,
+    /// e.g. by [`Config::rx_fifo_trigger`].
+    #[inline]
+    pub fn set_flow_control(&self, flow_control: FlowControl) {
+        let mcr = self.uart16550.mcr().read().0;
+        let mcr = match flow_control {
+            FlowControl::None => mcr & !Self::MCR_AFCE,
+            FlowControl::RtsCts => mcr | Self::MCR_AFCE | Self::MCR_RTS,
+        };
+        self.uart16550.mcr().write(ModemControl(mcr));
+    }
 }
 
 /// Serial configuration structure.
@@ -25,6 +81,12 @@ pub struct Config {
     pub parity: Parity,
     /// Number of stop bits, can be `One` or `Two`.
     pub stopbits: StopBits,
+    /// Flow control, can be `None` or `RtsCts`.
+    pub flow_control: FlowControl,
+    /// Receive FIFO level that triggers receive interrupt, DMA request or RTS deassertion.
+    pub rx_fifo_trigger: RxFifoTrigger,
+    /// Transmit FIFO level at or below which transmit empty interrupt triggers.
+    pub tx_fifo_trigger: TxFifoTrigger,
 }
 
 impl Default for Config {
@@ -36,6 +98,9 @@ impl Default for Config {
             wordlength: WordLength::Eight,
             parity: Parity::None,
             stopbits: StopBits::One,
+            flow_control: FlowControl::None,
+            rx_fifo_trigger: RxFifoTrigger::Half,
+            tx_fifo_trigger: TxFifoTrigger::Empty,
         }
     }
 }
@@ -73,6 +138,190 @@ pub enum StopBits {
     Two,
 }
 
+/// Flow control settings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FlowControl {
+    /// No flow control.
+    None,
+    /// Hardware flow control using RTS and CTS pads.
+    RtsCts,
+}
+
+/// Receive FIFO trigger level settings.
+///
+/// On a 16-byte FIFO these are 1, 4, 8 and 14 bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RxFifoTrigger {
+    /// 1 character in FIFO.
+    One,
+    /// FIFO 1/4 full.
+    Quarter,
+    /// FIFO 1/2 full.
+    Half,
+    /// FIFO 2 less than full.
+    TwoLessThanFull,
+}
+
+/// Transmit FIFO trigger level settings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TxFifoTrigger {
+    /// FIFO empty.
+    Empty,
+    /// 2 characters in FIFO.
+    Two,
+    /// FIFO 1/4 full.
+    Quarter,
+    /// FIFO 1/2 full.
+    Half,
+}
+
+impl Config {
+    /// Get FIFO control register value enabling FIFOs with configured trigger levels.
+    #[inline]
+    const fn fifo_control(&self) -> u8 {
+        let rx = match self.rx_fifo_trigger {
+            RxFifoTrigger::One => 0,
+            RxFifoTrigger::Quarter => 1,
+            RxFifoTrigger::Half => 2,
+            RxFifoTrigger::TwoLessThanFull => 3,
+        };
+        let tx = match self.tx_fifo_trigger {
+            TxFifoTrigger::Empty => 0,
+            TxFifoTrigger::Two => 1,
+            TxFifoTrigger::Quarter => 2,
+            TxFifoTrigger::Half => 3,
+        };
+        RegisterBlock::FCR_FIFO_ENABLE
+            | (rx << RegisterBlock::FCR_RT_SHIFT)
+            | (tx << RegisterBlock::FCR_TFT_SHIFT)
+    }
+    /// Get number of bits on the line for one character, including start and stop bits.
+    ///
+    /// One and a half stop bits are counted as two.
+    #[inline]
+    pub const fn bits_per_char(&self) -> u32 {
+        let data = match self.wordlength {
+            WordLength::Five => 5,
+            WordLength::Six => 6,
+            WordLength::Seven => 7,
+            WordLength::Eight => 8,
+        };
+        let parity = match self.parity {
+            Parity::None => 0,
+            Parity::Odd | Parity::Even => 1,
+        };
+        let stop = match self.stopbits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+        1 + data + parity + stop
+    }
+}
+
+/// Idle line detection for frames delimited by receive line idle time.
+///
+/// Receive activity is observed by increases of receive FIFO level, so the
+/// FIFO should be polled faster than it is drained. A frame is complete once
+/// the line stays idle for the configured number of character times after data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdleDetector {
+    idle_ns: u64,
+    last_level: usize,
+    last_activity: Option<u64>,
+}
+
+impl IdleDetector {
+    /// Create an idle detector ending a frame after `idle_chars` character times under `config`.
+    #[inline]
+    pub const fn new(config: &Config, idle_chars: u32) -> Self {
+        let char_ns = config.bits_per_char() as u64 * 1_000_000_000 / config.baudrate.0 as u64;
+        Self {
+            idle_ns: char_ns * idle_chars as u64,
+            last_level: 0,
+            last_activity: None,
+        }
+    }
+    /// Get idle time that ends a frame in nanoseconds.
+    #[inline]
+    pub const fn idle_ns(&self) -> u64 {
+        self.idle_ns
+    }
+    /// Update with receive FIFO level observed at `now_ns`.
+    ///
+    /// Returns `true` once for each frame, when the line has been idle for
+    /// long enough after its last data.
+    #[inline]
+    pub fn update(&mut self, rx_fifo_level: usize, now_ns: u64) -> bool {
+        if rx_fifo_level > self.last_level {
+            self.last_activity = Some(now_ns);
+        }
+        self.last_level = rx_fifo_level;
+        match self.last_activity {
+            Some(last) if now_ns.saturating_sub(last) >= self.idle_ns => {
+                self.last_activity = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Receive errors reported along with a character in line status register.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct LineErrors(u8);
+
+impl LineErrors {
+    const OVERRUN: u8 = 1 << 1;
+    const PARITY: u8 = 1 << 2;
+    const FRAMING: u8 = 1 << 3;
+    const BREAK: u8 = 1 << 4;
+
+    /// Get receive errors from a line status register value.
+    #[inline]
+    pub const fn from_line_status(lsr: LineStatus) -> Self {
+        let mut bits = 0;
+        if lsr.is_overrun_error() {
+            bits |= Self::OVERRUN;
+        }
+        if lsr.is_parity_error() {
+            bits |= Self::PARITY;
+        }
+        if lsr.is_framing_error() {
+            bits |= Self::FRAMING;
+        }
+        if lsr.is_break_condition() {
+            bits |= Self::BREAK;
+        }
+        Self(bits)
+    }
+    /// Check if no error is reported.
+    #[inline]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+    /// Check if receive FIFO overran before this character.
+    #[inline]
+    pub const fn is_overrun(self) -> bool {
+        self.0 & Self::OVERRUN != 0
+    }
+    /// Check if character has wrong parity.
+    #[inline]
+    pub const fn is_parity_error(self) -> bool {
+        self.0 & Self::PARITY != 0
+    }
+    /// Check if character has no valid stop bit.
+    #[inline]
+    pub const fn is_framing_error(self) -> bool {
+        self.0 & Self::FRAMING != 0
+    }
+    /// Check if line was held low for longer than a character, i.e. a break is received.
+    #[inline]
+    pub const fn is_break(self) -> bool {
+        self.0 & Self::BREAK != 0
+    }
+}
+
 impl core::ops::Deref for RegisterBlock {
     type Target = Uart16550<u32>;
 
@@ -86,10 +335,16 @@ impl core::ops::Deref for RegisterBlock {
 pub struct Serial<UART, const I: usize, PADS: Pads<I>> {
     uart: UART,
     pads: PADS,
+    // FIFO control register is write-only, keep trigger levels for FIFO resets
+    fifo_control: u8,
 }
 
 impl<UART: AsRef<RegisterBlock>, const I: usize, PADS: Pads<I>> Serial<UART, I, PADS> {
     /// Create a serial instance.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `RtsCts` flow control is requested but `pads` has no RTS and CTS pads.
     #[inline]
     pub fn new(
         uart: UART,
@@ -99,12 +354,20 @@ impl<UART: AsRef<RegisterBlock>, const I: usize, PADS: Pads<I>> Serial<UART, I,
         ccu: &ccu::RegisterBlock,
     ) -> Self {
         // 1. unwrap parameters
+        let config = config.into();
+        let fifo_control = config.fifo_control();
         let Config {
             baudrate,
             wordlength,
             parity,
             stopbits,
-        } = config.into();
+            flow_control,
+            ..
+        } = config;
+        assert!(
+            PADS::FLOW_CONTROL || flow_control == FlowControl::None,
+            "RTS/CTS flow control requires RTS and CTS pads"
+        );
         let bps = baudrate.0;
         // 2. init peripheral clocks
         // note(unsafe): async read and write using ccu registers
@@ -120,8 +383,8 @@ impl<UART: AsRef<RegisterBlock>, const I: usize, PADS: Pads<I>> Serial<UART, I,
                 .disable_thre(),
         );
         // 4. calculate and set baudrate
-        let uart_clk = (clocks.apb1.0 + 8 * bps) / (16 * bps);
-        uart.as_ref().write_divisor(uart_clk as u16);
+        let divisor = uart_divisor(ccu::apb1_frequency(ccu, clocks.psi), bps);
+        uart.as_ref().write_divisor(divisor);
         // 5. additional configurations
         let char_len = match wordlength {
             WordLength::Five => CharLen::FIVE,
@@ -141,8 +404,14 @@ impl<UART: AsRef<RegisterBlock>, const I: usize, PADS: Pads<I>> Serial<UART, I,
                 .set_one_stop_bit(one_stop_bit)
                 .set_parity(parity),
         );
+        uart.as_ref().set_flow_control(flow_control);
+        uart.as_ref().write_fifo_control(fifo_control);
         // 6. return the instance
-        Serial { uart, pads }
+        Serial {
+            uart,
+            pads,
+            fifo_control,
+        }
     }
     /// Get a temporary borrow on the underlying GPIO pads.
     #[inline]
@@ -152,6 +421,111 @@ impl<UART: AsRef<RegisterBlock>, const I: usize, PADS: Pads<I>> Serial<UART, I,
     {
         f(&mut self.pads)
     }
+    /// Get number of bytes waiting in transmit FIFO.
+    #[inline]
+    pub fn tx_fifo_level(&self) -> usize {
+        self.uart.as_ref().tx_fifo_level()
+    }
+    /// Get number of bytes waiting in receive FIFO.
+    #[inline]
+    pub fn rx_fifo_level(&self) -> usize {
+        self.uart.as_ref().rx_fifo_level()
+    }
+    /// Check if an idle-delimited frame has completed at `now_ns` using `detector`.
+    #[inline]
+    pub fn is_idle_frame_complete(&self, detector: &mut IdleDetector, now_ns: u64) -> bool {
+        detector.update(self.rx_fifo_level(), now_ns)
+    }
+    /// Check if receive timeout is pending.
+    ///
+    /// The controller raises it when data stays in receive FIFO and no
+    /// character arrives for four character times.
+    #[inline]
+    pub fn is_rx_timeout_pending(&self) -> bool {
+        self.uart.as_ref().is_rx_timeout_pending()
+    }
+    /// Hold transmit line low for at least `duration_bits` bit times.
+    ///
+    /// Pending transmission completes first. The break is timed by the
+    /// transmitter, so it is rounded up to whole character times.
+    #[inline]
+    pub fn send_break(&mut self, duration_bits: u8) {
+        uart_send_break(self.uart.as_ref(), duration_bits)
+    }
+    /// Block until a character is received, returning it with its receive errors.
+    #[inline]
+    pub fn read_with_status(&mut self) -> (u8, LineErrors) {
+        uart_read_with_status(self.uart.as_ref())
+    }
+    /// Change baudrate without reconfiguring other serial settings.
+    ///
+    /// Blocks until pending transmission completes, so that the last characters
+    /// are not sent with the new divisor.
+    #[inline]
+    pub fn set_baudrate(
+        &mut self,
+        baudrate: impl Into<Baud>,
+        clocks: &Clocks,
+        ccu: &ccu::RegisterBlock,
+    ) {
+        let uart_clock = ccu::apb1_frequency(ccu, clocks.psi);
+        let divisor = uart_divisor(uart_clock, baudrate.into().0);
+        self.wait_transmitter_empty();
+        self.uart.as_ref().write_divisor(divisor);
+    }
+    /// Discard all bytes in receive FIFO, e.g. garbage received while changing baudrate.
+    #[inline]
+    pub fn flush_rx(&mut self) {
+        let uart = self.uart.as_ref();
+        uart.write_fifo_control(self.fifo_control | RegisterBlock::FCR_RX_RESET);
+    }
+    /// Discard all bytes in transmit FIFO without sending them.
+    ///
+    /// Unlike `embedded_io::Write::flush`, this does not wait for pending transmission.
+    #[inline]
+    pub fn flush_tx(&mut self) {
+        let uart = self.uart.as_ref();
+        uart.write_fifo_control(self.fifo_control | RegisterBlock::FCR_TX_RESET);
+    }
+    /// Run `f` with transmit and receive FIFOs disabled, enabling them again afterwards.
+    ///
+    /// Without FIFOs every byte passes through a single holding register, so
+    /// each byte written starts transmission immediately and each byte received
+    /// is visible at once, which gives exact timing for protocol bring-up. In
+    /// exchange the software must keep up with every character: bytes received
+    /// before the previous one is read are lost as overruns, and RTS/CTS flow
+    /// control no longer has a receive FIFO level to act on.
+    ///
+    /// Pending transmission completes before FIFOs are switched, as switching
+    /// them clears their contents; bytes still in the receive FIFO are discarded.
+    #[inline]
+    pub fn with_fifos_disabled<F, T>(&mut self, f: F) -> T
+    where
+        F: FnOnce(&mut Self) -> T,
+    {
+        let fifo_control = self.fifo_control;
+        self.wait_transmitter_empty();
+        self.fifo_control = fifo_control & !RegisterBlock::FCR_FIFO_ENABLE;
+        self.uart.as_ref().write_fifo_control(self.fifo_control);
+        let ans = f(self);
+        self.wait_transmitter_empty();
+        self.fifo_control = fifo_control;
+        self.uart.as_ref().write_fifo_control(fifo_control);
+        ans
+    }
+    #[inline]
+    fn wait_transmitter_empty(&self) {
+        while !self
+            .uart
+            .as_ref()
+            .uart16550
+            .lsr()
+            .read()
+            .is_transmitter_empty()
+        {
+            core::hint::spin_loop()
+        }
+    }
     /// Close uart and release peripheral.
     #[inline]
     pub fn free(self, ccu: &ccu::RegisterBlock) -> (UART, PADS) {
@@ -194,9 +568,49 @@ pub struct ReceiveHalf<UART, const I: usize, PADS: Receive<I>> {
     _pads: PADS,
 }
 
+impl<UART: AsRef<RegisterBlock>, const I: usize, PADS: Transmit<I>> TransmitHalf<UART, I, PADS> {
+    /// Get number of bytes waiting in transmit FIFO.
+    #[inline]
+    pub fn tx_fifo_level(&self) -> usize {
+        self.uart.as_ref().tx_fifo_level()
+    }
+    /// Hold transmit line low for at least `duration_bits` bit times.
+    #[inline]
+    pub fn send_break(&mut self, duration_bits: u8) {
+        uart_send_break(self.uart.as_ref(), duration_bits)
+    }
+}
+
+impl<UART: AsRef<RegisterBlock>, const I: usize, PADS: Receive<I>> ReceiveHalf<UART, I, PADS> {
+    /// Get number of bytes waiting in receive FIFO.
+    #[inline]
+    pub fn rx_fifo_level(&self) -> usize {
+        self.uart.as_ref().rx_fifo_level()
+    }
+    /// Check if an idle-delimited frame has completed at `now_ns` using `detector`.
+    #[inline]
+    pub fn is_idle_frame_complete(&self, detector: &mut IdleDetector, now_ns: u64) -> bool {
+        detector.update(self.rx_fifo_level(), now_ns)
+    }
+    /// Check if receive timeout is pending.
+    #[inline]
+    pub fn is_rx_timeout_pending(&self) -> bool {
+        self.uart.as_ref().is_rx_timeout_pending()
+    }
+    /// Block until a character is received, returning it with its receive errors.
+    ///
+    /// Unlike `embedded_io::Read`, break, framing and parity errors are not discarded.
+    #[inline]
+    pub fn read_with_status(&mut self) -> (u8, LineErrors) {
+        uart_read_with_status(self.uart.as_ref())
+    }
+}
+
 /// Valid serial pads.
 pub trait Pads<const I: usize> {
     type Clock: ccu::ClockGate + ccu::ClockReset;
+    /// Whether RTS and CTS pads are included for hardware flow control.
+    const FLOW_CONTROL: bool = false;
 }
 
 /// Valid transmit pin for UART peripheral.
@@ -205,6 +619,12 @@ pub trait Transmit<const I: usize> {}
 /// Valid receive pin for UART peripheral.
 pub trait Receive<const I: usize> {}
 
+/// Valid request-to-send pin for UART peripheral.
+pub trait RequestToSend<const I: usize> {}
+
+/// Valid clear-to-send pin for UART peripheral.
+pub trait ClearToSend<const I: usize> {}
+
 #[inline]
 fn uart_write_blocking(
     uart: &RegisterBlock,
@@ -243,6 +663,72 @@ fn uart_read_blocking(
     Ok(len)
 }
 
+/// Get number of bits on the line for one character as currently configured in LCR.
+#[inline]
+fn line_bits_per_char(uart: &RegisterBlock) -> u8 {
+    let lcr = uart.uart16550.lcr().read();
+    let data = match lcr.char_len() {
+        CharLen::FIVE => 5,
+        CharLen::SIX => 6,
+        CharLen::SEVEN => 7,
+        CharLen::EIGHT => 8,
+    };
+    let parity = match lcr.parity() {
+        PARITY::NONE => 0,
+        PARITY::ODD | PARITY::EVEN => 1,
+    };
+    let stop = if lcr.is_one_stop_bit() { 1 } else { 2 };
+    1 + data + parity + stop
+}
+
+/// Calculate divisor latch value for `bps` from UART clock, rounded to nearest.
+#[inline]
+fn uart_divisor(uart_clock: Hertz, bps: u32) -> u16 {
+    ((uart_clock.0 + 8 * bps) / (16 * bps)) as u16
+}
+
+#[inline]
+fn uart_send_break(uart: &RegisterBlock, duration_bits: u8) {
+    // let the last character leave the shift register before the line is held low
+    while !uart.uart16550.lsr().read().is_transmitter_empty() {
+        core::hint::spin_loop()
+    }
+    let lcr = uart.uart16550.lcr().read();
+    uart.uart16550.lcr().write(lcr.enable_break_control());
+    // time the break with the transmitter itself: characters shifted out while
+    // break control is set keep the line low for whole character times
+    let chars = duration_bits.div_ceil(line_bits_per_char(uart)).max(1);
+    for _ in 0..chars {
+        uart.rbr_thr().tx_data(0);
+    }
+    while !uart.uart16550.lsr().read().is_transmitter_empty() {
+        core::hint::spin_loop()
+    }
+    uart.uart16550.lcr().write(lcr.disable_break_control());
+}
+
+#[inline]
+fn uart_read_with_status(uart: &RegisterBlock) -> (u8, LineErrors) {
+    loop {
+        let lsr = uart.uart16550.lsr().read();
+        if lsr.is_data_ready() {
+            // errors in line status belong to the character at the top of receive FIFO
+            return (uart.rbr_thr().rx_data(), LineErrors::from_line_status(lsr));
+        }
+        core::hint::spin_loop()
+    }
+}
+
+#[inline]
+fn uart_read_ready(uart: &RegisterBlock) -> Result<bool, core::convert::Infallible> {
+    Ok(uart.uart16550.lsr().read().is_data_ready())
+}
+
+#[inline]
+fn uart_write_ready(uart: &RegisterBlock) -> Result<bool, core::convert::Infallible> {
+    Ok(uart.uart16550.lsr().read().is_transmitter_fifo_empty())
+}
+
 impl<const I: usize, T, R> Pads<I> for (T, R)
 where
     T: Transmit<I>,
@@ -251,6 +737,17 @@ where
     type Clock = ccu::UART<I>;
 }
 
+impl<const I: usize, T, R, RTS, CTS> Pads<I> for (T, R, RTS, CTS)
+where
+    T: Transmit<I>,
+    R: Receive<I>,
+    RTS: RequestToSend<I>,
+    CTS: ClearToSend<I>,
+{
+    type Clock = ccu::UART<I>;
+    const FLOW_CONTROL: bool = true;
+}
+
 impl<UART: AsRef<RegisterBlock>, const I: usize, PADS: Pads<I>> embedded_io::ErrorType
     for Serial<UART, I, PADS>
 {
@@ -315,6 +812,42 @@ impl<UART: AsRef<RegisterBlock>, const I: usize, PADS: Receive<I>> embedded_io::
     }
 }
 
+impl<UART: AsRef<RegisterBlock>, const I: usize, PADS: Pads<I>> embedded_io::ReadReady
+    for Serial<UART, I, PADS>
+{
+    #[inline]
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        uart_read_ready(self.uart.as_ref())
+    }
+}
+
+impl<UART: AsRef<RegisterBlock>, const I: usize, PADS: Receive<I>> embedded_io::ReadReady
+    for ReceiveHalf<UART, I, PADS>
+{
+    #[inline]
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        uart_read_ready(self.uart.as_ref())
+    }
+}
+
+impl<UART: AsRef<RegisterBlock>, const I: usize, PADS: Pads<I>> embedded_io::WriteReady
+    for Serial<UART, I, PADS>
+{
+    #[inline]
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        uart_write_ready(self.uart.as_ref())
+    }
+}
+
+impl<UART: AsRef<RegisterBlock>, const I: usize, PADS: Transmit<I>> embedded_io::WriteReady
+    for TransmitHalf<UART, I, PADS>
+{
+    #[inline]
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        uart_write_ready(self.uart.as_ref())
+    }
+}
+
 /// UART Status Register.
 #[derive(Debug)]
 #[repr(transparent)]
@@ -379,10 +912,350 @@ impl UartStatus {
 
 #[cfg(test)]
 mod tests {
-    use super::RegisterBlock;
+    extern crate std;
+
+    use super::{
+        ClearToSend, Config, FlowControl, IdleDetector, Parity, Receive, RegisterBlock,
+        RequestToSend, RxFifoTrigger, Serial, StopBits, Transmit, TxFifoTrigger, WordLength,
+    };
+    use crate::ccu::{self, ApbClock, ApbClockSource, Clocks, PeriFactorN, PllPeri0Control};
+    use core::sync::atomic::{AtomicU32, Ordering};
+    use embedded_time::rate::Hertz;
     use memoffset::offset_of;
     #[test]
     fn offset_uart() {
         assert_eq!(offset_of!(RegisterBlock, usr), 0x7c);
+        assert_eq!(offset_of!(RegisterBlock, tfl), 0x80);
+        assert_eq!(offset_of!(RegisterBlock, rfl), 0x84);
+    }
+
+    #[test]
+    fn uart_fifo_levels() {
+        let memory = [const { AtomicU32::new(0) }; 0x22];
+        let uart = unsafe { &*(memory.as_ptr() as *const RegisterBlock) };
+        assert_eq!(uart.tx_fifo_level(), 0);
+        assert_eq!(uart.rx_fifo_level(), 0);
+
+        memory[0x80 / 4].store(0x0000_0040, Ordering::SeqCst);
+        memory[0x84 / 4].store(0x0000_0011, Ordering::SeqCst);
+        assert_eq!(uart.tx_fifo_level(), 64);
+        assert_eq!(uart.rx_fifo_level(), 17);
+
+        // reserved high bits are ignored
+        memory[0x80 / 4].store(0xFFFF_FE05, Ordering::SeqCst);
+        assert_eq!(uart.tx_fifo_level(), 5);
+    }
+
+    #[test]
+    fn idle_frame_detection() {
+        use embedded_time::rate::Extensions;
+        let config = Config::default();
+        assert_eq!(config.bits_per_char(), 10);
+        let odd = Config {
+            baudrate: 9600.Bd(),
+            wordlength: WordLength::Seven,
+            parity: Parity::Odd,
+            stopbits: StopBits::Two,
+            flow_control: FlowControl::None,
+            rx_fifo_trigger: RxFifoTrigger::One,
+            tx_fifo_trigger: TxFifoTrigger::Empty,
+        };
+        assert_eq!(odd.bits_per_char(), 11);
+
+        // 10 bits at 115200 Bd is 86805 ns per character
+        let mut detector = IdleDetector::new(&config, 3);
+        assert_eq!(detector.idle_ns(), 260_415);
+
+        // no data: never complete
+        assert!(!detector.update(0, 0));
+        assert!(!detector.update(0, 1_000_000));
+        // data arriving keeps the frame open
+        let t0 = 2_000_000;
+        assert!(!detector.update(1, t0));
+        assert!(!detector.update(4, t0 + 200_000));
+        // draining the FIFO is not receive activity
+        assert!(!detector.update(2, t0 + 400_000));
+        assert!(!detector.update(0, t0 + 460_414));
+        // idle for three character times after the last data
+        assert!(detector.update(0, t0 + 460_415));
+        // signalled only once per frame
+        assert!(!detector.update(0, t0 + 900_000));
+
+        // through the register block
+        let memory = [const { AtomicU32::new(0) }; 0x22];
+        let uart = unsafe { &*(memory.as_ptr() as *const RegisterBlock) };
+        memory[0x84 / 4].store(8, Ordering::SeqCst);
+        assert!(!detector.update(uart.rx_fifo_level(), 5_000_000));
+        assert!(detector.update(uart.rx_fifo_level(), 5_300_000));
+
+        // hardware receive timeout in interrupt identification register
+        memory[0x08 / 4].store(0xC1, Ordering::SeqCst);
+        assert!(!uart.is_rx_timeout_pending());
+        memory[0x08 / 4].store(0xC4, Ordering::SeqCst);
+        assert!(!uart.is_rx_timeout_pending());
+        memory[0x08 / 4].store(0xCC, Ordering::SeqCst);
+        assert!(uart.is_rx_timeout_pending());
+    }
+
+    struct MockUart<'a>(&'a [AtomicU32; 0x22]);
+
+    impl AsRef<RegisterBlock> for MockUart<'_> {
+        fn as_ref(&self) -> &RegisterBlock {
+            unsafe { &*(self.0 as *const _ as *const RegisterBlock) }
+        }
+    }
+
+    struct MockPad;
+    impl Transmit<0> for MockPad {}
+    impl Receive<0> for MockPad {}
+    impl RequestToSend<0> for MockPad {}
+    impl ClearToSend<0> for MockPad {}
+
+    #[test]
+    fn serial_flow_control() {
+        let memory = [const { AtomicU32::new(0) }; 0x22];
+        let ccu_memory = [const { AtomicU32::new(0) }; 0x400];
+        let ccu = unsafe { &*(ccu_memory.as_ptr() as *const ccu::RegisterBlock) };
+        let clocks = Clocks {
+            psi: Hertz(200_000_000u32),
+            apb1: Hertz(24_000_000u32),
+        };
+        let config = Config {
+            flow_control: FlowControl::RtsCts,
+            ..Config::default()
+        };
+        let pads = (MockPad, MockPad, MockPad, MockPad);
+        let serial = Serial::new(MockUart(&memory), pads, config, &clocks, ccu);
+        // FIFO enabled, receive trigger at half full
+        assert_eq!(memory[0x08 / 4].load(Ordering::SeqCst), 0x81);
+        // auto flow control enabled, RTS asserted
+        assert_eq!(memory[0x10 / 4].load(Ordering::SeqCst), 0x22);
+
+        let (uart, _) = serial.free(ccu);
+        uart.as_ref().set_flow_control(FlowControl::None);
+        assert_eq!(memory[0x10 / 4].load(Ordering::SeqCst), 0x02);
+        // trigger levels set before are kept
+        memory[0x08 / 4].store(0x51, Ordering::SeqCst);
+        uart.as_ref().set_flow_control(FlowControl::RtsCts);
+        assert_eq!(memory[0x08 / 4].load(Ordering::SeqCst), 0x51);
+        assert_eq!(memory[0x10 / 4].load(Ordering::SeqCst), 0x22);
+    }
+
+    #[test]
+    fn serial_fifo_triggers_and_flush() {
+        let memory = [const { AtomicU32::new(0) }; 0x22];
+        let ccu_memory = [const { AtomicU32::new(0) }; 0x400];
+        let ccu = unsafe { &*(ccu_memory.as_ptr() as *const ccu::RegisterBlock) };
+        let clocks = Clocks {
+            psi: Hertz(200_000_000u32),
+            apb1: Hertz(24_000_000u32),
+        };
+        let config = Config {
+            rx_fifo_trigger: RxFifoTrigger::Quarter,
+            tx_fifo_trigger: TxFifoTrigger::Two,
+            ..Config::default()
+        };
+        let mut serial = Serial::new(MockUart(&memory), (MockPad, MockPad), config, &clocks, ccu);
+        // FIFO enabled, receive trigger at 1/4 full, transmit trigger at 2 characters
+        assert_eq!(memory[0x08 / 4].load(Ordering::SeqCst), 0x51);
+        // FIFO resets keep trigger levels
+        serial.flush_rx();
+        assert_eq!(memory[0x08 / 4].load(Ordering::SeqCst), 0x53);
+        serial.flush_tx();
+        assert_eq!(memory[0x08 / 4].load(Ordering::SeqCst), 0x55);
+
+        let config = Config {
+            rx_fifo_trigger: RxFifoTrigger::TwoLessThanFull,
+            tx_fifo_trigger: TxFifoTrigger::Half,
+            ..Config::default()
+        };
+        Serial::new(MockUart(&memory), (MockPad, MockPad), config, &clocks, ccu);
+        assert_eq!(memory[0x08 / 4].load(Ordering::SeqCst), 0xF1);
+    }
+
+    #[test]
+    fn serial_with_fifos_disabled() {
+        let memory = [const { AtomicU32::new(0) }; 0x22];
+        let mut serial = Serial {
+            uart: MockUart(&memory),
+            pads: (MockPad, MockPad),
+            // FIFO enabled, receive trigger at 1/4 full
+            fifo_control: 0x41,
+        };
+        memory[0x08 / 4].store(0x41, Ordering::SeqCst);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                std::thread::sleep(std::time::Duration::from_millis(5));
+                // FIFOs must not be switched before transmitter is empty
+                assert_eq!(memory[0x08 / 4].load(Ordering::SeqCst), 0x41);
+                memory[0x14 / 4].store(0x60, Ordering::SeqCst);
+            });
+            let ans = serial.with_fifos_disabled(|serial| {
+                assert_eq!(memory[0x08 / 4].load(Ordering::SeqCst), 0x40);
+                // FIFO resets inside do not enable FIFOs again
+                serial.flush_rx();
+                assert_eq!(memory[0x08 / 4].load(Ordering::SeqCst), 0x42);
+                42
+            });
+            assert_eq!(ans, 42);
+        });
+        assert_eq!(memory[0x08 / 4].load(Ordering::SeqCst), 0x41);
+        serial.flush_tx();
+        assert_eq!(memory[0x08 / 4].load(Ordering::SeqCst), 0x45);
+    }
+
+    #[test]
+    fn serial_set_baudrate() {
+        use embedded_time::rate::Extensions;
+        let memory = [const { AtomicU32::new(0) }; 0x22];
+        let ccu_memory = [const { AtomicU32::new(0) }; 0x400];
+        let ccu = unsafe { &*(ccu_memory.as_ptr() as *const ccu::RegisterBlock) };
+        // UART clock is 24 MHz out of reset
+        let clocks = Clocks {
+            psi: Hertz(200_000_000u32),
+            apb1: Hertz(24_000_000u32),
+        };
+        let mut serial = Serial {
+            uart: MockUart(&memory),
+            pads: (MockPad, MockPad),
+            fifo_control: 0,
+        };
+        // 8N1, last character still in transmitter
+        memory[0x0C / 4].store(0x03, Ordering::SeqCst);
+        memory[0].store(0x55, Ordering::SeqCst);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                std::thread::sleep(std::time::Duration::from_millis(5));
+                // divisor must not be written before transmitter is empty
+                assert_eq!(memory[0].load(Ordering::SeqCst), 0x55);
+                memory[0x14 / 4].store(0x60, Ordering::SeqCst);
+            });
+            serial.set_baudrate(1_500_000.Bd(), &clocks, ccu);
+        });
+        assert_eq!(memory[0].load(Ordering::SeqCst), 1);
+        assert_eq!(memory[0x04 / 4].load(Ordering::SeqCst), 0);
+        // divisor latch access is disabled afterwards
+        assert_eq!(memory[0x0C / 4].load(Ordering::SeqCst), 0x03);
+
+        serial.set_baudrate(115200.Bd(), &clocks, ccu);
+        assert_eq!(memory[0].load(Ordering::SeqCst), 13);
+    }
+
+    #[test]
+    fn serial_read_write_ready() {
+        use embedded_io::{ReadReady, WriteReady};
+        let memory = [const { AtomicU32::new(0) }; 0x22];
+        let mut serial = Serial {
+            uart: MockUart(&memory),
+            pads: (MockPad, MockPad),
+            fifo_control: 0,
+        };
+        assert!(!serial.read_ready().unwrap());
+        assert!(!serial.write_ready().unwrap());
+
+        // data ready and transmit holding register empty in line status register
+        memory[0x14 / 4].store(0x21, Ordering::SeqCst);
+        assert!(serial.read_ready().unwrap());
+        assert!(serial.write_ready().unwrap());
+
+        let (mut tx, mut rx) = serial.split();
+        assert!(tx.write_ready().unwrap());
+        assert!(rx.read_ready().unwrap());
+        memory[0x14 / 4].store(0x01, Ordering::SeqCst);
+        assert!(!tx.write_ready().unwrap());
+        assert!(rx.read_ready().unwrap());
+    }
+
+    #[test]
+    fn break_send_and_detect() {
+        let memory = [const { AtomicU32::new(0) }; 0x22];
+        let serial = Serial {
+            uart: MockUart(&memory),
+            pads: (MockPad, MockPad),
+            fifo_control: 0,
+        };
+        let (mut tx, mut rx) = serial.split();
+        // 8N1, transmitter empty
+        memory[0x0C / 4].store(0x03, Ordering::SeqCst);
+        memory[0x14 / 4].store(0x60, Ordering::SeqCst);
+        tx.send_break(13);
+        // break control is released afterwards
+        assert_eq!(memory[0x0C / 4].load(Ordering::SeqCst), 0x03);
+
+        // break received with a zero character
+        memory[0x14 / 4].store(0x11, Ordering::SeqCst);
+        let (data, errors) = rx.read_with_status();
+        assert_eq!(data, 0);
+        assert!(errors.is_break());
+        assert!(!errors.is_framing_error());
+        memory[0x14 / 4].store(0x0D, Ordering::SeqCst);
+        memory[0].store(0x55, Ordering::SeqCst);
+        let (data, errors) = rx.read_with_status();
+        assert_eq!(data, 0x55);
+        assert!(errors.is_framing_error() && errors.is_parity_error());
+        assert!(!errors.is_overrun() && !errors.is_break());
+        memory[0x14 / 4].store(0x01, Ordering::SeqCst);
+        assert!(rx.read_with_status().1.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "RTS/CTS flow control requires RTS and CTS pads")]
+    fn serial_flow_control_without_pads() {
+        let memory = [const { AtomicU32::new(0) }; 0x22];
+        let ccu_memory = [const { AtomicU32::new(0) }; 0x400];
+        let ccu = unsafe { &*(ccu_memory.as_ptr() as *const ccu::RegisterBlock) };
+        let clocks = Clocks {
+            psi: Hertz(200_000_000u32),
+            apb1: Hertz(24_000_000u32),
+        };
+        let config = Config {
+            flow_control: FlowControl::RtsCts,
+            ..Config::default()
+        };
+        Serial::new(MockUart(&memory), (MockPad, MockPad), config, &clocks, ccu);
+    }
+
+    #[test]
+    fn uart_clock_from_ccu() {
+        let memory = [const { AtomicU32::new(0) }; 0x22];
+        let ccu_memory = [const { AtomicU32::new(0) }; 0x400];
+        let ccu = unsafe { &*(ccu_memory.as_ptr() as *const ccu::RegisterBlock) };
+        // a wrong fixed APB1 value must not be used
+        let clocks = Clocks {
+            psi: Hertz(200_000_000u32),
+            apb1: Hertz(1u32),
+        };
+        let divisor =
+            || memory[0x00].load(Ordering::SeqCst) | memory[0x04 / 4].load(Ordering::SeqCst) << 8;
+
+        // APB1 from 24 MHz oscillator: 24 MHz / 16 / 115200 rounds to 13
+        Serial::new(
+            MockUart(&memory),
+            (MockPad, MockPad),
+            Config::default(),
+            &clocks,
+            ccu,
+        );
+        assert_eq!(divisor(), 13);
+
+        // PLL_PERI(1X) = 24 MHz * 100 / 2 / 2 = 600 MHz, APB1 = 600 MHz / 2 / 3 = 100 MHz
+        unsafe {
+            ccu.pll_peri0_control
+                .write(PllPeri0Control::default().set_pll_n(99).set_pll_p0(1));
+            ccu.apb1_clock.write(
+                ApbClock::default()
+                    .set_clock_source(ApbClockSource::PllPeri1x)
+                    .set_factor_n(PeriFactorN::N2)
+                    .set_factor_m(2),
+            )
+        };
+        Serial::new(
+            MockUart(&memory),
+            (MockPad, MockPad),
+            Config::default(),
+            &clocks,
+            ccu,
+        );
+        assert_eq!(divisor(), 54);
     }
 }
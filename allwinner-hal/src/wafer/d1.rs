@@ -1,6 +1,6 @@
 //! SoC configuration on D1-like chips.
 
-use crate::{smhc, spi, uart};
+use crate::{ccu, smhc, spi, uart};
 use core::num::NonZeroU32;
 
 impl_gpio_pins! {
@@ -144,6 +144,8 @@ impl_pins_trait! {
     ('G', 5, 3): uart::Receive<5>;
     ('G', 6, 2): uart::Transmit<1>;
     ('G', 7, 2): uart::Receive<1>;
+    ('G', 8, 2): uart::RequestToSend<1>;
+    ('G', 9, 2): uart::ClearToSend<1>;
     ('G', 8, 5): uart::Transmit<3>;
     ('G', 9, 5): uart::Receive<3>;
     ('G', 17, 2): uart::Transmit<2>;
@@ -185,6 +187,14 @@ impl_pins_trait! {
     ('C', 7, 3): smhc::Data<3>;
 }
 
+/// Get clock configuration as left by the boot ROM.
+///
+/// Same as [`ccu::d1_boot_clocks`].
+#[inline]
+pub const fn boot_clocks() -> ccu::Clocks {
+    ccu::d1_boot_clocks()
+}
+
 /// Allwinner D1 interrupts.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 #[repr(u32)]
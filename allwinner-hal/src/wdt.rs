@@ -0,0 +1,385 @@
+//! Watchdog timer.
+//!
+//! Control, configuration, mode and soft reset registers only take a write
+//! with [`KEY`] in their key field; values of them built from `Default` carry it.
+
+use volatile_register::RW;
+
+/// Key to be written along with control, configuration, mode and soft reset registers.
+pub const KEY: u16 = 0x16AA;
+
+/// Watchdog timer registers.
+#[repr(C)]
+pub struct RegisterBlock {
+    /// 0x00 - Watchdog IRQ Enable register.
+    pub irq_enable: RW<u32>,
+    /// 0x04 - Watchdog Status register.
+    pub irq_status: RW<u32>,
+    /// 0x08 - Watchdog Software Reset register.
+    pub soft_reset: RW<SoftReset>,
+    _reserved0: u32,
+    /// 0x10 - Watchdog Control register.
+    pub control: RW<Control>,
+    /// 0x14 - Watchdog Configuration register.
+    pub config: RW<Config>,
+    /// 0x18 - Watchdog Mode register.
+    pub mode: RW<Mode>,
+    /// 0x1C - Watchdog Output Configuration register.
+    pub output_config: RW<u32>,
+}
+
+impl RegisterBlock {
+    /// Reset the whole system using the watchdog.
+    ///
+    /// Watchdog is set to reset the system on its shortest interval and
+    /// restarted; the reset happens about half a second later.
+    #[inline]
+    pub fn trigger_reset(&self) {
+        unsafe {
+            self.config.write(
+                Config::default()
+                    .set_action(Action::ResetSystem)
+                    .set_clock_source(ClockSource::Hosc32K),
+            );
+            self.mode
+                .write(Mode::default().set_interval(Interval::Ms500).enable());
+            self.control.write(Control::default().restart());
+        }
+    }
+    /// Restart watchdog counting from zero.
+    #[inline]
+    pub fn feed(&self) {
+        unsafe { self.control.write(Control::default().restart()) }
+    }
+    /// Stop the watchdog.
+    #[inline]
+    pub fn disable(&self) {
+        unsafe { self.mode.write(Mode::default().disable()) }
+    }
+}
+
+/// Watchdog Control register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct Control(u32);
+
+impl Control {
+    const KEY_FIELD: u32 = 0xFFFF << 16;
+    const WDOG_RESTART: u32 = 1 << 0;
+
+    /// Restart watchdog counting.
+    #[inline]
+    pub const fn restart(self) -> Self {
+        Self(self.0 | Self::WDOG_RESTART)
+    }
+    /// Is restart requested?
+    #[inline]
+    pub const fn is_restart(self) -> bool {
+        self.0 & Self::WDOG_RESTART != 0
+    }
+    /// Get key field.
+    #[inline]
+    pub const fn key(self) -> u16 {
+        ((self.0 & Self::KEY_FIELD) >> 16) as u16
+    }
+}
+
+impl Default for Control {
+    #[inline]
+    fn default() -> Self {
+        Self((KEY as u32) << 16)
+    }
+}
+
+/// Action on watchdog timeout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// Reset the whole system.
+    ResetSystem,
+    /// Only raise an interrupt.
+    InterruptOnly,
+}
+
+/// Watchdog clock source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ClockSource {
+    /// 32 kHz from HOSC divided by 750.
+    Hosc32K,
+    /// 32 kHz low speed oscillator.
+    Losc32K,
+}
+
+/// Watchdog Configuration register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct Config(u32);
+
+impl Config {
+    const KEY_FIELD: u32 = 0xFFFF << 16;
+    const WDOG_CLK_SRC: u32 = 1 << 8;
+    const WDOG_CONFIG: u32 = 0x3;
+
+    /// Set action on timeout.
+    #[inline]
+    pub const fn set_action(self, action: Action) -> Self {
+        let val = match action {
+            Action::ResetSystem => 0x1,
+            Action::InterruptOnly => 0x2,
+        };
+        Self((self.0 & !Self::WDOG_CONFIG) | val)
+    }
+    /// Get action on timeout.
+    #[inline]
+    pub const fn action(self) -> Option<Action> {
+        match self.0 & Self::WDOG_CONFIG {
+            0x1 => Some(Action::ResetSystem),
+            0x2 => Some(Action::InterruptOnly),
+            _ => None,
+        }
+    }
+    /// Set clock source.
+    #[inline]
+    pub const fn set_clock_source(self, source: ClockSource) -> Self {
+        match source {
+            ClockSource::Hosc32K => Self(self.0 & !Self::WDOG_CLK_SRC),
+            ClockSource::Losc32K => Self(self.0 | Self::WDOG_CLK_SRC),
+        }
+    }
+    /// Get clock source.
+    #[inline]
+    pub const fn clock_source(self) -> ClockSource {
+        if self.0 & Self::WDOG_CLK_SRC != 0 {
+            ClockSource::Losc32K
+        } else {
+            ClockSource::Hosc32K
+        }
+    }
+    /// Get key field.
+    #[inline]
+    pub const fn key(self) -> u16 {
+        ((self.0 & Self::KEY_FIELD) >> 16) as u16
+    }
+}
+
+impl Default for Config {
+    #[inline]
+    fn default() -> Self {
+        Self((KEY as u32) << 16)
+    }
+}
+
+/// Watchdog timeout interval.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Interval {
+    /// 0.5 seconds.
+    Ms500,
+    /// 1 second.
+    S1,
+    /// 2 seconds.
+    S2,
+    /// 3 seconds.
+    S3,
+    /// 4 seconds.
+    S4,
+    /// 5 seconds.
+    S5,
+    /// 6 seconds.
+    S6,
+    /// 8 seconds.
+    S8,
+    /// 10 seconds.
+    S10,
+    /// 12 seconds.
+    S12,
+    /// 14 seconds.
+    S14,
+    /// 16 seconds.
+    S16,
+}
+
+/// Watchdog Mode register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct Mode(u32);
+
+impl Mode {
+    const KEY_FIELD: u32 = 0xFFFF << 16;
+    const WDOG_INTV_VALUE: u32 = 0xF << 4;
+    const WDOG_EN: u32 = 1 << 0;
+
+    /// Set timeout interval.
+    #[inline]
+    pub const fn set_interval(self, interval: Interval) -> Self {
+        let val = match interval {
+            Interval::Ms500 => 0x0,
+            Interval::S1 => 0x1,
+            Interval::S2 => 0x2,
+            Interval::S3 => 0x3,
+            Interval::S4 => 0x4,
+            Interval::S5 => 0x5,
+            Interval::S6 => 0x6,
+            Interval::S8 => 0x7,
+            Interval::S10 => 0x8,
+            Interval::S12 => 0x9,
+            Interval::S14 => 0xA,
+            Interval::S16 => 0xB,
+        };
+        Self((self.0 & !Self::WDOG_INTV_VALUE) | (val << 4))
+    }
+    /// Get timeout interval.
+    #[inline]
+    pub const fn interval(self) -> Option<Interval> {
+        match (self.0 & Self::WDOG_INTV_VALUE) >> 4 {
+            0x0 => Some(Interval::Ms500),
+            0x1 => Some(Interval::S1),
+            0x2 => Some(Interval::S2),
+            0x3 => Some(Interval::S3),
+            0x4 => Some(Interval::S4),
+            0x5 => Some(Interval::S5),
+            0x6 => Some(Interval::S6),
+            0x7 => Some(Interval::S8),
+            0x8 => Some(Interval::S10),
+            0x9 => Some(Interval::S12),
+            0xA => Some(Interval::S14),
+            0xB => Some(Interval::S16),
+            _ => None,
+        }
+    }
+    /// Enable watchdog.
+    #[inline]
+    pub const fn enable(self) -> Self {
+        Self(self.0 | Self::WDOG_EN)
+    }
+    /// Disable watchdog.
+    #[inline]
+    pub const fn disable(self) -> Self {
+        Self(self.0 & !Self::WDOG_EN)
+    }
+    /// Is watchdog enabled?
+    #[inline]
+    pub const fn is_enabled(self) -> bool {
+        self.0 & Self::WDOG_EN != 0
+    }
+    /// Get key field.
+    #[inline]
+    pub const fn key(self) -> u16 {
+        ((self.0 & Self::KEY_FIELD) >> 16) as u16
+    }
+}
+
+impl Default for Mode {
+    #[inline]
+    fn default() -> Self {
+        Self((KEY as u32) << 16)
+    }
+}
+
+/// Watchdog Software Reset register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct SoftReset(u32);
+
+impl SoftReset {
+    const KEY_FIELD: u32 = 0xFFFF << 16;
+    const SOFT_RST_EN: u32 = 1 << 0;
+
+    /// Reset the system immediately.
+    #[inline]
+    pub const fn assert_reset(self) -> Self {
+        Self(self.0 | Self::SOFT_RST_EN)
+    }
+    /// Is reset asserted?
+    #[inline]
+    pub const fn is_reset_asserted(self) -> bool {
+        self.0 & Self::SOFT_RST_EN != 0
+    }
+    /// Get key field.
+    #[inline]
+    pub const fn key(self) -> u16 {
+        ((self.0 & Self::KEY_FIELD) >> 16) as u16
+    }
+}
+
+impl Default for SoftReset {
+    #[inline]
+    fn default() -> Self {
+        Self((KEY as u32) << 16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Action, ClockSource, Config, Control, Interval, Mode, RegisterBlock, SoftReset, KEY,
+    };
+    use core::sync::atomic::{AtomicU32, Ordering};
+    use memoffset::offset_of;
+
+    #[test]
+    fn offset_wdt() {
+        assert_eq!(offset_of!(RegisterBlock, irq_enable), 0x00);
+        assert_eq!(offset_of!(RegisterBlock, irq_status), 0x04);
+        assert_eq!(offset_of!(RegisterBlock, soft_reset), 0x08);
+        assert_eq!(offset_of!(RegisterBlock, control), 0x10);
+        assert_eq!(offset_of!(RegisterBlock, config), 0x14);
+        assert_eq!(offset_of!(RegisterBlock, mode), 0x18);
+        assert_eq!(offset_of!(RegisterBlock, output_config), 0x1C);
+    }
+
+    #[test]
+    fn struct_wdt_functions() {
+        let val = Control::default().restart();
+        assert!(val.is_restart());
+        assert_eq!(val.key(), KEY);
+        assert_eq!(val.0, 0x16AA_0001);
+
+        let mut val = Config::default();
+        assert_eq!(val.action(), None);
+        val = val.set_action(Action::ResetSystem);
+        assert_eq!(val.action(), Some(Action::ResetSystem));
+        assert_eq!(val.0, 0x16AA_0001);
+        val = val.set_action(Action::InterruptOnly);
+        assert_eq!(val.action(), Some(Action::InterruptOnly));
+        val = val.set_clock_source(ClockSource::Losc32K);
+        assert_eq!(val.clock_source(), ClockSource::Losc32K);
+        assert_eq!(val.0, 0x16AA_0102);
+        val = val.set_clock_source(ClockSource::Hosc32K);
+        assert_eq!(val.clock_source(), ClockSource::Hosc32K);
+        assert_eq!(val.key(), KEY);
+
+        let mut val = Mode::default();
+        assert_eq!(val.interval(), Some(Interval::Ms500));
+        val = val.set_interval(Interval::S16).enable();
+        assert_eq!(val.interval(), Some(Interval::S16));
+        assert!(val.is_enabled());
+        assert_eq!(val.0, 0x16AA_00B1);
+        val = val.set_interval(Interval::S8);
+        assert_eq!(val.interval(), Some(Interval::S8));
+        assert_eq!(val.0, 0x16AA_0071);
+        assert_eq!(Mode(0xC0).interval(), None);
+        val = val.disable();
+        assert!(!val.is_enabled());
+        assert_eq!(val.0, 0x16AA_0070);
+
+        let val = SoftReset::default().assert_reset();
+        assert!(val.is_reset_asserted());
+        assert_eq!(val.key(), KEY);
+        assert_eq!(val.0, 0x16AA_0001);
+    }
+
+    #[test]
+    fn wdt_trigger_reset() {
+        let memory = [const { AtomicU32::new(0) }; 8];
+        let wdt = unsafe { &*(memory.as_ptr() as *const RegisterBlock) };
+        wdt.trigger_reset();
+        // reset whole system, HOSC/750 clock source
+        assert_eq!(memory[0x14 / 4].load(Ordering::SeqCst), 0x16AA_0001);
+        // 0.5 seconds, enabled
+        assert_eq!(memory[0x18 / 4].load(Ordering::SeqCst), 0x16AA_0001);
+        // restarted
+        assert_eq!(memory[0x10 / 4].load(Ordering::SeqCst), 0x16AA_0001);
+
+        wdt.disable();
+        assert_eq!(memory[0x18 / 4].load(Ordering::SeqCst), 0x16AA_0000);
+    }
+}
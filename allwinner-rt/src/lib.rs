@@ -130,6 +130,27 @@ core::arch::global_asm! {
     sym start,
 }
 
+/// Critical section of the only running hart, masking machine interrupts.
+struct SingleHartCriticalSection;
+
+critical_section::set_impl!(SingleHartCriticalSection);
+
+unsafe impl critical_section::Impl for SingleHartCriticalSection {
+    #[inline]
+    unsafe fn acquire() -> critical_section::RawRestoreState {
+        let mstatus: usize;
+        // clear `mstatus.MIE`, returning whether it was set
+        unsafe { core::arch::asm!("csrrci {}, mstatus, 0b1000", out(reg) mstatus) };
+        mstatus & 0b1000 != 0
+    }
+    #[inline]
+    unsafe fn release(was_enabled: critical_section::RawRestoreState) {
+        if was_enabled {
+            unsafe { core::arch::asm!("csrsi mstatus, 0b1000") };
+        }
+    }
+}
+
 #[cfg(any(feature = "nezha", feature = "lichee"))]
 pub use {
     self::soc::d1::{Peripherals, __rom_init_params},
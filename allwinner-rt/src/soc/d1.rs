@@ -1,7 +1,10 @@
 //! D1-H, D1s, F133, F133A/B chip platforms.
 
-use allwinner_hal::{ccu::Clocks, gpio::Disabled, wafer::d1::Pads};
-use embedded_time::rate::Extensions;
+use allwinner_hal::{
+    ccu::Clocks,
+    gpio::Disabled,
+    wafer::d1::{self, Pads},
+};
 
 /// ROM runtime peripheral ownership and configurations.
 pub struct Peripherals<'a> {
@@ -9,8 +12,12 @@ pub struct Peripherals<'a> {
     pub gpio: Pads<'a>,
     /// Clock control unit peripheral.
     pub ccu: CCU,
+    /// Direct Memory Access Controller.
+    pub dma: DMA,
     /// Universal Asynchronous Receiver/Transmitter 0.
     pub uart0: UART0,
+    /// Watchdog timer.
+    pub wdt: WDT,
     /// Common control peripheral of DDR SDRAM.
     pub com: COM,
     /// Memory controller physical layer (PHY) of DDR SDRAM.
@@ -32,8 +39,12 @@ soc! {
     pub struct GPIO => 0x02000000, allwinner_hal::gpio::RegisterBlock;
     /// Clock control unit peripheral.
     pub struct CCU => 0x02001000, allwinner_hal::ccu::RegisterBlock;
+    /// Direct Memory Access Controller.
+    pub struct DMA => 0x03002000, allwinner_hal::dma::RegisterBlock;
     /// Universal Asynchronous Receiver/Transmitter 0.
     pub struct UART0 => 0x02500000, allwinner_hal::uart::RegisterBlock;
+    /// Watchdog timer.
+    pub struct WDT => 0x020500A0, allwinner_hal::wdt::RegisterBlock;
     /// Common control peripheral of DDR SDRAM.
     pub struct COM => 0x03102000, allwinner_hal::com::RegisterBlock;
     /// Memory controller physical layer (PHY) of DDR SDRAM.
@@ -146,7 +157,9 @@ pub fn __rom_init_params() -> (Peripherals<'static>, Clocks) {
             pg18: unsafe { Disabled::__new(&_GPIO) },
         },
         ccu: CCU { _private: () },
+        dma: DMA { _private: () },
         uart0: UART0 { _private: () },
+        wdt: WDT { _private: () },
         com: COM { _private: () },
         phy: PHY { _private: () },
         smhc0: SMHC0 { _private: () },
@@ -155,9 +168,5 @@ pub fn __rom_init_params() -> (Peripherals<'static>, Clocks) {
         spi0: SPI0 { _private: () },
         plic: PLIC { _private: () },
     };
-    let clocks = Clocks {
-        psi: 600_000_000.Hz(),
-        apb1: 24_000_000.Hz(),
-    };
-    (peripherals, clocks)
+    (peripherals, d1::boot_clocks())
 }
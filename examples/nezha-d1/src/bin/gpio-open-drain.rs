@@ -0,0 +1,27 @@
+#![no_std]
+#![no_main]
+
+use allwinner_hal::gpio::{DriveLevel, Pull};
+use allwinner_rt::{entry, Clocks, Peripherals};
+use panic_halt as _;
+
+#[entry]
+fn main(p: Peripherals, _c: Clocks) {
+    // PB0 as open-drain I2C SCL line: released high by pull-up, driven low as output
+    let mut scl = p.gpio.pb0.into_flex();
+    scl.set_pull(Pull::Up);
+    scl.set_drive_strength(DriveLevel::L1);
+    // data register of PB0 is low out of reset, so output mode drives the line low
+    loop {
+        scl.set_function(1);
+        delay();
+        scl.set_function(0);
+        delay();
+    }
+}
+
+fn delay() {
+    for _ in 0..1000 {
+        core::hint::spin_loop();
+    }
+}
@@ -45,7 +45,7 @@ fn main(p: Peripherals, c: Clocks) {
     let sdcard = match SdCard::new(&mut smhc) {
         Ok(card) => card,
         Err(e) => {
-            writeln!(serial, "Failed to initialize SD card: {:?}", e).ok();
+            writeln!(serial, "Failed to initialize SD card: {}", e).ok();
             loop {}
         }
     };
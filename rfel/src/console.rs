@@ -0,0 +1,96 @@
+//! Serial console bridge for payloads started by `exec`.
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    time::Duration,
+};
+
+/// Timeout of one serial read; reads are retried until the console is closed.
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Open host serial port `path` at `baud` for console output.
+pub fn open(path: &str, baud: u32) -> io::Result<Box<dyn serialport::SerialPort>> {
+    serialport::new(path, baud)
+        .timeout(READ_TIMEOUT)
+        .open()
+        .map_err(io::Error::from)
+}
+
+/// Copy console output from `port` to `out` until `port` is closed.
+///
+/// Read timeouts are ignored, so this function runs until interrupted (Ctrl-C)
+/// if the serial port stays open.
+pub fn bridge(port: &mut impl Read, out: &mut impl Write) -> io::Result<()> {
+    let mut buf = [0u8; 1024];
+    loop {
+        match port.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => {
+                out.write_all(&buf[..n])?;
+                out.flush()?;
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::Interrupted) => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Execute payload and then bridge its console output to `out`.
+///
+/// Console is opened only after `exec` returns, so that a failed `exec` never
+/// leaves the serial port held.
+pub fn exec_then_bridge<R: Read, E: From<io::Error>>(
+    exec: impl FnOnce() -> Result<(), E>,
+    open_console: impl FnOnce() -> io::Result<R>,
+    out: &mut impl Write,
+) -> Result<(), E> {
+    exec()?;
+    let mut port = open_console()?;
+    Ok(bridge(&mut port, out)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::exec_then_bridge;
+    use std::{
+        cell::RefCell,
+        io::{self, ErrorKind, Read},
+    };
+
+    /// Serial port yielding scripted reads.
+    struct MockPort(Vec<io::Result<Vec<u8>>>);
+
+    impl Read for MockPort {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                return Ok(0);
+            }
+            let data = self.0.remove(0)?;
+            buf[..data.len()].copy_from_slice(&data);
+            Ok(data.len())
+        }
+    }
+
+    #[test]
+    fn exec_before_bridge() {
+        let events = RefCell::new(Vec::new());
+        let mut out = Vec::new();
+        exec_then_bridge(
+            || {
+                events.borrow_mut().push("exec");
+                Ok::<_, io::Error>(())
+            },
+            || {
+                events.borrow_mut().push("open");
+                Ok(MockPort(vec![
+                    Ok(b"hello ".to_vec()),
+                    Err(ErrorKind::TimedOut.into()),
+                    Ok(b"world\n".to_vec()),
+                ]))
+            },
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(*events.borrow(), ["exec", "open"]);
+        assert_eq!(out, b"hello world\n");
+    }
+}
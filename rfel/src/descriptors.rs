@@ -0,0 +1,98 @@
+//! USB descriptor inspection.
+use core::fmt::Write;
+use nusb::{
+    descriptors::InterfaceAltSetting,
+    transfer::{Direction, EndpointType},
+};
+
+/// Find bulk IN and bulk OUT endpoint addresses in interface alternate settings.
+///
+/// If more than one endpoint of a direction exists, the last one is returned.
+pub fn find_bulk_endpoints<'a>(
+    alt_settings: impl Iterator<Item = InterfaceAltSetting<'a>>,
+) -> (Option<u8>, Option<u8>) {
+    let mut endpoint_in = None;
+    let mut endpoint_out = None;
+    for descriptor in alt_settings {
+        for endpoint in descriptor.endpoints() {
+            if endpoint.transfer_type() != EndpointType::Bulk {
+                continue;
+            }
+            match endpoint.direction() {
+                Direction::In => endpoint_in = Some(endpoint.address()),
+                Direction::Out => endpoint_out = Some(endpoint.address()),
+            }
+        }
+    }
+    (endpoint_in, endpoint_out)
+}
+
+/// Format interfaces, alternate settings and endpoints for display.
+pub fn format_alt_settings<'a>(
+    alt_settings: impl Iterator<Item = InterfaceAltSetting<'a>>,
+) -> String {
+    let mut ans = String::new();
+    for descriptor in alt_settings {
+        let _ = writeln!(
+            ans,
+            "interface {}, alt setting {}, class 0x{:02x}, subclass 0x{:02x}, protocol 0x{:02x}",
+            descriptor.interface_number(),
+            descriptor.alternate_setting(),
+            descriptor.class(),
+            descriptor.subclass(),
+            descriptor.protocol()
+        );
+        for endpoint in descriptor.endpoints() {
+            let direction = match endpoint.direction() {
+                Direction::In => "in",
+                Direction::Out => "out",
+            };
+            let transfer_type = match endpoint.transfer_type() {
+                EndpointType::Control => "control",
+                EndpointType::Isochronous => "isochronous",
+                EndpointType::Bulk => "bulk",
+                EndpointType::Interrupt => "interrupt",
+            };
+            let _ = writeln!(
+                ans,
+                "  endpoint 0x{:02x}: {}, {}, max packet size {}",
+                endpoint.address(),
+                direction,
+                transfer_type,
+                endpoint.max_packet_size()
+            );
+        }
+    }
+    ans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_bulk_endpoints, format_alt_settings};
+    use nusb::descriptors::Configuration;
+
+    /// Configuration with one vendor interface having bulk in, bulk out and interrupt in endpoints.
+    const CONFIGURATION: &[u8] = &[
+        0x09, 0x02, 0x27, 0x00, 0x01, 0x01, 0x00, 0x80, 0x32, // configuration
+        0x09, 0x04, 0x00, 0x00, 0x03, 0xff, 0xff, 0xff, 0x00, // interface 0
+        0x07, 0x05, 0x81, 0x02, 0x00, 0x02, 0x00, // endpoint 0x81, bulk in
+        0x07, 0x05, 0x01, 0x02, 0x00, 0x02, 0x00, // endpoint 0x01, bulk out
+        0x07, 0x05, 0x82, 0x03, 0x40, 0x00, 0x01, // endpoint 0x82, interrupt in
+    ];
+
+    #[test]
+    fn format_synthetic_descriptors() {
+        let configuration = Configuration::new(CONFIGURATION);
+        assert_eq!(
+            format_alt_settings(configuration.interface_alt_settings()),
+            "interface 0, alt setting 0, class 0xff, subclass 0xff, protocol 0xff\n\
+             \x20 endpoint 0x81: in, bulk, max packet size 512\n\
+             \x20 endpoint 0x01: out, bulk, max packet size 512\n\
+             \x20 endpoint 0x82: in, interrupt, max packet size 64\n"
+        );
+        assert_eq!(
+            find_bulk_endpoints(configuration.interface_alt_settings()),
+            (Some(0x81), Some(0x01))
+        );
+    }
+}
@@ -0,0 +1,240 @@
+//! eGON boot image header.
+use core::fmt;
+
+/// Magic of an eGON.BT0 boot image.
+pub const EGON_MAGIC: [u8; 8] = *b"eGON.BT0";
+/// Stamp value placed in checksum field while the checksum is being calculated.
+pub const CHECKSUM_STAMP: u32 = 0x5F0A6C39;
+/// Address where boot ROM loads eGON images on D1 series chips.
+pub const D1_LOAD_ADDRESS: u32 = 0x0002_0000;
+
+/// Offset of eGON header in image, after the leading jump instruction.
+const HEAD_OFFSET: usize = 4;
+/// Offset of checksum field in image.
+const CHECKSUM_OFFSET: usize = HEAD_OFFSET + 8;
+/// Offset of run address field in image.
+const RUN_ADDR_OFFSET: usize = HEAD_OFFSET + 28;
+/// Size of jump instruction and header in bytes.
+const HEAD_SIZE: usize = HEAD_OFFSET + 44;
+
+/// eGON.BT0 image header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EgonHead {
+    /// Jump instruction at the very beginning of image.
+    pub jump_instruction: u32,
+    /// Checksum recorded in image.
+    pub checksum: u32,
+    /// Total length of image in bytes.
+    pub length: u32,
+    /// Size of public header.
+    pub pub_head_size: u32,
+    /// Version of public header.
+    pub pub_head_version: [u8; 4],
+    /// Return address.
+    pub return_addr: u32,
+    /// Run address; zero if image runs where boot ROM loads it.
+    pub run_addr: u32,
+    /// Boot CPU.
+    pub boot_cpu: u32,
+    /// Platform information.
+    pub platform: [u8; 8],
+}
+
+/// Error while parsing an eGON image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EgonError {
+    /// Image is shorter than eGON header.
+    TooShort,
+    /// eGON magic is not found.
+    InvalidMagic,
+    /// Length field is not a multiple of 4, or is larger than image.
+    InvalidLength(u32),
+}
+
+impl fmt::Display for EgonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EgonError::TooShort => write!(f, "image is too short to contain an eGON header"),
+            EgonError::InvalidMagic => write!(f, "eGON.BT0 magic not found"),
+            EgonError::InvalidLength(length) => {
+                write!(f, "invalid eGON image length 0x{:x}", length)
+            }
+        }
+    }
+}
+
+impl EgonHead {
+    /// Parse eGON header from the start of an image.
+    pub fn parse(image: &[u8]) -> Result<EgonHead, EgonError> {
+        if image.len() < HEAD_SIZE {
+            return Err(EgonError::TooShort);
+        }
+        if image[HEAD_OFFSET..HEAD_OFFSET + 8] != EGON_MAGIC {
+            return Err(EgonError::InvalidMagic);
+        }
+        let word =
+            |offset: usize| u32::from_le_bytes(image[offset..offset + 4].try_into().unwrap());
+        let head = EgonHead {
+            jump_instruction: word(0),
+            checksum: word(CHECKSUM_OFFSET),
+            length: word(16),
+            pub_head_size: word(20),
+            pub_head_version: image[24..28].try_into().unwrap(),
+            return_addr: word(28),
+            run_addr: word(RUN_ADDR_OFFSET),
+            boot_cpu: word(36),
+            platform: image[40..48].try_into().unwrap(),
+        };
+        if !head.length.is_multiple_of(4)
+            || (head.length as usize) < HEAD_SIZE
+            || head.length as usize > image.len()
+        {
+            return Err(EgonError::InvalidLength(head.length));
+        }
+        Ok(head)
+    }
+
+    /// Address where image is loaded, using D1 boot ROM default if run address is not set.
+    #[inline]
+    pub fn load_address(&self) -> u32 {
+        if self.run_addr != 0 {
+            self.run_addr
+        } else {
+            D1_LOAD_ADDRESS
+        }
+    }
+
+    /// Entry point decoded from leading jump instruction.
+    ///
+    /// Returns `None` if the instruction is neither a RISC-V `j` nor an ARM `b`.
+    #[inline]
+    pub fn entry_point(&self) -> Option<u32> {
+        jump_offset(self.jump_instruction).map(|offset| self.load_address().wrapping_add(offset))
+    }
+
+    /// Check if recorded checksum matches image contents.
+    #[inline]
+    pub fn checksum_valid(&self, image: &[u8]) -> bool {
+        checksum(&image[..self.length as usize]) == self.checksum
+    }
+}
+
+/// Set run address of eGON `image` to `new_base`, and update its checksum.
+///
+/// Returns the previous load address.
+pub fn relocate(image: &mut [u8], new_base: u32) -> Result<u32, EgonError> {
+    let head = EgonHead::parse(image)?;
+    image[RUN_ADDR_OFFSET..RUN_ADDR_OFFSET + 4].copy_from_slice(&new_base.to_le_bytes());
+    let checksum = checksum(&image[..head.length as usize]);
+    image[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4].copy_from_slice(&checksum.to_le_bytes());
+    Ok(head.load_address())
+}
+
+/// Calculate eGON checksum over the image with checksum field replaced by stamp value.
+#[inline]
+pub fn checksum(image: &[u8]) -> u32 {
+    checksum_at(image, CHECKSUM_OFFSET)
+}
+
+/// Calculate eGON-style checksum with the checksum field at `checksum_offset`.
+///
+/// TOC0 and TOC1 containers use the same sum, with their own field offsets.
+pub(crate) fn checksum_at(image: &[u8], checksum_offset: usize) -> u32 {
+    image
+        .chunks(4)
+        .enumerate()
+        .map(|(i, chunk)| {
+            if i * 4 == checksum_offset {
+                return CHECKSUM_STAMP;
+            }
+            let mut word = [0u8; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            u32::from_le_bytes(word)
+        })
+        .fold(0u32, u32::wrapping_add)
+}
+
+/// Decode forward offset of a RISC-V `jal x0` or an ARM `b` instruction.
+fn jump_offset(instruction: u32) -> Option<u32> {
+    if instruction & 0xfff == 0x06f {
+        let imm = ((instruction >> 31) & 0x1) << 20
+            | ((instruction >> 21) & 0x3ff) << 1
+            | ((instruction >> 20) & 0x1) << 11
+            | ((instruction >> 12) & 0xff) << 12;
+        // sign extend from bit 20
+        Some(((imm << 11) as i32 >> 11) as u32)
+    } else if instruction >> 24 == 0xea {
+        // ARM `b`, offset relative to pc + 8
+        let imm = (instruction & 0x00ff_ffff) << 2;
+        Some((((imm << 6) as i32 >> 6) as u32).wrapping_add(8))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{checksum, jump_offset, relocate, EgonError, EgonHead};
+
+    const FIXTURE: &[u8] = include_bytes!("../fixtures/egon-d1.bin");
+
+    #[test]
+    fn parse_fixture_image() {
+        let head = EgonHead::parse(FIXTURE).unwrap();
+        assert_eq!(head.jump_instruction, 0x0600006f);
+        assert_eq!(head.checksum, 0xcdc4d20e);
+        assert_eq!(head.length, 512);
+        assert_eq!(&head.pub_head_version, b"3000");
+        assert_eq!(head.run_addr, 0);
+        assert_eq!(head.load_address(), 0x0002_0000);
+        assert_eq!(head.entry_point(), Some(0x0002_0060));
+        assert!(head.checksum_valid(FIXTURE));
+        assert_eq!(checksum(FIXTURE), 0xcdc4d20e);
+
+        let mut corrupted = FIXTURE.to_vec();
+        corrupted[0x100] ^= 0xff;
+        let head = EgonHead::parse(&corrupted).unwrap();
+        assert!(!head.checksum_valid(&corrupted));
+    }
+
+    #[test]
+    fn parse_invalid_image() {
+        assert_eq!(EgonHead::parse(&FIXTURE[..16]), Err(EgonError::TooShort));
+        let mut image = FIXTURE.to_vec();
+        image[4] = b'x';
+        assert_eq!(EgonHead::parse(&image), Err(EgonError::InvalidMagic));
+        assert_eq!(
+            EgonHead::parse(&FIXTURE[..256]),
+            Err(EgonError::InvalidLength(512))
+        );
+    }
+
+    #[test]
+    fn decode_jump_instruction() {
+        assert_eq!(jump_offset(0x0600006f), Some(0x60));
+        assert_eq!(jump_offset(0xea000016), Some(0x60));
+        assert_eq!(jump_offset(0x00000013), None);
+    }
+
+    #[test]
+    fn relocate_run_address() {
+        let mut image = FIXTURE.to_vec();
+        assert_eq!(relocate(&mut image, 0x4000_0000), Ok(0x0002_0000));
+        let head = EgonHead::parse(&image).unwrap();
+        assert_eq!(head.run_addr, 0x4000_0000);
+        assert_eq!(head.load_address(), 0x4000_0000);
+        assert_eq!(head.entry_point(), Some(0x4000_0060));
+        assert!(head.checksum_valid(&image));
+        // only run address and checksum change
+        let changed: Vec<_> = (0..image.len())
+            .filter(|&i| image[i] != FIXTURE[i])
+            .collect();
+        assert!(changed
+            .iter()
+            .all(|&i| (12..16).contains(&i) || (32..36).contains(&i)));
+
+        assert_eq!(relocate(&mut image, 0), Ok(0x4000_0000));
+        assert_eq!(image, FIXTURE);
+        assert_eq!(relocate(&mut image[..16], 0), Err(EgonError::TooShort));
+    }
+}
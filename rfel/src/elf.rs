@@ -0,0 +1,409 @@
+//! Loadable segments of ELF images.
+use core::fmt;
+
+/// Magic at the start of every ELF file.
+pub const ELF_MAGIC: [u8; 4] = *b"\x7fELF";
+/// Program header type of a loadable segment.
+pub const PT_LOAD: u32 = 1;
+/// Machine type of RISC-V.
+pub const EM_RISCV: u16 = 243;
+/// Largest segment in memory, the whole 32-bit FEL address space.
+pub const MAX_SEGMENT_SIZE: u64 = 1 << 32;
+
+/// Loadable segment of an ELF image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment<'a> {
+    /// Physical address the segment is loaded at.
+    pub paddr: u64,
+    /// Virtual address of the segment.
+    pub vaddr: u64,
+    /// Size of the segment in memory; bytes after `data` are zero.
+    pub mem_size: u64,
+    /// Contents of the segment in file.
+    pub data: &'a [u8],
+}
+
+impl Segment<'_> {
+    /// Contents of the segment in memory, including trailing zero bytes.
+    ///
+    /// Zero bytes are appended up to at most [`MAX_SEGMENT_SIZE`] bytes.
+    pub fn memory(&self) -> Vec<u8> {
+        let mut memory = self.data.to_vec();
+        let mem_size = self.mem_size.min(MAX_SEGMENT_SIZE);
+        if (memory.len() as u64) < mem_size {
+            memory.resize(mem_size as usize, 0);
+        }
+        memory
+    }
+}
+
+/// Error while walking segments of an ELF image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElfError {
+    /// Image is shorter than ELF header.
+    TooShort,
+    /// ELF magic is not found.
+    InvalidMagic,
+    /// Image is not a little endian ELF32 or ELF64 file.
+    Unsupported,
+    /// Program header or segment data lies outside the image.
+    Truncated,
+    /// Segment is larger than [`MAX_SEGMENT_SIZE`] in memory.
+    SegmentTooLarge,
+}
+
+impl fmt::Display for ElfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ElfError::TooShort => write!(f, "image is too short to contain an ELF header"),
+            ElfError::InvalidMagic => write!(f, "ELF magic not found"),
+            ElfError::Unsupported => write!(f, "only little endian ELF32 and ELF64 are supported"),
+            ElfError::Truncated => write!(f, "program header or segment is out of image"),
+            ElfError::SegmentTooLarge => write!(f, "segment is larger than 4 GiB in memory"),
+        }
+    }
+}
+
+/// Check if `image` starts with ELF magic.
+#[inline]
+pub fn is_elf(image: &[u8]) -> bool {
+    image.starts_with(&ELF_MAGIC)
+}
+
+/// Layout of ELF header and program header fields for one ELF class.
+struct Layout {
+    header_size: usize,
+    phoff: usize,
+    phentsize: usize,
+    phnum: usize,
+    ph_offset: usize,
+    ph_vaddr: usize,
+    ph_paddr: usize,
+    ph_filesz: usize,
+    ph_memsz: usize,
+    /// Size of address and offset fields in bytes.
+    word: usize,
+}
+
+const ELF32: Layout = Layout {
+    header_size: 52,
+    phoff: 28,
+    phentsize: 42,
+    phnum: 44,
+    ph_offset: 4,
+    ph_vaddr: 8,
+    ph_paddr: 12,
+    ph_filesz: 16,
+    ph_memsz: 20,
+    word: 4,
+};
+
+const ELF64: Layout = Layout {
+    header_size: 64,
+    phoff: 32,
+    phentsize: 54,
+    phnum: 56,
+    ph_offset: 8,
+    ph_vaddr: 16,
+    ph_paddr: 24,
+    ph_filesz: 32,
+    ph_memsz: 40,
+    word: 8,
+};
+
+/// Read a little endian unsigned integer of `size` bytes at `offset`.
+fn read_le(image: &[u8], offset: usize, size: usize) -> Result<u64, ElfError> {
+    let end = offset.checked_add(size).ok_or(ElfError::Truncated)?;
+    let bytes = image.get(offset..end).ok_or(ElfError::Truncated)?;
+    Ok(bytes
+        .iter()
+        .rev()
+        .fold(0u64, |acc, &byte| (acc << 8) | byte as u64))
+}
+
+/// Write `value` as a little endian unsigned integer of `size` bytes at `offset`.
+fn write_le(image: &mut [u8], offset: usize, size: usize, value: u64) -> Result<(), ElfError> {
+    let end = offset.checked_add(size).ok_or(ElfError::Truncated)?;
+    let bytes = image.get_mut(offset..end).ok_or(ElfError::Truncated)?;
+    bytes.copy_from_slice(&value.to_le_bytes()[..size]);
+    Ok(())
+}
+
+/// Convert a file offset or size read from `image` into `usize`.
+fn to_usize(value: u64) -> Result<usize, ElfError> {
+    usize::try_from(value).map_err(|_| ElfError::Truncated)
+}
+
+/// Field layout of `image`, and file offsets of its `PT_LOAD` program headers.
+fn load_headers(image: &[u8]) -> Result<(&'static Layout, Vec<usize>), ElfError> {
+    if image.len() < 6 {
+        return Err(ElfError::TooShort);
+    }
+    if !is_elf(image) {
+        return Err(ElfError::InvalidMagic);
+    }
+    let layout = match (image[4], image[5]) {
+        (1, 1) => &ELF32,
+        (2, 1) => &ELF64,
+        _ => return Err(ElfError::Unsupported),
+    };
+    if image.len() < layout.header_size {
+        return Err(ElfError::TooShort);
+    }
+    let phoff = to_usize(read_le(image, layout.phoff, layout.word)?)?;
+    let phentsize = read_le(image, layout.phentsize, 2)? as usize;
+    let phnum = read_le(image, layout.phnum, 2)? as usize;
+    let mut headers = Vec::new();
+    for i in 0..phnum {
+        let base = i
+            .checked_mul(phentsize)
+            .and_then(|offset| offset.checked_add(phoff))
+            .ok_or(ElfError::Truncated)?;
+        if read_le(image, base, 4)? as u32 == PT_LOAD {
+            headers.push(base);
+        }
+    }
+    Ok((layout, headers))
+}
+
+/// Walk program headers of `image`, returning its loadable segments in file order.
+pub fn load_segments(image: &[u8]) -> Result<Vec<Segment<'_>>, ElfError> {
+    let (layout, headers) = load_headers(image)?;
+    let mut segments = Vec::new();
+    for base in headers {
+        let field = |offset: usize| read_le(image, base + offset, layout.word);
+        let offset = to_usize(field(layout.ph_offset)?)?;
+        let filesz = to_usize(field(layout.ph_filesz)?)?;
+        let end = offset.checked_add(filesz).ok_or(ElfError::Truncated)?;
+        let data = image.get(offset..end).ok_or(ElfError::Truncated)?;
+        let mem_size = field(layout.ph_memsz)?;
+        if mem_size > MAX_SEGMENT_SIZE {
+            return Err(ElfError::SegmentTooLarge);
+        }
+        segments.push(Segment {
+            paddr: field(layout.ph_paddr)?,
+            vaddr: field(layout.ph_vaddr)?,
+            mem_size,
+            data,
+        });
+    }
+    Ok(segments)
+}
+
+/// Move loadable segments of `image` so the lowest one is loaded at `new_base`.
+///
+/// Physical address of every `PT_LOAD` segment is shifted by the same delta;
+/// virtual addresses and segment contents are kept. Returns the previous
+/// lowest physical address, or `None` if the image has no loadable segment.
+pub fn relocate(image: &mut [u8], new_base: u64) -> Result<Option<u64>, ElfError> {
+    let (layout, headers) = load_headers(image)?;
+    let mut paddrs = Vec::with_capacity(headers.len());
+    for &base in &headers {
+        paddrs.push(read_le(image, base + layout.ph_paddr, layout.word)?);
+    }
+    let Some(&old_base) = paddrs.iter().min() else {
+        return Ok(None);
+    };
+    let delta = new_base.wrapping_sub(old_base);
+    let mask = if layout.word == 4 {
+        0xFFFF_FFFF
+    } else {
+        u64::MAX
+    };
+    for (base, paddr) in headers.into_iter().zip(paddrs) {
+        let paddr = paddr.wrapping_add(delta) & mask;
+        write_le(image, base + layout.ph_paddr, layout.word, paddr)?;
+    }
+    Ok(Some(old_base))
+}
+
+/// ELF64 header and single `PT_LOAD` program header of a memory dump.
+///
+/// The dump of `length` bytes follows right after the returned headers in file,
+/// and is loaded at both physical and virtual address `address`.
+pub fn dump_header(address: u64, length: u64, machine: u16) -> Vec<u8> {
+    /// Executable file type, so that tools load its segment.
+    const ET_EXEC: u16 = 2;
+    /// Segment is readable, writable and executable.
+    const PF_RWX: u32 = 0x7;
+    let layout = &ELF64;
+    let phentsize = 56;
+    let data_offset = (layout.header_size + phentsize) as u64;
+    let mut header = Vec::with_capacity(data_offset as usize);
+    // e_ident: magic, 64-bit, little endian, version 1, System V ABI
+    header.extend_from_slice(&ELF_MAGIC);
+    header.extend_from_slice(&[2, 1, 1, 0]);
+    header.resize(16, 0);
+    header.extend_from_slice(&ET_EXEC.to_le_bytes());
+    header.extend_from_slice(&machine.to_le_bytes());
+    header.extend_from_slice(&1u32.to_le_bytes()); // e_version
+    header.extend_from_slice(&address.to_le_bytes()); // e_entry
+    header.extend_from_slice(&(layout.header_size as u64).to_le_bytes()); // e_phoff
+    header.extend_from_slice(&0u64.to_le_bytes()); // e_shoff
+    header.extend_from_slice(&0u32.to_le_bytes()); // e_flags
+    header.extend_from_slice(&(layout.header_size as u16).to_le_bytes()); // e_ehsize
+    header.extend_from_slice(&(phentsize as u16).to_le_bytes()); // e_phentsize
+    header.extend_from_slice(&1u16.to_le_bytes()); // e_phnum
+    header.extend_from_slice(&[0; 6]); // no section headers
+
+    // program header: one loadable segment covering the dumped memory
+    header.extend_from_slice(&PT_LOAD.to_le_bytes());
+    header.extend_from_slice(&PF_RWX.to_le_bytes());
+    for value in [data_offset, address, address, length, length, 1] {
+        header.extend_from_slice(&value.to_le_bytes());
+    }
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dump_header, load_segments, relocate, ElfError, Segment, EM_RISCV};
+
+    /// Build a little endian ELF32 image with one `PT_LOAD` segment per `(paddr, mem_size, data)`.
+    fn elf32(segments: &[(u32, u32, &[u8])]) -> Vec<u8> {
+        let phoff = 52;
+        let mut image = vec![0u8; phoff + 32 * segments.len()];
+        image[..6].copy_from_slice(b"\x7fELF\x01\x01");
+        image[28..32].copy_from_slice(&(phoff as u32).to_le_bytes());
+        image[42..44].copy_from_slice(&32u16.to_le_bytes());
+        image[44..46].copy_from_slice(&(segments.len() as u16).to_le_bytes());
+        for (i, (paddr, mem_size, data)) in segments.iter().enumerate() {
+            let offset = image.len() as u32;
+            let ph = phoff + 32 * i;
+            let fields = [1, offset, *paddr, *paddr, data.len() as u32, *mem_size];
+            for (j, value) in fields.iter().enumerate() {
+                image[ph + 4 * j..ph + 4 * j + 4].copy_from_slice(&value.to_le_bytes());
+            }
+            image.extend_from_slice(data);
+        }
+        image
+    }
+
+    #[test]
+    fn walk_load_segments() {
+        let image = elf32(&[(0x4000_0000, 8, &[1, 2, 3, 4]), (0x4010_0000, 2, &[5, 6])]);
+        let segments = load_segments(&image).unwrap();
+        assert_eq!(
+            segments,
+            [
+                Segment {
+                    paddr: 0x4000_0000,
+                    vaddr: 0x4000_0000,
+                    mem_size: 8,
+                    data: &[1, 2, 3, 4],
+                },
+                Segment {
+                    paddr: 0x4010_0000,
+                    vaddr: 0x4010_0000,
+                    mem_size: 2,
+                    data: &[5, 6],
+                },
+            ]
+        );
+        assert_eq!(segments[0].memory(), [1, 2, 3, 4, 0, 0, 0, 0]);
+
+        // ELF64 with one loadable segment and a non-loadable one
+        let mut image = vec![0u8; 64 + 2 * 56];
+        image[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        image[32..40].copy_from_slice(&64u64.to_le_bytes());
+        image[54..56].copy_from_slice(&56u16.to_le_bytes());
+        image[56..58].copy_from_slice(&2u16.to_le_bytes());
+        image[64..68].copy_from_slice(&4u32.to_le_bytes());
+        let ph = 64 + 56;
+        image[ph..ph + 4].copy_from_slice(&1u32.to_le_bytes());
+        image[ph + 8..ph + 16].copy_from_slice(&176u64.to_le_bytes());
+        image[ph + 16..ph + 24].copy_from_slice(&0xFFFF_FFC0_8000_0000u64.to_le_bytes());
+        image[ph + 24..ph + 32].copy_from_slice(&0x8000_0000u64.to_le_bytes());
+        image[ph + 32..ph + 40].copy_from_slice(&3u64.to_le_bytes());
+        image[ph + 40..ph + 48].copy_from_slice(&3u64.to_le_bytes());
+        image.extend_from_slice(&[7, 8, 9]);
+        let segments = load_segments(&image).unwrap();
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].paddr, 0x8000_0000);
+        assert_eq!(segments[0].vaddr, 0xFFFF_FFC0_8000_0000);
+        assert_eq!(segments[0].data, [7, 8, 9]);
+
+        assert_eq!(load_segments(b"\x7fEL"), Err(ElfError::TooShort));
+        assert_eq!(load_segments(b"eGON.BT0"), Err(ElfError::InvalidMagic));
+        assert_eq!(
+            load_segments(b"\x7fELF\x01\x02"),
+            Err(ElfError::Unsupported)
+        );
+        let mut image = elf32(&[(0, 4, &[1, 2, 3, 4])]);
+        image.truncate(image.len() - 1);
+        assert_eq!(load_segments(&image), Err(ElfError::Truncated));
+    }
+
+    #[test]
+    fn overflowing_headers() {
+        let mut image = dump_header(0x4000_0000, 8, EM_RISCV);
+        image.extend_from_slice(&[0; 8]);
+        let ph = 64;
+
+        // segment offset and size wrap around
+        let mut p_offset = image.clone();
+        p_offset[ph + 8..ph + 16].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(load_segments(&p_offset), Err(ElfError::Truncated));
+
+        // program header table offset wraps around
+        let mut e_phoff = image.clone();
+        e_phoff[32..40].copy_from_slice(&(u64::MAX - 1).to_le_bytes());
+        assert_eq!(load_segments(&e_phoff), Err(ElfError::Truncated));
+        assert_eq!(relocate(&mut e_phoff, 0), Err(ElfError::Truncated));
+
+        // zero-filled size is limited
+        let mut p_memsz = image;
+        p_memsz[ph + 40..ph + 48].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(load_segments(&p_memsz), Err(ElfError::SegmentTooLarge));
+    }
+
+    #[test]
+    fn memory_dump_elf() {
+        let data = [0x13, 0x00, 0x00, 0x00, 0x67, 0x80, 0x00, 0x00];
+        let mut image = dump_header(0x4000_0000, data.len() as u64, EM_RISCV);
+        assert_eq!(image.len(), 120);
+        image.extend_from_slice(&data);
+        assert_eq!(&image[..6], b"\x7fELF\x02\x01");
+        assert_eq!(u16::from_le_bytes([image[18], image[19]]), EM_RISCV);
+        assert_eq!(
+            load_segments(&image).unwrap(),
+            [Segment {
+                paddr: 0x4000_0000,
+                vaddr: 0x4000_0000,
+                mem_size: 8,
+                data: &data,
+            }]
+        );
+    }
+
+    #[test]
+    fn relocate_fixture_segments() {
+        let mut image = include_bytes!("../fixtures/diff-a.elf").to_vec();
+        let before: Vec<_> = load_segments(&image)
+            .unwrap()
+            .iter()
+            .map(|s| (s.paddr, s.vaddr, s.data.to_vec()))
+            .collect();
+        assert_eq!(relocate(&mut image, 0x8000_0000), Ok(Some(0x4000_0000)));
+        let after = load_segments(&image).unwrap();
+        assert_eq!(after.len(), before.len());
+        for (segment, (paddr, vaddr, data)) in after.iter().zip(&before) {
+            assert_eq!(segment.paddr, paddr + 0x4000_0000);
+            assert_eq!(segment.vaddr, *vaddr);
+            assert_eq!(segment.data, &data[..]);
+        }
+        // moving down wraps within the 32-bit address space
+        assert_eq!(relocate(&mut image, 0x2000), Ok(Some(0x8000_0000)));
+        let paddrs: Vec<_> = load_segments(&image)
+            .unwrap()
+            .iter()
+            .map(|s| s.paddr)
+            .collect();
+        assert_eq!(paddrs, [0x2000, 0x10_2000, 0x20_2000]);
+
+        let mut image = dump_header(0x4000_0000, 0, EM_RISCV);
+        assert_eq!(relocate(&mut image, 0x1_0000_0000), Ok(Some(0x4000_0000)));
+        assert_eq!(load_segments(&image).unwrap()[0].paddr, 0x1_0000_0000);
+        assert_eq!(relocate(&mut [0u8; 4], 0), Err(ElfError::TooShort));
+    }
+}
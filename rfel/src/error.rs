@@ -0,0 +1,152 @@
+//! Command line errors and process exit codes.
+use crate::{manifest::ManifestError, FelError};
+use std::{fmt, io};
+
+/// Error of an `rfel` command.
+///
+/// Each category maps to a distinct process exit code, so that scripts can
+/// tell failures apart without parsing the error message:
+///
+/// | Exit code | Category                                    |
+/// |-----------|---------------------------------------------|
+/// | 0         | success                                     |
+/// | 1         | local I/O error                             |
+/// | 2         | invalid command line argument               |
+/// | 3         | FEL device not found or cannot be opened    |
+/// | 4         | FEL protocol error                          |
+/// | 5         | flash operation failed                      |
+/// | 6         | USB transfer or console timeout             |
+/// | 7         | invalid boot image                          |
+/// | 8         | invalid or unreadable signing key           |
+#[derive(Debug)]
+pub enum CliError {
+    /// Local I/O error, e.g. on reading a file or opening a serial port.
+    Io(io::Error),
+    /// Invalid command line argument.
+    Usage(String),
+    /// FEL device is not found or cannot be opened.
+    Device(String),
+    /// Device replied with unexpected data.
+    Protocol(String),
+    /// Flash memory operation failed.
+    Flash(String),
+    /// Operation did not complete in time.
+    Timeout,
+    /// Local boot image is malformed.
+    Image(String),
+    /// Signing key cannot be encoded or decoded.
+    Key(String),
+}
+
+impl CliError {
+    /// Process exit code of this error.
+    #[inline]
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            CliError::Io(_) => 1,
+            CliError::Usage(_) => 2,
+            CliError::Device(_) => 3,
+            CliError::Protocol(_) => 4,
+            CliError::Flash(_) => 5,
+            CliError::Timeout => 6,
+            CliError::Image(_) => 7,
+            CliError::Key(_) => 8,
+        }
+    }
+    /// Check if this error is a failure of device communication.
+    #[inline]
+    pub fn is_device_failure(&self) -> bool {
+        matches!(
+            self,
+            CliError::Device(_) | CliError::Protocol(_) | CliError::Timeout
+        )
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Io(e) => write!(f, "{}", e),
+            CliError::Usage(msg) => write!(f, "{}", msg),
+            CliError::Device(msg) => write!(f, "device: {}", msg),
+            CliError::Protocol(msg) => write!(f, "protocol: {}", msg),
+            CliError::Flash(msg) => write!(f, "flash: {}", msg),
+            CliError::Timeout => write!(f, "operation timed out"),
+            CliError::Image(msg) => write!(f, "image: {}", msg),
+            CliError::Key(msg) => write!(f, "key: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl From<io::Error> for CliError {
+    #[inline]
+    fn from(e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::TimedOut {
+            CliError::Timeout
+        } else {
+            CliError::Io(e)
+        }
+    }
+}
+
+impl From<FelError> for CliError {
+    #[inline]
+    fn from(e: FelError) -> Self {
+        match e {
+            FelError::Timeout => CliError::Timeout,
+            e => CliError::Protocol(e.to_string()),
+        }
+    }
+}
+
+impl From<ManifestError> for CliError {
+    #[inline]
+    fn from(e: ManifestError) -> Self {
+        match e {
+            ManifestError::Parse { .. } => CliError::Usage(e.to_string()),
+            ManifestError::Unsupported(_) => CliError::Flash(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CliError;
+    use crate::FelError;
+    use std::io;
+
+    #[test]
+    fn exit_codes_are_distinct() {
+        let errors = [
+            CliError::Io(io::ErrorKind::NotFound.into()),
+            CliError::Usage("invalid address".into()),
+            CliError::Device("not found".into()),
+            CliError::Protocol("invalid response".into()),
+            CliError::Flash("erase failed".into()),
+            CliError::Timeout,
+            CliError::Image("invalid magic".into()),
+            CliError::Key("invalid private key".into()),
+        ];
+        let codes: Vec<u8> = errors.iter().map(CliError::exit_code).collect();
+        assert_eq!(codes, [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert!(!codes.contains(&0));
+    }
+
+    #[test]
+    fn io_error_conversion() {
+        let e = CliError::from(io::Error::from(io::ErrorKind::TimedOut));
+        assert!(matches!(e, CliError::Timeout));
+        assert_eq!(e.exit_code(), 6);
+        let e = CliError::from(io::Error::from(io::ErrorKind::PermissionDenied));
+        assert!(matches!(e, CliError::Io(_)));
+        assert_eq!(e.exit_code(), 1);
+    }
+
+    #[test]
+    fn fel_error_conversion() {
+        assert_eq!(CliError::from(FelError::Timeout).exit_code(), 6);
+        assert_eq!(CliError::from(FelError::InvalidResponse).exit_code(), 4);
+    }
+}
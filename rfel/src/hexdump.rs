@@ -0,0 +1,81 @@
+//! Hexadecimal dump formatting.
+use std::io::{self, Write};
+
+/// Number of bytes shown in one hexdump line.
+pub const BYTES_PER_LINE: usize = 16;
+
+/// ANSI escape sequence starting reverse video.
+const HIGHLIGHT_START: &str = "\x1b[7m";
+/// ANSI escape sequence resetting all attributes.
+const HIGHLIGHT_END: &str = "\x1b[0m";
+
+/// Format one hexdump line of at most 16 bytes starting at `address`.
+pub fn format_hexdump_line(line: &[u8], address: u32) -> String {
+    format_hexdump_line_highlighted(line, address, &[])
+}
+
+/// Format one hexdump line, showing bytes whose `highlight` flag is set in reverse video.
+///
+/// Bytes without a flag are not highlighted.
+pub fn format_hexdump_line_highlighted(line: &[u8], address: u32, highlight: &[bool]) -> String {
+    let highlighted = |i: usize| highlight.get(i).copied().unwrap_or(false);
+    let mut ans = format!("{:08x}: ", address);
+    for (i, byte) in line.iter().enumerate() {
+        if highlighted(i) {
+            ans.push_str(&format!(
+                "{}{:02x}{} ",
+                HIGHLIGHT_START, byte, HIGHLIGHT_END
+            ));
+        } else {
+            ans.push_str(&format!("{:02x} ", byte));
+        }
+    }
+    ans.push(' ');
+    for _ in line.len()..BYTES_PER_LINE {
+        ans.push_str("   ");
+    }
+    for (i, byte) in line.iter().enumerate() {
+        let ch = if byte.is_ascii_graphic() || *byte == b' ' {
+            *byte as char
+        } else {
+            '.'
+        };
+        if highlighted(i) {
+            ans.push_str(HIGHLIGHT_START);
+            ans.push(ch);
+            ans.push_str(HIGHLIGHT_END);
+        } else {
+            ans.push(ch);
+        }
+    }
+    ans
+}
+
+/// Write hexdump of `buf` starting at `base_address` into `out`.
+pub fn hexdump(out: &mut impl Write, buf: &[u8], base_address: u32) -> io::Result<()> {
+    for (i, line) in buf.chunks(BYTES_PER_LINE).enumerate() {
+        let address = base_address.wrapping_add((i * BYTES_PER_LINE) as u32);
+        writeln!(out, "{}", format_hexdump_line(line, address))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_hexdump_line, hexdump};
+
+    #[test]
+    fn format_lines() {
+        assert_eq!(
+            format_hexdump_line(b"eGON.BT0\x00\x01\x02\x03 abc", 0x20),
+            "00000020: 65 47 4f 4e 2e 42 54 30 00 01 02 03 20 61 62 63  eGON.BT0.... abc"
+        );
+        let mut out = Vec::new();
+        hexdump(&mut out, b"0123456789abcdefXY\n", 0x4000_0000).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "40000000: 30 31 32 33 34 35 36 37 38 39 61 62 63 64 65 66  0123456789abcdef\n\
+             40000010: 58 59 0a                                         XY.\n"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! Best-effort capture of chip identity after a failed device command.
+//!
+//! When a command fails with a device or protocol error, or panics, the chip
+//! version and SID are read back and printed, so bug reports always include
+//! which chip was connected. Errors during the capture are recorded instead
+//! of being propagated.
+use crate::{error::CliError, Fel, Transport, Version};
+use std::{
+    io::{self, Write},
+    panic::{self, AssertUnwindSafe},
+};
+
+/// Chip identity as far as it could be read.
+#[derive(Debug)]
+pub struct Identity {
+    /// FEL version response, or why it cannot be read.
+    pub version: Result<Version, String>,
+    /// Security ID of chip, or why it cannot be read.
+    pub sid: Result<[u8; 16], String>,
+}
+
+/// Read chip identity, ignoring any further errors or panics.
+pub fn capture<T: Transport>(fel: &Fel<T>) -> Identity {
+    let version = catch(|| fel.get_version().map_err(|e| e.to_string()));
+    let sid = match &version {
+        Ok(version) => match version.chip() {
+            Some(chip) => catch(|| {
+                let mut sid = [0u8; 16];
+                fel.read_address(chip.sid_address(), &mut sid)
+                    .map(|_| sid)
+                    .map_err(|e| e.to_string())
+            }),
+            None => Err("unknown chip".into()),
+        },
+        Err(_) => Err("chip version unavailable".into()),
+    };
+    Identity { version, sid }
+}
+
+#[inline]
+fn catch<R>(f: impl FnOnce() -> Result<R, String>) -> Result<R, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| Err("panicked".into()))
+}
+
+/// Print identity block of `identity` into `out`.
+pub fn write_identity(out: &mut impl Write, identity: &Identity) -> io::Result<()> {
+    writeln!(out, "--- chip identity ---")?;
+    match &identity.version {
+        Ok(version) => writeln!(out, "version: {}", version)?,
+        Err(e) => writeln!(out, "version: unavailable ({})", e)?,
+    }
+    match &identity.sid {
+        Ok(sid) => {
+            write!(out, "sid: ")?;
+            for byte in sid {
+                write!(out, "{:02x}", byte)?;
+            }
+            writeln!(out)?;
+        }
+        Err(e) => writeln!(out, "sid: unavailable ({})", e)?,
+    }
+    writeln!(out, "---------------------")
+}
+
+/// Run `command` on `fel`, printing chip identity into `out` if it fails on
+/// device communication or panics.
+///
+/// Errors are returned and panics resumed after the identity is printed.
+pub fn guard<T: Transport, R>(
+    fel: &Fel<T>,
+    out: &mut impl Write,
+    command: impl FnOnce(&Fel<T>) -> Result<R, CliError>,
+) -> Result<R, CliError> {
+    let ans = panic::catch_unwind(AssertUnwindSafe(|| command(fel)));
+    match &ans {
+        Ok(Err(e)) if !e.is_device_failure() => {}
+        Ok(Ok(_)) => {}
+        _ => {
+            // the identity is only a diagnostic; never mask original failure
+            let _ = write_identity(out, &capture(fel));
+        }
+    }
+    ans.unwrap_or_else(|payload| panic::resume_unwind(payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{capture, guard};
+    use crate::{error::CliError, mock::MockFel, Chip};
+    use std::panic::{self, AssertUnwindSafe};
+
+    const SID: [u8; 16] = *b"\x93\x00\x48\x00\x1c\x84\x45\x01\x00\x50\x07\x14\x31\x3a\x14\x0b";
+
+    #[test]
+    fn identity_on_failure() {
+        let mut mock = MockFel::default();
+        let fel = mock.fel();
+        fel.write_address(Chip::D1.sid_address(), &SID).unwrap();
+        let identity = capture(&fel);
+        assert!(matches!(identity.version.unwrap().chip(), Some(Chip::D1)));
+        assert_eq!(identity.sid, Ok(SID));
+
+        let mut out = Vec::new();
+        let e = guard(&fel, &mut out, |_| {
+            Err::<(), _>(CliError::Protocol("induced".into()))
+        })
+        .unwrap_err();
+        assert_eq!(e.to_string(), "protocol: induced");
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("--- chip identity ---\n"), "{}", out);
+        assert!(out.contains("D1"), "{}", out);
+        assert!(
+            out.contains("sid: 930048001c84450100500714313a140b\n"),
+            "{}",
+            out
+        );
+
+        // panics also print identity before unwinding further
+        let mut out = Vec::new();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            guard(&fel, &mut out, |_| -> Result<(), CliError> {
+                panic!("induced")
+            })
+        }));
+        assert!(result.is_err());
+        assert!(String::from_utf8(out).unwrap().contains("sid: "));
+
+        // usage errors are not device failures
+        let mut out = Vec::new();
+        let _ = guard(&fel, &mut out, |_| {
+            Err::<(), _>(CliError::Usage("bad address".into()))
+        });
+        assert!(out.is_empty());
+    }
+}
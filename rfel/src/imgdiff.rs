@@ -0,0 +1,170 @@
+//! Comparing two local images.
+use crate::elf::Segment;
+use std::ops::Range;
+
+/// Default block size of raw image comparison in bytes.
+pub const DEFAULT_BLOCK_SIZE: usize = 512;
+
+/// Byte ranges of `block_size`-aligned blocks that differ between `a` and `b`.
+///
+/// Bytes past the end of the shorter image count as different. Adjacent
+/// changed blocks are merged, and the last range ends at the longer image's end.
+pub fn changed_blocks(a: &[u8], b: &[u8], block_size: usize) -> Vec<Range<usize>> {
+    let len = a.len().max(b.len());
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for start in (0..len).step_by(block_size) {
+        let end = (start + block_size).min(len);
+        let block = |image: &[u8]| {
+            image
+                .get(start..end.min(image.len()))
+                .unwrap_or(&[])
+                .to_vec()
+        };
+        if block(a) == block(b) {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some(last) if last.end == start => last.end = end,
+            _ => ranges.push(start..end),
+        }
+    }
+    ranges
+}
+
+/// Difference of one loadable segment between two ELF images.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SegmentDiff {
+    /// Segment at this physical address is the same in both images.
+    Unchanged { paddr: u64, size: u64 },
+    /// Segment only exists in the second image.
+    Added { paddr: u64, size: u64 },
+    /// Segment only exists in the first image.
+    Removed { paddr: u64, size: u64 },
+    /// Segment exists in both images with contents changed in `ranges`, relative to `paddr`.
+    Changed {
+        paddr: u64,
+        ranges: Vec<Range<usize>>,
+    },
+}
+
+impl SegmentDiff {
+    /// Physical address of the segment.
+    #[inline]
+    pub fn paddr(&self) -> u64 {
+        match self {
+            SegmentDiff::Unchanged { paddr, .. }
+            | SegmentDiff::Added { paddr, .. }
+            | SegmentDiff::Removed { paddr, .. }
+            | SegmentDiff::Changed { paddr, .. } => *paddr,
+        }
+    }
+}
+
+/// Compare loadable segments of two ELF images, matched by physical address.
+///
+/// Segments are compared as loaded into memory, including zero-filled bytes
+/// after file contents. Returns differences sorted by physical address.
+pub fn diff_segments(a: &[Segment], b: &[Segment], block_size: usize) -> Vec<SegmentDiff> {
+    let mut diffs = Vec::new();
+    for old in a {
+        let diff = match b.iter().find(|new| new.paddr == old.paddr) {
+            None => SegmentDiff::Removed {
+                paddr: old.paddr,
+                size: old.mem_size,
+            },
+            Some(new) => {
+                let ranges = changed_blocks(&old.memory(), &new.memory(), block_size);
+                if ranges.is_empty() {
+                    SegmentDiff::Unchanged {
+                        paddr: old.paddr,
+                        size: old.mem_size,
+                    }
+                } else {
+                    SegmentDiff::Changed {
+                        paddr: old.paddr,
+                        ranges,
+                    }
+                }
+            }
+        };
+        diffs.push(diff);
+    }
+    for new in b
+        .iter()
+        .filter(|new| a.iter().all(|old| old.paddr != new.paddr))
+    {
+        diffs.push(SegmentDiff::Added {
+            paddr: new.paddr,
+            size: new.mem_size,
+        });
+    }
+    diffs.sort_by_key(SegmentDiff::paddr);
+    diffs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{changed_blocks, diff_segments, SegmentDiff};
+    use crate::elf::load_segments;
+
+    #[test]
+    fn diff_raw_images() {
+        let a = vec![0u8; 2048];
+        assert!(changed_blocks(&a, &a, 512).is_empty());
+
+        let mut b = a.clone();
+        b[10] = 1;
+        b[600] = 1;
+        b[1700] = 1;
+        // blocks 0 and 1 merge, block 2 is unchanged
+        assert_eq!(changed_blocks(&a, &b, 512), [0..1024, 1536..2048]);
+
+        // longer image differs from the end of the shorter one
+        let mut c = a.clone();
+        c[0] = 1;
+        c.extend_from_slice(&[0; 100]);
+        assert_eq!(changed_blocks(&a, &c, 512), [0..512, 2048..2148]);
+        assert_eq!(changed_blocks(&c, &a, 512), [0..512, 2048..2148]);
+        // a partial block differs even if the common part is the same
+        assert_eq!(changed_blocks(&c[..1500], &a, 256), [0..256, 1280..2048]);
+    }
+
+    #[test]
+    fn diff_fixture_images() {
+        let a = include_bytes!("../fixtures/diff-a.bin");
+        let b = include_bytes!("../fixtures/diff-b.bin");
+        assert_eq!(changed_blocks(a, b, 512), [0..1536, 2048..2148]);
+        assert_eq!(
+            changed_blocks(a, b, 256),
+            [0..256, 512..768, 1280..1536, 2048..2148]
+        );
+
+        let a = load_segments(include_bytes!("../fixtures/diff-a.elf")).unwrap();
+        let b = load_segments(include_bytes!("../fixtures/diff-b.elf")).unwrap();
+        assert_eq!(
+            diff_segments(&a, &b, 256),
+            [
+                SegmentDiff::Changed {
+                    paddr: 0x4000_0000,
+                    ranges: vec![0..256, 512..768],
+                },
+                // zero-filled memory is the same as zero file contents
+                SegmentDiff::Unchanged {
+                    paddr: 0x4010_0000,
+                    size: 64,
+                },
+                SegmentDiff::Removed {
+                    paddr: 0x4020_0000,
+                    size: 16,
+                },
+                SegmentDiff::Added {
+                    paddr: 0x4030_0000,
+                    size: 8,
+                },
+            ]
+        );
+        assert!(diff_segments(&a, &a, 512)
+            .iter()
+            .all(|d| matches!(d, SegmentDiff::Unchanged { .. })));
+    }
+}
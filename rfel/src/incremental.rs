@@ -0,0 +1,253 @@
+//! Incremental writes using an index of block hashes last written to the device.
+//!
+//! The index is only valid while the device keeps memory contents written
+//! with it; it cannot tell if memory is changed by other means or lost.
+use core::fmt;
+use std::ops::Range;
+
+/// Default block size of incremental writes in bytes.
+pub const DEFAULT_BLOCK_SIZE: usize = 4096;
+
+/// First line of an index file.
+const INDEX_HEADER: &str = "rfel block index v1";
+
+/// 64-bit FNV-1a hash of `data`.
+///
+/// Only used to detect changed blocks, not to protect against tampering.
+pub fn block_hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Hashes of blocks of an image written at `address`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockIndex {
+    /// Address the image is written at.
+    pub address: u32,
+    /// Block size in bytes.
+    pub block_size: usize,
+    /// Length of the image in bytes.
+    pub length: usize,
+    /// Hash of each block; the last block may be partial.
+    pub hashes: Vec<u64>,
+}
+
+/// Error while parsing an index file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexError {
+    /// First line is not an index header.
+    InvalidHeader,
+    /// Line at this number, counted from 1, is malformed.
+    InvalidLine(usize),
+    /// Number of hashes does not match image length and block size.
+    HashCount { expected: usize, found: usize },
+}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexError::InvalidHeader => write!(f, "not an rfel block index"),
+            IndexError::InvalidLine(line) => write!(f, "malformed block index line {}", line),
+            IndexError::HashCount { expected, found } => {
+                write!(f, "block index has {} hashes, expected {}", found, expected)
+            }
+        }
+    }
+}
+
+impl BlockIndex {
+    /// Build index of `data` to be written at `address`.
+    pub fn build(address: u32, block_size: usize, data: &[u8]) -> BlockIndex {
+        BlockIndex {
+            address,
+            block_size,
+            length: data.len(),
+            hashes: data.chunks(block_size).map(block_hash).collect(),
+        }
+    }
+
+    /// Parse index from text written by [`Display`](fmt::Display).
+    pub fn parse(text: &str) -> Result<BlockIndex, IndexError> {
+        let mut lines = text.lines().enumerate();
+        if lines.next().map(|(_, line)| line.trim()) != Some(INDEX_HEADER) {
+            return Err(IndexError::InvalidHeader);
+        }
+        let mut field = |key: &str| {
+            let (index, line) = lines.next().ok_or(IndexError::InvalidHeader)?;
+            line.strip_prefix(key)
+                .and_then(|value| {
+                    let value = value.trim();
+                    match value.strip_prefix("0x") {
+                        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+                        None => value.parse().ok(),
+                    }
+                })
+                .ok_or(IndexError::InvalidLine(index + 1))
+        };
+        let address = field("address")?;
+        let block_size = field("block_size")?;
+        let length = field("length")?;
+        let (Ok(address), true) = (u32::try_from(address), block_size > 0) else {
+            return Err(IndexError::InvalidHeader);
+        };
+        let mut hashes = Vec::new();
+        for (index, line) in lines {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let hash =
+                u64::from_str_radix(line, 16).map_err(|_| IndexError::InvalidLine(index + 1))?;
+            hashes.push(hash);
+        }
+        let expected = (length as usize).div_ceil(block_size as usize);
+        if hashes.len() != expected {
+            return Err(IndexError::HashCount {
+                expected,
+                found: hashes.len(),
+            });
+        }
+        Ok(BlockIndex {
+            address,
+            block_size: block_size as usize,
+            length: length as usize,
+            hashes,
+        })
+    }
+
+    /// Byte ranges of this image whose blocks differ from `previous`.
+    ///
+    /// The whole image is changed if there is no previous index, or if it was
+    /// written at another address or with another block size. Adjacent changed
+    /// blocks are merged, and the last range ends at the end of the image.
+    pub fn changed_ranges(&self, previous: Option<&BlockIndex>) -> Vec<Range<usize>> {
+        let previous = previous
+            .filter(|prev| prev.address == self.address && prev.block_size == self.block_size);
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for (i, hash) in self.hashes.iter().enumerate() {
+            let unchanged = previous.is_some_and(|prev| {
+                prev.hashes.get(i) == Some(hash)
+                    && (i + 1 < self.hashes.len() || prev.length == self.length)
+            });
+            if unchanged {
+                continue;
+            }
+            let start = i * self.block_size;
+            let end = (start + self.block_size).min(self.length);
+            match ranges.last_mut() {
+                Some(last) if last.end == start => last.end = end,
+                _ => ranges.push(start..end),
+            }
+        }
+        ranges
+    }
+}
+
+impl fmt::Display for BlockIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", INDEX_HEADER)?;
+        writeln!(f, "address 0x{:08x}", self.address)?;
+        writeln!(f, "block_size {}", self.block_size)?;
+        writeln!(f, "length {}", self.length)?;
+        for hash in &self.hashes {
+            writeln!(f, "{:016x}", hash)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write `ranges` of `data` into chip memory at `address`, in chunks of at most `chunk_size`.
+///
+/// Function `write` is called with chunk address and data. Returns total
+/// number of bytes written.
+pub fn write_ranges<E>(
+    data: &[u8],
+    address: u32,
+    ranges: &[Range<usize>],
+    chunk_size: usize,
+    mut write: impl FnMut(u32, &[u8]) -> Result<usize, E>,
+) -> Result<usize, E> {
+    let mut written = 0;
+    for range in ranges {
+        let start = address.wrapping_add(range.start as u32);
+        written +=
+            crate::transfer::write_slice(&data[range.clone()], start, chunk_size, &mut write)?;
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{block_hash, write_ranges, BlockIndex, IndexError};
+
+    #[test]
+    fn index_round_trip() {
+        let data: Vec<u8> = (0..2500u32).map(|i| i as u8).collect();
+        let index = BlockIndex::build(0x4000_0000, 1024, &data);
+        assert_eq!(index.hashes.len(), 3);
+        assert_eq!(index.hashes[2], block_hash(&data[2048..]));
+        let text = index.to_string();
+        assert!(text.starts_with("rfel block index v1\naddress 0x40000000\n"));
+        assert_eq!(BlockIndex::parse(&text), Ok(index));
+
+        assert_eq!(BlockIndex::parse("hello"), Err(IndexError::InvalidHeader));
+        let truncated = text.lines().take(5).collect::<Vec<_>>().join("\n");
+        assert_eq!(
+            BlockIndex::parse(&truncated),
+            Err(IndexError::HashCount {
+                expected: 3,
+                found: 1
+            })
+        );
+        let corrupted = text.replace("block_size 1024", "block_size 1k");
+        assert_eq!(
+            BlockIndex::parse(&corrupted),
+            Err(IndexError::InvalidLine(3))
+        );
+    }
+
+    #[test]
+    fn write_only_changed_blocks() {
+        let old: Vec<u8> = (0..5000u32).map(|i| (i * 7) as u8).collect();
+        let previous = BlockIndex::build(0x4000_0000, 1024, &old);
+        let mut new = old.clone();
+        new[10] ^= 0xFF;
+        new[1030] ^= 0xFF;
+        new[3500] ^= 0xFF;
+        let index = BlockIndex::build(0x4000_0000, 1024, &new);
+
+        let ranges = index.changed_ranges(Some(&previous));
+        assert_eq!(ranges, [0..2048, 3072..4096]);
+        let mut writes = Vec::new();
+        let written = write_ranges(&new, 0x4000_0000, &ranges, 1536, |address, buf| {
+            writes.push((address, buf.len()));
+            Ok::<_, ()>(buf.len())
+        })
+        .unwrap();
+        assert_eq!(written, 3072);
+        assert_eq!(
+            writes,
+            [(0x4000_0000, 1536), (0x4000_0600, 512), (0x4000_0C00, 1024)]
+        );
+
+        // unchanged image writes nothing
+        assert!(previous.changed_ranges(Some(&previous)).is_empty());
+        // without a matching index everything is written
+        let moved = BlockIndex::build(0x4010_0000, 1024, &new);
+        for ranges in [
+            index.changed_ranges(None),
+            moved.changed_ranges(Some(&index)),
+        ] {
+            assert_eq!(ranges.len(), 1);
+            assert_eq!(ranges[0], 0..5000);
+        }
+        // a partial last block that grows is written again
+        let mut longer = new.clone();
+        longer.push(0);
+        let longer = BlockIndex::build(0x4000_0000, 1024, &longer);
+        let ranges = longer.changed_ranges(Some(&index));
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0], 4096..5001);
+    }
+}
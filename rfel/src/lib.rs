@@ -1,33 +1,136 @@
 use core::fmt;
-use futures::executor::block_on;
-use log::{debug, error, trace};
-use nusb::transfer::EndpointType;
+use futures::task::{waker, ArcWake};
+use log::{debug, error, trace, warn};
+use nusb::transfer::TransferError;
+use stats::SessionStats;
+use std::{
+    cell::Cell,
+    future::Future,
+    pin::pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
 
-pub struct Fel<'a> {
-    iface: &'a mut nusb::Interface,
+pub mod console;
+pub mod descriptors;
+pub mod egon;
+pub mod elf;
+pub mod error;
+pub mod hexdump;
+pub mod identity;
+pub mod imgdiff;
+pub mod incremental;
+pub mod manifest;
+pub mod memtest;
+#[cfg(test)]
+mod mock;
+pub mod monitor;
+pub mod parallel;
+pub mod progress;
+pub mod remote;
+pub mod selftest;
+pub mod sign;
+pub mod snapshot;
+pub mod stats;
+pub mod toc;
+pub mod transfer;
+
+pub struct Fel<'a, T = nusb::Interface> {
+    iface: &'a mut T,
     endpoint_in: u8,
     endpoint_out: u8,
     version: Option<Version>,
+    timeout: Duration,
+    stats: Cell<SessionStats>,
 }
 
-const CHUNK_SIZE: usize = 65536;
+/// Maximum size of one FEL read or write request.
+pub const CHUNK_SIZE: usize = 65536;
 
-impl<'a> Fel<'a> {
+/// Number of times the remainder of a short USB read is requested again.
+const SHORT_READ_RETRIES: usize = 3;
+
+/// Default timeout of one USB transfer.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Bulk transfers on a USB interface.
+pub trait Transport {
+    /// Send `buf` to bulk OUT endpoint `endpoint`.
+    fn bulk_out(
+        &self,
+        endpoint: u8,
+        buf: Vec<u8>,
+    ) -> impl Future<Output = Result<(), TransferError>>;
+    /// Receive `len` bytes from bulk IN endpoint `endpoint`.
+    fn bulk_in(
+        &self,
+        endpoint: u8,
+        len: usize,
+    ) -> impl Future<Output = Result<Vec<u8>, TransferError>>;
+}
+
+impl Transport for nusb::Interface {
     #[inline]
-    pub fn open_interface(iface: &'a mut nusb::Interface) -> Result<Self, ()> {
-        let mut endpoint_in = None;
-        let mut endpoint_out = None;
-        for descriptor in iface.descriptors() {
-            for endpoint in descriptor.endpoints() {
-                if endpoint.transfer_type() != EndpointType::Bulk {
-                    continue;
-                }
-                match endpoint.direction() {
-                    nusb::transfer::Direction::In => endpoint_in = Some(endpoint.address()),
-                    nusb::transfer::Direction::Out => endpoint_out = Some(endpoint.address()),
-                }
+    fn bulk_out(
+        &self,
+        endpoint: u8,
+        buf: Vec<u8>,
+    ) -> impl Future<Output = Result<(), TransferError>> {
+        let transfer = nusb::Interface::bulk_out(self, endpoint, buf);
+        async move { transfer.await.status }
+    }
+    #[inline]
+    fn bulk_in(
+        &self,
+        endpoint: u8,
+        len: usize,
+    ) -> impl Future<Output = Result<Vec<u8>, TransferError>> {
+        let buf = nusb::transfer::RequestBuffer::new(len);
+        let transfer = nusb::Interface::bulk_in(self, endpoint, buf);
+        async move { transfer.await.into_result() }
+    }
+}
+
+/// Error on FEL communication.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FelError {
+    /// USB transfer did not complete in time and was cancelled.
+    Timeout,
+    /// USB transfer failed.
+    Transfer(TransferError),
+    /// Device replied with unexpected data.
+    InvalidResponse,
+    /// Device returned fewer bytes than requested, even after retries.
+    ShortRead { expected: usize, received: usize },
+}
+
+impl fmt::Display for FelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FelError::Timeout => write!(f, "USB transfer timed out"),
+            FelError::Transfer(e) => write!(f, "USB transfer failed: {}", e),
+            FelError::InvalidResponse => write!(f, "invalid USB response from device"),
+            FelError::ShortRead { expected, received } => {
+                write!(f, "USB read returned {} of {} bytes", received, expected)
             }
         }
+    }
+}
+
+impl std::error::Error for FelError {}
+
+impl From<TransferError> for FelError {
+    #[inline]
+    fn from(e: TransferError) -> Self {
+        FelError::Transfer(e)
+    }
+}
+
+impl<'a> Fel<'a> {
+    #[inline]
+    pub fn open_interface(iface: &'a mut nusb::Interface) -> Result<Self, ()> {
+        let (endpoint_in, endpoint_out) = descriptors::find_bulk_endpoints(iface.descriptors());
         let (Some(endpoint_in), Some(endpoint_out)) = (endpoint_in, endpoint_out) else {
             error!("Malformed device. Allwinner USB FEL device should include exactly one bulk in and one bulk out endpoint.");
             return Err(());
@@ -36,95 +139,197 @@ impl<'a> Fel<'a> {
             "Endpoint in ID 0x{:x}, out ID 0x{:x}",
             endpoint_in, endpoint_out
         );
-        Ok(Self {
+        Ok(Self::new(iface, endpoint_in, endpoint_out))
+    }
+}
+
+impl<'a, T: Transport> Fel<'a, T> {
+    /// Create an FEL handle on a transport with known bulk endpoints.
+    #[inline]
+    pub fn new(iface: &'a mut T, endpoint_in: u8, endpoint_out: u8) -> Self {
+        Self {
             iface,
             endpoint_in,
             endpoint_out,
             version: None,
-        })
+            timeout: DEFAULT_TIMEOUT,
+            stats: Cell::new(SessionStats::default()),
+        }
+    }
+    /// Set timeout of every single USB transfer.
+    #[inline]
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+    /// Get transfer statistics accumulated since this handle was created.
+    #[inline]
+    pub fn stats(&self) -> SessionStats {
+        self.stats.get()
+    }
+    /// Count one operation retried by the caller in transfer statistics.
+    #[inline]
+    pub fn record_retry(&self) {
+        self.update_stats(SessionStats::record_retry);
     }
 
-    pub fn get_version(&self) -> Version {
-        self.version.unwrap_or_else(|| {
-            let mut buf = [0u8; 32];
-            self.send_fel_request(FelRequest::get_version());
-            self.usb_read(&mut buf);
-            self.read_fel_status();
-            unsafe { core::mem::transmute(buf) }
-        })
+    pub fn get_version(&self) -> Result<Version, FelError> {
+        if let Some(version) = self.version {
+            return Ok(version);
+        }
+        let mut buf = [0u8; 32];
+        self.send_fel_request(FelRequest::get_version())?;
+        self.usb_read(&mut buf)?;
+        self.read_fel_status()?;
+        Ok(Version::from_bytes(buf))
     }
 
-    pub fn read_address(&self, address: u32, buf: &mut [u8]) -> usize {
+    pub fn read_address(&self, address: u32, buf: &mut [u8]) -> Result<usize, FelError> {
         trace!("read_address");
-        for chunk in buf.chunks_mut(CHUNK_SIZE) {
-            self.send_fel_request(FelRequest::read_raw(address, chunk.len() as u32));
-            self.usb_read(chunk);
-            self.read_fel_status();
+        for (i, chunk) in buf.chunks_mut(CHUNK_SIZE).enumerate() {
+            let address = address.wrapping_add((i * CHUNK_SIZE) as u32);
+            self.send_fel_request(FelRequest::read_raw(address, chunk.len() as u32))?;
+            self.usb_read(chunk)?;
+            self.read_fel_status()?;
+            self.update_stats(|stats| stats.record_read(chunk.len()));
         }
-        buf.len()
+        Ok(buf.len())
     }
 
-    pub fn write_address(&self, address: u32, buf: &[u8]) -> usize {
+    pub fn exec(&self, address: u32) -> Result<(), FelError> {
+        trace!("exec");
+        self.send_fel_request(FelRequest::exec(address))?;
+        self.read_fel_status()
+    }
+
+    pub fn write_address(&self, address: u32, buf: &[u8]) -> Result<usize, FelError> {
         trace!("write_address");
-        for chunk in buf.chunks(CHUNK_SIZE) {
-            self.send_fel_request(FelRequest::write_raw(address, chunk.len() as u32));
-            self.usb_write(chunk);
-            self.read_fel_status();
+        for (i, chunk) in buf.chunks(CHUNK_SIZE).enumerate() {
+            let address = address.wrapping_add((i * CHUNK_SIZE) as u32);
+            self.send_fel_request(FelRequest::write_raw(address, chunk.len() as u32))?;
+            self.usb_write(chunk)?;
+            self.read_fel_status()?;
+            self.update_stats(|stats| stats.record_write(chunk.len()));
         }
-        buf.len()
+        Ok(buf.len())
     }
 
-    fn send_fel_request(&self, request: FelRequest) {
+    fn send_fel_request(&self, request: FelRequest) -> Result<(), FelError> {
         trace!("send_fel_request");
         let buf: [u8; 16] = unsafe { core::mem::transmute(request) };
-        self.usb_write(&buf);
+        self.usb_write(&buf)
     }
 
-    fn read_fel_status(&self) {
+    fn read_fel_status(&self) -> Result<(), FelError> {
         trace!("read_fel_status");
         let mut buf = [0u8; 8];
-        self.usb_read(&mut buf);
+        self.usb_read(&mut buf)
     }
 
-    fn usb_read(&self, buf: &mut [u8]) {
+    fn usb_read(&self, buf: &mut [u8]) -> Result<(), FelError> {
         trace!("usb_read");
         let buf_1: [u8; 36] =
             unsafe { core::mem::transmute(UsbRequest::usb_read(buf.len() as u32)) };
-        block_on(self.iface.bulk_out(self.endpoint_out, buf_1.to_vec()))
-            .status
-            .expect("send_usb_request on usb_read transfer");
-        let buf_2 = nusb::transfer::RequestBuffer::new(buf.len());
-        let ans = block_on(self.iface.bulk_in(self.endpoint_in, buf_2));
-        ans.status.expect("usb bulk out on usb_read transfer");
-        let buf_3 = nusb::transfer::RequestBuffer::new(13);
-        let ans_1 = block_on(self.iface.bulk_in(self.endpoint_in, buf_3));
-        ans_1
-            .status
-            .expect("read_usb_response on usb_read transfer");
-        if ans_1.data != *b"AWUS\0\0\0\0\0\0\0\0\0" {
-            panic!("invalid data received from read_usb_response")
-        }
-        buf.copy_from_slice(&ans.data);
-    }
-
-    fn usb_write(&self, buf: &[u8]) {
+        self.block_on(self.iface.bulk_out(self.endpoint_out, buf_1.to_vec()))?;
+        // marginal links may split the data phase; fetch the remainder before giving up
+        let (mut received, mut retries) = (0, 0);
+        loop {
+            let remaining = buf.len() - received;
+            let data = self.block_on(self.iface.bulk_in(self.endpoint_in, remaining))?;
+            if data.len() > remaining {
+                warn!(
+                    "USB read returned {} bytes, expected {}; extra bytes discarded",
+                    data.len(),
+                    remaining
+                );
+            }
+            let len = data.len().min(remaining);
+            buf[received..received + len].copy_from_slice(&data[..len]);
+            received += len;
+            if received == buf.len() {
+                break;
+            }
+            if retries == SHORT_READ_RETRIES {
+                return Err(FelError::ShortRead {
+                    expected: buf.len(),
+                    received,
+                });
+            }
+            debug!(
+                "short USB read, {} of {} bytes received",
+                received,
+                buf.len()
+            );
+            retries += 1;
+            self.update_stats(SessionStats::record_retry);
+        }
+        self.read_usb_response()
+    }
+
+    fn usb_write(&self, buf: &[u8]) -> Result<(), FelError> {
         trace!("usb_write");
         let buf_1: [u8; 36] =
             unsafe { core::mem::transmute(UsbRequest::usb_write(buf.len() as u32)) };
-        block_on(self.iface.bulk_out(self.endpoint_out, buf_1.to_vec()))
-            .status
-            .expect("send_usb_request on usb_write transfer");
-        block_on(self.iface.bulk_out(self.endpoint_out, buf.to_vec()))
-            .status
-            .expect("usb bulk out on usb_write transfer");
-        let buf_3 = nusb::transfer::RequestBuffer::new(13);
-        let ans_1 = block_on(self.iface.bulk_in(self.endpoint_in, buf_3));
-        ans_1
-            .status
-            .expect("read_usb_response on usb_write transfer");
-        if ans_1.data != *b"AWUS\0\0\0\0\0\0\0\0\0" {
-            panic!("invalid data received from read_usb_response")
+        self.block_on(self.iface.bulk_out(self.endpoint_out, buf_1.to_vec()))?;
+        self.block_on(self.iface.bulk_out(self.endpoint_out, buf.to_vec()))?;
+        self.read_usb_response()
+    }
+
+    fn read_usb_response(&self) -> Result<(), FelError> {
+        let data = self.block_on(self.iface.bulk_in(self.endpoint_in, 13))?;
+        if data != *b"AWUS\0\0\0\0\0\0\0\0\0" {
+            return Err(FelError::InvalidResponse);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn update_stats(&self, f: impl FnOnce(&mut SessionStats)) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
+
+    /// Run one USB transfer to completion, cancelling it on timeout.
+    fn block_on<R>(
+        &self,
+        transfer: impl Future<Output = Result<R, TransferError>>,
+    ) -> Result<R, FelError> {
+        self.update_stats(SessionStats::record_transfer);
+        match block_on_timeout(transfer, self.timeout) {
+            Some(ans) => Ok(ans?),
+            None => Err(FelError::Timeout),
+        }
+    }
+}
+
+/// Wakes a thread parked in [`block_on_timeout`].
+struct ThreadWaker(std::thread::Thread);
+
+impl ArcWake for ThreadWaker {
+    #[inline]
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.0.unpark();
+    }
+}
+
+/// Run `future` on current thread until it completes or `timeout` expires.
+///
+/// Returns `None` on timeout; the future is dropped then, which cancels a
+/// pending USB transfer.
+fn block_on_timeout<F: Future>(future: F, timeout: Duration) -> Option<F::Output> {
+    let deadline = Instant::now() + timeout;
+    let mut future = pin!(future);
+    let waker = waker(Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(ans) = future.as_mut().poll(&mut cx) {
+            return Some(ans);
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return None;
         }
+        std::thread::park_timeout(deadline - now);
     }
 }
 
@@ -201,8 +406,18 @@ impl FelRequest {
             pad: 0,
         }
     }
+    #[inline]
+    pub const fn exec(address: u32) -> Self {
+        FelRequest {
+            request: 0x102,
+            address,
+            length: 0,
+            pad: 0,
+        }
+    }
 }
 
+/// Response of FEL version request, identifying the boot ROM.
 #[derive(Copy, Clone)]
 #[repr(C)]
 pub struct Version {
@@ -217,6 +432,64 @@ pub struct Version {
 }
 
 impl Version {
+    /// Decode version from the 32-byte little endian response.
+    pub fn from_bytes(buf: [u8; 32]) -> Self {
+        let word = |i: usize| u32::from_le_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]]);
+        Version {
+            magic: buf[0..8].try_into().unwrap(),
+            id: word(8),
+            firmware: word(12),
+            protocol: u16::from_le_bytes([buf[16], buf[17]]),
+            dflag: buf[18],
+            dlength: buf[19],
+            scratchpad: word(20),
+            pad: buf[24..32].try_into().unwrap(),
+        }
+    }
+    /// Get magic string, `AWUSBFEX` on all known boot ROMs.
+    #[inline]
+    pub fn magic(&self) -> &[u8; 8] {
+        &self.magic
+    }
+    /// Get raw chip ID; SoC ID in bits 8..24, e.g. `0x1859` on D1.
+    #[inline]
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+    /// Get SoC ID part of chip ID.
+    #[inline]
+    pub fn soc_id(&self) -> u16 {
+        (self.id >> 8) as u16
+    }
+    /// Get boot ROM firmware version, 1 on D1.
+    #[inline]
+    pub fn firmware(&self) -> u32 {
+        self.firmware
+    }
+    /// Get FEL protocol version, 1 on D1.
+    #[inline]
+    pub fn protocol(&self) -> u16 {
+        self.protocol
+    }
+    /// Get data flag.
+    ///
+    /// Not documented by the vendor; always 0x44 on known boot ROMs including D1.
+    #[inline]
+    pub fn dflag(&self) -> u8 {
+        self.dflag
+    }
+    /// Get data length.
+    ///
+    /// Not documented by the vendor; always 8 on known boot ROMs including D1.
+    #[inline]
+    pub fn dlength(&self) -> u8 {
+        self.dlength
+    }
+    /// Get address of boot ROM scratch area, 0x27000 on D1.
+    #[inline]
+    pub fn scratchpad(&self) -> u32 {
+        self.scratchpad
+    }
     /// Get chip from version.
     pub fn chip(self) -> Option<Chip> {
         match self.id {
@@ -241,9 +514,330 @@ impl fmt::Debug for Version {
     }
 }
 
+/// Human-readable boot ROM identity.
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ", String::from_utf8_lossy(&self.magic))?;
+        match self.chip() {
+            Some(chip) => write!(f, "{:?}", chip)?,
+            None => write!(f, "unknown chip")?,
+        }
+        write!(
+            f,
+            " (SoC ID 0x{:04x}), BROM firmware {}, FEL protocol {}, \
+            dflag 0x{:02x}, dlength {}, scratchpad 0x{:08x}",
+            self.soc_id(),
+            self.firmware,
+            self.protocol,
+            self.dflag,
+            self.dlength,
+            self.scratchpad
+        )
+    }
+}
+
 #[derive(Debug)]
 #[repr(u32)]
 pub enum Chip {
     /// D1-H, D1s or F133 chip.
     D1 = 0x00185900,
 }
+
+/// Memory region in chip address space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRegion {
+    /// Name of this region.
+    pub name: &'static str,
+    /// Start address.
+    pub start: u32,
+    /// Size in bytes.
+    pub size: u32,
+    /// Whether code can be executed from this region.
+    pub executable: bool,
+}
+
+impl MemoryRegion {
+    /// Check if `address` is inside this region.
+    #[inline]
+    pub fn contains(&self, address: u32) -> bool {
+        address.wrapping_sub(self.start) < self.size
+    }
+}
+
+const D1_REGIONS: &[MemoryRegion] = &[
+    MemoryRegion {
+        name: "SRAM A1",
+        start: 0x0002_0000,
+        size: 0x8000,
+        executable: true,
+    },
+    MemoryRegion {
+        name: "peripherals",
+        start: 0x0200_0000,
+        size: 0x0600_0000,
+        executable: false,
+    },
+    MemoryRegion {
+        name: "DRAM",
+        start: 0x4000_0000,
+        size: 0x8000_0000,
+        executable: true,
+    },
+];
+
+/// Scratch memory on D1, at the start of SRAM A1 where boot ROM loads payloads.
+const D1_SCRATCH: MemoryRegion = MemoryRegion {
+    name: "scratch",
+    start: 0x0002_0000,
+    size: 0x4000,
+    executable: true,
+};
+
+impl Chip {
+    /// Memory region that can be freely overwritten while the chip is in FEL mode.
+    #[inline]
+    pub fn scratch_region(&self) -> &'static MemoryRegion {
+        match self {
+            Chip::D1 => &D1_SCRATCH,
+        }
+    }
+    /// Machine code that returns to its caller immediately.
+    #[inline]
+    pub fn return_stub(&self) -> &'static [u8] {
+        match self {
+            // RISC-V `ret`
+            Chip::D1 => &[0x67, 0x80, 0x00, 0x00],
+        }
+    }
+    /// ELF machine type of the application processor.
+    #[inline]
+    pub fn elf_machine(&self) -> u16 {
+        match self {
+            Chip::D1 => elf::EM_RISCV,
+        }
+    }
+    /// Address of chip ID in security ID (SID) area.
+    #[inline]
+    pub fn sid_address(&self) -> u32 {
+        match self {
+            Chip::D1 => 0x0300_6200,
+        }
+    }
+    /// Known memory regions of this chip.
+    #[inline]
+    pub fn memory_regions(&self) -> &'static [MemoryRegion] {
+        match self {
+            Chip::D1 => D1_REGIONS,
+        }
+    }
+    /// Get memory region containing `address`.
+    #[inline]
+    pub fn region_of(&self, address: u32) -> Option<&'static MemoryRegion> {
+        self.memory_regions().iter().find(|r| r.contains(address))
+    }
+}
+
+/// Error on checking the target address of `exec`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecAddressError {
+    /// Address is inside a region where code cannot be executed.
+    NotExecutable(&'static MemoryRegion),
+    /// Address is not inside any known memory region.
+    Unmapped,
+}
+
+impl fmt::Display for ExecAddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecAddressError::NotExecutable(region) => {
+                write!(f, "address is in non-executable region {}", region.name)
+            }
+            ExecAddressError::Unmapped => write!(f, "address is not in any mapped region"),
+        }
+    }
+}
+
+/// Check if `address` is an executable `exec` target on `chip`.
+///
+/// Any address is accepted on unknown chips. With `force`, a rejected address
+/// only produces a warning.
+pub fn check_exec_address(
+    chip: Option<&Chip>,
+    address: u32,
+    force: bool,
+) -> Result<(), ExecAddressError> {
+    let Some(chip) = chip else {
+        return Ok(());
+    };
+    let ans = match chip.region_of(address) {
+        Some(region) if region.executable => Ok(()),
+        Some(region) => Err(ExecAddressError::NotExecutable(region)),
+        None => Err(ExecAddressError::Unmapped),
+    };
+    match ans {
+        Err(e) if force => {
+            warn!("exec target 0x{:08x}: {}, continue as forced", address, e);
+            Ok(())
+        }
+        ans => ans,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_exec_address, Chip, ExecAddressError, Fel, FelError, Transport, Version, CHUNK_SIZE,
+    };
+    use crate::mock::MockFel;
+    use nusb::transfer::TransferError;
+    use std::{
+        future::Future,
+        time::{Duration, Instant},
+    };
+
+    /// USB interface whose IN transfers never complete, like a dead device.
+    struct DeadInterface;
+
+    impl Transport for DeadInterface {
+        fn bulk_out(
+            &self,
+            _endpoint: u8,
+            _buf: Vec<u8>,
+        ) -> impl Future<Output = Result<(), TransferError>> {
+            futures::future::ready(Ok(()))
+        }
+        fn bulk_in(
+            &self,
+            _endpoint: u8,
+            _len: usize,
+        ) -> impl Future<Output = Result<Vec<u8>, TransferError>> {
+            futures::future::pending()
+        }
+    }
+
+    #[test]
+    fn usb_transfer_timeout() {
+        let mut iface = DeadInterface;
+        let mut fel = Fel {
+            iface: &mut iface,
+            endpoint_in: 0x81,
+            endpoint_out: 0x01,
+            version: None,
+            timeout: Duration::from_secs(5),
+            stats: Default::default(),
+        };
+        fel.set_timeout(Duration::from_millis(50));
+        let start = Instant::now();
+        let mut buf = [0u8; 4];
+        assert_eq!(
+            fel.read_address(0x4000_0000, &mut buf),
+            Err(FelError::Timeout)
+        );
+        assert_eq!(fel.exec(0x4000_0000), Err(FelError::Timeout));
+        assert!(fel.get_version().is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn usb_short_and_long_reads() {
+        let data: Vec<u8> = (0..64).collect();
+        let mut mock = MockFel::default();
+        let fel = mock.fel();
+        fel.write_address(0x4000_0000, &data).unwrap();
+        // remainder of a short read is fetched again
+        fel.iface.short_reads.set(1);
+        let mut buf = [0u8; 64];
+        assert_eq!(fel.read_address(0x4000_0000, &mut buf), Ok(64));
+        assert_eq!(buf[..], data[..]);
+        assert_eq!(fel.iface.short_reads.get(), 0);
+        assert_eq!(fel.stats().retries, 1);
+
+        // over-long reads are truncated
+        let mut mock = MockFel::default();
+        mock.extra_bytes = 4;
+        let fel = mock.fel();
+        fel.write_address(0x4000_0000, &data).unwrap();
+        let mut buf = [0u8; 64];
+        assert_eq!(fel.read_address(0x4000_0000, &mut buf), Ok(64));
+        assert_eq!(buf[..], data[..]);
+        assert_eq!(fel.stats().retries, 0);
+
+        // fail once retries run out
+        let mut mock = MockFel::default();
+        let fel = mock.fel();
+        fel.iface.short_reads.set(4);
+        let mut buf = [0u8; 64];
+        assert_eq!(
+            fel.read_address(0x4000_0000, &mut buf),
+            Err(FelError::ShortRead {
+                expected: 64,
+                received: 60
+            })
+        );
+    }
+
+    #[test]
+    fn transfer_larger_than_chunk() {
+        let data: Vec<u8> = (0..2 * CHUNK_SIZE + 100).map(|i| (i / 7) as u8).collect();
+        let mut mock = MockFel::default();
+        let fel = mock.fel();
+        assert_eq!(fel.write_address(0x4000_0000, &data), Ok(data.len()));
+        let mut buf = vec![0u8; data.len()];
+        assert_eq!(fel.read_address(0x4000_0000, &mut buf), Ok(data.len()));
+        assert!(buf == data);
+        assert_eq!(mock.byte(0x4000_0000 + CHUNK_SIZE as u32), data[CHUNK_SIZE]);
+        assert_eq!(
+            mock.byte(0x4000_0000 + data.len() as u32 - 1),
+            data[data.len() - 1]
+        );
+    }
+
+    #[test]
+    fn decode_version() {
+        let mut buf = [0u8; 32];
+        buf[..8].copy_from_slice(b"AWUSBFEX");
+        buf[8..24].copy_from_slice(&[
+            0x00, 0x59, 0x18, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x44, 0x08, 0x00, 0x70,
+            0x02, 0x00,
+        ]);
+        let version = Version::from_bytes(buf);
+        assert!(matches!(version.chip(), Some(Chip::D1)));
+        assert_eq!(version.magic(), b"AWUSBFEX");
+        assert_eq!(version.id(), 0x0018_5900);
+        assert_eq!(version.soc_id(), 0x1859);
+        assert_eq!(version.firmware(), 1);
+        assert_eq!(version.protocol(), 1);
+        assert_eq!(version.dflag(), 0x44);
+        assert_eq!(version.dlength(), 8);
+        assert_eq!(version.scratchpad(), 0x0002_7000);
+        assert_eq!(
+            version.to_string(),
+            "AWUSBFEX D1 (SoC ID 0x1859), BROM firmware 1, FEL protocol 1, \
+            dflag 0x44, dlength 8, scratchpad 0x00027000"
+        );
+
+        buf[9] = 0x21;
+        let version = Version::from_bytes(buf);
+        assert!(version.chip().is_none());
+        assert!(version
+            .to_string()
+            .starts_with("AWUSBFEX unknown chip (SoC ID 0x1821)"));
+    }
+
+    #[test]
+    fn exec_address_on_d1() {
+        let chip = Chip::D1;
+        assert_eq!(check_exec_address(Some(&chip), 0x0002_0000, false), Ok(()));
+        assert_eq!(check_exec_address(Some(&chip), 0x4000_0000, false), Ok(()));
+        assert!(matches!(
+            check_exec_address(Some(&chip), 0x0200_1000, false),
+            Err(ExecAddressError::NotExecutable(region)) if region.name == "peripherals"
+        ));
+        assert_eq!(
+            check_exec_address(Some(&chip), 0x1000_0000, false),
+            Err(ExecAddressError::Unmapped)
+        );
+        assert_eq!(check_exec_address(Some(&chip), 0x0200_1000, true), Ok(()));
+        assert_eq!(check_exec_address(None, 0x0200_1000, false), Ok(()));
+    }
+}
@@ -1,7 +1,30 @@
 use clap::{Parser, Subcommand};
 use clap_verbosity_flag::Verbosity;
-use log::{debug, error};
-use rfel::Fel;
+use log::debug;
+use rfel::{
+    descriptors,
+    egon::{self, EgonHead},
+    elf,
+    error::CliError,
+    hexdump::hexdump,
+    identity,
+    imgdiff::{self, SegmentDiff},
+    incremental::{self, BlockIndex},
+    manifest::{self, Backend, Backends},
+    memtest::{self, Pattern},
+    monitor, parallel,
+    progress::{Progress, ProgressMode},
+    remote::{self, RemoteTransport},
+    sign, snapshot,
+    toc::TocHead,
+    transfer::{self, RetryPolicy},
+    Chip, Fel, Transport, CHUNK_SIZE,
+};
+use std::{
+    io::{IsTerminal, Write},
+    process::ExitCode,
+    time::{Duration, Instant},
+};
 
 #[derive(Parser)]
 #[clap(name = "rfel")]
@@ -9,11 +32,29 @@ use rfel::Fel;
 struct Cli {
     #[clap(flatten)]
     verbose: Verbosity,
+    /// Always show live progress bar, even if output is not a terminal
+    #[clap(long, global = true)]
+    progress: bool,
+    /// Timeout of one USB transfer in milliseconds
+    #[clap(long, global = true, default_value_t = 5000)]
+    timeout: u64,
+    /// Connect to an FEL proxy at host:port instead of a local USB device
+    #[clap(long, global = true, value_name = "HOST:PORT")]
+    remote: Option<String>,
+    /// Run the command on every connected FEL device at once, each on its own thread
+    #[clap(long, global = true, conflicts_with = "remote")]
+    parallel: bool,
+    /// Print transfer statistics of the session on exit
+    #[clap(long, global = true)]
+    stats: bool,
+    /// Retry a failed chunk of read or write this many times, with growing delay
+    #[clap(long, global = true, default_value_t = 0, value_name = "N")]
+    retries: u32,
     #[clap(subcommand)]
     command: Commands,
 }
 
-#[derive(Debug, Subcommand)]
+#[derive(Clone, Debug, Subcommand)]
 enum Commands {
     /// Show chip version
     Version,
@@ -24,11 +65,47 @@ enum Commands {
         /// Length of memory to be dumped
         length: String,
     },
+    /// Repeatedly dump a memory region in place, highlighting changed bytes
+    Monitor {
+        /// The address to be watched
+        address: String,
+        /// Length of memory to be watched
+        length: String,
+        /// Interval between two reads in milliseconds
+        #[clap(long, default_value_t = 500)]
+        interval_ms: u64,
+    },
     /// Read a 32-bit value from chip memory
     Read32 {
         /// The address to be read
         address: String,
     },
+    /// Read chip memory into a local file
+    Read {
+        /// The address to be read
+        address: String,
+        /// Length of memory to be read
+        length: String,
+        /// Path to the file to be written
+        file: std::path::PathBuf,
+        /// Output format: raw bytes, or an ELF file loading them at the read address
+        #[clap(long, value_enum, default_value_t = ReadFormat::Bin)]
+        format: ReadFormat,
+    },
+    /// Save a chip memory region into a snapshot file carrying its address
+    Save {
+        /// The address of the region
+        address: String,
+        /// Length of the region
+        length: String,
+        /// Path to the snapshot file to be written
+        file: std::path::PathBuf,
+    },
+    /// Restore chip memory from a snapshot file written by save
+    Restore {
+        /// Path to the snapshot file
+        file: std::path::PathBuf,
+    },
     /// Write a 32-bit value into chip memory
     Write32 {
         /// The address to be written
@@ -36,116 +113,1020 @@ enum Commands {
         /// The 32-bit value to be written
         value: String,
     },
+    /// Execute code at the given address
+    Exec {
+        /// The address to jump to
+        address: String,
+        /// Execute even if the address is not in an executable region
+        #[clap(long)]
+        force: bool,
+        /// Host serial port to show payload console output from, e.g. /dev/ttyUSB0
+        #[clap(long)]
+        console: Option<String>,
+        /// Baud rate of console serial port
+        #[clap(long, default_value_t = 115200, requires = "console")]
+        baud: u32,
+    },
+    /// Write a local file into chip memory
+    Write {
+        /// The address to be written
+        address: String,
+        /// Path to the file to be written
+        file: std::path::PathBuf,
+        /// Memory-map the file instead of reading it through a buffer
+        #[clap(long)]
+        mmap: bool,
+        /// Read back and compare written data
+        #[clap(long)]
+        verify: bool,
+        /// Save device bytes of the mismatching range to this file if verify fails
+        #[clap(long, value_name = "FILE", requires = "verify")]
+        output_on_mismatch: Option<std::path::PathBuf>,
+        /// Cap average write rate in bytes per second
+        #[clap(long, value_name = "BYTES_PER_SEC", value_parser = clap::value_parser!(u64).range(1..))]
+        throttle: Option<u64>,
+        /// Always write in maximum-size chunks instead of adapting chunk size to the link
+        #[clap(long)]
+        fixed_chunk: bool,
+        /// Only write blocks changed since the block index in this file, then update it
+        #[clap(long, value_name = "INDEX", conflicts_with_all = ["mmap", "fixed_chunk"])]
+        since: Option<std::path::PathBuf>,
+    },
+    /// Write images listed in a manifest file to their targets in order
+    Flash {
+        /// Path to the manifest file, with `<target> <offset> <file>` on each line
+        manifest: std::path::PathBuf,
+    },
+    /// Write, read back and verify scratch memory to check FEL communication
+    Selftest {
+        /// Also execute a return-immediately stub
+        #[clap(long)]
+        exec: bool,
+    },
+    /// Write a pattern across a memory region and read it back, e.g. to check DRAM
+    Memtest {
+        /// Start address of the region, word aligned
+        address: String,
+        /// Length of the region in bytes, a multiple of 4
+        length: String,
+        /// Pattern to write: walking1, walking0, addr or random
+        #[clap(long, default_value_t = Pattern::Walking1)]
+        pattern: Pattern,
+    },
+    /// Show USB descriptors of connected Allwinner devices
+    UsbDescriptors,
+    /// List connected FEL devices and their chips
+    List,
+    /// Show eGON header information of a local image
+    Imginfo {
+        /// Path to the image file
+        file: std::path::PathBuf,
+    },
+    /// Compare two local images, per loadable segment for ELF files or per block otherwise
+    DiffImage {
+        /// Path to the original image
+        a: std::path::PathBuf,
+        /// Path to the image to compare against
+        b: std::path::PathBuf,
+        /// Block size of raw image comparison in bytes
+        #[clap(long, default_value_t = imgdiff::DEFAULT_BLOCK_SIZE, value_parser = parse_block_size)]
+        block_size: usize,
+    },
+    /// Rewrite load address of a local ELF or eGON image for a new base
+    Relocate {
+        /// Path to the image file
+        image: std::path::PathBuf,
+        /// New load address of the image
+        new_base: String,
+        /// Path to write the relocated image into
+        out: std::path::PathBuf,
+    },
+    /// Inspect or unpack a TOC0 or TOC1 secure boot container
+    Toc {
+        #[clap(subcommand)]
+        command: TocCommand,
+    },
+    /// Sign SHA-256 of chip SID with an ECDSA P-256 key
+    Sign {
+        /// Path to the PEM public key, checked against the signature
+        public: std::path::PathBuf,
+        /// Path to the PEM private key
+        private: std::path::PathBuf,
+        /// Path to write the 64-byte raw signature into
+        file: std::path::PathBuf,
+    },
+    /// Generate an ECDSA P-256 key pair for signing
+    Keygen {
+        /// Path to write the PEM public key into
+        #[clap(long)]
+        public: std::path::PathBuf,
+        /// Path to write the PEM private key into
+        #[clap(long)]
+        private: std::path::PathBuf,
+    },
 }
 
+/// File format of memory read into a local file.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum ReadFormat {
+    /// Raw memory contents
+    Bin,
+    /// Minimal ELF file with one loadable segment at the read address
+    Elf,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+enum TocCommand {
+    /// Show header and items of a container
+    Info {
+        /// Path to the container file
+        file: std::path::PathBuf,
+    },
+    /// Write contents of one item into a file
+    Extract {
+        /// Path to the container file
+        file: std::path::PathBuf,
+        /// Name or index of the item
+        item: String,
+        /// Path to write item contents into
+        output: std::path::PathBuf,
+    },
+}
+
+/// Smallest chunk size of adaptive writes.
+const MIN_CHUNK_SIZE: usize = 4096;
+
 /// USB vendor ID 0x1f3a: Allwinner Technology Co., Ltd.
 const VENDOR_ALLWINNER: u16 = 0x1f3a;
 /// Product 0xefe8: sunxi SoC OTG connector in FEL/flashing mode.
 const PRODUCT_FEL: u16 = 0xefe8;
 
-fn main() {
+fn main() -> ExitCode {
     let cli = Cli::parse();
     env_logger::Builder::new()
         .filter_level(cli.verbose.log_level_filter())
         .init();
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            println!("error: {}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<(), CliError> {
+    match &cli.command {
+        Commands::Imginfo { file } => return imginfo(file),
+        Commands::DiffImage { a, b, block_size } => return diff_image(a, b, *block_size),
+        Commands::Relocate {
+            image,
+            new_base,
+            out,
+        } => return relocate(image, new_base, out),
+        Commands::Toc { command } => return toc(command),
+        Commands::Keygen { public, private } => {
+            return sign::write_key_pair(&sign::generate_key(), public, private)
+        }
+        Commands::UsbDescriptors => return usb_descriptors(),
+        Commands::List => return list(Duration::from_millis(cli.timeout)),
+        _ => {}
+    }
+    let timeout = Duration::from_millis(cli.timeout);
+    let retry = RetryPolicy::new(cli.retries);
+    let quiet = cli.verbose.is_silent();
+    let start = Instant::now();
+    if let Some(addr) = &cli.remote {
+        let mut remote = RemoteTransport::connect(addr.as_str(), timeout)
+            .map_err(|e| CliError::Device(format!("cannot connect to {}: {}", addr, e)))?;
+        let mut fel = Fel::new(&mut remote, remote::ENDPOINT_IN, remote::ENDPOINT_OUT);
+        fel.set_timeout(timeout);
+        let ans = identity::guard(&fel, &mut std::io::stdout(), |fel| {
+            execute_device_command(fel, cli.command, quiet, cli.progress, retry)
+        });
+        if cli.stats {
+            eprintln!("{}", fel.stats().summary(start.elapsed()));
+        }
+        return ans;
+    }
     let devices: Vec<_> = nusb::list_devices()
-        .expect("list devices")
+        .map_err(|e| CliError::Device(format!("cannot list USB devices: {}", e)))?
         .filter(|dev| dev.vendor_id() == VENDOR_ALLWINNER && dev.product_id() == PRODUCT_FEL)
         .inspect(|dev| debug!("Allwinner FEL device {:?}", dev))
         .collect();
     if devices.len() == 0 {
-        error!("Cannot find any Allwinner FEL device connected.");
-        return;
+        return Err(CliError::Device(
+            "cannot find any Allwinner FEL device connected".into(),
+        ));
+    }
+    if cli.parallel {
+        return execute_parallel(&devices, cli.command, timeout, retry);
     }
     if devices.len() > 1 {
-        error!("TODO: rfel does not support connecting to multiple Allwinner FEL devices by now.");
-        return;
+        return Err(CliError::Device(format!(
+            "{} Allwinner FEL devices connected; use --parallel to run on all of them",
+            devices.len()
+        )));
+    }
+    let mut interface = open_device(&devices[0])?;
+    let mut fel = Fel::open_interface(&mut interface)
+        .map_err(|()| CliError::Device("cannot open USB interface as an FEL device".into()))?;
+    fel.set_timeout(timeout);
+    let ans = identity::guard(&fel, &mut std::io::stdout(), |fel| {
+        execute_device_command(fel, cli.command, quiet, cli.progress, retry)
+    });
+    if cli.stats {
+        eprintln!("{}", fel.stats().summary(start.elapsed()));
+    }
+    ans
+}
+
+/// Open USB interface 0 of FEL device `info`.
+fn open_device(info: &nusb::DeviceInfo) -> Result<nusb::Interface, CliError> {
+    let device = info
+        .open()
+        .map_err(|e| CliError::Device(format!("cannot open USB device: {}", e)))?;
+    device
+        .claim_interface(0)
+        .map_err(|e| CliError::Device(format!("cannot open USB interface 0: {}", e)))
+}
+
+/// Run `command` on all `devices` in parallel, then report result of each device.
+///
+/// Progress is hidden as output of devices interleaves. Returns the first error
+/// if any device failed.
+fn execute_parallel(
+    devices: &[nusb::DeviceInfo],
+    command: Commands,
+    timeout: Duration,
+    retry: RetryPolicy,
+) -> Result<(), CliError> {
+    let devices = devices
+        .iter()
+        .map(|info| {
+            let name = format!(
+                "bus {} address {}",
+                info.bus_number(),
+                info.device_address()
+            );
+            (name, info)
+        })
+        .collect();
+    let outcomes = parallel::fan_out(devices, |info| {
+        let mut interface = open_device(info)?;
+        let mut fel = Fel::open_interface(&mut interface)
+            .map_err(|()| CliError::Device("cannot open USB interface as an FEL device".into()))?;
+        fel.set_timeout(timeout);
+        identity::guard(&fel, &mut std::io::stdout(), |fel| {
+            execute_device_command(fel, command.clone(), true, false, retry)
+        })
+    });
+    let total = outcomes.len();
+    let mut first_error = None;
+    let mut failed = 0;
+    for outcome in outcomes {
+        let result = outcome
+            .result
+            .unwrap_or_else(|| Err(CliError::Protocol("command panicked".into())));
+        match result {
+            Ok(()) => println!("{}: ok", outcome.device),
+            Err(e) => {
+                println!("{}: error: {}", outcome.device, e);
+                failed += 1;
+                first_error.get_or_insert(e);
+            }
+        }
     }
-    let device = devices[0].open().expect("open USB device");
-    let mut interface = device.claim_interface(0).expect("open USB interface 0");
-    let fel = Fel::open_interface(&mut interface).expect("open usb interface as an FEL device");
-    match cli.command {
+    println!("{} of {} devices succeeded", total - failed, total);
+    first_error.map_or(Ok(()), Err)
+}
+
+fn execute_device_command<T: Transport>(
+    fel: &Fel<T>,
+    command: Commands,
+    quiet: bool,
+    force_progress: bool,
+    retry: RetryPolicy,
+) -> Result<(), CliError> {
+    match command {
         Commands::Version => {
-            let version = fel.get_version();
-            println!("{:x?}", version);
+            let version = fel.get_version()?;
+            println!("{}", version);
         }
         Commands::Hexdump { address, length } => {
-            let address: usize = match parse_value(address.trim()) {
-                Some(address) => address,
-                None => {
-                    println!("error: invalid address, shoule be hexadecimal like 0x40000000, or decimal like 1073741824");
-                    return;
-                }
-            };
-            let length: usize = match parse_value(length.trim()) {
-                Some(address) => address,
-                None => {
-                    println!("error: invalid data, shoule be hexadecimal like 0x40000000, or decimal like 1073741824");
-                    return;
-                }
-            };
-            const CHUNK_SIZE: usize = 65536;
+            let address: usize = parse_address(&address)?;
+            let length: usize = parse_argument(&length, "data")?;
             let mut buf = Vec::new();
             buf.resize(CHUNK_SIZE, 0);
+            // dumped lines already show progress on terminal; only report when redirected
+            let mode = if std::io::stdout().is_terminal() {
+                ProgressMode::Hidden
+            } else {
+                ProgressMode::detect(quiet, force_progress)
+            };
+            let mut progress = Progress::new("hexdump", length, mode);
             for offset in (0..length).step_by(CHUNK_SIZE) {
                 let chunk_len = (length - offset).min(CHUNK_SIZE);
-                fel.read_address((address + offset) as u32, &mut buf[..chunk_len]);
-                hexdump(&buf[..chunk_len], (address + offset) as u32);
+                fel.read_address((address + offset) as u32, &mut buf[..chunk_len])?;
+                hexdump(
+                    &mut std::io::stdout().lock(),
+                    &buf[..chunk_len],
+                    (address + offset) as u32,
+                )?;
+                progress.inc(chunk_len);
+            }
+            progress.finish();
+        }
+        Commands::Monitor {
+            address,
+            length,
+            interval_ms,
+        } => {
+            let address: u32 = parse_address(&address)?;
+            let length: usize = parse_argument(&length, "data")?;
+            let mut previous: Option<Vec<u8>> = None;
+            let mut out = std::io::stdout().lock();
+            // start from a clear screen; later frames are drawn over the previous one
+            write!(out, "\x1b[2J")?;
+            loop {
+                let mut buf = vec![0u8; length];
+                fel.read_address(address, &mut buf)?;
+                let changed = monitor::changed_bytes(previous.as_deref(), &buf);
+                monitor::draw_frame(&mut out, &buf, address, &changed)?;
+                previous = Some(buf);
+                std::thread::sleep(Duration::from_millis(interval_ms));
             }
         }
         Commands::Read32 { address } => {
-            let address: u32 = match parse_value(address.trim()) {
-                Some(address) => address,
-                None => {
-                    println!("error: invalid address, shoule be hexadecimal like 0x40000000, or decimal like 1073741824");
-                    return;
-                }
-            };
+            let address: u32 = parse_address(&address)?;
             let mut buf = [0u8; 4];
-            fel.read_address(address, &mut buf);
+            fel.read_address(address, &mut buf)?;
             let ans = u32::from_le_bytes(buf);
             println!("0x{:08x}", ans);
         }
+        Commands::Read {
+            address,
+            length,
+            file,
+            format,
+        } => {
+            let address: u32 = parse_address(&address)?;
+            let length: usize = parse_argument(&length, "data")?;
+            check_address_range(address, length)?;
+            let mut out = std::io::BufWriter::new(std::fs::File::create(&file)?);
+            if let ReadFormat::Elf = format {
+                let chip = fel.get_version()?.chip().ok_or_else(|| {
+                    CliError::Device("ELF output does not support this chip".into())
+                })?;
+                let header = elf::dump_header(address as u64, length as u64, chip.elf_machine());
+                out.write_all(&header)?;
+            }
+            let mode = ProgressMode::detect(quiet, force_progress);
+            let mut progress = Progress::new("read", length, mode);
+            let ans = transfer::read_to_writer(&mut out, address, length, CHUNK_SIZE, |at, buf| {
+                retry.run(|| fel.read_address(at, buf), |_| fel.record_retry())?;
+                progress.inc(buf.len());
+                Ok::<_, CliError>(())
+            });
+            progress.finish();
+            ans?;
+            out.flush()?;
+        }
+        Commands::Save {
+            address,
+            length,
+            file,
+        } => {
+            let address: u32 = parse_address(&address)?;
+            let length: usize = parse_argument(&length, "data")?;
+            let chip = fel
+                .get_version()?
+                .chip()
+                .ok_or_else(|| CliError::Device("snapshot does not support this chip".into()))?;
+            let mut out = std::io::BufWriter::new(std::fs::File::create(&file)?);
+            snapshot::save(fel, &mut out, address, length, chip.elf_machine())?;
+            out.flush()?;
+            println!(
+                "saved 0x{:08x}..0x{:08x} to {}",
+                address,
+                address as u64 + length as u64,
+                file.display()
+            );
+        }
+        Commands::Restore { file } => {
+            let image = read_image(&file)?;
+            let written = snapshot::restore(fel, &image)?;
+            println!("restored {} bytes from {}", written, file.display());
+        }
         Commands::Write32 { address, value } => {
-            let address: u32 = match parse_value(address.trim()) {
-                Some(address) => address,
-                None => {
-                    println!("error: invalid address, shoule be hexadecimal like 0x40000000, or decimal like 1073741824");
-                    return;
+            let address: u32 = parse_address(&address)?;
+            let value: u32 = parse_address(&value)?;
+            fel.write_address(address, &value.to_le_bytes())?;
+        }
+        Commands::Exec {
+            address,
+            force,
+            console,
+            baud,
+        } => {
+            let address: u32 = parse_address(&address)?;
+            let chip = fel.get_version()?.chip();
+            check_exec_target(chip.as_ref(), address, force)?;
+            match console {
+                None => fel.exec(address)?,
+                Some(path) => rfel::console::exec_then_bridge(
+                    || fel.exec(address).map_err(CliError::from),
+                    || rfel::console::open(&path, baud),
+                    &mut std::io::stdout(),
+                )?,
+            }
+        }
+        Commands::Write {
+            address,
+            file,
+            mmap,
+            verify,
+            output_on_mismatch,
+            throttle,
+            fixed_chunk,
+            since,
+        } => {
+            let address: u32 = parse_address(&address)?;
+            let incremental = match &since {
+                Some(index) => Some(plan_incremental(&file, address, index)?),
+                None => None,
+            };
+            let file = std::fs::File::open(&file).map_err(|e| {
+                CliError::Io(std::io::Error::new(
+                    e.kind(),
+                    format!("cannot open {}: {}", file.display(), e),
+                ))
+            })?;
+            let file_length = match &incremental {
+                Some(plan) => plan.data.len(),
+                None => file.metadata()?.len() as usize,
+            };
+            check_address_range(address, file_length)?;
+            let length = match &incremental {
+                Some(plan) => plan.ranges.iter().map(|range| range.len()).sum(),
+                None => file_length,
+            };
+            let mode = ProgressMode::detect(quiet, force_progress);
+            let mut progress = Progress::new("write", length, mode);
+            if throttle.is_some() {
+                progress = progress.with_rate();
+            }
+            let mut throttle = throttle.map(transfer::Throttle::new);
+            // throttling and progress stay out of the timed USB transfer
+            let mut written = |len: usize| {
+                if let Some(throttle) = &mut throttle {
+                    throttle.pace(len);
                 }
+                progress.inc(len);
+            };
+            let usb_write =
+                |address, buf: &[u8]| Ok::<_, CliError>(fel.write_address(address, buf)?);
+            let write = |address, buf: &[u8]| {
+                let len = retry.run(|| usb_write(address, buf), |_| fel.record_retry())?;
+                written(len);
+                Ok::<_, CliError>(len)
+            };
+            let ans = if let Some(plan) = &incremental {
+                incremental::write_ranges(&plan.data, address, &plan.ranges, CHUNK_SIZE, write)
+            } else if fixed_chunk {
+                transfer::write_file(&file, address, CHUNK_SIZE, mmap, write)
+            } else {
+                // the controller retries failed chunks itself, with smaller chunks
+                let mut controller =
+                    transfer::ChunkController::new(MIN_CHUNK_SIZE, CHUNK_SIZE).with_retry(retry);
+                let on_retry = |_: &CliError| fel.record_retry();
+                transfer::write_file_adaptive(
+                    &file,
+                    address,
+                    mmap,
+                    &mut controller,
+                    usb_write,
+                    written,
+                    on_retry,
+                )
             };
-            let value: u32 = match parse_value(value.trim()) {
-                Some(value) => value,
-                None => {
-                    println!("error: invalid address, shoule be hexadecimal like 0x40000000, or decimal like 1073741824");
-                    return;
+            progress.finish();
+            ans?;
+            if verify {
+                let read = |address, buf: &mut [u8]| {
+                    fel.read_address(address, buf)
+                        .map(|_| ())
+                        .map_err(CliError::from)
+                };
+                std::io::Seek::rewind(&mut &file)?;
+                if let Some(mismatch) =
+                    transfer::verify_reader(&mut &file, address, CHUNK_SIZE, read)?
+                {
+                    if let Some(path) = &output_on_mismatch {
+                        transfer::dump_mismatch(mismatch, CHUNK_SIZE, read, path)?;
+                        eprintln!(
+                            "device bytes of mismatching range saved to {}",
+                            path.display()
+                        );
+                    }
+                    return Err(CliError::Protocol(format!(
+                        "verify failed: {} bytes differ in range 0x{:08x}..0x{:08x}",
+                        mismatch.len(),
+                        mismatch.start,
+                        mismatch.end
+                    )));
                 }
+            }
+            // only record blocks as written once they are verified, if requested
+            if let (Some(path), Some(plan)) = (&since, &incremental) {
+                std::fs::write(path, plan.index.to_string()).map_err(|e| {
+                    CliError::Io(std::io::Error::new(
+                        e.kind(),
+                        format!("cannot write {}: {}", path.display(), e),
+                    ))
+                })?;
+            }
+        }
+        Commands::Flash { manifest } => {
+            let text = std::fs::read_to_string(&manifest).map_err(|e| {
+                CliError::Io(std::io::Error::new(
+                    e.kind(),
+                    format!("cannot read {}: {}", manifest.display(), e),
+                ))
+            })?;
+            let base_dir = manifest.parent().unwrap_or(std::path::Path::new("."));
+            let entries = manifest::parse(&text, base_dir)?;
+            let total = manifest::total_size(&entries)?;
+            let mut memory = FelMemory(fel);
+            let mut backends = Backends {
+                spinor: None,
+                spinand: None,
+                memory: Some(&mut memory),
             };
-            fel.write_address(address, &value.to_le_bytes());
+            let mode = ProgressMode::detect(quiet, force_progress);
+            let mut progress = Progress::new("flash", total, mode);
+            let ans = manifest::flash(&entries, &mut backends, CHUNK_SIZE, &mut progress);
+            progress.finish();
+            ans?;
+        }
+        Commands::Selftest { exec } => {
+            let chip = fel
+                .get_version()?
+                .chip()
+                .ok_or_else(|| CliError::Device("selftest does not support this chip".into()))?;
+            let results = rfel::selftest::run(fel, &chip, exec);
+            for test in &results {
+                match &test.result {
+                    Ok(()) => println!("{:<12} pass", test.name),
+                    Err(e) => println!("{:<12} FAIL: {}", test.name, e),
+                }
+            }
+            let failed = results.iter().filter(|t| !t.passed()).count();
+            if failed > 0 {
+                return Err(CliError::Protocol(format!(
+                    "{} of {} selftest items failed",
+                    failed,
+                    results.len()
+                )));
+            }
+            println!("all {} selftest items passed", results.len());
+        }
+        Commands::Sign {
+            public,
+            private,
+            file,
+        } => {
+            let (public, private) = (
+                sign::read_public_key(&public)?,
+                sign::read_private_key(&private)?,
+            );
+            let chip = fel
+                .get_version()?
+                .chip()
+                .ok_or_else(|| CliError::Device("sign does not support this chip".into()))?;
+            let mut sid = [0u8; 16];
+            fel.read_address(chip.sid_address(), &mut sid)?;
+            let signature = sign::sign_checked(&private, &public, &sid)?;
+            std::fs::write(&file, signature).map_err(|e| {
+                CliError::Io(std::io::Error::new(
+                    e.kind(),
+                    format!("cannot write {}: {}", file.display(), e),
+                ))
+            })?;
+            println!("signed SID, signature written to {}", file.display());
+        }
+        Commands::Memtest {
+            address,
+            length,
+            pattern,
+        } => {
+            let address: u32 = parse_address(&address)?;
+            let length: usize = parse_argument(&length, "data")?;
+            if !address.is_multiple_of(4) || !length.is_multiple_of(4) {
+                return Err(CliError::Usage(
+                    "memtest address and length should be multiples of 4".into(),
+                ));
+            }
+            let mode = ProgressMode::detect(quiet, force_progress);
+            let progress = std::cell::RefCell::new(Progress::new("memtest", 2 * length, mode));
+            let ans = memtest::run(
+                address,
+                length,
+                pattern,
+                CHUNK_SIZE,
+                |address, data| {
+                    fel.write_address(address, data)?;
+                    progress.borrow_mut().inc(data.len());
+                    Ok::<_, CliError>(())
+                },
+                |address, buf| {
+                    fel.read_address(address, buf)?;
+                    progress.borrow_mut().inc(buf.len());
+                    Ok(())
+                },
+            );
+            progress.into_inner().finish();
+            if let Some(mismatch) = ans? {
+                return Err(CliError::Protocol(format!(
+                    "memtest {}: {}",
+                    pattern, mismatch
+                )));
+            }
+            println!(
+                "memtest {} passed on 0x{:08x}..0x{:08x}",
+                pattern,
+                address,
+                address as u64 + length as u64
+            );
+        }
+        Commands::Imginfo { .. }
+        | Commands::DiffImage { .. }
+        | Commands::Relocate { .. }
+        | Commands::Toc { .. }
+        | Commands::UsbDescriptors
+        | Commands::List
+        | Commands::Keygen { .. } => {
+            unreachable!()
+        }
+    }
+    Ok(())
+}
+
+/// File contents to be written incrementally.
+struct IncrementalWrite {
+    data: Vec<u8>,
+    /// Index of `data`, saved once it is written.
+    index: BlockIndex,
+    /// Byte ranges of `data` changed since the previous index.
+    ranges: Vec<std::ops::Range<usize>>,
+}
+
+/// Read `file` and find byte ranges changed since block index at `index_path`.
+///
+/// A missing index file means nothing is known to be written, so the whole
+/// file is changed.
+fn plan_incremental(
+    file: &std::path::Path,
+    address: u32,
+    index_path: &std::path::Path,
+) -> Result<IncrementalWrite, CliError> {
+    let data = read_image(file)?;
+    let previous = match std::fs::read_to_string(index_path) {
+        Ok(text) => Some(
+            BlockIndex::parse(&text)
+                .map_err(|e| CliError::Image(format!("{}: {}", index_path.display(), e)))?,
+        ),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            return Err(CliError::Io(std::io::Error::new(
+                e.kind(),
+                format!("cannot read {}: {}", index_path.display(), e),
+            )))
+        }
+    };
+    let index = BlockIndex::build(address, incremental::DEFAULT_BLOCK_SIZE, &data);
+    let ranges = index.changed_ranges(previous.as_ref());
+    let changed: usize = ranges.iter().map(|range| range.len()).sum();
+    eprintln!(
+        "{} of {} bytes changed since {}",
+        changed,
+        data.len(),
+        index_path.display()
+    );
+    Ok(IncrementalWrite {
+        data,
+        index,
+        ranges,
+    })
+}
+
+fn read_image(file: &std::path::Path) -> Result<Vec<u8>, CliError> {
+    std::fs::read(file).map_err(|e| {
+        CliError::Io(std::io::Error::new(
+            e.kind(),
+            format!("cannot read {}: {}", file.display(), e),
+        ))
+    })
+}
+
+fn imginfo(file: &std::path::Path) -> Result<(), CliError> {
+    let image = read_image(file)?;
+    let head = EgonHead::parse(&image)
+        .map_err(|e| CliError::Image(format!("{}: {}", file.display(), e)))?;
+    println!("load address: 0x{:08x}", head.load_address());
+    match head.entry_point() {
+        Some(entry) => println!("entry point:  0x{:08x}", entry),
+        None => println!(
+            "entry point:  unknown (instruction 0x{:08x})",
+            head.jump_instruction
+        ),
+    }
+    println!("length:       0x{:x} ({} bytes)", head.length, head.length);
+    if head.checksum_valid(&image) {
+        println!("checksum:     0x{:08x} (valid)", head.checksum);
+    } else {
+        println!(
+            "checksum:     0x{:08x} (invalid, expected 0x{:08x})",
+            head.checksum,
+            egon::checksum(&image[..head.length as usize])
+        );
+    }
+    Ok(())
+}
+
+fn relocate(file: &std::path::Path, new_base: &str, out: &std::path::Path) -> Result<(), CliError> {
+    let mut image = read_image(file)?;
+    let image_error =
+        |e: &dyn core::fmt::Display| CliError::Image(format!("{}: {}", file.display(), e));
+    let (old_base, new_base) = if elf::is_elf(&image) {
+        let new_base = parse_address::<u64>(new_base)?;
+        let old_base = elf::relocate(&mut image, new_base)
+            .map_err(|e| image_error(&e))?
+            .ok_or_else(|| image_error(&"no loadable segment"))?;
+        (old_base, new_base)
+    } else {
+        let new_base = parse_address::<u32>(new_base)?;
+        let old_base = egon::relocate(&mut image, new_base).map_err(|e| image_error(&e))?;
+        (old_base as u64, new_base as u64)
+    };
+    std::fs::write(out, &image).map_err(|e| {
+        CliError::Io(std::io::Error::new(
+            e.kind(),
+            format!("cannot write {}: {}", out.display(), e),
+        ))
+    })?;
+    println!(
+        "relocated from 0x{:08x} to 0x{:08x}, written to {}",
+        old_base,
+        new_base,
+        out.display()
+    );
+    Ok(())
+}
+
+fn toc(command: &TocCommand) -> Result<(), CliError> {
+    let file = match command {
+        TocCommand::Info { file } | TocCommand::Extract { file, .. } => file,
+    };
+    let image = read_image(file)?;
+    let head = TocHead::parse(&image)
+        .map_err(|e| CliError::Image(format!("{}: {}", file.display(), e)))?;
+    match command {
+        TocCommand::Info { .. } => {
+            println!("container:    {:?} \"{}\"", head.kind, head.name);
+            println!("serial:       0x{:08x}", head.serial);
+            println!("length:       0x{:x} ({} bytes)", head.length, head.length);
+            if head.checksum_valid(&image) {
+                println!("checksum:     0x{:08x} (valid)", head.checksum);
+            } else {
+                println!("checksum:     0x{:08x} (invalid)", head.checksum);
+            }
+            println!("items:");
+            for (index, item) in head.items.iter().enumerate() {
+                println!(
+                    "  {}: {:<12} offset 0x{:08x} length 0x{:08x} type {} load 0x{:08x}",
+                    index, item.name, item.offset, item.length, item.item_type, item.load_address
+                );
+            }
+        }
+        TocCommand::Extract { item, output, .. } => {
+            let item = head.item(item).ok_or_else(|| {
+                CliError::Usage(format!("no item {} in {}", item, file.display()))
+            })?;
+            std::fs::write(output, item.data(&image)).map_err(|e| {
+                CliError::Io(std::io::Error::new(
+                    e.kind(),
+                    format!("cannot write {}: {}", output.display(), e),
+                ))
+            })?;
+            println!(
+                "extracted {} ({} bytes) to {}",
+                item.name,
+                item.length,
+                output.display()
+            );
         }
     }
+    Ok(())
 }
 
-fn hexdump(buf: &[u8], base_address: u32) {
-    for i in (0..buf.len()).step_by(16) {
-        print!("{:08x}: ", base_address as usize + i);
-        let chunk_len = 16.min(buf.len() - i);
-        for j in 0..chunk_len {
-            print!("{:02x} ", buf[i + j]);
+fn parse_block_size(s: &str) -> Result<usize, String> {
+    let size: usize = parse_argument(s, "block size").map_err(|e| e.to_string())?;
+    if size == 0 || !size.is_power_of_two() {
+        return Err(format!("block size {} is not a power of two", s));
+    }
+    Ok(size)
+}
+
+fn diff_image(a: &std::path::Path, b: &std::path::Path, block_size: usize) -> Result<(), CliError> {
+    let (image_a, image_b) = (read_image(a)?, read_image(b)?);
+    match (elf::is_elf(&image_a), elf::is_elf(&image_b)) {
+        (true, true) => {
+            let segments = |file: &std::path::Path, image| {
+                elf::load_segments(image)
+                    .map_err(|e| CliError::Image(format!("{}: {}", file.display(), e)))
+            };
+            let diffs = imgdiff::diff_segments(
+                &segments(a, &image_a)?,
+                &segments(b, &image_b)?,
+                block_size,
+            );
+            for diff in &diffs {
+                match diff {
+                    SegmentDiff::Unchanged { paddr, size } => {
+                        println!("segment 0x{:08x}: unchanged (0x{:x} bytes)", paddr, size)
+                    }
+                    SegmentDiff::Added { paddr, size } => {
+                        println!("segment 0x{:08x}: added (0x{:x} bytes)", paddr, size)
+                    }
+                    SegmentDiff::Removed { paddr, size } => {
+                        println!("segment 0x{:08x}: removed (0x{:x} bytes)", paddr, size)
+                    }
+                    SegmentDiff::Changed { paddr, ranges } => {
+                        println!("segment 0x{:08x}: changed", paddr);
+                        for range in ranges {
+                            let start = paddr + range.start as u64;
+                            let end = paddr + range.end as u64;
+                            println!("  0x{:08x}..0x{:08x} ({} bytes)", start, end, range.len());
+                        }
+                    }
+                }
+            }
+            let changed = diffs
+                .iter()
+                .filter(|d| !matches!(d, SegmentDiff::Unchanged { .. }))
+                .count();
+            println!("{} of {} segments differ", changed, diffs.len());
         }
-        print!(" ");
-        for _ in chunk_len..16 {
-            print!("   ");
+        (false, false) => {
+            let ranges = imgdiff::changed_blocks(&image_a, &image_b, block_size);
+            for range in &ranges {
+                println!(
+                    "0x{:08x}..0x{:08x} ({} bytes)",
+                    range.start,
+                    range.end,
+                    range.len()
+                );
+            }
+            let bytes: usize = ranges.iter().map(|r| r.len()).sum();
+            println!("{} bytes in {} ranges differ", bytes, ranges.len());
+        }
+        _ => {
+            return Err(CliError::Image(format!(
+                "cannot compare ELF and raw images: {} and {}",
+                a.display(),
+                b.display()
+            )))
         }
-        for byte in &buf[i..(i + chunk_len)] {
-            if byte.is_ascii_graphic() || *byte == b' ' {
-                print!("{}", *byte as char);
+    }
+    Ok(())
+}
+
+fn list(timeout: Duration) -> Result<(), CliError> {
+    let devices: Vec<_> = nusb::list_devices()
+        .map_err(|e| CliError::Device(format!("cannot list USB devices: {}", e)))?
+        .filter(|dev| dev.vendor_id() == VENDOR_ALLWINNER && dev.product_id() == PRODUCT_FEL)
+        .collect();
+    if devices.is_empty() {
+        println!("none found");
+        return Ok(());
+    }
+    for info in devices {
+        let version = open_device(&info).and_then(|mut interface| {
+            let mut fel = Fel::open_interface(&mut interface).map_err(|()| {
+                CliError::Device("cannot open USB interface as an FEL device".into())
+            })?;
+            fel.set_timeout(timeout);
+            Ok(fel.get_version()?)
+        });
+        let chip = match version {
+            Ok(version) => match version.chip() {
+                Some(chip) => format!("{:?} (SoC ID 0x{:04x})", chip, version.soc_id()),
+                None => format!("unknown chip (SoC ID 0x{:04x})", version.soc_id()),
+            },
+            Err(e) => format!("cannot detect chip: {}", e),
+        };
+        println!(
+            "bus {:03} device {:03}: {}",
+            info.bus_number(),
+            info.device_address(),
+            chip
+        );
+    }
+    Ok(())
+}
+
+fn usb_descriptors() -> Result<(), CliError> {
+    let devices: Vec<_> = nusb::list_devices()
+        .map_err(|e| CliError::Device(format!("cannot list USB devices: {}", e)))?
+        .filter(|dev| dev.vendor_id() == VENDOR_ALLWINNER)
+        .collect();
+    if devices.is_empty() {
+        return Err(CliError::Device(
+            "cannot find any Allwinner USB device connected".into(),
+        ));
+    }
+    for info in devices {
+        println!(
+            "bus {:03} device {:03}: ID {:04x}:{:04x} {}{}",
+            info.bus_number(),
+            info.device_address(),
+            info.vendor_id(),
+            info.product_id(),
+            info.product_string().unwrap_or("unknown product"),
+            if info.product_id() == PRODUCT_FEL {
+                " (FEL mode)"
             } else {
-                print!(".");
+                ""
+            }
+        );
+        let device = match info.open() {
+            Ok(device) => device,
+            Err(e) => {
+                println!("  cannot open device: {}", e);
+                continue;
             }
+        };
+        match device.active_configuration() {
+            Ok(configuration) => print!(
+                "{}",
+                descriptors::format_alt_settings(configuration.interface_alt_settings())
+            ),
+            Err(e) => println!("  cannot read active configuration: {}", e),
         }
-        println!()
     }
+    Ok(())
+}
+
+fn check_exec_target(chip: Option<&Chip>, address: u32, force: bool) -> Result<(), CliError> {
+    rfel::check_exec_address(chip, address, force).map_err(|e| {
+        CliError::Usage(format!(
+            "refuse to execute at 0x{:08x}: {}; use --force to override",
+            address, e
+        ))
+    })
+}
+
+/// Manifest backend writing into chip memory.
+struct FelMemory<'a, 'b, T>(&'a Fel<'b, T>);
+
+impl<T: Transport> Backend<CliError> for FelMemory<'_, '_, T> {
+    #[inline]
+    fn erase(&mut self, _offset: u32, _len: usize) -> Result<(), CliError> {
+        Ok(())
+    }
+    #[inline]
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<usize, CliError> {
+        Ok(self.0.write_address(offset, data)?)
+    }
+}
+
+/// Check that `length` bytes from `address` stay inside the 32-bit address space.
+fn check_address_range(address: u32, length: usize) -> Result<(), CliError> {
+    let end = address as u64 + length as u64;
+    if end > 1 << 32 {
+        return Err(CliError::Usage(format!(
+            "range 0x{:08x}..0x{:x} wraps past end of address space",
+            address, end
+        )));
+    }
+    Ok(())
+}
+
+fn parse_address<T: core::str::FromStr + num_traits::Num>(value: &str) -> Result<T, CliError> {
+    parse_argument(value, "address")
+}
+
+fn parse_argument<T: core::str::FromStr + num_traits::Num>(
+    value: &str,
+    name: &str,
+) -> Result<T, CliError> {
+    parse_value(value.trim()).ok_or_else(|| {
+        CliError::Usage(format!(
+            "invalid {}, shoule be hexadecimal like 0x40000000, or decimal like 1073741824",
+            name
+        ))
+    })
 }
 
 fn parse_value<T: core::str::FromStr + num_traits::Num>(value: &str) -> Option<T> {
@@ -155,3 +1136,141 @@ fn parse_value<T: core::str::FromStr + num_traits::Num>(value: &str) -> Option<T
         value.parse::<T>().ok()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_address_range, check_exec_target, diff_image, imginfo, parse_address, parse_argument,
+        toc, Cli, Commands, TocCommand,
+    };
+    use clap::Parser;
+    use rfel::Chip;
+
+    #[test]
+    fn address_range_wrap() {
+        let e = check_address_range(0xFFFF_F000, 0x2000).unwrap_err();
+        assert_eq!(e.exit_code(), 2);
+        assert_eq!(
+            e.to_string(),
+            "range 0xfffff000..0x100001000 wraps past end of address space"
+        );
+        // ending exactly at the top of address space is fine
+        assert!(check_address_range(0xFFFF_F000, 0x1000).is_ok());
+        assert!(check_address_range(0x4000_0000, 0).is_ok());
+    }
+
+    #[test]
+    fn invalid_argument_exit_codes() {
+        let e = parse_address::<u32>("0xzz").unwrap_err();
+        assert_eq!(e.exit_code(), 2);
+        let e = parse_argument::<usize>("-1", "data").unwrap_err();
+        assert_eq!(e.exit_code(), 2);
+        assert_eq!(parse_address::<u32>(" 0x40000000 ").unwrap(), 0x4000_0000);
+
+        let e = check_exec_target(Some(&Chip::D1), 0x0200_1000, false).unwrap_err();
+        assert_eq!(e.exit_code(), 2);
+    }
+
+    #[test]
+    fn imginfo_exit_codes() {
+        let e = imginfo(std::path::Path::new("fixtures/does-not-exist.bin")).unwrap_err();
+        assert_eq!(e.exit_code(), 1);
+        let e = imginfo(std::path::Path::new("Cargo.toml")).unwrap_err();
+        assert_eq!(e.exit_code(), 7);
+        assert!(imginfo(std::path::Path::new("fixtures/egon-d1.bin")).is_ok());
+    }
+
+    #[test]
+    fn diff_image_exit_codes() {
+        let path = std::path::Path::new;
+        let e = diff_image(
+            path("fixtures/diff-a.elf"),
+            path("fixtures/diff-b.bin"),
+            512,
+        );
+        assert_eq!(e.unwrap_err().exit_code(), 7);
+        let e = diff_image(path("fixtures/diff-a.elf"), path("Cargo.toml"), 512);
+        assert_eq!(e.unwrap_err().exit_code(), 7);
+        assert!(diff_image(
+            path("fixtures/diff-a.elf"),
+            path("fixtures/diff-b.elf"),
+            512
+        )
+        .is_ok());
+        assert!(diff_image(
+            path("fixtures/diff-a.bin"),
+            path("fixtures/diff-b.bin"),
+            512
+        )
+        .is_ok());
+        assert!(
+            Cli::try_parse_from(["rfel", "diff-image", "a", "b", "--block-size", "3"]).is_err()
+        );
+    }
+
+    #[test]
+    fn toc_exit_codes() {
+        let info = TocCommand::Info {
+            file: "fixtures/toc0.bin".into(),
+        };
+        assert!(toc(&info).is_ok());
+        let info = TocCommand::Info {
+            file: "fixtures/egon-d1.bin".into(),
+        };
+        assert_eq!(toc(&info).unwrap_err().exit_code(), 7);
+        let extract = TocCommand::Extract {
+            file: "fixtures/toc0.bin".into(),
+            item: "rootkey".into(),
+            output: "/dev/null".into(),
+        };
+        assert_eq!(toc(&extract).unwrap_err().exit_code(), 2);
+        let output = std::env::temp_dir().join("rfel-toc0-firmware.bin");
+        let extract = TocCommand::Extract {
+            file: "fixtures/toc0.bin".into(),
+            item: "firmware".into(),
+            output: output.clone(),
+        };
+        assert!(toc(&extract).is_ok());
+        let firmware = std::fs::read(&output).unwrap();
+        std::fs::remove_file(&output).unwrap();
+        assert_eq!(firmware, (0..=255).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn parse_exec_console() {
+        let cli = Cli::try_parse_from([
+            "rfel",
+            "exec",
+            "0x40000000",
+            "--console",
+            "/dev/ttyUSB0",
+            "--baud",
+            "1500000",
+        ])
+        .unwrap();
+        let Commands::Exec {
+            address,
+            force,
+            console,
+            baud,
+        } = cli.command
+        else {
+            panic!("expected exec command");
+        };
+        assert_eq!(address, "0x40000000");
+        assert!(!force);
+        assert_eq!(console.as_deref(), Some("/dev/ttyUSB0"));
+        assert_eq!(baud, 1500000);
+
+        let cli = Cli::try_parse_from(["rfel", "exec", "0x40000000"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Exec {
+                console: None,
+                baud: 115200,
+                ..
+            }
+        ));
+        assert!(Cli::try_parse_from(["rfel", "exec", "0x40000000", "--baud", "9600"]).is_err());
+    }
+}
@@ -0,0 +1,303 @@
+//! Provisioning manifests writing several images to several targets.
+//!
+//! A manifest is a text file with one entry per line, in the form of
+//! `<target> <offset> <file>`. Empty lines and lines starting with `#` are
+//! ignored, and relative file paths are resolved from the manifest directory:
+//!
+//! ```text
+//! # target  offset      file
+//! spinor    0x0         boot0.bin
+//! memory    0x40000000  opensbi.bin
+//! ```
+use crate::{progress::Progress, transfer};
+use std::{
+    fmt,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+/// Storage an image is written into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// SPI NOR flash.
+    Spinor,
+    /// SPI NAND flash.
+    Spinand,
+    /// Chip memory.
+    Memory,
+}
+
+impl Target {
+    /// Name of this target as written in manifests.
+    #[inline]
+    pub fn name(self) -> &'static str {
+        match self {
+            Target::Spinor => "spinor",
+            Target::Spinand => "spinand",
+            Target::Memory => "memory",
+        }
+    }
+}
+
+/// One image to be written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Target storage.
+    pub target: Target,
+    /// Offset in target storage, or address for chip memory.
+    pub offset: u32,
+    /// Path to the image file.
+    pub file: PathBuf,
+}
+
+/// Error on parsing or applying a manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifestError {
+    /// Manifest line is malformed; line numbers start from 1.
+    Parse { line: usize, message: String },
+    /// No backend is available for the target.
+    Unsupported(Target),
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestError::Parse { line, message } => {
+                write!(f, "manifest line {}: {}", line, message)
+            }
+            ManifestError::Unsupported(target) => {
+                write!(f, "writing to {} is not supported", target.name())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+/// Parse manifest `text`, resolving relative paths from `base_dir`.
+pub fn parse(text: &str, base_dir: &Path) -> Result<Vec<Entry>, ManifestError> {
+    let mut entries = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: String| ManifestError::Parse {
+            line: index + 1,
+            message,
+        };
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [target, offset, file] = fields[..] else {
+            return Err(error(format!(
+                "expected `<target> <offset> <file>`, found {} fields",
+                fields.len()
+            )));
+        };
+        let target = match target {
+            "spinor" => Target::Spinor,
+            "spinand" => Target::Spinand,
+            "memory" => Target::Memory,
+            other => return Err(error(format!("unknown target `{}`", other))),
+        };
+        let offset = match offset.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => offset.parse(),
+        }
+        .map_err(|_| error(format!("invalid offset `{}`", offset)))?;
+        entries.push(Entry {
+            target,
+            offset,
+            file: base_dir.join(file),
+        });
+    }
+    Ok(entries)
+}
+
+/// Writer for one kind of target.
+pub trait Backend<E> {
+    /// Prepare `len` bytes at `offset` for writing, e.g. erase flash blocks.
+    fn erase(&mut self, offset: u32, len: usize) -> Result<(), E>;
+    /// Write `data` at `offset`, returning number of bytes written.
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<usize, E>;
+}
+
+/// Available backends for each target.
+pub struct Backends<'a, E> {
+    /// SPI NOR flash backend.
+    pub spinor: Option<&'a mut dyn Backend<E>>,
+    /// SPI NAND flash backend.
+    pub spinand: Option<&'a mut dyn Backend<E>>,
+    /// Chip memory backend.
+    pub memory: Option<&'a mut dyn Backend<E>>,
+}
+
+impl<'a, E> Backends<'a, E> {
+    #[inline]
+    fn get(&mut self, target: Target) -> Option<&mut (dyn Backend<E> + 'a)> {
+        match target {
+            Target::Spinor => self.spinor.as_deref_mut(),
+            Target::Spinand => self.spinand.as_deref_mut(),
+            Target::Memory => self.memory.as_deref_mut(),
+        }
+    }
+}
+
+/// Total size of all entry files in bytes.
+pub fn total_size(entries: &[Entry]) -> std::io::Result<usize> {
+    let mut total = 0;
+    for entry in entries {
+        total += std::fs::metadata(&entry.file)?.len() as usize;
+    }
+    Ok(total)
+}
+
+/// Erase and write each entry in order, reporting combined progress.
+///
+/// Checks that every target has a backend before writing anything.
+/// Returns total number of bytes written.
+pub fn flash<E: From<std::io::Error> + From<ManifestError>>(
+    entries: &[Entry],
+    backends: &mut Backends<'_, E>,
+    chunk_size: usize,
+    progress: &mut Progress,
+) -> Result<usize, E> {
+    if let Some(entry) = entries.iter().find(|e| backends.get(e.target).is_none()) {
+        return Err(ManifestError::Unsupported(entry.target).into());
+    }
+    let mut written = 0;
+    for entry in entries {
+        let backend = backends.get(entry.target).unwrap();
+        let file = File::open(&entry.file)?;
+        let len = file.metadata()?.len() as usize;
+        backend.erase(entry.offset, len)?;
+        written += transfer::write_file(&file, entry.offset, chunk_size, false, |offset, buf| {
+            let len = backend.write(offset, buf)?;
+            progress.inc(len);
+            Ok::<_, E>(len)
+        })?;
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{flash, parse, Backend, Backends, Entry, ManifestError, Target};
+    use crate::progress::{Progress, ProgressMode};
+    use std::path::Path;
+
+    #[test]
+    fn parse_manifest() {
+        let text = "\
+            # target  offset      file\n\
+            spinor    0x0         boot0.bin\n\
+            \n\
+            spinand   0x100000    /images/rootfs.img\n\
+            memory    1073741824  opensbi.bin\n";
+        let entries = parse(text, Path::new("/board")).unwrap();
+        assert_eq!(
+            entries,
+            [
+                Entry {
+                    target: Target::Spinor,
+                    offset: 0,
+                    file: "/board/boot0.bin".into()
+                },
+                Entry {
+                    target: Target::Spinand,
+                    offset: 0x100000,
+                    file: "/images/rootfs.img".into()
+                },
+                Entry {
+                    target: Target::Memory,
+                    offset: 0x4000_0000,
+                    file: "/board/opensbi.bin".into()
+                },
+            ]
+        );
+
+        let error = |line, message: &str| {
+            Err(ManifestError::Parse {
+                line,
+                message: message.into(),
+            })
+        };
+        assert_eq!(
+            parse("spinor 0x0\n", Path::new("")),
+            error(1, "expected `<target> <offset> <file>`, found 2 fields")
+        );
+        assert_eq!(
+            parse("\nemmc 0x0 a.bin\n", Path::new("")),
+            error(2, "unknown target `emmc`")
+        );
+        assert_eq!(
+            parse("memory 0xZZ a.bin\n", Path::new("")),
+            error(1, "invalid offset `0xZZ`")
+        );
+    }
+
+    /// Backend recording every call.
+    struct MockBackend<'a>(&'a str, &'a mut Vec<String>);
+
+    impl Backend<std::io::Error> for MockBackend<'_> {
+        fn erase(&mut self, offset: u32, len: usize) -> Result<(), std::io::Error> {
+            self.1
+                .push(format!("{} erase 0x{:x} {}", self.0, offset, len));
+            Ok(())
+        }
+        fn write(&mut self, offset: u32, data: &[u8]) -> Result<usize, std::io::Error> {
+            self.1
+                .push(format!("{} write 0x{:x} {}", self.0, offset, data.len()));
+            Ok(data.len())
+        }
+    }
+
+    impl From<ManifestError> for std::io::Error {
+        fn from(e: ManifestError) -> Self {
+            std::io::Error::new(std::io::ErrorKind::Unsupported, e)
+        }
+    }
+
+    #[test]
+    fn flash_dispatch() {
+        let dir = std::env::temp_dir().join(format!("rfel-manifest-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.bin"), [0xAA; 300]).unwrap();
+        std::fs::write(dir.join("b.bin"), [0xBB; 100]).unwrap();
+        let text = "spinor 0x1000 a.bin\nmemory 0x40000000 b.bin\nspinor 0x0 b.bin\n";
+        let entries = parse(text, &dir).unwrap();
+
+        let (mut spinor_log, mut memory_log) = (Vec::new(), Vec::new());
+        let mut spinor = MockBackend("spinor", &mut spinor_log);
+        let mut memory = MockBackend("memory", &mut memory_log);
+        let mut backends = Backends {
+            spinor: Some(&mut spinor),
+            spinand: None,
+            memory: Some(&mut memory),
+        };
+        let mut progress = Progress::new("flash", 500, ProgressMode::Hidden);
+        let written = flash(&entries, &mut backends, 256, &mut progress).unwrap();
+        assert_eq!(written, 500);
+        assert_eq!(progress.percent(), 100);
+
+        // a SPI NAND entry is rejected before anything is written
+        let entries = parse("memory 0x0 a.bin\nspinand 0x0 a.bin\n", &dir).unwrap();
+        let e = flash(&entries, &mut backends, 256, &mut progress).unwrap_err();
+        assert_eq!(e.to_string(), "writing to spinand is not supported");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            spinor_log,
+            [
+                "spinor erase 0x1000 300",
+                "spinor write 0x1000 256",
+                "spinor write 0x1100 44",
+                "spinor erase 0x0 100",
+                "spinor write 0x0 100",
+            ]
+        );
+        assert_eq!(
+            memory_log,
+            ["memory erase 0x40000000 100", "memory write 0x40000000 100"]
+        );
+    }
+}
@@ -0,0 +1,258 @@
+//! Memory tests writing a pattern across a region and reading it back.
+use core::fmt;
+use std::str::FromStr;
+
+/// Pattern of 32-bit words written by a memory test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// One set bit walking from bit 0 to bit 31, one bit further each word.
+    Walking1,
+    /// One clear bit walking from bit 0 to bit 31, one bit further each word.
+    Walking0,
+    /// Each word holds its own address.
+    Address,
+    /// Pseudo-random words derived from their addresses.
+    Random,
+}
+
+impl Pattern {
+    /// Expected word at `address` in a region starting at `start`.
+    #[inline]
+    pub fn word(self, start: u32, address: u32) -> u32 {
+        let index = address.wrapping_sub(start) / 4;
+        match self {
+            Pattern::Walking1 => 1 << (index % 32),
+            Pattern::Walking0 => !(1 << (index % 32)),
+            Pattern::Address => address,
+            Pattern::Random => {
+                // murmur3 finalizer; any change of address changes about half the bits
+                let mut x = address ^ 0x9e37_79b9;
+                x = (x ^ (x >> 16)).wrapping_mul(0x85eb_ca6b);
+                x = (x ^ (x >> 13)).wrapping_mul(0xc2b2_ae35);
+                x ^ (x >> 16)
+            }
+        }
+    }
+    /// Fill `buf` with little endian words of the pattern at `address`.
+    ///
+    /// `buf` length must be a multiple of 4.
+    pub fn fill(self, start: u32, address: u32, buf: &mut [u8]) {
+        for (i, word) in buf.chunks_exact_mut(4).enumerate() {
+            let value = self.word(start, address.wrapping_add(4 * i as u32));
+            word.copy_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "walking1" => Ok(Pattern::Walking1),
+            "walking0" => Ok(Pattern::Walking0),
+            "addr" => Ok(Pattern::Address),
+            "random" => Ok(Pattern::Random),
+            _ => Err(format!(
+                "unknown pattern '{}', expected walking1, walking0, addr or random",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Pattern::Walking1 => "walking1",
+            Pattern::Walking0 => "walking0",
+            Pattern::Address => "addr",
+            Pattern::Random => "random",
+        })
+    }
+}
+
+/// First word read back different from the pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Address of the word.
+    pub address: u32,
+    /// Word written.
+    pub expected: u32,
+    /// Word read back.
+    pub actual: u32,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "mismatch at 0x{:08x}: expected 0x{:08x}, read 0x{:08x}",
+            self.address, self.expected, self.actual
+        )
+    }
+}
+
+/// Write `pattern` into `length` bytes at `address`, then read it all back.
+///
+/// Both `address` and `length` must be word aligned. Data is moved in chunks
+/// of at most `chunk_size` bytes, a multiple of 4: function `write` is called
+/// with chunk address and data, and `read` with chunk address and buffer.
+/// Returns the first mismatching word, or `None` if the region holds the pattern.
+pub fn run<E>(
+    address: u32,
+    length: usize,
+    pattern: Pattern,
+    chunk_size: usize,
+    mut write: impl FnMut(u32, &[u8]) -> Result<(), E>,
+    mut read: impl FnMut(u32, &mut [u8]) -> Result<(), E>,
+) -> Result<Option<Mismatch>, E> {
+    assert!(address.is_multiple_of(4) && length.is_multiple_of(4) && chunk_size.is_multiple_of(4));
+    let mut buf = vec![0u8; chunk_size.min(length)];
+    for offset in (0..length).step_by(chunk_size) {
+        let chunk_address = address.wrapping_add(offset as u32);
+        let chunk = &mut buf[..(length - offset).min(chunk_size)];
+        pattern.fill(address, chunk_address, chunk);
+        write(chunk_address, chunk)?;
+    }
+    for offset in (0..length).step_by(chunk_size) {
+        let chunk_address = address.wrapping_add(offset as u32);
+        let chunk = &mut buf[..(length - offset).min(chunk_size)];
+        read(chunk_address, chunk)?;
+        for (i, word) in chunk.chunks_exact(4).enumerate() {
+            let word_address = chunk_address.wrapping_add(4 * i as u32);
+            let expected = pattern.word(address, word_address);
+            let actual = u32::from_le_bytes(word.try_into().unwrap());
+            if actual != expected {
+                return Ok(Some(Mismatch {
+                    address: word_address,
+                    expected,
+                    actual,
+                }));
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, Mismatch, Pattern};
+    use std::cell::RefCell;
+
+    #[test]
+    fn pattern_generators() {
+        let start = 0x4000_0000;
+        let words = |pattern: Pattern| {
+            let mut buf = [0u8; 20];
+            pattern.fill(start, start + 124, &mut buf);
+            buf.chunks(4)
+                .map(|w| u32::from_le_bytes(w.try_into().unwrap()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(words(Pattern::Walking1), [0x8000_0000, 0x1, 0x2, 0x4, 0x8]);
+        assert_eq!(
+            words(Pattern::Walking0),
+            [
+                0x7FFF_FFFF,
+                0xFFFF_FFFE,
+                0xFFFF_FFFD,
+                0xFFFF_FFFB,
+                0xFFFF_FFF7
+            ]
+        );
+        assert_eq!(
+            words(Pattern::Address),
+            [
+                0x4000_007C,
+                0x4000_0080,
+                0x4000_0084,
+                0x4000_0088,
+                0x4000_008C
+            ]
+        );
+        let random = words(Pattern::Random);
+        assert_eq!(random[0], Pattern::Random.word(0, start + 124));
+        for pair in random.windows(2) {
+            assert_ne!(pair[0], pair[1]);
+        }
+
+        for name in ["walking1", "walking0", "addr", "random"] {
+            assert_eq!(name.parse::<Pattern>().unwrap().to_string(), name);
+        }
+        assert!("ones".parse::<Pattern>().is_err());
+    }
+
+    /// Run memory test on simulated memory, with bit 5 of the word at `stuck`
+    /// stuck at zero; returns test result and number of writes.
+    fn simulate(length: usize, pattern: Pattern, stuck: Option<u32>) -> (Option<Mismatch>, usize) {
+        let start = 0x4000_0000u32;
+        let memory = RefCell::new(vec![0u8; length]);
+        let mut writes = 0;
+        let ans = run(
+            start,
+            length,
+            pattern,
+            256,
+            |address, data| {
+                writes += 1;
+                let offset = (address - start) as usize;
+                let mut memory = memory.borrow_mut();
+                memory[offset..offset + data.len()].copy_from_slice(data);
+                if let Some(stuck) = stuck {
+                    memory[(stuck - start) as usize] &= !(1 << 5);
+                }
+                Ok::<_, ()>(())
+            },
+            |address, buf| {
+                let offset = (address - start) as usize;
+                buf.copy_from_slice(&memory.borrow()[offset..offset + buf.len()]);
+                Ok(())
+            },
+        )
+        .unwrap();
+        (ans, writes)
+    }
+
+    #[test]
+    fn detect_faulty_memory() {
+        let faulty = 0x4000_0194;
+        for pattern in [
+            Pattern::Walking1,
+            Pattern::Walking0,
+            Pattern::Address,
+            Pattern::Random,
+        ] {
+            let (ans, writes) = simulate(1024, pattern, Some(faulty));
+            assert_eq!(writes, 4);
+            let expected = pattern.word(0x4000_0000, faulty);
+            if expected & (1 << 5) == 0 {
+                // stuck bit is invisible to this word
+                assert_eq!(ans, None, "{}", pattern);
+                continue;
+            }
+            assert_eq!(
+                ans,
+                Some(Mismatch {
+                    address: faulty,
+                    expected,
+                    actual: expected & !(1 << 5),
+                }),
+                "{}",
+                pattern
+            );
+        }
+
+        // healthy memory passes, including a partial last chunk
+        assert_eq!(simulate(1000, Pattern::Random, None), (None, 4));
+        let mismatch = Mismatch {
+            address: 0x4000_0010,
+            expected: 0x10,
+            actual: 0x0,
+        };
+        assert_eq!(
+            mismatch.to_string(),
+            "mismatch at 0x40000010: expected 0x00000010, read 0x00000000"
+        );
+    }
+}
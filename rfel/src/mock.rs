@@ -0,0 +1,145 @@
+//! Mock FEL device emulating the USB protocol over an in-memory address space.
+use crate::{Fel, Transport};
+use nusb::transfer::TransferError;
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, VecDeque},
+    future::{ready, Future},
+};
+
+/// Version response of a D1 chip.
+const VERSION_D1: [u8; 32] = [
+    b'A', b'W', b'U', b'S', b'B', b'F', b'E', b'X', // magic
+    0x00, 0x59, 0x18, 0x00, // id
+    0x01, 0x00, 0x00, 0x00, // firmware
+    0x01, 0x00, // protocol
+    0x44, // dflag
+    0x08, // dlength
+    0x00, 0x70, 0x02, 0x00, // scratchpad
+    0, 0, 0, 0, 0, 0, 0, 0, // pad
+];
+
+#[derive(Default)]
+struct State {
+    memory: HashMap<u32, u8>,
+    /// Responses to upcoming USB reads, in order.
+    responses: VecDeque<Vec<u8>>,
+    /// Address of a pending FEL write, whose data is the next USB write.
+    write_address: Option<u32>,
+    /// Addresses passed to FEL `exec`.
+    executed: Vec<u32>,
+}
+
+/// Mock FEL device storing written data and returning it on reads.
+#[derive(Default)]
+pub struct MockFel {
+    state: RefCell<State>,
+    /// Flip the lowest bit of every byte returned by FEL reads.
+    pub corrupt_reads: bool,
+    /// Return only half of the requested data on this many upcoming USB reads.
+    pub short_reads: Cell<usize>,
+    /// Append this many garbage bytes to every USB read.
+    pub extra_bytes: usize,
+    /// Fail this many upcoming USB writes before the device sees them.
+    pub failed_writes: Cell<usize>,
+}
+
+impl MockFel {
+    /// Create an FEL handle on this mock device.
+    pub fn fel(&mut self) -> Fel<'_, MockFel> {
+        Fel::new(self, 0x81, 0x01)
+    }
+    /// Read byte from mocked address space; unwritten bytes read as zero.
+    pub fn byte(&self, address: u32) -> u8 {
+        self.state
+            .borrow()
+            .memory
+            .get(&address)
+            .copied()
+            .unwrap_or(0)
+    }
+    /// Addresses passed to FEL `exec` so far.
+    pub fn executed(&self) -> Vec<u32> {
+        self.state.borrow().executed.clone()
+    }
+
+    fn handle_fel_request(&self, state: &mut State, buf: &[u8]) {
+        let word = |offset: usize| u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap());
+        let (request, address, length) = (word(0), word(4), word(8));
+        match request {
+            0x001 => state.responses.push_back(VERSION_D1.to_vec()),
+            0x101 => {
+                state.write_address = Some(address);
+                return;
+            }
+            0x102 => state.executed.push(address),
+            0x103 => {
+                let data = (0..length)
+                    .map(|i| {
+                        let byte = state
+                            .memory
+                            .get(&address.wrapping_add(i))
+                            .copied()
+                            .unwrap_or(0);
+                        if self.corrupt_reads {
+                            byte ^ 1
+                        } else {
+                            byte
+                        }
+                    })
+                    .collect();
+                state.responses.push_back(data);
+            }
+            _ => panic!("unexpected FEL request 0x{:x}", request),
+        }
+        state.responses.push_back(vec![0; 8]);
+    }
+}
+
+impl Transport for MockFel {
+    fn bulk_out(
+        &self,
+        _endpoint: u8,
+        buf: Vec<u8>,
+    ) -> impl Future<Output = Result<(), TransferError>> {
+        let failed_writes = self.failed_writes.get();
+        if failed_writes > 0 {
+            self.failed_writes.set(failed_writes - 1);
+            return ready(Err(TransferError::Stall));
+        }
+        let mut state = self.state.borrow_mut();
+        if buf.len() == 36 && buf.starts_with(b"AWUC") {
+            // USB request header, data phase follows
+        } else if let Some(address) = state.write_address.take() {
+            for (i, byte) in buf.iter().enumerate() {
+                state.memory.insert(address.wrapping_add(i as u32), *byte);
+            }
+            state.responses.push_back(vec![0; 8]);
+        } else {
+            self.handle_fel_request(&mut state, &buf);
+        }
+        ready(Ok(()))
+    }
+    fn bulk_in(
+        &self,
+        _endpoint: u8,
+        len: usize,
+    ) -> impl Future<Output = Result<Vec<u8>, TransferError>> {
+        if len == 13 {
+            return ready(Ok(b"AWUS\0\0\0\0\0\0\0\0\0".to_vec()));
+        }
+        let mut state = self.state.borrow_mut();
+        let mut data = state
+            .responses
+            .pop_front()
+            .expect("USB read without pending response");
+        assert_eq!(data.len(), len, "unexpected USB read length");
+        let short_reads = self.short_reads.get();
+        if short_reads > 0 && len > 1 {
+            self.short_reads.set(short_reads - 1);
+            state.responses.push_front(data.split_off(len / 2));
+        }
+        data.resize(data.len() + self.extra_bytes, 0xEE);
+        ready(Ok(data))
+    }
+}
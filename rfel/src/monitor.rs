@@ -0,0 +1,72 @@
+//! Live view of a memory region, redrawn in place on every frame.
+use crate::hexdump::{format_hexdump_line_highlighted, BYTES_PER_LINE};
+use std::io::{self, Write};
+
+/// Flags of bytes in `current` that differ from `previous` frame.
+///
+/// Nothing is flagged on the first frame, when there is no previous one.
+pub fn changed_bytes(previous: Option<&[u8]>, current: &[u8]) -> Vec<bool> {
+    match previous {
+        Some(previous) => current
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| previous.get(i) != Some(byte))
+            .collect(),
+        None => vec![false; current.len()],
+    }
+}
+
+/// Draw hexdump of `buf` over the previous frame, highlighting `changed` bytes.
+///
+/// Cursor is moved to the top left corner before drawing, and whatever remains
+/// of the previous frame below is cleared.
+pub fn draw_frame(
+    out: &mut impl Write,
+    buf: &[u8],
+    base_address: u32,
+    changed: &[bool],
+) -> io::Result<()> {
+    write!(out, "\x1b[H")?;
+    for (i, line) in buf.chunks(BYTES_PER_LINE).enumerate() {
+        let offset = i * BYTES_PER_LINE;
+        let address = base_address.wrapping_add(offset as u32);
+        let highlight = changed.get(offset..).unwrap_or(&[]);
+        writeln!(
+            out,
+            "{}\x1b[K",
+            format_hexdump_line_highlighted(line, address, highlight)
+        )?;
+    }
+    write!(out, "\x1b[J")?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{changed_bytes, draw_frame};
+
+    #[test]
+    fn change_highlight() {
+        let first = *b"0123456789abcdefXY";
+        assert_eq!(changed_bytes(None, &first), [false; 18]);
+        let mut second = first;
+        second[1] = b'!';
+        second[17] = b'Z';
+        let changed = changed_bytes(Some(&first), &second);
+        let expected: Vec<_> = (0..18).map(|i| i == 1 || i == 17).collect();
+        assert_eq!(changed, expected);
+        assert_eq!(changed_bytes(Some(&second), &second), [false; 18]);
+
+        let mut out = Vec::new();
+        draw_frame(&mut out, &second, 0x4000_0000, &changed).unwrap();
+        let expected = format!(
+            "\x1b[H\
+             40000000: 30 \x1b[7m21\x1b[0m 32 33 34 35 36 37 38 39 61 62 63 64 65 66  \
+             0\x1b[7m!\x1b[0m23456789abcdef\x1b[K\n\
+             40000010: 58 \x1b[7m5a\x1b[0m {}X\x1b[7mZ\x1b[0m\x1b[K\n\
+             \x1b[J",
+            " ".repeat(1 + 14 * 3)
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+}
@@ -0,0 +1,74 @@
+//! Running one command against several FEL devices at once.
+use std::thread;
+
+/// Outcome of a job on one device.
+#[derive(Debug)]
+pub struct Outcome<R> {
+    /// Name of the device, e.g. its USB bus and address.
+    pub device: String,
+    /// Result of the job, or `None` if the job panicked.
+    pub result: Option<R>,
+}
+
+/// Run `job` on each of `devices` on its own thread.
+///
+/// Each device is given with a name for reporting. Waits for all jobs and
+/// returns their outcomes in the order of `devices`; a job that panics does
+/// not affect jobs on other devices.
+pub fn fan_out<D, R>(devices: Vec<(String, D)>, job: impl Fn(D) -> R + Sync) -> Vec<Outcome<R>>
+where
+    D: Send,
+    R: Send,
+{
+    let job = &job;
+    thread::scope(|s| {
+        let handles: Vec<_> = devices
+            .into_iter()
+            .map(|(device, d)| (device, s.spawn(move || job(d))))
+            .collect();
+        handles
+            .into_iter()
+            .map(|(device, handle)| Outcome {
+                device,
+                result: handle.join().ok(),
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fan_out;
+    use crate::mock::MockFel;
+
+    #[test]
+    fn fan_out_mock_devices() {
+        const N: usize = 4;
+        let devices = (0..N)
+            .map(|i| (format!("mock {}", i), MockFel::default()))
+            .collect();
+        let outcomes = fan_out(devices, |mut device| {
+            let fel = device.fel();
+            let version = fel.get_version().map_err(|e| e.to_string())?;
+            fel.write_address(0x2_0000, &[0xA5; 64])
+                .map_err(|e| e.to_string())?;
+            Ok::<_, String>((version.chip().is_some(), device.byte(0x2_003F)))
+        });
+        assert_eq!(outcomes.len(), N);
+        for (i, outcome) in outcomes.iter().enumerate() {
+            assert_eq!(outcome.device, format!("mock {}", i));
+            assert_eq!(outcome.result, Some(Ok((true, 0xA5))));
+        }
+
+        // a panicking job is reported without affecting others
+        let devices = (0..N).map(|i| (format!("mock {}", i), i)).collect();
+        let outcomes = fan_out(devices, |i| {
+            if i == 2 {
+                panic!("device lost");
+            }
+            i * 10
+        });
+        let results: Vec<_> = outcomes.into_iter().map(|o| o.result).collect();
+        assert_eq!(results, [Some(0), Some(10), None, Some(30)]);
+    }
+}
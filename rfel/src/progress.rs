@@ -0,0 +1,169 @@
+//! Transfer progress reporting.
+use std::{
+    io::{IsTerminal, Write},
+    time::{Duration, Instant},
+};
+
+/// How progress is shown to the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressMode {
+    /// Live bar redrawn in place with `\r`.
+    Bar,
+    /// Newline-terminated percentage lines, suitable for logs and pipes.
+    Lines,
+    /// No progress output.
+    Hidden,
+}
+
+impl ProgressMode {
+    /// Select progress mode from command line overrides and terminal state.
+    ///
+    /// `quiet` hides progress, `force` shows the live bar even if `is_tty` is false.
+    #[inline]
+    pub fn select(quiet: bool, force: bool, is_tty: bool) -> ProgressMode {
+        if quiet {
+            ProgressMode::Hidden
+        } else if force || is_tty {
+            ProgressMode::Bar
+        } else {
+            ProgressMode::Lines
+        }
+    }
+    /// Select progress mode according to whether standard error is a terminal.
+    #[inline]
+    pub fn detect(quiet: bool, force: bool) -> ProgressMode {
+        Self::select(quiet, force, std::io::stderr().is_terminal())
+    }
+}
+
+/// Width of live progress bar in characters.
+const BAR_WIDTH: usize = 40;
+/// Percentage step between two progress lines in `Lines` mode.
+const LINE_STEP: usize = 10;
+
+/// Progress of a transfer, printed to standard error.
+pub struct Progress {
+    title: &'static str,
+    mode: ProgressMode,
+    total: usize,
+    current: usize,
+    last_percent: Option<usize>,
+    started: Option<Instant>,
+}
+
+impl Progress {
+    /// Create a progress reporter for a transfer of `total` bytes.
+    #[inline]
+    pub fn new(title: &'static str, total: usize, mode: ProgressMode) -> Self {
+        Progress {
+            title,
+            mode,
+            total,
+            current: 0,
+            last_percent: None,
+            started: None,
+        }
+    }
+    /// Also show effective transfer rate since now.
+    #[inline]
+    pub fn with_rate(mut self) -> Self {
+        self.started = Some(Instant::now());
+        self
+    }
+    /// Advance progress by `n` bytes.
+    pub fn inc(&mut self, n: usize) {
+        self.current = (self.current + n).min(self.total);
+        let percent = self.percent();
+        let should_print = match (self.mode, self.last_percent) {
+            (ProgressMode::Hidden, _) => false,
+            (_, None) => true,
+            (ProgressMode::Bar, Some(last)) => percent != last,
+            (ProgressMode::Lines, Some(last)) => {
+                percent / LINE_STEP != last / LINE_STEP || percent == 100 && last != 100
+            }
+        };
+        if should_print {
+            self.last_percent = Some(percent);
+            let mut stderr = std::io::stderr().lock();
+            let _ = self.draw(&mut stderr);
+        }
+    }
+    /// Finish progress output.
+    pub fn finish(&mut self) {
+        if self.mode == ProgressMode::Bar && self.last_percent.is_some() {
+            eprintln!();
+        }
+    }
+    /// Current progress in percent.
+    #[inline]
+    pub fn percent(&self) -> usize {
+        (self.current * 100).checked_div(self.total).unwrap_or(100)
+    }
+    /// Effective rate in bytes per second if `elapsed` time has passed.
+    fn rate_at(&self, elapsed: Duration) -> f64 {
+        self.current as f64 / elapsed.as_secs_f64().max(1e-3)
+    }
+    fn draw(&self, w: &mut impl Write) -> std::io::Result<()> {
+        let rate = self
+            .started
+            .map(|started| format!(", {:.1} KiB/s", self.rate_at(started.elapsed()) / 1024.0))
+            .unwrap_or_default();
+        let percent = self.percent();
+        match self.mode {
+            ProgressMode::Bar => {
+                let filled = BAR_WIDTH * percent / 100;
+                write!(
+                    w,
+                    "\r{} [{}{}] {:3}%{}",
+                    self.title,
+                    "#".repeat(filled),
+                    " ".repeat(BAR_WIDTH - filled),
+                    percent,
+                    rate
+                )?;
+                w.flush()
+            }
+            ProgressMode::Lines => writeln!(
+                w,
+                "{}: {}% ({}/{} bytes{})",
+                self.title, percent, self.current, self.total, rate
+            ),
+            ProgressMode::Hidden => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Progress, ProgressMode};
+    use std::time::Duration;
+
+    #[test]
+    fn progress_mode_select() {
+        assert_eq!(ProgressMode::select(false, false, true), ProgressMode::Bar);
+        assert_eq!(
+            ProgressMode::select(false, false, false),
+            ProgressMode::Lines
+        );
+        assert_eq!(ProgressMode::select(false, true, false), ProgressMode::Bar);
+        assert_eq!(
+            ProgressMode::select(true, false, true),
+            ProgressMode::Hidden
+        );
+        assert_eq!(
+            ProgressMode::select(true, true, false),
+            ProgressMode::Hidden
+        );
+    }
+
+    #[test]
+    fn progress_lines_output() {
+        let mut progress = Progress::new("read", 200, ProgressMode::Lines);
+        progress.current = 50;
+        let mut buf = Vec::new();
+        progress.draw(&mut buf).unwrap();
+        assert_eq!(buf, b"read: 25% (50/200 bytes)\n");
+        assert!(!buf.contains(&b'\r'));
+        assert_eq!(progress.rate_at(Duration::from_secs(2)), 25.0);
+    }
+}
@@ -0,0 +1,245 @@
+//! Remote FEL device reached through a TCP proxy.
+//!
+//! The proxy forwards bulk transfers to an FEL device attached to its host.
+//! Each request starts with a 6-byte header: direction (`b'O'` for OUT, `b'I'`
+//! for IN), endpoint address, and transfer length as little-endian `u32`; OUT
+//! requests are followed by `length` bytes of data. The proxy replies with one
+//! status byte: 0 on success, 1 on endpoint stall, 2 if the device is
+//! disconnected, any other value on other USB errors. Successful IN replies are
+//! followed by received length as little-endian `u32` and the received data.
+//!
+//! After a timeout, a short read or a malformed reply the connection is out of
+//! step with the proxy, so it is closed and later transfers fail as disconnected.
+use crate::Transport;
+use nusb::transfer::TransferError;
+use std::{
+    cell::RefCell,
+    future::{ready, Future},
+    io::{self, Read, Write},
+    net::{Shutdown, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+/// Bulk IN endpoint of FEL devices, addressed on the proxy.
+pub const ENDPOINT_IN: u8 = 0x82;
+/// Bulk OUT endpoint of FEL devices, addressed on the proxy.
+pub const ENDPOINT_OUT: u8 = 0x01;
+
+/// Bulk transfers forwarded to a TCP proxy.
+pub struct RemoteTransport {
+    // `None` once the connection is closed after an error
+    stream: RefCell<Option<TcpStream>>,
+}
+
+impl RemoteTransport {
+    /// Connect to proxy at `addr`, using `timeout` on connection and every transfer.
+    pub fn connect(addr: impl ToSocketAddrs, timeout: Duration) -> io::Result<Self> {
+        let mut last_error = None;
+        for addr in addr.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(timeout))?;
+                    stream.set_write_timeout(Some(timeout))?;
+                    stream.set_nodelay(true)?;
+                    return Ok(Self::from_stream(stream));
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| io::ErrorKind::AddrNotAvailable.into()))
+    }
+    /// Use an established connection to the proxy.
+    #[inline]
+    pub fn from_stream(stream: TcpStream) -> Self {
+        Self {
+            stream: RefCell::new(Some(stream)),
+        }
+    }
+    fn request(
+        &self,
+        direction: u8,
+        endpoint: u8,
+        len: usize,
+        data: &[u8],
+    ) -> Result<Option<Vec<u8>>, TransferError> {
+        let mut stream = self.stream.borrow_mut();
+        let connection = stream.as_mut().ok_or(TransferError::Disconnected)?;
+        match exchange(connection, direction, endpoint, len, data) {
+            Ok(ans) => ans,
+            Err(e) => {
+                // partial reply may be left in the stream, later replies would mismatch
+                if let Some(connection) = stream.take() {
+                    let _ = connection.shutdown(Shutdown::Both);
+                }
+                Err(io_error(e))
+            }
+        }
+    }
+}
+
+/// Send one request and receive its reply.
+///
+/// Returns an I/O error if the stream is no longer usable, or the status
+/// reported by the proxy otherwise.
+fn exchange(
+    stream: &mut TcpStream,
+    direction: u8,
+    endpoint: u8,
+    len: usize,
+    data: &[u8],
+) -> io::Result<Result<Option<Vec<u8>>, TransferError>> {
+    let mut header = [direction, endpoint, 0, 0, 0, 0];
+    header[2..].copy_from_slice(&(len as u32).to_le_bytes());
+    stream.write_all(&header)?;
+    stream.write_all(data)?;
+    let mut status = [0u8];
+    stream.read_exact(&mut status)?;
+    match status[0] {
+        0 => {}
+        1 => return Ok(Err(TransferError::Stall)),
+        2 => return Ok(Err(TransferError::Disconnected)),
+        _ => return Ok(Err(TransferError::Fault)),
+    }
+    if direction != b'I' {
+        return Ok(Ok(None));
+    }
+    let mut len_bytes = [0u8; 4];
+    stream.read_exact(&mut len_bytes)?;
+    let received = u32::from_le_bytes(len_bytes) as usize;
+    if received > len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("proxy replied {} bytes to a {}-byte request", received, len),
+        ));
+    }
+    let mut buf = vec![0u8; received];
+    stream.read_exact(&mut buf)?;
+    Ok(Ok(Some(buf)))
+}
+
+#[inline]
+fn io_error(e: io::Error) -> TransferError {
+    match e.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => TransferError::Cancelled,
+        io::ErrorKind::InvalidData => TransferError::Fault,
+        _ => TransferError::Disconnected,
+    }
+}
+
+impl Transport for RemoteTransport {
+    #[inline]
+    fn bulk_out(
+        &self,
+        endpoint: u8,
+        buf: Vec<u8>,
+    ) -> impl Future<Output = Result<(), TransferError>> {
+        ready(self.request(b'O', endpoint, buf.len(), &buf).map(|_| ()))
+    }
+    #[inline]
+    fn bulk_in(
+        &self,
+        endpoint: u8,
+        len: usize,
+    ) -> impl Future<Output = Result<Vec<u8>, TransferError>> {
+        ready(
+            self.request(b'I', endpoint, len, &[])
+                .map(Option::unwrap_or_default),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RemoteTransport, ENDPOINT_IN, ENDPOINT_OUT};
+    use crate::Transport;
+    use futures::executor::block_on;
+    use nusb::transfer::TransferError;
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        time::Duration,
+    };
+
+    #[test]
+    fn remote_framing() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let peer = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut requests = Vec::new();
+            // OUT request with data
+            let mut header = [0u8; 6];
+            stream.read_exact(&mut header).unwrap();
+            let mut data = vec![0u8; u32::from_le_bytes(header[2..].try_into().unwrap()) as usize];
+            stream.read_exact(&mut data).unwrap();
+            requests.push((header, data));
+            stream.write_all(&[0]).unwrap();
+            // IN request
+            stream.read_exact(&mut header).unwrap();
+            requests.push((header, Vec::new()));
+            stream
+                .write_all(&[0, 4, 0, 0, 0, b'A', b'W', b'U', b'S'])
+                .unwrap();
+            // IN request on a stalled endpoint
+            stream.read_exact(&mut header).unwrap();
+            requests.push((header, Vec::new()));
+            stream.write_all(&[1]).unwrap();
+            requests
+        });
+
+        let remote = RemoteTransport::connect(addr, Duration::from_secs(5)).unwrap();
+        block_on(remote.bulk_out(ENDPOINT_OUT, b"AWUC".to_vec())).unwrap();
+        assert_eq!(block_on(remote.bulk_in(ENDPOINT_IN, 4)).unwrap(), b"AWUS");
+        assert_eq!(
+            block_on(remote.bulk_in(ENDPOINT_IN, 13)),
+            Err(TransferError::Stall)
+        );
+
+        let requests = peer.join().unwrap();
+        assert_eq!(requests[0], ([b'O', 0x01, 4, 0, 0, 0], b"AWUC".to_vec()));
+        assert_eq!(requests[1], ([b'I', 0x82, 4, 0, 0, 0], Vec::new()));
+        assert_eq!(requests[2], ([b'I', 0x82, 13, 0, 0, 0], Vec::new()));
+    }
+
+    #[test]
+    fn remote_drops_desynchronized_stream() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let peer = std::thread::spawn(move || {
+            let mut header = [0u8; 6];
+            // reply longer than requested
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.read_exact(&mut header).unwrap();
+            stream.write_all(&[0, 0, 0, 0, 0x80]).unwrap();
+            // no reply until the client gives up
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.read_exact(&mut header).unwrap();
+            let mut rest = Vec::new();
+            // client closes the connection after its timeout
+            stream.read_to_end(&mut rest).unwrap();
+            rest
+        });
+
+        let remote = RemoteTransport::connect(addr, Duration::from_secs(5)).unwrap();
+        assert_eq!(
+            block_on(remote.bulk_in(ENDPOINT_IN, 4)),
+            Err(TransferError::Fault)
+        );
+        assert_eq!(
+            block_on(remote.bulk_in(ENDPOINT_IN, 4)),
+            Err(TransferError::Disconnected)
+        );
+
+        let remote = RemoteTransport::connect(addr, Duration::from_millis(50)).unwrap();
+        assert_eq!(
+            block_on(remote.bulk_in(ENDPOINT_IN, 4)),
+            Err(TransferError::Cancelled)
+        );
+        assert_eq!(
+            block_on(remote.bulk_out(ENDPOINT_OUT, b"AWUC".to_vec())),
+            Err(TransferError::Disconnected)
+        );
+        // nothing more is sent on the closed connection
+        assert!(peer.join().unwrap().is_empty());
+    }
+}
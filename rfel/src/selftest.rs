@@ -0,0 +1,126 @@
+//! Smoke test of FEL communication on chip scratch memory.
+use crate::{Chip, Fel, MemoryRegion, Transport};
+
+/// Result of one sub-test.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubTest {
+    /// Name of this sub-test.
+    pub name: &'static str,
+    /// `Err` carries the failure reason.
+    pub result: Result<(), String>,
+}
+
+impl SubTest {
+    /// Check if this sub-test passed.
+    #[inline]
+    pub fn passed(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+/// Size of test pattern written into scratch memory.
+const PATTERN_SIZE: usize = 4096;
+
+/// Test pattern covering all byte values and both bit polarities.
+fn pattern(len: usize) -> Vec<u8> {
+    (0..len)
+        .map(|i| match (i / 256) % 4 {
+            0 => i as u8,
+            1 => !(i as u8),
+            2 => 0x55,
+            _ => 0xaa,
+        })
+        .collect()
+}
+
+/// Run self test on `fel` using scratch region of `chip`.
+///
+/// Writes a known pattern, reads it back and verifies it. If `exec_stub` is set,
+/// a return-immediately stub is executed and the device must keep responding.
+/// Later sub-tests are skipped if the device stops responding.
+pub fn run<T: Transport>(fel: &Fel<T>, chip: &Chip, exec_stub: bool) -> Vec<SubTest> {
+    let scratch: &MemoryRegion = chip.scratch_region();
+    let mut ans = Vec::new();
+
+    let version = fel.get_version().map(|_| ()).map_err(|e| e.to_string());
+    let alive = version.is_ok();
+    ans.push(SubTest {
+        name: "version",
+        result: version,
+    });
+    if !alive {
+        return ans;
+    }
+
+    let len = PATTERN_SIZE.min(scratch.size as usize);
+    let expected = pattern(len);
+    let write = fel
+        .write_address(scratch.start, &expected)
+        .map(|_| ())
+        .map_err(|e| e.to_string());
+    ans.push(SubTest {
+        name: "write",
+        result: write,
+    });
+
+    let mut actual = vec![0u8; len];
+    let read = match fel.read_address(scratch.start, &mut actual) {
+        Ok(_) => match expected.iter().zip(&actual).position(|(a, b)| a != b) {
+            None => Ok(()),
+            Some(offset) => Err(format!(
+                "mismatch at 0x{:08x}: expected 0x{:02x}, read 0x{:02x}",
+                scratch.start as usize + offset,
+                expected[offset],
+                actual[offset]
+            )),
+        },
+        Err(e) => Err(e.to_string()),
+    };
+    ans.push(SubTest {
+        name: "read back",
+        result: read,
+    });
+
+    if exec_stub {
+        let stub = chip.return_stub();
+        let exec = fel
+            .write_address(scratch.start, stub)
+            .and_then(|_| fel.exec(scratch.start))
+            .and_then(|_| fel.get_version())
+            .map(|_| ())
+            .map_err(|e| e.to_string());
+        ans.push(SubTest {
+            name: "exec",
+            result: exec,
+        });
+    }
+    ans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run;
+    use crate::{mock::MockFel, Chip};
+
+    #[test]
+    fn selftest_on_mock() {
+        let mut mock = MockFel::default();
+        let results = run(&mock.fel(), &Chip::D1, true);
+        let names: Vec<_> = results.iter().map(|t| t.name).collect();
+        assert_eq!(names, ["version", "write", "read back", "exec"]);
+        assert!(results.iter().all(|t| t.passed()), "{:?}", results);
+        let scratch = Chip::D1.scratch_region().start;
+        assert_eq!(mock.executed(), [scratch]);
+        // RISC-V `ret` stub was written at the start of scratch region
+        assert_eq!(mock.byte(scratch), 0x67);
+        assert_eq!(mock.byte(scratch + 1), 0x80);
+
+        let mut mock = MockFel::default();
+        mock.corrupt_reads = true;
+        let results = run(&mock.fel(), &Chip::D1, false);
+        assert_eq!(results.len(), 3);
+        assert!(results[1].passed());
+        assert!(!results[2].passed());
+        assert!(mock.executed().is_empty());
+    }
+}
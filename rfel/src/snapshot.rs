@@ -0,0 +1,83 @@
+//! Snapshots of chip memory regions, to roll back a destructive operation.
+//!
+//! A snapshot is an ELF file with one loadable segment, the same as written by
+//! `read --format elf`, so it carries the address of the region it was taken from.
+use crate::{elf, error::CliError, transfer, Fel, Transport, CHUNK_SIZE};
+use std::io::Write;
+
+/// Save `length` bytes of chip memory at `address` into `writer` as a snapshot.
+///
+/// Parameter `machine` is the ELF machine type of the chip. Returns number of
+/// bytes of memory saved.
+pub fn save<T: Transport>(
+    fel: &Fel<T>,
+    writer: &mut impl Write,
+    address: u32,
+    length: usize,
+    machine: u16,
+) -> Result<usize, CliError> {
+    writer.write_all(&elf::dump_header(address as u64, length as u64, machine))?;
+    transfer::read_to_writer(writer, address, length, CHUNK_SIZE, |at, buf| {
+        fel.read_address(at, buf)?;
+        Ok::<_, CliError>(())
+    })
+}
+
+/// Write memory saved in snapshot `image` back to where it was taken from.
+///
+/// Every loadable segment is written at its physical address. Returns total
+/// number of bytes written.
+pub fn restore<T: Transport>(fel: &Fel<T>, image: &[u8]) -> Result<usize, CliError> {
+    let segments = elf::load_segments(image).map_err(|e| CliError::Image(e.to_string()))?;
+    let mut written = 0;
+    for segment in segments {
+        let address = u32::try_from(segment.paddr).map_err(|_| {
+            CliError::Image(format!(
+                "segment at 0x{:x} is outside 32-bit address space",
+                segment.paddr
+            ))
+        })?;
+        written += transfer::write_slice(&segment.memory(), address, CHUNK_SIZE, |at, buf| {
+            Ok::<_, CliError>(fel.write_address(at, buf)?)
+        })?;
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{restore, save};
+    use crate::{elf::EM_RISCV, error::CliError, mock::MockFel};
+
+    #[test]
+    fn save_modify_restore() {
+        let mut mock = MockFel::default();
+        let original: Vec<u8> = (0..5000u32).map(|i| (i * 13) as u8).collect();
+        let mut snapshot = Vec::new();
+        {
+            let fel = mock.fel();
+            fel.write_address(0x0002_0000, &original).unwrap();
+            assert_eq!(
+                save(&fel, &mut snapshot, 0x0002_0400, 4096, EM_RISCV).unwrap(),
+                4096
+            );
+            // payload clobbers the region
+            fel.write_address(0x0002_0000, &[0xFF; 5000]).unwrap();
+            assert_eq!(restore(&fel, &snapshot).unwrap(), 4096);
+        }
+        for (i, &byte) in original.iter().enumerate() {
+            let expected = if (0x400..0x1400).contains(&i) {
+                byte
+            } else {
+                0xFF
+            };
+            assert_eq!(mock.byte(0x0002_0000 + i as u32), expected, "offset {}", i);
+        }
+
+        let fel = mock.fel();
+        assert!(matches!(
+            restore(&fel, b"not a snapshot"),
+            Err(CliError::Image(_))
+        ));
+    }
+}
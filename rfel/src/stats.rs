@@ -0,0 +1,105 @@
+//! Transfer statistics of one FEL session.
+use std::time::Duration;
+
+/// Accumulated transfer counts of a session.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SessionStats {
+    /// Payload bytes read from device memory.
+    pub bytes_read: u64,
+    /// Payload bytes written into device memory.
+    pub bytes_written: u64,
+    /// USB bulk transfers, including protocol requests and responses.
+    pub transfers: u64,
+    /// Operations repeated after a failed attempt.
+    pub retries: u64,
+}
+
+impl SessionStats {
+    /// Record `len` payload bytes read from device.
+    #[inline]
+    pub fn record_read(&mut self, len: usize) {
+        self.bytes_read += len as u64;
+    }
+    /// Record `len` payload bytes written into device.
+    #[inline]
+    pub fn record_write(&mut self, len: usize) {
+        self.bytes_written += len as u64;
+    }
+    /// Record one USB bulk transfer.
+    #[inline]
+    pub fn record_transfer(&mut self) {
+        self.transfers += 1;
+    }
+    /// Record one retried operation.
+    #[inline]
+    pub fn record_retry(&mut self) {
+        self.retries += 1;
+    }
+    /// Add counts of `other` into this accumulator.
+    #[inline]
+    pub fn merge(&mut self, other: &SessionStats) {
+        self.bytes_read += other.bytes_read;
+        self.bytes_written += other.bytes_written;
+        self.transfers += other.transfers;
+        self.retries += other.retries;
+    }
+    /// One-line summary of the session which took `elapsed`.
+    pub fn summary(&self, elapsed: Duration) -> String {
+        format!(
+            "read {} bytes, wrote {} bytes in {} USB transfers, {} retries, {:.3} s",
+            self.bytes_read,
+            self.bytes_written,
+            self.transfers,
+            self.retries,
+            elapsed.as_secs_f64()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SessionStats;
+    use crate::mock::MockFel;
+    use std::time::Duration;
+
+    #[test]
+    fn accumulate_session_stats() {
+        let mut stats = SessionStats::default();
+        stats.record_write(4096);
+        stats.record_transfer();
+        stats.record_read(100);
+        stats.record_read(28);
+        stats.record_transfer();
+        stats.record_retry();
+        stats.record_transfer();
+        let expected = SessionStats {
+            bytes_read: 128,
+            bytes_written: 4096,
+            transfers: 3,
+            retries: 1,
+        };
+        assert_eq!(stats, expected);
+        let mut total = stats;
+        total.merge(&stats);
+        assert_eq!(total.bytes_read, 256);
+        assert_eq!(total.transfers, 6);
+        assert_eq!(
+            stats.summary(Duration::from_millis(1500)),
+            "read 128 bytes, wrote 4096 bytes in 3 USB transfers, 1 retries, 1.500 s"
+        );
+
+        // counted by FEL operations: request, data and status are 3 transfers each
+        let mut device = MockFel::default();
+        let fel = device.fel();
+        assert_eq!(fel.stats(), SessionStats::default());
+        fel.write_address(0x2_0000, &[1, 2, 3, 4, 5]).unwrap();
+        let mut buf = [0u8; 3];
+        fel.read_address(0x2_0000, &mut buf).unwrap();
+        fel.exec(0x2_0000).unwrap();
+        let stats = fel.stats();
+        assert_eq!(stats.bytes_written, 5);
+        assert_eq!(stats.bytes_read, 3);
+        assert_eq!(stats.transfers, 9 + 9 + 6);
+        assert_eq!(stats.retries, 0);
+    }
+}
@@ -0,0 +1,326 @@
+//! sunxi TOC0 and TOC1 secure boot containers.
+use crate::egon::checksum_at;
+use core::fmt;
+
+/// Name of a TOC0 container.
+pub const TOC0_NAME: [u8; 8] = *b"TOC0.GLH";
+/// Magic of TOC0 and TOC1 containers.
+pub const TOC_MAGIC: u32 = 0x8911_9800;
+/// TOC0 item holding the certificate.
+pub const TOC0_ITEM_CERTIFICATE: u32 = 0x0001_0101;
+/// TOC0 item holding the firmware.
+pub const TOC0_ITEM_FIRMWARE: u32 = 0x0001_0202;
+/// TOC0 item holding the key.
+pub const TOC0_ITEM_KEY: u32 = 0x0001_0303;
+
+/// End marker of main information.
+const MAIN_END: [u8; 4] = *b"MIE;";
+/// End marker of each item information.
+const ITEM_END: [u8; 4] = *b"IIE;";
+
+/// Layout of main and item information for one container kind.
+struct Layout {
+    main_size: usize,
+    item_size: usize,
+    magic: usize,
+    checksum: usize,
+    serial: usize,
+    num_items: usize,
+    length: usize,
+    item_offset: usize,
+    item_length: usize,
+    item_type: usize,
+    item_load_address: usize,
+}
+
+const TOC0: Layout = Layout {
+    main_size: 48,
+    item_size: 32,
+    magic: 8,
+    checksum: 12,
+    serial: 16,
+    num_items: 24,
+    length: 28,
+    item_offset: 4,
+    item_length: 8,
+    item_type: 16,
+    item_load_address: 20,
+};
+
+const TOC1: Layout = Layout {
+    main_size: 64,
+    item_size: 368,
+    magic: 16,
+    checksum: 20,
+    serial: 24,
+    num_items: 32,
+    length: 36,
+    item_offset: 64,
+    item_length: 68,
+    item_type: 76,
+    item_load_address: 80,
+};
+
+/// Kind of a secure boot container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TocKind {
+    /// TOC0, loaded by boot ROM and holding the first stage firmware.
+    Toc0,
+    /// TOC1, loaded by the first stage and holding later stages.
+    Toc1,
+}
+
+impl TocKind {
+    #[inline]
+    fn layout(self) -> &'static Layout {
+        match self {
+            TocKind::Toc0 => &TOC0,
+            TocKind::Toc1 => &TOC1,
+        }
+    }
+}
+
+/// Item of a secure boot container.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TocItem {
+    /// Name of item; TOC0 item identifiers are shown by role.
+    pub name: String,
+    /// Offset of item contents from the start of the container.
+    pub offset: u32,
+    /// Length of item contents in bytes.
+    pub length: u32,
+    /// Item type as recorded in container.
+    pub item_type: u32,
+    /// Address where item is loaded.
+    pub load_address: u32,
+}
+
+impl TocItem {
+    /// Contents of this item in `image`.
+    #[inline]
+    pub fn data<'a>(&self, image: &'a [u8]) -> &'a [u8] {
+        &image[self.offset as usize..(self.offset + self.length) as usize]
+    }
+}
+
+/// TOC0 or TOC1 container header and items.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TocHead {
+    /// Kind of container.
+    pub kind: TocKind,
+    /// Name recorded in main information.
+    pub name: String,
+    /// Checksum recorded in container.
+    pub checksum: u32,
+    /// Serial number.
+    pub serial: u32,
+    /// Total length of container in bytes.
+    pub length: u32,
+    /// Items in container order.
+    pub items: Vec<TocItem>,
+}
+
+/// Error while parsing a TOC0 or TOC1 container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TocError {
+    /// Image is shorter than container header.
+    TooShort,
+    /// Neither TOC0 name nor TOC magic is found.
+    InvalidMagic,
+    /// Length field is not a multiple of 4, or is larger than image.
+    InvalidLength(u32),
+    /// Item information at this index is malformed or points out of container.
+    InvalidItem(usize),
+}
+
+impl fmt::Display for TocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TocError::TooShort => write!(f, "image is too short to contain a TOC header"),
+            TocError::InvalidMagic => write!(f, "TOC0 or TOC1 magic not found"),
+            TocError::InvalidLength(length) => {
+                write!(f, "invalid TOC container length 0x{:x}", length)
+            }
+            TocError::InvalidItem(index) => write!(f, "invalid TOC item {}", index),
+        }
+    }
+}
+
+/// Trim trailing NUL bytes off a fixed size name field.
+fn name_field(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// Name of a TOC0 item by its identifier.
+fn toc0_item_name(id: u32) -> String {
+    match id {
+        TOC0_ITEM_CERTIFICATE => "certificate".into(),
+        TOC0_ITEM_FIRMWARE => "firmware".into(),
+        TOC0_ITEM_KEY => "key".into(),
+        _ => format!("0x{:08x}", id),
+    }
+}
+
+impl TocHead {
+    /// Parse container header and items from the start of an image.
+    pub fn parse(image: &[u8]) -> Result<TocHead, TocError> {
+        if image.len() < TOC0.main_size {
+            return Err(TocError::TooShort);
+        }
+        let word =
+            |offset: usize| u32::from_le_bytes(image[offset..offset + 4].try_into().unwrap());
+        let kind = if image[..8] == TOC0_NAME {
+            TocKind::Toc0
+        } else if image.len() >= TOC1.main_size && word(TOC1.magic) == TOC_MAGIC {
+            TocKind::Toc1
+        } else {
+            return Err(TocError::InvalidMagic);
+        };
+        let layout = kind.layout();
+        if word(layout.magic) != TOC_MAGIC
+            || image[layout.main_size - 4..layout.main_size] != MAIN_END
+        {
+            return Err(TocError::InvalidMagic);
+        }
+        let length = word(layout.length);
+        if !length.is_multiple_of(4)
+            || (length as usize) < layout.main_size
+            || length as usize > image.len()
+        {
+            return Err(TocError::InvalidLength(length));
+        }
+        let num_items = word(layout.num_items) as usize;
+        let mut items = Vec::with_capacity(num_items.min(64));
+        for index in 0..num_items {
+            let base = layout.main_size + index * layout.item_size;
+            let info = image
+                .get(base..base + layout.item_size)
+                .ok_or(TocError::InvalidItem(index))?;
+            if info[layout.item_size - 4..] != ITEM_END {
+                return Err(TocError::InvalidItem(index));
+            }
+            let field = |offset: usize| word(base + offset);
+            let item = TocItem {
+                name: match kind {
+                    TocKind::Toc0 => toc0_item_name(field(0)),
+                    TocKind::Toc1 => name_field(&info[..64]),
+                },
+                offset: field(layout.item_offset),
+                length: field(layout.item_length),
+                item_type: field(layout.item_type),
+                load_address: field(layout.item_load_address),
+            };
+            if item.offset as u64 + item.length as u64 > length as u64 {
+                return Err(TocError::InvalidItem(index));
+            }
+            items.push(item);
+        }
+        Ok(TocHead {
+            kind,
+            name: name_field(&image[..layout.magic]),
+            checksum: word(layout.checksum),
+            serial: word(layout.serial),
+            length,
+            items,
+        })
+    }
+
+    /// Check if recorded checksum matches container contents.
+    #[inline]
+    pub fn checksum_valid(&self, image: &[u8]) -> bool {
+        checksum_at(&image[..self.length as usize], self.kind.layout().checksum) == self.checksum
+    }
+
+    /// Find an item by name, or by index if `key` is a decimal number.
+    pub fn item(&self, key: &str) -> Option<&TocItem> {
+        match key.parse::<usize>() {
+            Ok(index) => self.items.get(index),
+            Err(_) => self.items.iter().find(|item| item.name == key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TocError, TocHead, TocKind, TOC_MAGIC};
+    use crate::egon::checksum_at;
+
+    const FIXTURE: &[u8] = include_bytes!("../fixtures/toc0.bin");
+
+    #[test]
+    fn parse_fixture_toc0() {
+        let head = TocHead::parse(FIXTURE).unwrap();
+        assert_eq!(head.kind, TocKind::Toc0);
+        assert_eq!(head.name, "TOC0.GLH");
+        assert_eq!(head.length, 1024);
+        assert!(head.checksum_valid(FIXTURE));
+        let names: Vec<_> = head.items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, ["certificate", "firmware", "key"]);
+
+        let firmware = head.item("firmware").unwrap();
+        assert_eq!(head.item("1"), Some(firmware));
+        assert_eq!(firmware.offset, 0x200);
+        assert_eq!(firmware.load_address, 0x0002_0060);
+        let data = firmware.data(FIXTURE);
+        assert_eq!(data.len(), 0x100);
+        assert!(data.iter().enumerate().all(|(i, &b)| b == i as u8));
+        assert_eq!(head.item("3"), None);
+        assert_eq!(head.item("rootkey"), None);
+
+        let mut corrupted = FIXTURE.to_vec();
+        corrupted[0x210] ^= 0xff;
+        assert!(!TocHead::parse(&corrupted)
+            .unwrap()
+            .checksum_valid(&corrupted));
+    }
+
+    #[test]
+    fn parse_toc1() {
+        let mut image = vec![0u8; 1024];
+        image[..12].copy_from_slice(b"sunxi-secure");
+        image[16..20].copy_from_slice(&TOC_MAGIC.to_le_bytes());
+        image[32..36].copy_from_slice(&1u32.to_le_bytes());
+        image[36..40].copy_from_slice(&1024u32.to_le_bytes());
+        image[60..64].copy_from_slice(b"MIE;");
+        let info = 64;
+        image[info..info + 6].copy_from_slice(b"u-boot");
+        image[info + 64..info + 68].copy_from_slice(&0x200u32.to_le_bytes());
+        image[info + 68..info + 72].copy_from_slice(&0x10u32.to_le_bytes());
+        image[info + 76..info + 80].copy_from_slice(&2u32.to_le_bytes());
+        image[info + 80..info + 84].copy_from_slice(&0x4a00_0000u32.to_le_bytes());
+        image[info + 364..info + 368].copy_from_slice(b"IIE;");
+        image[0x200..0x210].fill(0xAB);
+        let checksum = checksum_at(&image, 20);
+        image[20..24].copy_from_slice(&checksum.to_le_bytes());
+
+        let head = TocHead::parse(&image).unwrap();
+        assert_eq!(head.kind, TocKind::Toc1);
+        assert_eq!(head.name, "sunxi-secure");
+        assert!(head.checksum_valid(&image));
+        let item = head.item("u-boot").unwrap();
+        assert_eq!(item.item_type, 2);
+        assert_eq!(item.load_address, 0x4a00_0000);
+        assert_eq!(item.data(&image), [0xAB; 16]);
+
+        // item pointing past the end of container
+        image[info + 64..info + 68].copy_from_slice(&0x3F8u32.to_le_bytes());
+        assert_eq!(TocHead::parse(&image), Err(TocError::InvalidItem(0)));
+    }
+
+    #[test]
+    fn parse_invalid_toc() {
+        assert_eq!(TocHead::parse(&FIXTURE[..16]), Err(TocError::TooShort));
+        assert_eq!(
+            TocHead::parse(include_bytes!("../fixtures/egon-d1.bin")),
+            Err(TocError::InvalidMagic)
+        );
+        assert_eq!(
+            TocHead::parse(&FIXTURE[..512]),
+            Err(TocError::InvalidLength(1024))
+        );
+        let mut image = FIXTURE.to_vec();
+        image[48 + 32 + 28] = b'x';
+        assert_eq!(TocHead::parse(&image), Err(TocError::InvalidItem(1)));
+    }
+}
@@ -0,0 +1,843 @@
+//! Chunked transfers between local files and chip memory.
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+/// Write contents of `file` into chip memory at `address` chunk by chunk.
+///
+/// If `use_mmap` is set, the file is memory-mapped and chunk slices are passed to
+/// `write` directly; if mapping is unavailable or not requested, chunks are read
+/// into a single reusable buffer. The whole file is never loaded into memory.
+/// Function `write` is called with chunk address and data; it returns number of
+/// bytes written. Returns total number of bytes written, or the first error
+/// from either reading the file or `write`.
+pub fn write_file<E: From<std::io::Error>>(
+    file: &File,
+    address: u32,
+    chunk_size: usize,
+    use_mmap: bool,
+    mut write: impl FnMut(u32, &[u8]) -> Result<usize, E>,
+) -> Result<usize, E> {
+    if use_mmap {
+        // SAFETY: file is opened read-only by us; concurrent modification by other
+        // processes could change data being written but cannot cause memory unsafety
+        // on platforms we support.
+        match unsafe { memmap2::Mmap::map(file) } {
+            Ok(map) => return write_slice(&map, address, chunk_size, write),
+            Err(e) => log::warn!("cannot memory-map file, fall back to buffered read: {}", e),
+        }
+    }
+    let mut reader = file;
+    write_reader(&mut reader, address, chunk_size, &mut write)
+}
+
+/// Write a byte slice in chunks.
+pub fn write_slice<E>(
+    data: &[u8],
+    address: u32,
+    chunk_size: usize,
+    mut write: impl FnMut(u32, &[u8]) -> Result<usize, E>,
+) -> Result<usize, E> {
+    let mut written = 0;
+    for chunk in data.chunks(chunk_size) {
+        written += write(address.wrapping_add(written as u32), chunk)?;
+    }
+    Ok(written)
+}
+
+/// Write data from a reader in chunks, using one buffer of `chunk_size` bytes.
+pub fn write_reader<E: From<std::io::Error>>(
+    reader: &mut impl Read,
+    address: u32,
+    chunk_size: usize,
+    mut write: impl FnMut(u32, &[u8]) -> Result<usize, E>,
+) -> Result<usize, E> {
+    let mut buf = vec![0u8; chunk_size];
+    let mut written = 0;
+    loop {
+        // fill the buffer completely so that chunk boundaries match `write_slice`
+        let mut filled = 0;
+        while filled < chunk_size {
+            match reader.read(&mut buf[filled..])? {
+                0 => break,
+                n => filled += n,
+            }
+        }
+        if filled == 0 {
+            return Ok(written);
+        }
+        written += write(address.wrapping_add(written as u32), &buf[..filled])?;
+        if filled < chunk_size {
+            return Ok(written);
+        }
+    }
+}
+
+/// Read `length` bytes of chip memory at `address` into `writer` chunk by chunk.
+///
+/// Function `read` fills the buffer with chip memory at the given address.
+/// Returns total number of bytes read.
+pub fn read_to_writer<E: From<std::io::Error>>(
+    writer: &mut impl Write,
+    address: u32,
+    length: usize,
+    chunk_size: usize,
+    mut read: impl FnMut(u32, &mut [u8]) -> Result<(), E>,
+) -> Result<usize, E> {
+    let mut buf = vec![0u8; chunk_size.min(length)];
+    let mut offset = 0;
+    while offset < length {
+        let chunk = &mut buf[..(length - offset).min(chunk_size)];
+        read(address.wrapping_add(offset as u32), chunk)?;
+        writer.write_all(chunk)?;
+        offset += chunk.len();
+    }
+    Ok(offset)
+}
+
+/// Range of chip memory whose contents differ from local data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Address of the first differing byte.
+    pub start: u32,
+    /// Address after the last differing byte.
+    pub end: u32,
+}
+
+impl Mismatch {
+    /// Length of mismatching range in bytes.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.end.wrapping_sub(self.start) as usize
+    }
+    /// Check if mismatching range is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// Compare data from a reader with chip memory at `address` chunk by chunk.
+///
+/// Function `read` fills the buffer with chip memory at the given address.
+/// Returns the range covering all differing bytes, or `None` if memory matches.
+pub fn verify_reader<E: From<std::io::Error>>(
+    reader: &mut impl Read,
+    address: u32,
+    chunk_size: usize,
+    mut read: impl FnMut(u32, &mut [u8]) -> Result<(), E>,
+) -> Result<Option<Mismatch>, E> {
+    let mut device = vec![0u8; chunk_size];
+    let mut mismatch: Option<Mismatch> = None;
+    let compare = |chunk_address: u32, expected: &[u8]| -> Result<usize, E> {
+        let device = &mut device[..expected.len()];
+        read(chunk_address, device)?;
+        for (i, _) in expected
+            .iter()
+            .zip(device.iter())
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+        {
+            let at = chunk_address.wrapping_add(i as u32);
+            let start = mismatch.map_or(at, |m| m.start);
+            mismatch = Some(Mismatch {
+                start,
+                end: at.wrapping_add(1),
+            });
+        }
+        Ok(expected.len())
+    };
+    write_reader(reader, address, chunk_size, compare)?;
+    Ok(mismatch)
+}
+
+/// Save chip memory of a mismatching range into file at `path`.
+pub fn dump_mismatch<E: From<std::io::Error>>(
+    mismatch: Mismatch,
+    chunk_size: usize,
+    mut read: impl FnMut(u32, &mut [u8]) -> Result<(), E>,
+    path: &Path,
+) -> Result<(), E> {
+    let mut file = File::create(path)?;
+    let mut buf = vec![0u8; chunk_size];
+    let mut offset = 0;
+    while offset < mismatch.len() {
+        let len = (mismatch.len() - offset).min(chunk_size);
+        read(mismatch.start.wrapping_add(offset as u32), &mut buf[..len])?;
+        file.write_all(&buf[..len])?;
+        offset += len;
+    }
+    Ok(())
+}
+
+/// Caps average transfer rate by sleeping between chunks.
+pub struct Throttle {
+    bytes_per_sec: u64,
+    transferred: u64,
+    start: Instant,
+}
+
+impl Throttle {
+    /// Create a throttle limiting average rate to `bytes_per_sec`, starting now.
+    #[inline]
+    pub fn new(bytes_per_sec: u64) -> Self {
+        assert!(bytes_per_sec > 0, "throttle rate must be positive");
+        Throttle {
+            bytes_per_sec,
+            transferred: 0,
+            start: Instant::now(),
+        }
+    }
+    /// Account `n` more transferred bytes and sleep until average rate is under limit.
+    #[inline]
+    pub fn pace(&mut self, n: usize) {
+        let delay = self.delay_after(n, self.start.elapsed());
+        if !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+    }
+    /// Account `n` more transferred bytes, returning delay needed if `elapsed`
+    /// time has passed since start.
+    fn delay_after(&mut self, n: usize, elapsed: Duration) -> Duration {
+        self.transferred += n as u64;
+        let target = Duration::from_secs_f64(self.transferred as f64 / self.bytes_per_sec as f64);
+        target.saturating_sub(elapsed)
+    }
+}
+
+/// Delay before the first retry of a failed chunk transfer.
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Retries of a single chunk transfer after transient failures.
+///
+/// Each retry waits twice as long as the one before, starting at `delay`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Number of retries after the first failed attempt.
+    pub retries: u32,
+    /// Delay before the first retry.
+    pub delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Create a policy with `retries` retries and the default delay.
+    #[inline]
+    pub const fn new(retries: u32) -> Self {
+        RetryPolicy {
+            retries,
+            delay: DEFAULT_RETRY_DELAY,
+        }
+    }
+    /// Run `op` until it succeeds or retries run out, returning its last result.
+    ///
+    /// Function `on_retry` is called with the error of every attempt that is retried.
+    pub fn run<T, E: core::fmt::Display>(
+        &self,
+        mut op: impl FnMut() -> Result<T, E>,
+        mut on_retry: impl FnMut(&E),
+    ) -> Result<T, E> {
+        let mut delay = self.delay;
+        for attempt in 1..=self.retries {
+            match op() {
+                Ok(ans) => return Ok(ans),
+                Err(e) => {
+                    log::warn!(
+                        "transfer failed, retry {} of {}: {}",
+                        attempt,
+                        self.retries,
+                        e
+                    );
+                    on_retry(&e);
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                }
+            }
+        }
+        op()
+    }
+}
+
+impl Default for RetryPolicy {
+    #[inline]
+    fn default() -> Self {
+        RetryPolicy::new(0)
+    }
+}
+
+/// Number of consecutive failed attempts before an adaptive transfer gives up.
+pub const MAX_CHUNK_ATTEMPTS: u32 = 4;
+
+/// Chunk size controller adapting to measured link throughput and errors.
+///
+/// The chunk size starts at the lower bound and doubles while throughput of
+/// successive chunks does not drop, and halves when it drops by more than
+/// a tenth. Every error halves it and holds it back from growing for a number
+/// of chunks, so that flaky links settle on small chunks.
+#[derive(Debug, Clone)]
+pub struct ChunkController {
+    size: usize,
+    min: usize,
+    max: usize,
+    last_rate: f64,
+    hold: u32,
+    retry: RetryPolicy,
+}
+
+impl ChunkController {
+    /// Number of successful chunks after an error before the size may grow again.
+    const HOLD_AFTER_ERROR: u32 = 8;
+
+    /// Create a controller with chunk size bounded by `min` and `max` bytes.
+    #[inline]
+    pub fn new(min: usize, max: usize) -> Self {
+        assert!(0 < min && min <= max, "invalid chunk size bounds");
+        ChunkController {
+            size: min,
+            min,
+            max,
+            last_rate: 0.0,
+            hold: 0,
+            retry: RetryPolicy {
+                retries: MAX_CHUNK_ATTEMPTS - 1,
+                delay: Duration::ZERO,
+            },
+        }
+    }
+    /// Retry failed chunks with delays of `retry`.
+    ///
+    /// At least [`MAX_CHUNK_ATTEMPTS`] attempts are made even if `retry` allows fewer.
+    #[inline]
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = RetryPolicy {
+            retries: retry.retries.max(MAX_CHUNK_ATTEMPTS - 1),
+            delay: retry.delay,
+        };
+        self
+    }
+    /// Current chunk size in bytes.
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+    /// Account a chunk of `len` bytes transferred successfully in `elapsed` time.
+    #[inline]
+    pub fn record(&mut self, len: usize, elapsed: Duration) {
+        let rate = if elapsed.is_zero() {
+            f64::INFINITY
+        } else {
+            len as f64 / elapsed.as_secs_f64()
+        };
+        if self.hold > 0 {
+            self.hold -= 1;
+        } else if rate >= self.last_rate {
+            self.size = (self.size * 2).min(self.max);
+        } else if rate < self.last_rate * 0.9 {
+            self.size = (self.size / 2).max(self.min);
+        }
+        self.last_rate = rate;
+    }
+    /// Account a failed chunk transfer.
+    #[inline]
+    pub fn record_error(&mut self) {
+        self.size = (self.size / 2).max(self.min);
+        self.hold = Self::HOLD_AFTER_ERROR;
+    }
+}
+
+/// Write contents of `file` into chip memory at `address`, with chunk size
+/// chosen by `controller`.
+///
+/// Like [`write_file`], but a failed chunk is retried with the reduced chunk
+/// size as allowed by the controller's retry policy, see [`ChunkController::with_retry`].
+///
+/// Only `write` is timed for chunk sizing, so it should do the bare transfer.
+/// Function `on_chunk` is called with the length of every chunk written, e.g.
+/// to report progress or throttle; `on_retry` with every error that is retried.
+pub fn write_file_adaptive<E: From<std::io::Error>>(
+    file: &File,
+    address: u32,
+    use_mmap: bool,
+    controller: &mut ChunkController,
+    write: impl FnMut(u32, &[u8]) -> Result<usize, E>,
+    on_chunk: impl FnMut(usize),
+    on_retry: impl FnMut(&E),
+) -> Result<usize, E> {
+    if use_mmap {
+        // SAFETY: see `write_file`.
+        match unsafe { memmap2::Mmap::map(file) } {
+            Ok(map) => {
+                return write_slice_adaptive(&map, address, controller, write, on_chunk, on_retry);
+            }
+            Err(e) => log::warn!("cannot memory-map file, fall back to buffered read: {}", e),
+        }
+    }
+    let mut reader = file;
+    write_reader_adaptive(&mut reader, address, controller, write, on_chunk, on_retry)
+}
+
+/// Write a byte slice with chunk size chosen by `controller`.
+///
+/// Chunks are passed to `write` as slices of `data` without copying.
+/// See [`write_file_adaptive`] for parameters.
+pub fn write_slice_adaptive<E>(
+    data: &[u8],
+    address: u32,
+    controller: &mut ChunkController,
+    mut write: impl FnMut(u32, &[u8]) -> Result<usize, E>,
+    mut on_chunk: impl FnMut(usize),
+    mut on_retry: impl FnMut(&E),
+) -> Result<usize, E> {
+    let mut written = 0;
+    let mut attempts = 0;
+    let mut delay = controller.retry.delay;
+    while written < data.len() {
+        let len = controller.size().min(data.len() - written);
+        let begin = Instant::now();
+        match write(
+            address.wrapping_add(written as u32),
+            &data[written..written + len],
+        ) {
+            Ok(n) => {
+                controller.record(n, begin.elapsed());
+                written += n;
+                attempts = 0;
+                delay = controller.retry.delay;
+                on_chunk(n);
+            }
+            Err(e) => {
+                attempts += 1;
+                controller.record_error();
+                if attempts > controller.retry.retries {
+                    return Err(e);
+                }
+                log::warn!("chunk write failed, retry with {} bytes", controller.size());
+                on_retry(&e);
+                std::thread::sleep(delay);
+                delay *= 2;
+            }
+        }
+    }
+    Ok(written)
+}
+
+/// Write data from a reader with chunk size chosen by `controller`.
+///
+/// See [`write_file_adaptive`] for parameters.
+pub fn write_reader_adaptive<E: From<std::io::Error>>(
+    reader: &mut impl Read,
+    address: u32,
+    controller: &mut ChunkController,
+    mut write: impl FnMut(u32, &[u8]) -> Result<usize, E>,
+    mut on_chunk: impl FnMut(usize),
+    mut on_retry: impl FnMut(&E),
+) -> Result<usize, E> {
+    let mut buf = vec![0u8; controller.max];
+    // pending data is `buf[start..filled]`
+    let (mut start, mut filled) = (0, 0);
+    let mut eof = false;
+    let mut written = 0;
+    let mut attempts = 0;
+    let mut delay = controller.retry.delay;
+    loop {
+        let want = controller.size();
+        if filled - start < want && !eof {
+            buf.copy_within(start..filled, 0);
+            (start, filled) = (0, filled - start);
+            while filled < want {
+                match reader.read(&mut buf[filled..want])? {
+                    0 => {
+                        eof = true;
+                        break;
+                    }
+                    n => filled += n,
+                }
+            }
+        }
+        let len = want.min(filled - start);
+        if len == 0 {
+            return Ok(written);
+        }
+        let begin = Instant::now();
+        match write(
+            address.wrapping_add(written as u32),
+            &buf[start..start + len],
+        ) {
+            Ok(n) => {
+                controller.record(n, begin.elapsed());
+                start += n;
+                written += n;
+                attempts = 0;
+                delay = controller.retry.delay;
+                on_chunk(n);
+            }
+            Err(e) => {
+                attempts += 1;
+                controller.record_error();
+                if attempts > controller.retry.retries {
+                    return Err(e);
+                }
+                log::warn!("chunk write failed, retry with {} bytes", controller.size());
+                on_retry(&e);
+                std::thread::sleep(delay);
+                delay *= 2;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        dump_mismatch, read_to_writer, verify_reader, write_file, write_reader_adaptive,
+        write_slice, write_slice_adaptive, ChunkController, Mismatch, RetryPolicy, Throttle,
+    };
+    use crate::{error::CliError, mock::MockFel, FelError};
+    use nusb::transfer::TransferError;
+    use std::io::Write;
+    use std::time::Duration;
+
+    #[test]
+    fn write_file_chunk_sequence() {
+        const CHUNK_SIZE: usize = 65536;
+        let length = 16 * CHUNK_SIZE + 1234;
+        let path = std::env::temp_dir().join(format!("rfel-write-{}.bin", std::process::id()));
+        let mut file = std::fs::File::create(&path).unwrap();
+        let pattern: Vec<u8> = (0..=255).collect();
+        for _ in 0..length / 256 {
+            file.write_all(&pattern).unwrap();
+        }
+        file.write_all(&pattern[..length % 256]).unwrap();
+        drop(file);
+
+        let file = std::fs::File::open(&path).unwrap();
+        let mut sequences = Vec::new();
+        for use_mmap in [true, false] {
+            let mut chunks = Vec::new();
+            let mut checksum = 0u64;
+            let written = write_file(&file, 0x4000_0000, CHUNK_SIZE, use_mmap, |addr, buf| {
+                assert!(buf.len() <= CHUNK_SIZE);
+                checksum = buf.iter().fold(checksum, |s, &b| s + b as u64);
+                chunks.push((addr, buf.len()));
+                Ok::<_, std::io::Error>(buf.len())
+            })
+            .unwrap();
+            assert_eq!(written, length);
+            sequences.push((chunks, checksum));
+            std::io::Seek::rewind(&mut &file).unwrap();
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(sequences[0], sequences[1]);
+        let chunks = &sequences[0].0;
+        assert_eq!(chunks.len(), 17);
+        assert_eq!(chunks[0], (0x4000_0000, CHUNK_SIZE));
+        assert_eq!(chunks[16], (0x4010_0000, 1234));
+    }
+
+    #[test]
+    fn read_chunks_into_writer() {
+        let mut out = Vec::new();
+        let mut reads = Vec::new();
+        let n = read_to_writer(&mut out, 0x4000_0000, 10, 4, |address, buf| {
+            reads.push((address, buf.len()));
+            buf.fill(address as u8);
+            Ok::<_, std::io::Error>(())
+        })
+        .unwrap();
+        assert_eq!(n, 10);
+        assert_eq!(
+            reads,
+            [(0x4000_0000, 4), (0x4000_0004, 4), (0x4000_0008, 2)]
+        );
+        assert_eq!(out, [0, 0, 0, 0, 4, 4, 4, 4, 8, 8]);
+    }
+
+    #[test]
+    fn retry_failed_chunks() {
+        let data: Vec<u8> = (0..100).collect();
+        let policy = RetryPolicy {
+            retries: 2,
+            delay: Duration::from_millis(1),
+        };
+        let mut mock = MockFel::default();
+        let fel = mock.fel();
+        // first attempt of the second chunk fails
+        let mut attempts = 0;
+        let written = write_slice(&data, 0x4000_0000, 64, |address, buf| {
+            policy.run(
+                || {
+                    attempts += 1;
+                    if attempts == 2 {
+                        fel.iface.failed_writes.set(1);
+                    }
+                    fel.write_address(address, buf)
+                },
+                |_| fel.record_retry(),
+            )
+        })
+        .unwrap();
+        assert_eq!(written, 100);
+        assert_eq!(attempts, 3);
+
+        fel.iface.failed_writes.set(1);
+        let mut out = Vec::new();
+        let read = read_to_writer(&mut out, 0x4000_0000, 100, 64, |address, buf| {
+            policy.run(
+                || {
+                    fel.read_address(address, buf)
+                        .map(|_| ())
+                        .map_err(CliError::from)
+                },
+                |_| fel.record_retry(),
+            )
+        })
+        .unwrap();
+        assert_eq!(read, 100);
+        assert_eq!(out, data);
+        assert_eq!(fel.stats().retries, 2);
+
+        // error is returned once retries run out
+        fel.iface.failed_writes.set(3);
+        let mut buf = [0u8; 4];
+        let ans = policy.run(|| fel.read_address(0x4000_0000, &mut buf), |_| {});
+        assert_eq!(ans, Err(FelError::Transfer(TransferError::Stall)));
+        // without retries the first failure is returned
+        fel.iface.failed_writes.set(1);
+        let ans = RetryPolicy::default().run(
+            || fel.read_address(0x4000_0000, &mut buf),
+            |_| panic!("no retry expected"),
+        );
+        assert!(ans.is_err());
+        assert_eq!(fel.read_address(0x4000_0000, &mut buf), Ok(4));
+        assert_eq!(buf, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn throttle_pacing() {
+        const CHUNK_SIZE: usize = 65536;
+        const RATE: u64 = 1024 * 1024;
+        // fast link: each chunk is sent in 1 ms, throttle must make up the rest
+        let mut throttle = Throttle::new(RATE);
+        let mut elapsed = Duration::ZERO;
+        for _ in 0..64 {
+            elapsed += Duration::from_millis(1);
+            elapsed += throttle.delay_after(CHUNK_SIZE, elapsed);
+        }
+        // 4 MiB at 1 MiB/s
+        assert_eq!(elapsed, Duration::from_secs(4));
+
+        // slow link: never delayed
+        let mut throttle = Throttle::new(RATE);
+        let mut elapsed = Duration::ZERO;
+        for _ in 0..64 {
+            elapsed += Duration::from_millis(100);
+            assert!(throttle.delay_after(CHUNK_SIZE, elapsed).is_zero());
+        }
+    }
+
+    #[test]
+    fn verify_mismatch_dump() {
+        const CHUNK_SIZE: usize = 256;
+        let expected: Vec<u8> = (0..4096).map(|i| i as u8).collect();
+        let mut device = expected.clone();
+        // corrupt a range crossing a chunk boundary
+        for byte in &mut device[500..700] {
+            *byte = !*byte;
+        }
+        let read = |address: u32, buf: &mut [u8]| {
+            let offset = (address - 0x4000_0000) as usize;
+            buf.copy_from_slice(&device[offset..offset + buf.len()]);
+            Ok::<_, std::io::Error>(())
+        };
+
+        let mismatch = verify_reader(&mut &expected[..], 0x4000_0000, CHUNK_SIZE, read)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            mismatch,
+            Mismatch {
+                start: 0x4000_01F4,
+                end: 0x4000_02BC
+            }
+        );
+        assert_eq!(mismatch.len(), 200);
+
+        let path = std::env::temp_dir().join(format!("rfel-mismatch-{}.bin", std::process::id()));
+        dump_mismatch(mismatch, CHUNK_SIZE, read, &path).unwrap();
+        let dumped = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(dumped, device[500..700]);
+
+        let matching = verify_reader(&mut &device[..], 0x4000_0000, CHUNK_SIZE, read).unwrap();
+        assert_eq!(matching, None);
+    }
+
+    #[test]
+    fn chunk_size_adaptation() {
+        const MIN: usize = 512;
+        const MAX: usize = 65536;
+        // per-chunk overhead of 1 ms on a 10 MB/s link
+        let latency = |len: usize| Duration::from_micros(1000 + len as u64 / 10);
+
+        // clean link converges to the largest chunk
+        let mut controller = ChunkController::new(MIN, MAX);
+        for _ in 0..32 {
+            let len = controller.size();
+            controller.record(len, latency(len));
+        }
+        assert_eq!(controller.size(), MAX);
+
+        // every fourth chunk fails; size must end up well below the clean link
+        let mut controller = ChunkController::new(MIN, MAX);
+        for i in 0..64 {
+            if i % 4 == 3 {
+                controller.record_error();
+            } else {
+                let len = controller.size();
+                controller.record(len, latency(len));
+            }
+        }
+        assert_eq!(controller.size(), MIN);
+
+        // a burst of errors on a fast link shrinks the chunk, then it recovers
+        let mut controller = ChunkController::new(MIN, MAX);
+        for _ in 0..32 {
+            let len = controller.size();
+            controller.record(len, latency(len));
+        }
+        for _ in 0..3 {
+            controller.record_error();
+        }
+        assert_eq!(controller.size(), MAX / 8);
+        for _ in 0..32 {
+            let len = controller.size();
+            controller.record(len, latency(len));
+        }
+        assert_eq!(controller.size(), MAX);
+    }
+
+    #[test]
+    fn write_adaptive_retries() {
+        let data: Vec<u8> = (0..20000).map(|i| i as u8).collect();
+        let mut device = vec![0u8; data.len()];
+        let mut calls = 0;
+        let (mut chunks, mut retries) = (0, 0);
+        let mut controller = ChunkController::new(512, 4096);
+        let written = write_reader_adaptive(
+            &mut &data[..],
+            0,
+            &mut controller,
+            |addr, buf| {
+                calls += 1;
+                if calls % 5 == 0 {
+                    return Err(std::io::Error::from(std::io::ErrorKind::TimedOut));
+                }
+                device[addr as usize..addr as usize + buf.len()].copy_from_slice(buf);
+                Ok(buf.len())
+            },
+            |len| chunks += len,
+            |_| retries += 1,
+        )
+        .unwrap();
+        assert_eq!(written, data.len());
+        assert_eq!(device, data);
+        // every chunk is reported once, every failed call is retried
+        assert_eq!(chunks, data.len());
+        assert_eq!(retries, calls / 5);
+
+        let failing =
+            |_: u32, _: &[u8]| Err::<usize, _>(std::io::Error::from(std::io::ErrorKind::TimedOut));
+        let mut calls = 0;
+        let mut controller = ChunkController::new(512, 4096);
+        let ans = write_reader_adaptive(
+            &mut &data[..],
+            0,
+            &mut controller,
+            |addr, buf| {
+                calls += 1;
+                failing(addr, buf)
+            },
+            |_| {},
+            |_| {},
+        );
+        assert!(ans.is_err());
+        assert_eq!(calls, 4);
+
+        // more retries of the policy are used, at least the built-in attempts
+        for (retries, expected) in [(5, 6), (1, 4)] {
+            let policy = RetryPolicy {
+                retries,
+                delay: Duration::from_millis(1),
+            };
+            let mut calls = 0;
+            let mut controller = ChunkController::new(512, 4096).with_retry(policy);
+            let ans = write_reader_adaptive(
+                &mut &data[..],
+                0,
+                &mut controller,
+                |addr, buf| {
+                    calls += 1;
+                    failing(addr, buf)
+                },
+                |_| {},
+                |_| {},
+            );
+            assert!(ans.is_err());
+            assert_eq!(calls, expected);
+        }
+    }
+
+    #[test]
+    fn write_slice_adaptive_borrows_data() {
+        let data: Vec<u8> = (0..20000).map(|i| i as u8).collect();
+        let range = data.as_ptr_range();
+        let mut device = vec![0u8; data.len()];
+        let (mut calls, mut retries) = (0, 0);
+        let mut sizes = Vec::new();
+        let mut controller = ChunkController::new(512, 4096);
+        let written = write_slice_adaptive(
+            &data,
+            0x100,
+            &mut controller,
+            |addr, buf| {
+                calls += 1;
+                if calls % 5 == 0 {
+                    return Err(std::io::Error::from(std::io::ErrorKind::TimedOut));
+                }
+                // chunks point into `data` itself, nothing is copied
+                assert!(range.contains(&buf.as_ptr()));
+                let offset = addr as usize - 0x100;
+                assert_eq!(buf.as_ptr(), data[offset..].as_ptr());
+                device[offset..offset + buf.len()].copy_from_slice(buf);
+                sizes.push(buf.len());
+                Ok(buf.len())
+            },
+            |_| {},
+            |_| retries += 1,
+        )
+        .unwrap();
+        assert_eq!(written, data.len());
+        assert_eq!(device, data);
+        assert_eq!(retries, calls / 5);
+        // chunk size adapts to the controller
+        assert!(sizes.iter().any(|&len| len != sizes[0]));
+
+        let mut calls = 0;
+        let ans = write_slice_adaptive(
+            &data,
+            0,
+            &mut ChunkController::new(512, 4096),
+            |_, _| {
+                calls += 1;
+                Err::<usize, _>(std::io::Error::from(std::io::ErrorKind::TimedOut))
+            },
+            |_| {},
+            |_| {},
+        );
+        assert!(ans.is_err());
+        assert_eq!(calls, 4);
+    }
+}